@@ -0,0 +1,13 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // the gRPC service is generated from proto/, protoc comes vendored unless PROTOC is set
+    #[cfg(feature = "grpc")]
+    {
+        if std::env::var_os("PROTOC").is_none() {
+            let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc");
+            std::env::set_var("PROTOC", protoc);
+        }
+        tonic_build::compile_protos("proto/enigmagen.proto").expect("compiling the gRPC protos");
+    }
+}
@@ -0,0 +1,87 @@
+import init, { Playground, encrypt } from "./pkg/enigmagen_web.js";
+
+const DEMO = "TO BE OR NOT TO BE THAT IS THE QUESTION WHETHER TIS NOBLER IN THE MIND TO SUFFER " +
+  "THE SLINGS AND ARROWS OF OUTRAGEOUS FORTUNE OR TO TAKE ARMS AGAINST A SEA OF TROUBLES " +
+  "AND BY OPPOSING END THEM TO DIE TO SLEEP NO MORE AND BY A SLEEP TO SAY WE END THE HEARTACHE " +
+  "AND THE THOUSAND NATURAL SHOCKS THAT FLESH IS HEIR TO";
+
+const $ = (id) => document.getElementById(id);
+let playground = null;
+let history = [];
+
+await init();
+
+$("encrypt").onclick = () => {
+  try {
+    $("ciphertext").value = encrypt($("key").value, DEMO);
+  } catch (err) {
+    $("status").textContent = err.message;
+  }
+};
+
+$("start").onclick = () => {
+  try {
+    playground = new Playground(
+      $("ciphertext").value,
+      Number($("population").value),
+      Number($("generations").value),
+      Number($("mutation").value),
+      Number($("seed").value),
+    );
+  } catch (err) {
+    $("status").textContent = err.message;
+    return;
+  }
+  history = [];
+  $("start").disabled = true;
+  $("stop").disabled = false;
+  setTimeout(run, 0);
+};
+
+$("stop").onclick = () => finish("stopped");
+
+// one generation per timer tick, the page redraws in between
+function run() {
+  if (!playground) {
+    return;
+  }
+
+  const progress = playground.step();
+  history.push(progress);
+  $("status").textContent = `generation ${progress.generation}, ` +
+    `best fitness ${progress.best_fitness.toFixed(5)}, average ${progress.average_fitness.toFixed(5)}`;
+  $("key-found").textContent = progress.best_key;
+  $("plaintext").textContent = progress.plaintext;
+  draw();
+
+  if (progress.finished) {
+    finish(progress.reason);
+  } else {
+    setTimeout(run, 0);
+  }
+}
+
+function finish(reason) {
+  playground?.free();
+  playground = null;
+  $("status").textContent += `, ${reason}`;
+  $("start").disabled = false;
+  $("stop").disabled = true;
+}
+
+// best and average index of coincidence per generation
+function draw() {
+  const canvas = $("chart");
+  const ctx = canvas.getContext("2d");
+  const max = Math.max(0.07, ...history.map((p) => p.best_fitness));
+  const x = (i) => (i / Math.max(history.length - 1, 1)) * canvas.width;
+  const y = (fitness) => canvas.height - (fitness / max) * canvas.height;
+
+  ctx.clearRect(0, 0, canvas.width, canvas.height);
+  for (const [field, color] of [["best_fitness", "#c00"], ["average_fitness", "#06c"]]) {
+    ctx.strokeStyle = color;
+    ctx.beginPath();
+    history.forEach((p, i) => (i ? ctx.lineTo(x(i), y(p[field])) : ctx.moveTo(x(i), y(p[field]))));
+    ctx.stroke();
+  }
+}
@@ -0,0 +1,92 @@
+use enigmagen_rs::{
+    enigma::{self, Machine, Settings},
+    gen,
+    sim::{Simulation, StepResult},
+};
+use wasm_bindgen::prelude::*;
+
+/// The genetic search driven from the page one generation at a time, so the page can redraw
+/// between generations. The browser has no threads, the search runs on the caller.
+#[wasm_bindgen]
+pub struct Playground {
+    sim: Simulation,
+    ciphertext: String,
+}
+
+#[wasm_bindgen(getter_with_clone)]
+pub struct Progress {
+    pub generation: u32,
+    pub average_fitness: f64,
+    pub median_fitness: f64,
+    pub best_fitness: f64,
+    pub best_key: String,
+    pub plaintext: String,
+    pub finished: bool,
+    // why the search stopped, empty until it's finished
+    pub reason: String,
+}
+
+#[wasm_bindgen]
+impl Playground {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        ciphertext: &str,
+        population_size: usize,
+        generation_limit: u32,
+        mutation_rate: f64,
+        seed: u32,
+    ) -> Result<Playground, JsError> {
+        let ciphertext = enigma::normalize(ciphertext);
+        let opts = gen::Options {
+            engine: gen::EngineOptions {
+                population_size,
+                mutation_rate,
+                seed: Some(seed as u64),
+                threads: 1,
+                ..Default::default()
+            },
+            termination: gen::TerminationOptions {
+                generation_limit: generation_limit as u64,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        Ok(Playground {
+            sim: Simulation::new(&ciphertext, opts).map_err(to_js)?,
+            ciphertext,
+        })
+    }
+
+    pub fn step(&mut self) -> Result<Progress, JsError> {
+        let step = self.sim.step().map_err(to_js)?;
+        let generation = step.generation();
+
+        Ok(Progress {
+            generation: generation.iteration as u32,
+            average_fitness: generation.average_fitness.0,
+            median_fitness: generation.median_fitness.0,
+            best_fitness: generation.best_fitness.0,
+            best_key: generation.best_settings.to_string(),
+            plaintext: Machine::new(&generation.best_settings)
+                .map_err(to_js)?
+                .decrypt(&self.ciphertext),
+            finished: matches!(step, StepResult::Final { .. }),
+            reason: match &step {
+                StepResult::Final { reason, .. } => reason.clone(),
+                StepResult::Intermediate(_) => String::new(),
+            },
+        })
+    }
+}
+
+/// Encrypts a demo message with a key in key sheet notation, e.g. "II V III / 08 05 20 / M C U".
+#[wasm_bindgen]
+pub fn encrypt(key: &str, plaintext: &str) -> Result<String, JsError> {
+    let settings = key.parse::<Settings>().map_err(to_js)?;
+    Ok(Machine::new(&settings).map_err(to_js)?.encrypt(plaintext))
+}
+
+fn to_js(err: anyhow::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
@@ -0,0 +1,12 @@
+#![no_main]
+
+use enigmagen_rs::enigma::Settings;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|notation: &str| {
+    // anything that parses is valid and survives a round trip
+    if let Ok(settings) = notation.parse::<Settings>() {
+        assert!(settings.validate().is_ok());
+        assert_eq!(settings.to_string().parse::<Settings>().unwrap(), settings);
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use enigmagen_rs::enigma::{Machine, Reflector, Settings};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let byte = |i: usize| data.get(i).copied().unwrap_or(0);
+
+    let settings = Settings {
+        rotors: (byte(0), byte(1), byte(2)),
+        ring_settings: (byte(3), byte(4), byte(5)),
+        rotor_positions: (byte(6), byte(7), byte(8)),
+        plugboard: pairs(data.get(36..).unwrap_or(&[])),
+        reflector: match byte(9) % 3 {
+            0 => Reflector::B,
+            1 => Reflector::C,
+            _ => Reflector::D(pairs(data.get(10..36).unwrap_or(&[]))),
+        },
+    };
+
+    // arbitrary settings are either rejected or give a working machine
+    if let Ok(machine) = Machine::new(&settings) {
+        let ciphertext = machine.encrypt("FUZZ ME");
+        assert_eq!(machine.decrypt(&ciphertext), "FUZZ ME");
+    }
+});
+
+fn pairs(data: &[u8]) -> Vec<(u8, u8)> {
+    data.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+}
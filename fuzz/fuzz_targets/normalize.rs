@@ -0,0 +1,10 @@
+#![no_main]
+
+use enigmagen_rs::enigma;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    let normalized = enigma::normalize(text);
+    assert!(normalized.chars().all(|c| c.is_ascii_uppercase() || c == ' '));
+    assert_eq!(enigma::normalize(&normalized), normalized);
+});
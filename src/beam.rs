@@ -0,0 +1,144 @@
+// beam search over the rotor starting positions when the rotors, ring settings, reflector and
+// plugboard are known: every starting position is decrypted along the message in lockstep and
+// only the best hypotheses of every stretch of letters are carried on to the next
+use anyhow::ensure;
+
+use crate::{
+    enigma::{normalize, Machine, Settings, GARBLE, LETTERS_NUM},
+    language::Language,
+};
+
+// letters decrypted between two prunings of the beam
+pub const DEFAULT_STRIDE: usize = 20;
+
+#[derive(Debug, Clone)]
+struct Hypothesis {
+    // starting positions, A = 0
+    start: [u8; 3],
+    // positions after the last decrypted letter
+    positions: [u8; 3],
+    last: Option<u8>,
+    // ln of the probability of the letter pairs decrypted so far
+    log_likelihood: f64,
+}
+
+impl Hypothesis {
+    // a garble, None, steps the rotors and breaks the letter pairs
+    fn advance(&mut self, machine: &Machine, letters: &[Option<u8>], language: Language) {
+        for &c in letters {
+            machine.step(&mut self.positions);
+            let Some(c) = c else {
+                self.last = None;
+                continue;
+            };
+            let p = machine.press(c, &self.positions);
+            if let Some(last) = self.last {
+                self.log_likelihood += language.bigram_log_prob(last as usize, p as usize);
+            }
+            self.last = Some(p);
+        }
+    }
+}
+
+/// The `width` starting positions whose decryptions read best in the language, best first,
+/// with the geometric mean of the probabilities of their letter pairs. The search starts from
+/// all 26^3 positions and keeps the best `width` after every `stride` letters, so it is
+/// deterministic and costs about `width` decryptions of the message after the first stretch;
+/// the rotor positions of the settings are ignored. Positions the double step makes decrypt
+/// the same come out with the same score, the earlier first.
+pub fn search(
+    ciphertext: &str,
+    settings: &Settings,
+    language: Language,
+    width: usize,
+    stride: usize,
+) -> anyhow::Result<Vec<(Settings, f64)>> {
+    ensure!(width > 0, "the beam width must be positive");
+    ensure!(stride > 0, "the beam stride must be positive");
+    let machine = Machine::new(settings)?;
+    let letters = normalize(ciphertext)
+        .bytes()
+        .filter(|&c| c != b' ')
+        .map(|c| (c != GARBLE).then(|| c - b'A'))
+        .collect::<Vec<_>>();
+
+    let l = LETTERS_NUM;
+    let mut beam = (0..l)
+        .flat_map(|left| {
+            (0..l).flat_map(move |middle| (0..l).map(move |right| [left, middle, right]))
+        })
+        .map(|start| Hypothesis {
+            start,
+            positions: start,
+            last: None,
+            log_likelihood: 0.0,
+        })
+        .collect::<Vec<_>>();
+
+    for stretch in letters.chunks(stride) {
+        beam.iter_mut()
+            .for_each(|hypothesis| hypothesis.advance(&machine, stretch, language));
+        // ties go to the earlier starting position
+        beam.sort_by(|a, b| {
+            b.log_likelihood
+                .total_cmp(&a.log_likelihood)
+                .then(a.start.cmp(&b.start))
+        });
+        beam.truncate(width);
+    }
+    // nothing to decrypt, the positions stay in order
+    beam.truncate(width);
+
+    let pairs = letters
+        .windows(2)
+        .filter(|w| w[0].is_some() && w[1].is_some())
+        .count()
+        .max(1) as f64;
+    Ok(beam
+        .into_iter()
+        .map(|hypothesis| {
+            let [left, middle, right] = hypothesis.start.map(|p| p + 1);
+            let settings = Settings {
+                rotor_positions: (left, middle, right),
+                ..settings.clone()
+            };
+            (settings, (hypothesis.log_likelihood / pairs).exp())
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let settings = "IV II V / 04 11 23 / Q E W / AZ BY CX".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let unknown = Settings {
+            rotor_positions: (1, 1, 1),
+            ..settings.clone()
+        };
+
+        let beam = search(&ciphertext, &unknown, Language::English, 50, DEFAULT_STRIDE).unwrap();
+        assert_eq!(beam.len(), 50);
+        assert_eq!(beam[0].0, settings);
+        assert_relative_eq!(beam[0].1, Language::English.bigram_score(LONG_TEXT));
+        assert!(beam.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert_eq!(
+            search(&ciphertext, &unknown, Language::English, 50, DEFAULT_STRIDE).unwrap(),
+            beam
+        );
+
+        assert_eq!(
+            search("", &unknown, Language::English, 3, 5).unwrap().len(),
+            3
+        );
+        assert!(search(&ciphertext, &unknown, Language::English, 0, 5).is_err());
+    }
+}
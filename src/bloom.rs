@@ -0,0 +1,82 @@
+// remembers the keys a search evaluated in a fixed number of bits: a key that was inserted is
+// always found, one that wasn't only by a false positive, the more likely the fuller it gets
+use std::f64::consts::LN_2;
+
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// A filter of `bytes` with the number of hashes that keeps the false positives lowest
+    /// once `expected` keys are in.
+    pub fn new(bytes: usize, expected: u64) -> Self {
+        let words = (bytes / 8).max(1);
+        let bits_per_key = (words * 64) as f64 / expected.max(1) as f64;
+        Self {
+            bits: vec![0; words],
+            hashes: (bits_per_key * LN_2).round().clamp(1.0, 16.0) as u32,
+        }
+    }
+
+    pub fn insert(&mut self, key: u128) {
+        for bit in self.bits_of(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, key: u128) -> bool {
+        self.bits_of(key)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn hashes(&self) -> u32 {
+        self.hashes
+    }
+
+    // false positive rate with the bits set so far
+    pub fn false_positive_rate(&self) -> f64 {
+        let set = self.bits.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+        (set as f64 / (self.bits.len() * 64) as f64).powi(self.hashes as i32)
+    }
+
+    // double hashing, bit i of a key is h1 + i h2 of two halves of a mixed key
+    fn bits_of(&self, key: u128) -> impl Iterator<Item = usize> {
+        let len = (self.bits.len() * 64) as u64;
+        let h1 = mix(key as u64 ^ mix((key >> 64) as u64));
+        let h2 = mix(h1) | 1;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+// splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        // 10 bits per key, 7 hashes, 1% false positives
+        let mut filter = BloomFilter::new(12_500, 10_000);
+        assert_eq!(filter.hashes(), 7);
+        let key = |i: u128| (i << 64) | (i * 7919);
+        for i in 0..10_000 {
+            filter.insert(key(i));
+        }
+        assert!((0..10_000).all(|i| filter.contains(key(i))));
+
+        let false_positives = (10_000..110_000)
+            .filter(|&i| filter.contains(key(i)))
+            .count();
+        assert!(false_positives < 2_000, "{false_positives}");
+        assert!((filter.false_positive_rate() - 0.01).abs() < 0.005);
+        assert_eq!(BloomFilter::new(0, 0).bits.len(), 1);
+    }
+}
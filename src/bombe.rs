@@ -0,0 +1,513 @@
+// Turing-Welchman bombe: a menu of the letters a crib links to the ciphertext, whose loops test
+// a guess of the plugboard partner of one letter at every rotor position, and the checking
+// machine that turns the positions it stops at into keys
+use std::{collections::HashSet, fmt::Write};
+
+use anyhow::{anyhow, bail, ensure};
+use rayon::prelude::*;
+
+use crate::{
+    crib, engine,
+    enigma::{self, Machine, Reflector, Settings, LETTERS_NUM},
+    gen,
+    metrics::Metric,
+    pipeline,
+};
+
+const LETTERS: usize = LETTERS_NUM as usize;
+
+// plugboard partner of every letter, A = 0, where it is known
+type Partners = [Option<u8>; LETTERS];
+
+// every letter's links as the letter at the other end and the index in the crib
+type Adjacency = [Vec<(u8, usize)>; LETTERS];
+
+/// Plaintext and ciphertext letter of the crib at a position of the message, A = 0: with
+/// plugboard P and the scrambler S at that position, P(to) = S(P(from)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Link {
+    pub from: u8,
+    pub to: u8,
+    pub position: usize,
+}
+
+/// The connected part of a placement's letter graph a bombe runs on: the one with the most
+/// loops, and in it the central letter with the most links, whose partner is what the bombe
+/// guesses; a loop through it rules out a wrong guess.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Menu {
+    // letters of the ciphertext before the crib
+    pub offset: usize,
+    pub links: Vec<Link>,
+    pub central: u8,
+    pub loops: usize,
+}
+
+impl Menu {
+    pub fn new(ciphertext: &str, crib: &str, offset: usize) -> anyhow::Result<Self> {
+        let (ciphertext, crib) = (crib::letters(ciphertext), crib::letters(crib));
+        ensure!(!crib.is_empty(), "the crib has no letters");
+        let Some(links) = crib::links(&ciphertext, &crib, offset) else {
+            bail!("the crib doesn't fit at offset {offset}, it runs past the end or crashes")
+        };
+
+        let part = crib::parts(&links);
+        let mut letters = [0usize; LETTERS];
+        let mut part_links = [0; LETTERS];
+        for x in crib::used_letters(&links) {
+            letters[part[x]] += 1;
+        }
+        for &(p, _, _) in &links {
+            part_links[part[p as usize]] += 1;
+        }
+        // loops first, then links, then the earlier letter
+        let loops = |x: usize| part_links[x] + 1 - letters[x];
+        let chosen = (0..LETTERS)
+            .filter(|&x| letters[x] > 0)
+            .max_by_key(|&x| (loops(x), part_links[x], LETTERS - x))
+            .ok_or_else(|| anyhow!("no letter of the crib is linked, they are all garbled"))?;
+
+        let links = links
+            .into_iter()
+            .filter(|&(p, _, _)| part[p as usize] == chosen)
+            .map(|(from, to, position)| Link { from, to, position })
+            .collect::<Vec<_>>();
+        let degree = |x: u8| {
+            links
+                .iter()
+                .filter(|link| link.from == x || link.to == x)
+                .count()
+        };
+        let central = (0..LETTERS_NUM)
+            .max_by_key(|&x| (degree(x), LETTERS_NUM - x))
+            .expect("26 letters");
+
+        Ok(Self {
+            offset,
+            loops: loops(chosen),
+            links,
+            central,
+        })
+    }
+
+    /// The menu as drawn for the bombe operators: every letter with its partners and the
+    /// positions linking them, the central letter first.
+    pub fn diagram(&self) -> String {
+        let letter = |x: u8| (b'A' + x) as char;
+        let mut diagram = format!(
+            "central letter {}, {} loops, {} links from offset {}\n",
+            letter(self.central),
+            self.loops,
+            self.links.len(),
+            self.offset
+        );
+
+        let mut letters = self
+            .links
+            .iter()
+            .flat_map(|link| [link.from, link.to])
+            .collect::<Vec<_>>();
+        letters.sort_by_key(|&x| (x != self.central, x));
+        letters.dedup();
+        for x in letters {
+            let _ = write!(diagram, "{}", letter(x));
+            for link in &self.links {
+                let other = match (link.from == x, link.to == x) {
+                    (true, _) => link.to,
+                    (_, true) => link.from,
+                    _ => continue,
+                };
+                let _ = write!(diagram, "  {}-{}", link.position, letter(other));
+            }
+            diagram.push('\n');
+        }
+        diagram
+    }
+}
+
+/// A rotor position the bombe stopped at: with the central letter of the menu plugged to
+/// `partner`, nothing the menu implies contradicts itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stop {
+    pub rotors: (u8, u8, u8),
+    pub reflector: Reflector,
+    // rotor positions at the first letter of the crib with the rings at A, 1..=26
+    pub positions: (u8, u8, u8),
+    // A = 0
+    pub partner: u8,
+}
+
+/// A key the checking machine made of a stop, with its decryption of the whole message.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub stop: Stop,
+    pub settings: Settings,
+    // letters of the crib the key decrypts to
+    pub crib_matches: usize,
+    pub plaintext: String,
+    pub score: f64,
+}
+
+/// The rotor orders and reflectors a bombe run tries, all of them by default. Like Turing's
+/// bombe it assumes the middle rotor doesn't turn over within the crib, a menu across a
+/// turnover doesn't stop at the right position.
+#[derive(Debug, Clone)]
+pub struct Bombe {
+    pub rotor_orders: Vec<(u8, u8, u8)>,
+    pub reflectors: Vec<Reflector>,
+}
+
+impl Default for Bombe {
+    fn default() -> Self {
+        Self {
+            rotor_orders: pipeline::rotor_orders(),
+            reflectors: vec![Reflector::B, Reflector::C],
+        }
+    }
+}
+
+impl Bombe {
+    /// Every stop of the menu: all 26 partners of the central letter are tried at every
+    /// position of every rotor order and reflector, the wrong ones soon plug a letter to two
+    /// others through a loop. The threads are the ones of the engine options.
+    pub fn run(&self, menu: &Menu, engine: &gen::EngineOptions) -> anyhow::Result<Vec<Stop>> {
+        engine::thread_pool(engine)?.install(|| self.stops(menu))
+    }
+
+    fn stops(&self, menu: &Menu) -> anyhow::Result<Vec<Stop>> {
+        let adjacent = adjacency(&menu.links, menu.offset);
+        let cores = self
+            .rotor_orders
+            .iter()
+            .flat_map(|&rotors| {
+                self.reflectors
+                    .iter()
+                    .map(move |reflector| (rotors, reflector))
+            })
+            .collect::<Vec<_>>();
+
+        let stops = cores
+            .into_par_iter()
+            .map(|(rotors, reflector)| {
+                let machine = core_machine(rotors, reflector)?;
+                let l = LETTERS_NUM;
+                let positions = (0..l).flat_map(|left| {
+                    (0..l).flat_map(move |middle| (0..l).map(move |right| [left, middle, right]))
+                });
+
+                let mut stops = Vec::new();
+                for position in positions {
+                    let scramble = |i: usize, c: u8| scramble(&machine, position, i, c);
+                    for partner in 0..l {
+                        let mut partners = [None; LETTERS];
+                        if deduce(&adjacent, &scramble, &mut partners, (menu.central, partner)) {
+                            let [left, middle, right] = position.map(|p| p + 1);
+                            stops.push(Stop {
+                                rotors,
+                                reflector: reflector.clone(),
+                                positions: (left, middle, right),
+                                partner,
+                            });
+                        }
+                    }
+                }
+                Ok(stops)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(stops.concat())
+    }
+
+    /// The crib attack from end to end: the menu of the crib at the offset on the bombe, then
+    /// every stop through the checking machine; the keys come best first by the letters of the
+    /// crib they decrypt to, then by the metric of the whole decryption, the first of
+    /// equivalent keys only.
+    pub fn attack(
+        &self,
+        ciphertext: &str,
+        crib: &str,
+        offset: usize,
+        metric: &Metric,
+        engine: &gen::EngineOptions,
+    ) -> anyhow::Result<Vec<Candidate>> {
+        let menu = Menu::new(ciphertext, crib, offset)?;
+        let checked = engine::thread_pool(engine)?.install(|| {
+            self.stops(&menu)?
+                .par_iter()
+                .map(|stop| check(ciphertext, crib, &menu, stop, metric))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+        let mut candidates = checked.into_iter().flatten().collect::<Vec<_>>();
+        candidates.sort_by(|a, b| {
+            b.crib_matches
+                .cmp(&a.crib_matches)
+                .then(b.score.total_cmp(&a.score))
+        });
+        let mut seen = HashSet::new();
+        candidates.retain(|candidate| seen.insert(candidate.settings.canonicalize()));
+        Ok(candidates)
+    }
+}
+
+/// The checking machine: the plugs the stop implies through all the links of the crib, a
+/// part of the letter graph the menu left out tried with every partner of one of its letters
+/// and taken when exactly one fits; then the ring settings of the middle and right rotor with
+/// the starting positions that bring the rotors to the stop at the crib, picked by the
+/// decryption of the whole message. None if the stop contradicts the crib.
+pub fn check(
+    ciphertext: &str,
+    crib: &str,
+    menu: &Menu,
+    stop: &Stop,
+    metric: &Metric,
+) -> anyhow::Result<Option<Candidate>> {
+    let (letters, crib) = (crib::letters(ciphertext), crib::letters(crib));
+    let offset = menu.offset;
+    let links = crib::links(&letters, &crib, offset)
+        .ok_or_else(|| anyhow!("the crib doesn't fit at offset {offset}"))?;
+    let adjacent = adjacency(
+        &links
+            .iter()
+            .map(|&(from, to, position)| Link { from, to, position })
+            .collect::<Vec<_>>(),
+        offset,
+    );
+
+    let machine = core_machine(stop.rotors, &stop.reflector)?;
+    let (left, middle, right) = stop.positions;
+    let position = [left - 1, middle - 1, right - 1];
+    let scramble = |i: usize, c: u8| scramble(&machine, position, i, c);
+
+    let mut partners = [None; LETTERS];
+    if !deduce(
+        &adjacent,
+        &scramble,
+        &mut partners,
+        (menu.central, stop.partner),
+    ) {
+        return Ok(None);
+    }
+    let part = crib::parts(&links);
+    for x in crib::used_letters(&links).filter(|&x| part[x] == x) {
+        if (0..LETTERS).any(|y| part[y] == x && partners[y].is_some()) {
+            continue;
+        }
+        let fits = (0..LETTERS_NUM)
+            .filter_map(|partner| {
+                let mut trial = partners;
+                deduce(&adjacent, &scramble, &mut trial, (x as u8, partner)).then_some(trial)
+            })
+            .collect::<Vec<_>>();
+        match fits[..] {
+            [] => return Ok(None),
+            [fit] => partners = fit,
+            _ => {}
+        }
+    }
+    let plugboard = (0..LETTERS)
+        .filter_map(|x| {
+            let y = partners[x].filter(|&y| y as usize > x)?;
+            Some((x as u8 + 1, y + 1))
+        })
+        .collect::<Vec<_>>();
+
+    let keys = keys(&machine, stop, offset, plugboard);
+    let plaintexts = enigma::decrypt_batch(&keys, ciphertext)?;
+    let crib_matches = |plaintext: &str| {
+        let plaintext = crib::letters(plaintext);
+        crib.iter()
+            .zip(&plaintext[offset..])
+            .filter(|(c, p)| c == p)
+            .count()
+    };
+    // the first of equally good keys
+    let best = keys
+        .into_iter()
+        .zip(plaintexts)
+        .map(|(settings, plaintext)| {
+            let (matches, score) = (crib_matches(&plaintext), metric.score(&plaintext));
+            (settings, plaintext, matches, score)
+        })
+        .reduce(|best, key| match (key.2, key.3) > (best.2, best.3) {
+            true => key,
+            false => best,
+        });
+
+    Ok(
+        best.map(|(settings, plaintext, crib_matches, score)| Candidate {
+            stop: stop.clone(),
+            settings,
+            crib_matches,
+            plaintext,
+            score,
+        }),
+    )
+}
+
+// the keys with the left ring at A that bring the rotors to the stop's positions at the crib:
+// the ring settings of the middle and right rotor decide where they turn over before it
+fn keys(machine: &Machine, stop: &Stop, offset: usize, plugboard: Vec<(u8, u8)>) -> Vec<Settings> {
+    let l = LETTERS_NUM;
+    // the rotors step before every letter, the first of the crib included
+    let steps = offset + 1;
+    // the left and middle rotor after the steps from every start of the middle and right one
+    // with the left at A; the stepping only depends on the positions
+    let mut after = [[(0, 0); LETTERS]; LETTERS];
+    for right in 0..l {
+        for middle in 0..l {
+            let mut positions = [0, middle, right];
+            for _ in 0..steps {
+                machine.step(&mut positions);
+            }
+            after[right as usize][middle as usize] = (positions[0], positions[1]);
+        }
+    }
+
+    let (left, middle, right) = stop.positions;
+    let turn = |p: u8, by: usize| ((p as usize - 1 + by) % LETTERS) as u8;
+    let mut keys = Vec::new();
+    for right_ring in 0..LETTERS {
+        let right_start = turn(right, right_ring + LETTERS - steps % LETTERS);
+        for middle_ring in 0..LETTERS {
+            let middle_at_crib = turn(middle, middle_ring);
+            for middle_start in 0..l {
+                let (left_steps, middle_after) = after[right_start as usize][middle_start as usize];
+                if middle_after != middle_at_crib {
+                    continue;
+                }
+                let left_start = turn(left, LETTERS - left_steps as usize);
+                keys.push(Settings {
+                    rotors: stop.rotors,
+                    ring_settings: (1, middle_ring as u8 + 1, right_ring as u8 + 1),
+                    rotor_positions: (left_start + 1, middle_start + 1, right_start + 1),
+                    plugboard: plugboard.clone(),
+                    reflector: stop.reflector.clone(),
+                });
+            }
+        }
+    }
+    keys
+}
+
+// the rotors and reflector without a plugboard, with the rings at A
+fn core_machine(rotors: (u8, u8, u8), reflector: &Reflector) -> anyhow::Result<Machine> {
+    Machine::new(&Settings {
+        rotors,
+        ring_settings: (1, 1, 1),
+        rotor_positions: (1, 1, 1),
+        plugboard: vec![],
+        reflector: reflector.clone(),
+    })
+}
+
+// the letter scrambled at the i-th letter of the crib, only the right rotor moving on from
+// the positions
+fn scramble(machine: &Machine, positions: [u8; 3], i: usize, c: u8) -> u8 {
+    let [left, middle, right] = positions;
+    let right = ((right as usize + i) % LETTERS) as u8;
+    machine.press(c, &[left, middle, right])
+}
+
+fn adjacency(links: &[Link], offset: usize) -> Adjacency {
+    let mut adjacent = Adjacency::default();
+    for link in links {
+        let i = link.position - offset;
+        adjacent[link.from as usize].push((link.to, i));
+        adjacent[link.to as usize].push((link.from, i));
+    }
+    adjacent
+}
+
+// plugs the pair and every pair it implies through the links: a link of plaintext x and
+// ciphertext y with x plugged to p plugs y to the scrambled p, and the other way round; false
+// if a letter ends up plugged to two others
+fn deduce(
+    adjacent: &Adjacency,
+    scramble: &impl Fn(usize, u8) -> u8,
+    partners: &mut Partners,
+    pair: (u8, u8),
+) -> bool {
+    let mut pending = vec![pair];
+    while let Some((x, y)) = pending.pop() {
+        match (partners[x as usize], partners[y as usize]) {
+            (Some(p), _) if p == y => continue,
+            (None, None) => {}
+            _ => return false,
+        }
+        partners[x as usize] = Some(y);
+        partners[y as usize] = Some(x);
+
+        for &(other, i) in &adjacent[x as usize] {
+            pending.push((other, scramble(i, y)));
+        }
+        if x != y {
+            for &(other, i) in &adjacent[y as usize] {
+                pending.push((other, scramble(i, x)));
+            }
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{language::Language, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_menu() {
+        // A-B-C-A is a loop, D-X-E a chain on its own
+        let menu = Menu::new("BCAXE", "ABCDD", 0).unwrap();
+        assert_eq!(menu.loops, 1);
+        assert_eq!(menu.central, 0);
+        let link = |from, to, position| Link { from, to, position };
+        assert_eq!(
+            menu.links,
+            vec![link(0, 1, 0), link(1, 2, 1), link(2, 0, 2)]
+        );
+        assert!(menu
+            .diagram()
+            .starts_with("central letter A, 1 loops, 3 links from offset 0\nA  0-B  2-C\n"));
+
+        assert!(Menu::new("BCAXE", "ABCDD", 1).is_err());
+        assert!(Menu::new("ABC", "ABC", 0).is_err());
+    }
+
+    #[test]
+    fn test_attack() {
+        // the right rotor is past its notch for the whole crib
+        let settings = "IV I V / 05 17 09 / C X O / AQ BT CZ EK GM DW".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let crib = "THESLINGSANDARROWS";
+        let offset = 63;
+
+        let bombe = Bombe {
+            rotor_orders: vec![(2, 5, 3), (4, 1, 5)],
+            reflectors: vec![Reflector::B],
+        };
+        let metric = Metric::Bigram(Language::English);
+        let engine = gen::EngineOptions::default();
+        let candidates = bombe.attack(&ciphertext, crib, offset, &metric, &engine);
+        let candidates = candidates.unwrap();
+        let found = &candidates[0];
+        assert_eq!(found.stop.rotors, settings.rotors);
+        assert!(found.crib_matches >= crib.len() - 2);
+        assert!(found
+            .settings
+            .plugboard
+            .iter()
+            .all(|plug| settings.plugboard.contains(plug)));
+
+        // the rings and positions are equivalent to the key's, only plugs of letters the crib
+        // doesn't decide are missing
+        let completed = Settings {
+            plugboard: settings.plugboard.clone(),
+            ..found.settings.clone()
+        };
+        let plaintext = Machine::new(&completed).unwrap().decrypt(&ciphertext);
+        assert_eq!(plaintext, LONG_TEXT);
+    }
+}
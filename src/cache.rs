@@ -0,0 +1,297 @@
+use std::{
+    fmt,
+    hash::Hash,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use dashmap::DashMap;
+
+use crate::{
+    enigma::{RotorCore, Scrambler, Settings},
+    gen::Fitness,
+};
+
+/// Where a fitness cache keeps its scores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheBackend {
+    // bounded by frequency and recency, the most hits per entry but the most bookkeeping per
+    // lookup; without the `cache` feature no scores are kept
+    Moka,
+    // a dashmap, hash maps in shards behind a read-write lock each: the evaluation threads only
+    // wait on one another when one of them writes to the shard the other looks up; past the
+    // capacity an insert evicts a single entry
+    Sharded,
+    // every evaluation is computed, for short ciphertexts that score about as fast as a lookup
+    None,
+}
+
+impl Default for CacheBackend {
+    fn default() -> Self {
+        match cfg!(feature = "cache") {
+            true => CacheBackend::Moka,
+            false => CacheBackend::None,
+        }
+    }
+}
+
+impl FromStr for CacheBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moka" => Ok(CacheBackend::Moka),
+            "sharded" => Ok(CacheBackend::Sharded),
+            "none" => Ok(CacheBackend::None),
+            _ => Err(format!(
+                "unknown cache backend {s}, expected moka, sharded or none"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for CacheBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheBackend::Moka => write!(f, "moka"),
+            CacheBackend::Sharded => write!(f, "sharded"),
+            CacheBackend::None => write!(f, "none"),
+        }
+    }
+}
+
+/// The storage behind a fitness cache, a score with the revision of the ciphertext it was
+/// scored on per key; shared by the evaluation threads.
+pub trait FitnessStore<K>: Send + Sync {
+    fn get(&self, key: &K) -> Option<(Fitness, u64)>;
+
+    fn insert(&self, key: K, entry: (Fitness, u64));
+}
+
+#[cfg(feature = "cache")]
+impl<K: Hash + Eq + Send + Sync + 'static> FitnessStore<K>
+    for moka::sync::Cache<K, (Fitness, u64)>
+{
+    fn get(&self, key: &K) -> Option<(Fitness, u64)> {
+        moka::sync::Cache::get(self, key)
+    }
+
+    fn insert(&self, key: K, entry: (Fitness, u64)) {
+        moka::sync::Cache::insert(self, key, entry)
+    }
+}
+
+/// A `DashMap` of at most `capacity` scores. Past it every new key evicts one entry, whichever
+/// the map yields first, so the scores looked up often aren't dropped all at once.
+pub struct ShardedStore<K> {
+    map: DashMap<K, (Fitness, u64)>,
+    // DashMap::len locks every shard
+    len: AtomicUsize,
+    capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone> ShardedStore<K> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            map: DashMap::new(),
+            len: AtomicUsize::new(0),
+            capacity,
+        }
+    }
+
+    fn evict(&self) {
+        // the key is cloned so that no shard is locked when it's removed
+        let victim = self.map.iter().next().map(|entry| entry.key().clone());
+        if let Some(victim) = victim {
+            if self.map.remove(&victim).is_some() {
+                self.len.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone + Send + Sync> FitnessStore<K> for ShardedStore<K> {
+    fn get(&self, key: &K) -> Option<(Fitness, u64)> {
+        self.map.get(key).map(|entry| *entry)
+    }
+
+    fn insert(&self, key: K, entry: (Fitness, u64)) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.len.load(Ordering::Relaxed) >= self.capacity && !self.map.contains_key(&key) {
+            self.evict();
+        }
+        if self.map.insert(key, entry).is_none() {
+            self.len.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+pub struct NoStore;
+
+impl<K> FitnessStore<K> for NoStore {
+    fn get(&self, _key: &K) -> Option<(Fitness, u64)> {
+        None
+    }
+
+    fn insert(&self, _key: K, _entry: (Fitness, u64)) {}
+}
+
+// keyed by the cipher key, Enigma settings unless stated otherwise; a fitness is served for
+// the revision of the ciphertext it was scored on, older ones are scored again when they're
+// asked for. Clones share the scores.
+pub struct FitnessCache<K = Settings>(Arc<dyn FitnessStore<K>>);
+
+impl<K: Hash + Eq + Clone + Send + Sync + 'static> FitnessCache<K> {
+    // on the default backend
+    pub fn new(capacity: usize) -> Self {
+        Self::with_backend(CacheBackend::default(), capacity)
+    }
+
+    pub fn with_backend(backend: CacheBackend, capacity: usize) -> Self {
+        match backend {
+            #[cfg(feature = "cache")]
+            CacheBackend::Moka => Self::from_store(moka::sync::Cache::new(capacity as u64)),
+            // the command line rejects it, library callers get no cache as by default
+            #[cfg(not(feature = "cache"))]
+            CacheBackend::Moka => Self::from_store(NoStore),
+            CacheBackend::Sharded => Self::from_store(ShardedStore::new(capacity)),
+            CacheBackend::None => Self::from_store(NoStore),
+        }
+    }
+
+    pub fn from_store(store: impl FitnessStore<K> + 'static) -> Self {
+        Self(Arc::new(store))
+    }
+
+    pub fn get(&self, key: &K, revision: u64) -> Option<Fitness> {
+        self.0
+            .get(key)
+            .filter(|(_, scored)| *scored == revision)
+            .map(|(fitness, _)| fitness)
+    }
+
+    pub fn insert(&self, key: K, fitness: Fitness, revision: u64) {
+        self.0.insert(key, (fitness, revision))
+    }
+}
+
+// a derived Clone would require K: Clone
+impl<K> Clone for FitnessCache<K> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K> fmt::Debug for FitnessCache<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FitnessCache").finish_non_exhaustive()
+    }
+}
+
+/// Scramblers of the rotor configurations evaluated lately, shared by the keys that only
+/// differ in the plugboard; disabled with no capacity.
+#[cfg(feature = "cache")]
+#[derive(Debug, Clone)]
+pub struct ScramblerCache(Option<moka::sync::Cache<RotorCore, Arc<Scrambler>>>);
+
+#[cfg(feature = "cache")]
+impl ScramblerCache {
+    pub fn new(capacity: usize) -> Self {
+        Self((capacity > 0).then(|| moka::sync::Cache::new(capacity as u64)))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn get_or_insert(
+        &self,
+        core: RotorCore,
+        scrambler: impl FnOnce() -> anyhow::Result<Scrambler>,
+    ) -> anyhow::Result<Arc<Scrambler>> {
+        match &self.0 {
+            Some(cache) => cache
+                .try_get_with(core, || scrambler().map(Arc::new))
+                .map_err(|err| anyhow::anyhow!("{}", err)),
+            None => scrambler().map(Arc::new),
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Some(cache) = &self.0 {
+            cache.invalidate_all()
+        }
+    }
+}
+
+#[cfg(not(feature = "cache"))]
+#[derive(Debug, Clone)]
+pub struct ScramblerCache;
+
+#[cfg(not(feature = "cache"))]
+impl ScramblerCache {
+    pub fn new(_capacity: usize) -> Self {
+        Self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        false
+    }
+
+    pub fn get_or_insert(
+        &self,
+        _core: RotorCore,
+        scrambler: impl FnOnce() -> anyhow::Result<Scrambler>,
+    ) -> anyhow::Result<Arc<Scrambler>> {
+        scrambler().map(Arc::new)
+    }
+
+    pub fn clear(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_backends() {
+        let key = "II V III / 08 05 20 / M C U".parse::<Settings>().unwrap();
+        let backends = [CacheBackend::Moka, CacheBackend::Sharded]
+            .into_iter()
+            .filter(|&backend| backend != CacheBackend::Moka || cfg!(feature = "cache"));
+        for backend in backends {
+            let cache = FitnessCache::with_backend(backend, 1000);
+            let shared = cache.clone();
+            shared.insert(key.clone(), Fitness(0.07), 1);
+            assert_eq!(cache.get(&key, 1), Some(Fitness(0.07)), "{backend}");
+            // scored on an older ciphertext
+            assert_eq!(cache.get(&key, 2), None, "{backend}");
+        }
+
+        let none = FitnessCache::with_backend(CacheBackend::None, 1000);
+        none.insert(key.clone(), Fitness(0.07), 1);
+        assert_eq!(none.get(&key, 1), None);
+
+        // full, it evicts an entry per new key and keeps the last one inserted
+        let sharded = ShardedStore::new(64);
+        for i in 0..1000u32 {
+            sharded.insert(i, (Fitness(i as f64), 0));
+        }
+        let kept = (0..1000u32).filter(|i| sharded.get(i).is_some()).count();
+        assert_eq!(kept, 64);
+        assert_eq!(sharded.get(&999), Some((Fitness(999.0), 0)));
+        // a key already kept is updated without an eviction
+        sharded.insert(999, (Fitness(1.0), 1));
+        assert_eq!(sharded.map.len(), 64);
+        assert_eq!(sharded.get(&999), Some((Fitness(1.0), 1)));
+        let disabled = ShardedStore::new(0);
+        disabled.insert(1, (Fitness(1.0), 0));
+        assert_eq!(disabled.get(&1), None);
+        assert_eq!("sharded".parse(), Ok(CacheBackend::Sharded));
+    }
+}
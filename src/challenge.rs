@@ -0,0 +1,228 @@
+// ciphertexts with hidden keys for exercises and for benchmarking the solver
+use std::{
+    fmt::{self, Write as _},
+    fs,
+    path::Path,
+    str::FromStr,
+};
+
+use rand::Rng;
+
+use crate::{
+    engine::GenomeBuilder,
+    enigma::{normalize, Machine, Settings},
+    gen::{self, ReflectorSearch, SettingsBuilder},
+    metrics::ENGLISH_SAMPLE,
+};
+
+// letters of the ciphertext are written in groups of five, as they were sent
+const GROUP_SIZE: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Expert,
+}
+
+/// What makes a challenge hard: shorter messages carry less statistics, every plug and an
+/// unknown reflector multiply the keyspace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub message_length: usize,
+    pub plugs: usize,
+    pub reflector: ReflectorSearch,
+}
+
+impl Difficulty {
+    pub fn level(&self) -> Level {
+        let (message_length, plugs, reflector) = match self {
+            Difficulty::Easy => (600, 0, ReflectorSearch::default()),
+            Difficulty::Medium => (400, 2, ReflectorSearch::Standard),
+            Difficulty::Hard => (250, 5, ReflectorSearch::Standard),
+            Difficulty::Expert => (150, 10, ReflectorSearch::Rewirable),
+        };
+
+        Level {
+            message_length,
+            plugs,
+            reflector,
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+            Difficulty::Expert => write!(f, "expert"),
+        }
+    }
+}
+
+impl FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            "expert" => Ok(Difficulty::Expert),
+            _ => Err(format!(
+                "unknown difficulty {s}, expected easy, medium, hard or expert"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub settings: Settings,
+    // letters only, word breaks would give the words away
+    pub plaintext: String,
+    // in groups of five letters
+    pub ciphertext: String,
+}
+
+impl Challenge {
+    /// A random passage of English prose encrypted with a random key of the given level.
+    pub fn generate<R: Rng>(level: &Level, rng: &mut R) -> anyhow::Result<Self> {
+        let mut settings = SettingsBuilder {
+            max_plugs: 0,
+            reflector: level.reflector.clone(),
+            herivel_spread: None,
+            fixed_rings: false,
+        }
+        .build_genome(0, rng);
+        for _ in 0..level.plugs {
+            gen::add_plug(&mut settings.plugboard, rng);
+        }
+
+        let plaintext = passage(level.message_length, rng);
+        let ciphertext = Machine::new(&settings)?.encrypt(&plaintext);
+
+        Ok(Self {
+            settings,
+            plaintext,
+            ciphertext: groups(&ciphertext),
+        })
+    }
+}
+
+// consecutive words from a random place in the sample, wrapping around at its end
+fn passage<R: Rng>(length: usize, rng: &mut R) -> String {
+    let sample = normalize(ENGLISH_SAMPLE);
+    let words = sample.split_whitespace().collect::<Vec<_>>();
+    let start = rng.gen_range(0..words.len());
+
+    words
+        .iter()
+        .cycle()
+        .skip(start)
+        .flat_map(|word| word.chars())
+        .take(length)
+        .collect()
+}
+
+pub(crate) fn groups(text: &str) -> String {
+    text.as_bytes()
+        .chunks(GROUP_SIZE)
+        .map(|group| String::from_utf8_lossy(group))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes every ciphertext to its own file, challenge-01.txt and so on, into `dir` and the
+/// keys with their plaintexts to `answers`, which is meant to be kept apart from them.
+/// Answers are tab separated: file, key in key sheet notation, plaintext.
+pub fn write(dir: &Path, answers: &Path, challenges: &[Challenge]) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let width = challenges.len().to_string().len().max(2);
+
+    let mut lines = String::from("file\tkey\tplaintext\n");
+    for (i, challenge) in challenges.iter().enumerate() {
+        let name = format!("challenge-{:0width$}.txt", i + 1);
+        fs::write(dir.join(&name), format!("{}\n", challenge.ciphertext))?;
+        writeln!(
+            lines,
+            "{}\t{}\t{}",
+            name, challenge.settings, challenge.plaintext
+        )?;
+    }
+    fs::write(answers, lines)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::enigma::Reflector;
+
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        for difficulty in [
+            Difficulty::Easy,
+            Difficulty::Medium,
+            Difficulty::Hard,
+            Difficulty::Expert,
+        ] {
+            assert_eq!(difficulty.to_string().parse(), Ok(difficulty));
+            let level = difficulty.level();
+            let challenge = Challenge::generate(&level, &mut rng).unwrap();
+
+            assert_eq!(challenge.plaintext.len(), level.message_length);
+            assert!(challenge.plaintext.chars().all(|c| c.is_ascii_uppercase()));
+            assert_eq!(challenge.settings.plugboard.len(), level.plugs);
+            assert!(challenge
+                .ciphertext
+                .split(' ')
+                .all(|group| group.len() <= GROUP_SIZE));
+            let machine = Machine::new(&challenge.settings).unwrap();
+            assert_eq!(
+                machine.decrypt(&challenge.ciphertext.replace(' ', "")),
+                challenge.plaintext
+            );
+        }
+
+        let expert = Challenge::generate(&Difficulty::Expert.level(), &mut rng).unwrap();
+        assert!(matches!(expert.settings.reflector, Reflector::D(_)));
+        assert!("impossible".parse::<Difficulty>().is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = std::env::temp_dir().join(format!("enigmagen-challenge-{}", std::process::id()));
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let challenges = (0..3)
+            .map(|_| Challenge::generate(&Difficulty::Easy.level(), &mut rng).unwrap())
+            .collect::<Vec<_>>();
+        let answers = dir.join("answers.tsv");
+        write(&dir.join("challenges"), &answers, &challenges).unwrap();
+
+        let ciphertext = fs::read_to_string(dir.join("challenges/challenge-03.txt")).unwrap();
+        assert_eq!(ciphertext.trim_end(), challenges[2].ciphertext);
+        let answers = fs::read_to_string(answers).unwrap();
+        let last = answers
+            .lines()
+            .last()
+            .unwrap()
+            .split('\t')
+            .collect::<Vec<_>>();
+        assert_eq!(last[0], "challenge-03.txt");
+        assert_eq!(last[1].parse::<Settings>().unwrap(), challenges[2].settings);
+        assert_eq!(last[2], challenges[2].plaintext);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+};
+
+use crate::{cache::ScramblerCache, metrics::index_of_coincidence};
+
+/// A classical cipher the solvers can attack: a key, the machine built from it,
+/// and a fitness metric that tells right keys from wrong ones.
+pub trait Cipher: Sized {
+    type Key: Clone + Eq + Hash + fmt::Debug + Send + Sync + 'static;
+
+    const MODEL_NAME: &'static str;
+
+    fn new(key: &Self::Key) -> anyhow::Result<Self>;
+
+    fn encrypt(&self, text: &str) -> String;
+
+    fn decrypt(&self, text: &str) -> String;
+
+    // decryption under the key sharing work between keys through the cache, for rotor
+    // machines whose keys only differ in the plugboard; a fresh machine by default
+    fn decrypt_shared(
+        key: &Self::Key,
+        ciphertext: &str,
+        _scramblers: &ScramblerCache,
+    ) -> anyhow::Result<String> {
+        Ok(Self::new(key)?.decrypt(ciphertext))
+    }
+
+    // decryptions under many keys, an error for an invalid key; a machine at a time by default
+    fn decrypt_batch(keys: &[&Self::Key], ciphertext: &str) -> Vec<anyhow::Result<String>> {
+        keys.iter()
+            .map(|key| Ok(Self::new(key)?.decrypt(ciphertext)))
+            .collect()
+    }
+
+    // the one of the keys that decrypt alike this key is cached under, the key itself by default
+    fn canonical(key: &Self::Key) -> Self::Key {
+        key.clone()
+    }
+
+    // a number per key to count distinct keys by, a 64 bit hash by default, for which
+    // collisions are negligible at the numbers of keys a search evaluates
+    fn pack(key: &Self::Key) -> u128 {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish() as u128
+    }
+
+    // number of distinct keys of the model
+    fn keyspace_size() -> u128;
+
+    // higher is closer to the plaintext, the index of coincidence suits polyalphabetic ciphers
+    fn score(plaintext: &str) -> f64 {
+        index_of_coincidence(plaintext)
+    }
+}
@@ -0,0 +1,625 @@
+// plugboard hill climb with the rotors fixed, as in Gillogly's and Weierud and Sullivan's
+// attacks: a plug change only touches the positions where one of its letters is involved,
+// so a candidate is scored by updating the letter counts and n-grams of those positions
+use std::{fmt, str::FromStr, sync::Arc};
+
+use anyhow::bail;
+
+use crate::{
+    enigma::{self, Scrambler, Settings, LETTERS_NUM},
+    language::Language,
+    metrics::Metric,
+    ngrams::NgramTable,
+};
+
+// score improvements below this are rounding noise of the running sums
+const MIN_IMPROVEMENT: f64 = 1e-12;
+
+const LETTERS: usize = LETTERS_NUM as usize;
+
+/// How the plugboard is refined: hill climbing until no plug change improves the score, or
+/// tabu search from there, which takes the best change that isn't tabu even if it is worse
+/// and forbids changing the plugs it changed for `tenure` iterations; so it gets over the
+/// ridges where two plugs have to change at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlugboardSearch {
+    #[default]
+    HillClimb,
+    Tabu {
+        tenure: usize,
+        iterations: usize,
+    },
+}
+
+impl fmt::Display for PlugboardSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlugboardSearch::HillClimb => write!(f, "hill"),
+            PlugboardSearch::Tabu { tenure, iterations } => {
+                write!(f, "tabu:{tenure}:{iterations}")
+            }
+        }
+    }
+}
+
+// hill or tabu[:<tenure>[:<iterations>]]
+impl FromStr for PlugboardSearch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("unknown plugboard search {s}, expected hill or tabu:10:200");
+        let mut parts = s.split(':');
+        match parts.next() {
+            Some("hill") if parts.next().is_none() => Ok(PlugboardSearch::HillClimb),
+            Some("tabu") => {
+                let mut next = |default| {
+                    parts
+                        .next()
+                        .map_or(Ok(default), |n| n.parse().map_err(|_| invalid()))
+                };
+                let search = PlugboardSearch::Tabu {
+                    tenure: next(10)?,
+                    iterations: next(200)?,
+                };
+                match parts.next() {
+                    Some(_) => Err(invalid()),
+                    None => Ok(search),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+enum Scorer {
+    // scored from the letter histogram alone
+    Histogram(Metric),
+    Ngrams(usize, NgramModel),
+}
+
+enum NgramModel {
+    Bigrams(Language),
+    Table(Arc<NgramTable>),
+}
+
+impl Scorer {
+    fn new(metric: &Metric) -> anyhow::Result<Self> {
+        Ok(match metric {
+            Metric::Bigram(language) => Scorer::Ngrams(2, NgramModel::Bigrams(*language)),
+            Metric::Ngrams(_, table) => {
+                Scorer::Ngrams(table.order(), NgramModel::Table(table.clone()))
+            }
+            Metric::Trimmed(..) | Metric::Words(..) | Metric::Composite(_) => bail!(
+                "the plugboard climb scores with ioc, sinkov, entropy, bigram or ngrams, not {}",
+                metric
+            ),
+            metric => Scorer::Histogram(metric.clone()),
+        })
+    }
+
+    fn order(&self) -> usize {
+        match self {
+            Scorer::Histogram(_) => 0,
+            Scorer::Ngrams(order, _) => *order,
+        }
+    }
+
+    fn log_prob(&self, ngram: &[u8]) -> f64 {
+        match self {
+            Scorer::Histogram(_) => 0.0,
+            Scorer::Ngrams(_, NgramModel::Bigrams(language)) => {
+                language.bigram_log_prob(ngram[0] as usize, ngram[1] as usize)
+            }
+            Scorer::Ngrams(_, NgramModel::Table(table)) => {
+                let index = ngram
+                    .iter()
+                    .fold(0, |index, &c| index * LETTERS + c as usize);
+                table.log_prob_of(index)
+            }
+        }
+    }
+
+    // as Metric::score of the decryption
+    fn score(
+        &self,
+        hist: &[usize; LETTERS],
+        letters: usize,
+        ngrams: usize,
+        log_likelihood: f64,
+    ) -> f64 {
+        match self {
+            Scorer::Histogram(metric) => metric.score_counts(hist, letters).unwrap_or_default(),
+            Scorer::Ngrams(..) => match ngrams {
+                0 => 0.0,
+                ngrams => (log_likelihood / ngrams as f64).exp(),
+            },
+        }
+    }
+}
+
+/// The decryption under the rotors of the settings and a plugboard that changes by a plug
+/// at a time; with plugboard P the letter c at position i decrypts to P(S_i(P(c))).
+pub struct PlugboardClimb {
+    scorer: Scorer,
+    scrambler: Scrambler,
+    // ciphertext letters, A = 0, and the positions of every letter
+    cipher: Vec<u8>,
+    // key presses before every letter, garbles included; no n-gram spans a garble
+    keystrokes: Vec<usize>,
+    // starts of the n-grams of the scorer's order
+    ngram_starts: Vec<bool>,
+    ngram_count: usize,
+    by_cipher: [Vec<usize>; LETTERS],
+    wiring: [u8; LETTERS],
+    // S_i(P(c)) and the positions by it
+    scrambled: Vec<u8>,
+    by_scrambled: [Vec<usize>; LETTERS],
+    plain: Vec<u8>,
+    hist: [usize; LETTERS],
+    log_likelihood: f64,
+    score: f64,
+    evaluations: usize,
+    // positions and n-grams touched by the candidate being scored, marked with its number
+    marks: Vec<usize>,
+    ngram_marks: Vec<usize>,
+    touched: Vec<usize>,
+    ngrams: Vec<usize>,
+}
+
+impl PlugboardClimb {
+    pub fn new(ciphertext: &str, settings: &Settings, metric: &Metric) -> anyhow::Result<Self> {
+        settings.validate()?;
+        let (keystrokes, cipher) = enigma::normalize(ciphertext)
+            .bytes()
+            .filter(|&c| c != b' ')
+            .enumerate()
+            .filter(|&(_, c)| c.is_ascii_uppercase())
+            .map(|(i, c)| (i, c - b'A'))
+            .unzip::<_, _, Vec<_>, Vec<_>>();
+
+        let scorer = Scorer::new(metric)?;
+        let order = scorer.order().max(1);
+        let ngram_starts = (0..cipher.len())
+            .map(|i| {
+                let end = keystrokes.get(i + order - 1);
+                end.is_some_and(|&end| end - keystrokes[i] == order - 1)
+            })
+            .collect::<Vec<_>>();
+        let ngram_count = ngram_starts.iter().filter(|&&start| start).count();
+
+        let mut by_cipher: [Vec<usize>; LETTERS] = Default::default();
+        for (i, &c) in cipher.iter().enumerate() {
+            by_cipher[c as usize].push(i);
+        }
+
+        let mut climb = Self {
+            scorer,
+            scrambler: Scrambler::new(settings, enigma::keystrokes(ciphertext))?,
+            by_cipher,
+            wiring: wiring(&settings.plugboard),
+            scrambled: vec![0; cipher.len()],
+            by_scrambled: Default::default(),
+            plain: vec![0; cipher.len()],
+            hist: [0; LETTERS],
+            log_likelihood: 0.0,
+            score: 0.0,
+            evaluations: 0,
+            marks: vec![0; cipher.len()],
+            ngram_marks: vec![0; cipher.len()],
+            touched: Vec::new(),
+            ngrams: Vec::new(),
+            cipher,
+            keystrokes,
+            ngram_starts,
+            ngram_count,
+        };
+        climb.decrypt();
+        Ok(climb)
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    // candidate plugboards scored so far
+    pub fn evaluations(&self) -> usize {
+        self.evaluations
+    }
+
+    pub fn plugboard(&self) -> Vec<(u8, u8)> {
+        (0..LETTERS as u8)
+            .filter(|&a| self.wiring[a as usize] > a)
+            .map(|a| (a + 1, self.wiring[a as usize] + 1))
+            .collect()
+    }
+
+    // the whole decryption, after a plugboard is accepted
+    fn decrypt(&mut self) {
+        self.by_scrambled.iter_mut().for_each(Vec::clear);
+        self.hist = [0; LETTERS];
+
+        for (i, &c) in self.cipher.iter().enumerate() {
+            let s = self
+                .scrambler
+                .swap(self.keystrokes[i], self.wiring[c as usize]);
+            self.scrambled[i] = s;
+            self.by_scrambled[s as usize].push(i);
+            self.plain[i] = self.wiring[s as usize];
+            self.hist[self.plain[i] as usize] += 1;
+        }
+
+        let order = self.scorer.order();
+        self.log_likelihood = match order {
+            0 => 0.0,
+            order => self
+                .plain
+                .windows(order)
+                .zip(&self.ngram_starts)
+                .filter(|&(_, &start)| start)
+                .map(|(ngram, _)| self.scorer.log_prob(ngram))
+                .sum(),
+        };
+        self.score = self.scorer.score(
+            &self.hist,
+            self.plain.len(),
+            self.ngram_count,
+            self.log_likelihood,
+        );
+    }
+
+    /// Score of the decryption under another plugboard that differs from the current one in
+    /// the wiring of the `changed` letters only, A = 0; the current decryption is kept.
+    fn evaluate(&mut self, wiring: &[u8; LETTERS], changed: &[u8]) -> f64 {
+        self.evaluations += 1;
+        let mark = self.evaluations;
+
+        // positions whose ciphertext letter or scrambled letter is replugged
+        self.touched.clear();
+        for &c in changed {
+            for &i in self.by_cipher[c as usize]
+                .iter()
+                .chain(&self.by_scrambled[c as usize])
+            {
+                if self.marks[i] != mark {
+                    self.marks[i] = mark;
+                    self.touched.push(i);
+                }
+            }
+        }
+
+        // n-grams over a touched position, scored before and after
+        let order = self.scorer.order();
+        self.ngrams.clear();
+        if order > 0 && self.plain.len() >= order {
+            let last = self.plain.len() - order;
+            for &i in &self.touched {
+                for start in i.saturating_sub(order - 1)..=i.min(last) {
+                    if self.ngram_starts[start] && self.ngram_marks[start] != mark {
+                        self.ngram_marks[start] = mark;
+                        self.ngrams.push(start);
+                    }
+                }
+            }
+        }
+        let ngrams_log_likelihood = |climb: &Self| {
+            climb
+                .ngrams
+                .iter()
+                .map(|&start| climb.scorer.log_prob(&climb.plain[start..start + order]))
+                .sum::<f64>()
+        };
+
+        let before = ngrams_log_likelihood(self);
+        let mut hist = self.hist;
+        let mut saved = Vec::with_capacity(self.touched.len());
+        for &i in &self.touched {
+            let s = match changed.contains(&self.cipher[i]) {
+                true => {
+                    let c = self.cipher[i];
+                    self.scrambler.swap(self.keystrokes[i], wiring[c as usize])
+                }
+                false => self.scrambled[i],
+            };
+            let p = wiring[s as usize];
+            hist[self.plain[i] as usize] -= 1;
+            hist[p as usize] += 1;
+            saved.push(self.plain[i]);
+            self.plain[i] = p;
+        }
+        let after = ngrams_log_likelihood(self);
+
+        for (&i, &p) in self.touched.iter().zip(&saved) {
+            self.plain[i] = p;
+        }
+        self.scorer.score(
+            &hist,
+            self.plain.len(),
+            self.ngram_count,
+            self.log_likelihood - before + after,
+        )
+    }
+
+    /// Tries every pair of letters as a plug, also in place of the plugs either letter is
+    /// in, and keeps a change as soon as it improves the score, until none does. Returns the
+    /// final score.
+    pub fn run(&mut self, max_plugs: usize) -> f64 {
+        loop {
+            let mut improved = false;
+
+            for a in 0..LETTERS as u8 {
+                for b in a + 1..LETTERS as u8 {
+                    let best = self
+                        .candidates(a, b, max_plugs)
+                        .into_iter()
+                        .map(|(wiring, changed)| (self.evaluate(&wiring, &changed), wiring))
+                        .max_by(|x, y| x.0.total_cmp(&y.0));
+
+                    if let Some((score, wiring)) = best {
+                        if score > self.score + MIN_IMPROVEMENT {
+                            self.wiring = wiring;
+                            self.decrypt();
+                            improved = true;
+                        }
+                    }
+                }
+            }
+
+            if !improved {
+                return self.score;
+            }
+        }
+    }
+
+    // runs the search, returns the final score
+    pub fn refine(&mut self, max_plugs: usize, search: PlugboardSearch) -> f64 {
+        match search {
+            PlugboardSearch::HillClimb => self.run(max_plugs),
+            PlugboardSearch::Tabu { tenure, iterations } => {
+                self.tabu(max_plugs, tenure, iterations)
+            }
+        }
+    }
+
+    /// Hill climbs, then runs tabu search for the iterations and keeps the best plugboard it
+    /// came across. A change that beats the best so far is taken even if it is tabu. Returns
+    /// the final score.
+    pub fn tabu(&mut self, max_plugs: usize, tenure: usize, iterations: usize) -> f64 {
+        self.run(max_plugs);
+        let (mut best_score, mut best_wiring) = (self.score, self.wiring);
+        // the iteration until which the plug of a pair may not change
+        let mut tabu_until = [[0; LETTERS]; LETTERS];
+
+        for iteration in 1..=iterations {
+            let mut chosen: Option<(f64, [u8; LETTERS])> = None;
+            for a in 0..LETTERS as u8 {
+                for b in a + 1..LETTERS as u8 {
+                    for (wiring, changed) in self.candidates(a, b, max_plugs) {
+                        let score = self.evaluate(&wiring, &changed);
+                        let is_tabu = changed_pairs(&self.wiring, &wiring)
+                            .any(|(x, y)| tabu_until[x][y] >= iteration);
+                        if (!is_tabu || score > best_score + MIN_IMPROVEMENT)
+                            && chosen.is_none_or(|(chosen, _)| score > chosen)
+                        {
+                            chosen = Some((score, wiring));
+                        }
+                    }
+                }
+            }
+
+            let Some((_, wiring)) = chosen else {
+                break;
+            };
+            for (x, y) in changed_pairs(&self.wiring, &wiring).collect::<Vec<_>>() {
+                tabu_until[x][y] = iteration + tenure;
+            }
+            self.wiring = wiring;
+            self.decrypt();
+            if self.score > best_score + MIN_IMPROVEMENT {
+                (best_score, best_wiring) = (self.score, self.wiring);
+            }
+        }
+
+        self.wiring = best_wiring;
+        self.decrypt();
+        self.score
+    }
+
+    // plugboards that plug a and b together, or unplug them if they are, with the letters
+    // they were plugged to left unplugged or plugged together; and the replugged letters
+    fn candidates(&self, a: u8, b: u8, max_plugs: usize) -> Vec<([u8; LETTERS], Vec<u8>)> {
+        let current = &self.wiring;
+        let (pa, pb) = (current[a as usize], current[b as usize]);
+
+        if pa == b {
+            let mut wiring = *current;
+            wiring.swap(a as usize, b as usize);
+            return vec![(wiring, vec![a, b])];
+        }
+
+        let mut unplugged = *current;
+        unplugged[pa as usize] = pa;
+        unplugged[pb as usize] = pb;
+        let mut plugged = unplugged;
+        plugged[a as usize] = b;
+        plugged[b as usize] = a;
+
+        let mut changed = vec![a, b];
+        changed.extend([pa, pb].into_iter().filter(|&p| p != a && p != b));
+
+        let mut candidates = vec![(plugged, changed.clone())];
+        if pa != a && pb != b {
+            let mut swapped = plugged;
+            swapped[pa as usize] = pb;
+            swapped[pb as usize] = pa;
+            candidates.push((swapped, changed));
+        }
+        candidates.retain(|(wiring, _)| plugs(wiring) <= max_plugs);
+        candidates
+    }
+}
+
+fn wiring(pairs: &[(u8, u8)]) -> [u8; LETTERS] {
+    let mut wiring = std::array::from_fn(|i| i as u8);
+    for &(a, b) in pairs {
+        wiring.swap(a as usize - 1, b as usize - 1);
+    }
+    wiring
+}
+
+// the pairs x < y plugged in one wiring and not in the other
+fn changed_pairs<'a>(
+    from: &'a [u8; LETTERS],
+    to: &'a [u8; LETTERS],
+) -> impl Iterator<Item = (usize, usize)> + 'a {
+    (0..LETTERS)
+        .filter(|&x| from[x] != to[x])
+        .flat_map(|x| [(x, from[x] as usize), (x, to[x] as usize)])
+        .filter(|&(x, y)| x < y)
+}
+
+fn plugs(wiring: &[u8; LETTERS]) -> usize {
+    wiring
+        .iter()
+        .enumerate()
+        .filter(|&(i, &p)| p as usize > i)
+        .count()
+}
+
+/// The settings with the plugboard refined from theirs for the metric, and its score.
+pub fn climb(
+    ciphertext: &str,
+    settings: &Settings,
+    metric: &Metric,
+    max_plugs: usize,
+    search: PlugboardSearch,
+) -> anyhow::Result<(Settings, f64)> {
+    let mut climb = PlugboardClimb::new(ciphertext, settings, metric)?;
+    let score = climb.refine(max_plugs, search);
+
+    Ok((
+        Settings {
+            plugboard: climb.plugboard(),
+            ..settings.clone()
+        },
+        score,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::{enigma::Machine, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let settings = "II V III / 08 05 20 / M C U".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        // unreadable letters step the rotors and break the n-grams
+        let garbled = ciphertext.replacen("W", "?", 3).replacen("M", "*", 2);
+
+        for (ciphertext, metric) in [
+            (&ciphertext, "ioc"),
+            (&ciphertext, "sinkov"),
+            (&ciphertext, "entropy"),
+            (&ciphertext, "bigram"),
+            (&ciphertext, "bigram:german"),
+            (&garbled, "bigram"),
+            (&garbled, "ioc"),
+        ] {
+            let metric = metric.parse::<Metric>().unwrap();
+            let mut climb = PlugboardClimb::new(ciphertext, &settings, &metric).unwrap();
+
+            // the running scores follow random plug changes as a full decryption would
+            for _ in 0..50 {
+                let (a, b) = (rng.gen_range(0..26), rng.gen_range(0..26));
+                if a == b {
+                    continue;
+                }
+                for (wiring, changed) in climb.candidates(a.min(b), a.max(b), 13) {
+                    let score = climb.evaluate(&wiring, &changed);
+                    let plugboard = Settings {
+                        plugboard: (0..26u8)
+                            .filter(|&x| wiring[x as usize] > x)
+                            .map(|x| (x + 1, wiring[x as usize] + 1))
+                            .collect(),
+                        ..settings.clone()
+                    };
+                    let plaintext = Machine::new(&plugboard).unwrap().decrypt(ciphertext);
+                    assert_relative_eq!(score, metric.score(&plaintext), max_relative = 1e-9);
+
+                    if rng.gen_bool(0.3) {
+                        climb.wiring = wiring;
+                        climb.decrypt();
+                        assert_relative_eq!(climb.score(), score, max_relative = 1e-9);
+                    }
+                }
+            }
+        }
+
+        let composite = "0.5*ioc+0.5*bigram".parse::<Metric>().unwrap();
+        assert!(PlugboardClimb::new(&ciphertext, &settings, &composite).is_err());
+    }
+
+    #[test]
+    fn test_climb() {
+        let settings = "II V III / 08 05 20 / M C U / AQ BT CZ EK FX HM".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let unplugged = Settings {
+            plugboard: vec![],
+            ..settings.clone()
+        };
+        let metric = Metric::Bigram(Language::English);
+
+        let (found, score) =
+            climb(&ciphertext, &unplugged, &metric, 10, Default::default()).unwrap();
+        assert_eq!(found, settings);
+        assert_relative_eq!(score, metric.score(LONG_TEXT), max_relative = 1e-9);
+
+        // no plugs allowed, nothing to climb
+        let (found, _) = climb(&ciphertext, &unplugged, &metric, 0, Default::default()).unwrap();
+        assert_eq!(found, unplugged);
+    }
+
+    #[test]
+    fn test_tabu() {
+        for s in ["hill", "tabu:10:50"] {
+            assert_eq!(s.parse::<PlugboardSearch>().unwrap().to_string(), s);
+        }
+        assert_eq!(
+            "tabu".parse::<PlugboardSearch>(),
+            Ok(PlugboardSearch::Tabu {
+                tenure: 10,
+                iterations: 200
+            })
+        );
+        assert!("hill:3".parse::<PlugboardSearch>().is_err());
+
+        // the hill climb gets stuck a plug short on this key
+        let settings = "II V III / 14 19 15 / M C U / BI CP EJ FW HX LU NV QT".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let unplugged = Settings {
+            plugboard: vec![],
+            ..settings.clone()
+        };
+        let metric = Metric::Bigram(Language::English);
+
+        let (hill, _) = climb(&ciphertext, &unplugged, &metric, 10, Default::default()).unwrap();
+        assert_ne!(hill, settings);
+
+        let tabu = PlugboardSearch::Tabu {
+            tenure: 10,
+            iterations: 50,
+        };
+        let (found, score) = climb(&ciphertext, &unplugged, &metric, 10, tabu).unwrap();
+        assert_eq!(found, settings);
+        assert_relative_eq!(score, metric.score(LONG_TEXT), max_relative = 1e-9);
+    }
+}
@@ -0,0 +1,134 @@
+// decryptions of competing keys side by side, so a reader sees at a glance where they part and
+// which one reads as plaintext when their scores are close
+use std::fmt::Write;
+
+// ANSI escapes of the letters a decryption gets right and wrong
+const RIGHT: &str = "\x1b[1;32m";
+const WRONG: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// The texts in aligned blocks of `width` characters, a row per text labelled #1, #2 and so
+/// on, and under every row but the first a row with ^ under the characters where it differs
+/// from the first text; rows that agree with it get none. The texts decrypt the same
+/// ciphertext, so their word breaks and garbles are in the same places.
+pub fn side_by_side(texts: &[String], width: usize) -> String {
+    let width = width.max(1);
+    let len = texts
+        .iter()
+        .map(|text| text.chars().count())
+        .max()
+        .unwrap_or(0);
+    // padded to the longest, in case they aren't of the same ciphertext after all
+    let rows = texts
+        .iter()
+        .map(|text| {
+            let mut row = text.chars().collect::<Vec<_>>();
+            row.resize(len, ' ');
+            row
+        })
+        .collect::<Vec<_>>();
+    let label_width = format!("#{}", rows.len()).len();
+
+    let mut out = String::new();
+    for start in (0..len).step_by(width) {
+        let end = (start + width).min(len);
+        for (i, row) in rows.iter().enumerate() {
+            let label = format!("#{}", i + 1);
+            let line = row[start..end].iter().collect::<String>();
+            writeln!(out, "{label:>label_width$} {}", line.trim_end()).unwrap();
+            if i == 0 {
+                continue;
+            }
+            let marks = row[start..end]
+                .iter()
+                .zip(&rows[0][start..end])
+                .map(|(c, first)| if c == first { ' ' } else { '^' })
+                .collect::<String>();
+            if marks.contains('^') {
+                writeln!(out, "{:label_width$} {}", "", marks.trim_end()).unwrap();
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The decryption with the letters that match the known plaintext in bold green and the others
+/// in red, anything else than A..Z left as it is, and how many letters match of how many.
+/// Without `color` the wrong letters are lowercased instead, for pipes and NO_COLOR.
+pub fn highlight(text: &str, known: &str, color: bool) -> (String, usize, usize) {
+    let (mut right, mut letters) = (0, 0);
+    let mut out = String::new();
+    let mut current = "";
+    let mut known = known.chars();
+    for c in text.chars() {
+        let expected = known.next();
+        let next = match c.is_ascii_uppercase() {
+            true if expected == Some(c) => RIGHT,
+            true => WRONG,
+            false => "",
+        };
+        if color && next != current {
+            out.push_str(if next.is_empty() { RESET } else { next });
+            current = next;
+        }
+        out.push(match color || next != WRONG {
+            true => c,
+            false => c.to_ascii_lowercase(),
+        });
+        letters += c.is_ascii_uppercase() as usize;
+        right += (next == RIGHT) as usize;
+    }
+    if !current.is_empty() {
+        out.push_str(RESET);
+    }
+    (out, right, letters)
+}
+
+/// Characters where each text differs from the first one.
+pub fn differences(texts: &[String]) -> Vec<usize> {
+    let Some(first) = texts.first() else {
+        return Vec::new();
+    };
+    texts
+        .iter()
+        .map(|text| {
+            let (a, b) = (first.chars().count(), text.chars().count());
+            let differing = first.chars().zip(text.chars()).filter(|(x, y)| x != y);
+            differing.count() + a.abs_diff(b)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_side_by_side() {
+        let texts = ["TOBE ORNOT", "TOBX ORNOT", "TOBE ORNOT"].map(String::from);
+        let expected = [
+            "#1 TOBE O",
+            "#2 TOBX O",
+            "      ^",
+            "#3 TOBE O",
+            "",
+            "#1 RNOT",
+            "#2 RNOT",
+            "#3 RNOT",
+            "",
+            "",
+        ];
+        assert_eq!(side_by_side(&texts, 6), expected.join("\n"));
+        assert_eq!(differences(&texts), [0, 1, 0]);
+        assert_eq!(differences(&["AB".into(), "ABC".into()]), [0, 1]);
+        assert_eq!(side_by_side(&[], 10), "");
+
+        let (line, right, letters) = highlight("TOBX OR", "TOBE OR", true);
+        assert_eq!((right, letters), (5, 6));
+        let expected = format!("{RIGHT}TOB{WRONG}X{RESET} {RIGHT}OR{RESET}");
+        assert_eq!(line, expected);
+        let (line, right, letters) = highlight("TOBX OR", "TOBE OR", false);
+        assert_eq!((line.as_str(), right, letters), ("TOBx OR", 5, 6));
+    }
+}
@@ -0,0 +1,268 @@
+// genevo bindings for the operators of this crate, kept while migrating to the in-crate engine
+use std::fmt::Debug;
+
+use chrono::Duration;
+use genevo::{
+    genetic::{Children, Parents},
+    operator::{
+        prelude::{ElitistReinserter, MaximizeSelector, RandomValueMutator, UniformCrossBreeder},
+        CrossoverOp, GeneticOperator, MutationOp,
+    },
+    prelude::{
+        build_population, genetic_algorithm, or, simulate, Fitness as GaFitness, FitnessFunction,
+        FitnessLimit, GenerationLimit, GenomeBuilder, Genotype, Rng, SimResult, Simulation as _,
+        SimulationBuilder as _, TimeLimit,
+    },
+};
+
+use crate::{
+    dna::{self, Encoding},
+    engine,
+    enigma::Settings,
+    gen::{self, Fitness},
+    sim::{Generation, Step, StepResult},
+};
+
+impl Genotype for Settings {
+    type Dna = u8;
+}
+
+impl GaFitness for Fitness {
+    fn zero() -> Self {
+        Fitness(0.0)
+    }
+
+    fn abs_diff(&self, other: &Self) -> Self {
+        Fitness((self.0 - other.0).abs())
+    }
+}
+
+impl FitnessFunction<Settings, Fitness> for gen::FitnessCalc {
+    fn fitness_of(&self, s: &Settings) -> Fitness {
+        engine::FitnessFunction::fitness_of(self, s)
+    }
+
+    fn average(&self, fitness_values: &[Fitness]) -> Fitness {
+        Fitness(gen::fitness_stats(fitness_values).mean)
+    }
+
+    fn highest_possible_fitness(&self) -> Fitness {
+        Fitness(1.0)
+    }
+
+    fn lowest_possible_fitness(&self) -> Fitness {
+        Fitness(0.0)
+    }
+}
+
+impl FitnessFunction<Vec<u8>, Fitness> for dna::DnaFitnessCalc {
+    fn fitness_of(&self, dna: &Vec<u8>) -> Fitness {
+        engine::FitnessFunction::fitness_of(self, dna)
+    }
+
+    fn average(&self, fitness_values: &[Fitness]) -> Fitness {
+        self.inner.average(fitness_values)
+    }
+
+    fn highest_possible_fitness(&self) -> Fitness {
+        self.inner.highest_possible_fitness()
+    }
+
+    fn lowest_possible_fitness(&self) -> Fitness {
+        self.inner.lowest_possible_fitness()
+    }
+}
+
+impl GenomeBuilder<Settings> for gen::SettingsBuilder {
+    fn build_genome<R>(&self, index: usize, rng: &mut R) -> Settings
+    where
+        R: Rng + Sized,
+    {
+        engine::GenomeBuilder::build_genome(self, index, rng)
+    }
+}
+
+impl GenomeBuilder<Vec<u8>> for dna::DnaBuilder {
+    fn build_genome<R>(&self, index: usize, rng: &mut R) -> Vec<u8>
+    where
+        R: Rng + Sized,
+    {
+        engine::GenomeBuilder::build_genome(self, index, rng)
+    }
+}
+
+impl GeneticOperator for gen::SettingsCrossover {
+    fn name() -> String {
+        "Settings-Crossover".to_string()
+    }
+}
+
+impl CrossoverOp<Settings> for gen::SettingsCrossover {
+    fn crossover<R>(&self, parents: Parents<Settings>, rng: &mut R) -> Children<Settings>
+    where
+        R: Rng + Sized,
+    {
+        engine::CrossoverOp::crossover(self, parents, rng)
+    }
+}
+
+impl GeneticOperator for gen::SettingsMutator {
+    fn name() -> String {
+        "Settings-Mutator".to_string()
+    }
+}
+
+impl MutationOp<Settings> for gen::SettingsMutator {
+    fn mutate<R>(&self, sett: Settings, rng: &mut R) -> Settings
+    where
+        R: Rng + Sized,
+    {
+        engine::MutationOp::mutate(self, sett, rng)
+    }
+}
+
+pub(crate) fn build_step(fitness_calc: gen::FitnessCalc, opts: &gen::Options) -> Step {
+    let max_plugs = opts.engine.max_plugs;
+
+    let mut step = match opts.engine.encoding {
+        Encoding::Settings => build_genevo_step(
+            fitness_calc,
+            gen::SettingsBuilder {
+                max_plugs,
+                reflector: opts.engine.reflector.clone(),
+                herivel_spread: opts.engine.herivel_spread,
+                fixed_rings: opts.engine.fixed_rings,
+            },
+            gen::SettingsCrossover {
+                mode: opts.engine.crossover,
+                rotors: opts.engine.rotor_crossover,
+                plugboard: opts.engine.plugboard_crossover,
+            },
+            gen::SettingsMutator::new(&opts.engine),
+            |settings| settings,
+            opts,
+        ),
+        Encoding::FlatDna => build_genevo_step(
+            dna::DnaFitnessCalc {
+                inner: fitness_calc,
+                max_plugs,
+            },
+            dna::DnaBuilder { max_plugs },
+            UniformCrossBreeder::new(),
+            RandomValueMutator::new(opts.engine.mutation_rate, 0, dna::GENE_VALUES - 1),
+            move |genome: Vec<u8>| dna::decode(&genome, max_plugs),
+            opts,
+        ),
+    };
+    // genevo's clock keeps running while paused
+    let pause = opts.engine.pause.clone();
+    Box::new(move || {
+        pause.wait();
+        step()
+    })
+}
+
+fn build_genevo_step<G, E, B, C, M, D>(
+    fitness_calc: E,
+    genome_builder: B,
+    crossover: C,
+    mutator: M,
+    decode: D,
+    opts: &gen::Options,
+) -> Step
+where
+    G: Genotype + 'static,
+    E: FitnessFunction<G, Fitness> + Debug + Sync + 'static,
+    B: GenomeBuilder<G>,
+    C: CrossoverOp<G> + Debug + Sync + 'static,
+    M: MutationOp<G> + Debug + Sync + 'static,
+    D: Fn(G) -> Settings + 'static,
+{
+    let engine = &opts.engine;
+    let termination = &opts.termination;
+
+    let selector =
+        MaximizeSelector::new(engine.selection_ratio, engine.num_individuals_per_parents);
+
+    let reinserter = ElitistReinserter::new(fitness_calc.clone(), true, engine.reinsertion_ratio);
+
+    let initial_population = build_population()
+        .with_genome_builder(genome_builder)
+        .of_size(engine.population_size)
+        .uniform_at_random();
+
+    let termination = or(
+        or(
+            GenerationLimit::new(termination.generation_limit),
+            TimeLimit::new(termination.time_limit),
+        ),
+        FitnessLimit::new(termination.target_fitness.unwrap_or(Fitness(1.0))),
+    );
+
+    let mut sim = simulate(
+        genetic_algorithm()
+            .with_evaluation(fitness_calc)
+            .with_selection(selector)
+            .with_crossover(crossover)
+            .with_mutation(mutator)
+            .with_reinsertion(reinserter)
+            .with_initial_population(initial_population)
+            .build(),
+    )
+    .until(termination)
+    .build();
+
+    Box::new(move || match sim.step() {
+        Ok(SimResult::Intermediate(step)) => {
+            let evaluated_population = step.result.evaluated_population;
+            let stats = gen::fitness_stats(evaluated_population.fitness_values());
+            let best_solution = step.result.best_solution;
+            Ok(StepResult::Intermediate(Generation {
+                iteration: step.iteration,
+                average_fitness: *evaluated_population.average_fitness(),
+                median_fitness: stats.median,
+                fitness_std_dev: stats.std_dev,
+                best_fitness: best_solution.solution.fitness,
+                best_settings: decode(best_solution.solution.genome),
+                best_found_in: best_solution.generation,
+                duration: to_std(step.duration),
+                processing_time: to_std(step.processing_time.duration()),
+                // the genevo engine doesn't report individuals
+                sample: Vec::new(),
+                top: Vec::new(),
+                ancestry: Vec::new(),
+                coverage: None,
+                invalid_genomes: None,
+            }))
+        }
+        Ok(SimResult::Final(step, processing_time, duration, reason)) => {
+            let evaluated_population = step.result.evaluated_population;
+            let stats = gen::fitness_stats(evaluated_population.fitness_values());
+            let best_solution = step.result.best_solution;
+            Ok(StepResult::Final {
+                generation: Generation {
+                    iteration: step.iteration,
+                    average_fitness: *evaluated_population.average_fitness(),
+                    median_fitness: stats.median,
+                    fitness_std_dev: stats.std_dev,
+                    best_fitness: best_solution.solution.fitness,
+                    best_settings: decode(best_solution.solution.genome),
+                    best_found_in: best_solution.generation,
+                    duration: to_std(duration),
+                    processing_time: to_std(processing_time.duration()),
+                    sample: Vec::new(),
+                    top: Vec::new(),
+                    ancestry: Vec::new(),
+                    coverage: None,
+                    invalid_genomes: None,
+                },
+                reason,
+            })
+        }
+        Err(err) => Err(anyhow::anyhow!(err)),
+    })
+}
+
+fn to_std(duration: Duration) -> std::time::Duration {
+    duration.to_std().unwrap_or_default()
+}
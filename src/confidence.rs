@@ -0,0 +1,164 @@
+// how far the score of a found key stands out from the scores of random keys on the same
+// ciphertext: a search that only found the least bad of many random keys lands about as far
+// out as the best of that many samples from the random-key distribution would, a broken key far
+// beyond it
+use std::fmt;
+
+use anyhow::ensure;
+use rand::Rng;
+
+use crate::{
+    engine::GenomeBuilder,
+    enigma::{self, Settings},
+    gen::{self, Fitness, ReflectorSearch, SettingsBuilder},
+    metrics::Metric,
+};
+
+// random keys decrypted at a time
+const BATCH: usize = 256;
+
+/// Scores of random keys on a ciphertext, drawn from the keyspace the search covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Baseline {
+    pub samples: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    // best score of the random keys
+    pub max: f64,
+    // keys the search chooses from
+    pub keyspace: u128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    // no further out than the best random key of the keyspace would be by chance
+    Noise,
+    // out of reach of random keys, with a part of the key right, e.g. the rotors but not all
+    // the plugs
+    Partial,
+    Solved,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Noise => write!(f, "no better than chance"),
+            Level::Partial => write!(f, "partly solved"),
+            Level::Solved => write!(f, "solved"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Confidence {
+    // standard deviations above the mean of random keys
+    pub sigmas: f64,
+    // standard deviations the best random key of the keyspace reaches by chance
+    pub chance: f64,
+    pub level: Level,
+}
+
+impl Baseline {
+    /// Scores `samples` random keys of up to `max_plugs` plugs with the reflectors of the search.
+    pub fn sample<R: Rng>(
+        ciphertext: &str,
+        metric: &Metric,
+        reflector: &ReflectorSearch,
+        max_plugs: usize,
+        samples: usize,
+        rng: &mut R,
+    ) -> anyhow::Result<Self> {
+        ensure!(samples > 1, "the baseline needs at least 2 random keys");
+        let builder = SettingsBuilder {
+            max_plugs,
+            reflector: reflector.clone(),
+            herivel_spread: None,
+            fixed_rings: false,
+        };
+
+        let mut scores = Vec::with_capacity(samples);
+        while scores.len() < samples {
+            let keys = (0..BATCH.min(samples - scores.len()))
+                .map(|_| builder.build_genome(0, rng))
+                .collect::<Vec<Settings>>();
+            let plaintexts = enigma::decrypt_batch(&keys, ciphertext)?;
+            scores.extend(plaintexts.iter().map(|p| Fitness(metric.score(p))));
+        }
+
+        let stats = gen::fitness_stats(&scores);
+        Ok(Self {
+            samples,
+            mean: stats.mean,
+            std_dev: stats.std_dev,
+            max: scores.iter().map(|f| f.0).fold(f64::MIN, f64::max),
+            keyspace: enigma::keyspace_size(max_plugs) * reflector.choices(),
+        })
+    }
+
+    /// How far the score lies above the random keys. The best of n samples of a normal
+    /// distribution lies about sqrt(2 ln n) standard deviations out, so a score short of that
+    /// for the whole keyspace is what a search finds without breaking anything; twice as far
+    /// is taken as solved. Scores of random keys have a longer tail than a normal distribution,
+    /// by as much as the best sample lies further out than it would.
+    pub fn confidence(&self, score: f64) -> Confidence {
+        let sigmas = |score: f64| match self.std_dev {
+            0.0 => 0.0,
+            std_dev => (score - self.mean) / std_dev,
+        };
+        let expected = |n: f64| (2.0 * n.ln()).sqrt();
+        let tail = (sigmas(self.max) / expected(self.samples as f64)).max(1.0);
+        let chance = tail * expected(self.keyspace as f64);
+        let sigmas = sigmas(score);
+        let level = match sigmas {
+            s if s >= 2.0 * chance => Level::Solved,
+            s if s >= chance => Level::Partial,
+            _ => Level::Noise,
+        };
+        Confidence {
+            sigmas,
+            chance,
+            level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{enigma::Machine, language::Language, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_confidence() {
+        let settings = "II V III / 08 05 20 / M C U / AQ BT CZ EK".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..400]);
+        let metric = Metric::Bigram(Language::English);
+        let reflector = ReflectorSearch::Standard;
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let baseline = Baseline::sample(&ciphertext, &metric, &reflector, 10, 500, &mut rng);
+        let baseline = baseline.unwrap();
+        assert_eq!(baseline.samples, 500);
+        assert!(baseline.std_dev > 0.0);
+
+        let solved = baseline.confidence(metric.score(&LONG_TEXT[..400]));
+        assert_eq!(solved.level, Level::Solved, "{solved:?}");
+
+        // the rotors right and two plugs missing
+        let partial = Settings {
+            plugboard: settings.plugboard[..2].to_vec(),
+            ..settings.clone()
+        };
+        let plaintext = Machine::new(&partial).unwrap().decrypt(&ciphertext);
+        let partial = baseline.confidence(metric.score(&plaintext));
+        assert!(partial.sigmas < solved.sigmas);
+
+        let noise = baseline.confidence(baseline.mean + baseline.std_dev);
+        assert_eq!(noise.level, Level::Noise);
+        assert!((noise.sigmas - 1.0).abs() < 1e-9);
+        assert!(Baseline::sample(&ciphertext, &metric, &reflector, 10, 1, &mut rng).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+// how much of the keyspace a search looked at: the distinct keys it evaluated, counted by
+// their packed canonical form, so keys that encrypt alike count once
+use std::{
+    collections::HashSet,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Distinct keys evaluated, shared by clones; disabled it counts nothing. Every key is kept,
+/// 16 bytes and the set's overhead each, so it's enabled on request.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage(Option<Arc<Mutex<HashSet<u128>>>>);
+
+impl Coverage {
+    pub fn new(enabled: bool) -> Self {
+        Self(enabled.then(Default::default))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    // packed keys, a batch at a time to take the lock once
+    pub fn insert(&self, keys: impl IntoIterator<Item = u128>) {
+        if let Some(seen) = &self.0 {
+            seen.lock().expect("not poisoned").extend(keys);
+        }
+    }
+
+    pub fn keys(&self) -> u64 {
+        self.0
+            .as_ref()
+            .map_or(0, |seen| seen.lock().expect("not poisoned").len() as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyCoverage {
+    // distinct canonical keys evaluated
+    pub keys: u64,
+    // canonical keys the search chooses from
+    pub keyspace: u128,
+}
+
+impl KeyCoverage {
+    pub fn fraction(&self) -> f64 {
+        self.keys as f64 / self.keyspace as f64
+    }
+}
+
+impl fmt::Display for KeyCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} distinct keys, {:.3e} of the {:.3e} keys searched",
+            self.keys,
+            self.fraction(),
+            self.keyspace as f64
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cache::{FitnessCache, ScramblerCache},
+        engine::FitnessFunction,
+        enigma::Settings,
+        gen::{Ciphertext, FitnessCalc},
+        metrics::tests::LONG_TEXT,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        let coverage = Coverage::new(true);
+        let fitness_calc = FitnessCalc {
+            ciphertext: Ciphertext::new(LONG_TEXT[..100].to_string()),
+            cache: FitnessCache::new(100),
+            scramblers: ScramblerCache::new(0),
+            metric: None,
+            coverage: coverage.clone(),
+            invalid: Default::default(),
+        };
+        let keys = [
+            "II V III / 08 05 20 / M C U / AQ BT",
+            "II V III / 08 05 20 / M C U / TB QA",
+            // the left ring turned along with the left position
+            "II V III / 09 05 20 / N C U / AQ BT",
+            "II V III / 08 05 20 / M C V / AQ BT",
+        ]
+        .map(|key| key.parse::<Settings>().unwrap());
+        fitness_calc.fitness_of(&keys[0]);
+        fitness_calc.fitness_of_batch(&keys);
+        assert_eq!(coverage.keys(), 2);
+
+        let report = KeyCoverage {
+            keys: coverage.keys(),
+            keyspace: 400,
+        };
+        assert_eq!(report.fraction(), 0.005);
+        assert_eq!(
+            report.to_string(),
+            "2 distinct keys, 5.000e-3 of the 4.000e2 keys searched"
+        );
+
+        let disabled = Coverage::new(false);
+        disabled.insert([1, 2]);
+        assert!(!disabled.is_enabled());
+        assert_eq!(disabled.keys(), 0);
+    }
+}
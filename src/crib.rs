@@ -0,0 +1,170 @@
+// crib dragging: a guessed stretch of plaintext, e.g. WETTERVORHERSAGE, slid along the
+// ciphertext; the machine never encrypts a letter to itself, which rules out most placements,
+// and the letter pairs of a placement linked into loops are what a bombe menu tests
+use std::cmp::Reverse;
+
+use anyhow::ensure;
+
+use crate::enigma::{normalize, GARBLE, LETTERS_NUM};
+
+const LETTERS: usize = LETTERS_NUM as usize;
+// a garble among the letters
+pub(crate) const GARBLED: u8 = u8::MAX;
+
+/// A crib placement that survives the crash test, with the structure of its menu: the graph
+/// of letters linked by the plaintext and ciphertext letter at every position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    // letters of the ciphertext before the crib
+    pub offset: usize,
+    // independent closed loops of the menu, each a check on a bombe hypothesis
+    pub loops: usize,
+    // links and letters of the largest connected part of the menu
+    pub links: usize,
+    pub letters: usize,
+}
+
+/// The links of the crib placed at the offset: the crib letter, the ciphertext letter and the
+/// position in the message, A = 0; None if a letter would encrypt to itself. A garble on
+/// either side links nothing.
+pub fn links(ciphertext: &[u8], crib: &[u8], offset: usize) -> Option<Vec<(u8, u8, usize)>> {
+    let cipher = ciphertext.get(offset..offset + crib.len())?;
+    crib.iter()
+        .zip(cipher)
+        .enumerate()
+        .filter(|&(_, (&p, &c))| p != GARBLED && c != GARBLED)
+        .map(|(i, (&p, &c))| (p != c).then_some((p, c, offset + i)))
+        .collect()
+}
+
+/// Every placement of the crib in the ciphertext that doesn't crash, best menus first: the
+/// most loops, then the largest connected part, then the earliest.
+pub fn drag(ciphertext: &str, crib: &str) -> anyhow::Result<Vec<Placement>> {
+    let (ciphertext, crib) = (letters(ciphertext), letters(crib));
+    ensure!(!crib.is_empty(), "the crib has no letters");
+    ensure!(
+        crib.len() <= ciphertext.len(),
+        "the crib is longer than the ciphertext"
+    );
+
+    let mut placements = (0..=ciphertext.len() - crib.len())
+        .filter_map(|offset| {
+            let links = links(&ciphertext, &crib, offset)?;
+            Some(menu_structure(offset, &links))
+        })
+        .collect::<Vec<_>>();
+    placements.sort_by_key(|p| (Reverse((p.loops, p.links)), p.offset));
+    Ok(placements)
+}
+
+// the letters of a text, A = 0, and its garbles
+pub(crate) fn letters(text: &str) -> Vec<u8> {
+    normalize(text)
+        .bytes()
+        .filter(|&c| c != b' ')
+        .map(|c| match c {
+            GARBLE => GARBLED,
+            c => c - b'A',
+        })
+        .collect()
+}
+
+// loops by the cyclomatic number links - letters + connected parts, which counts a letter pair
+// linked twice as a loop as well
+fn menu_structure(offset: usize, links: &[(u8, u8, usize)]) -> Placement {
+    let part = parts(links);
+    let mut letters = [0; LETTERS];
+    let mut part_links = [0; LETTERS];
+    for x in used_letters(links) {
+        letters[part[x]] += 1;
+    }
+    for &(p, _, _) in links {
+        part_links[part[p as usize]] += 1;
+    }
+
+    let parts = letters.iter().filter(|&&n| n > 0).count();
+    let largest = (0..LETTERS)
+        .max_by_key(|&x| (part_links[x], letters[x]))
+        .unwrap_or(0);
+    Placement {
+        offset,
+        loops: links.len() + parts - used_letters(links).count(),
+        links: part_links[largest],
+        letters: letters[largest],
+    }
+}
+
+// the letters the links touch, A = 0, in order
+pub(crate) fn used_letters(links: &[(u8, u8, usize)]) -> impl Iterator<Item = usize> + '_ {
+    (0..LETTERS).filter(|&x| {
+        links
+            .iter()
+            .any(|&(p, c, _)| p as usize == x || c as usize == x)
+    })
+}
+
+// the connected part of every letter as the letter that stands for it, by union-find
+pub(crate) fn parts(links: &[(u8, u8, usize)]) -> [usize; LETTERS] {
+    let mut parent: [usize; LETTERS] = std::array::from_fn(|i| i);
+    for &(p, c, _) in links {
+        let (a, b) = (root(&mut parent, p as usize), root(&mut parent, c as usize));
+        parent[a] = b;
+    }
+    std::array::from_fn(|x| root(&mut parent, x))
+}
+
+// with path halving
+fn root(parent: &mut [usize; LETTERS], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        enigma::{Machine, Settings},
+        metrics::tests::LONG_TEXT,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_drag() {
+        // A-B twice is a loop, C-D-E a chain on its own
+        let placements = drag("BADE", "ABCD").unwrap();
+        assert_eq!(
+            placements,
+            vec![Placement {
+                offset: 0,
+                loops: 1,
+                links: 2,
+                letters: 3
+            }]
+        );
+        assert_eq!(links(&[1, 0], &[0, 0], 0), None);
+        assert_eq!(links(&[1, GARBLED], &[0, 0], 0), Some(vec![(0, 1, 0)]));
+        assert_eq!(letters("a?B*"), [0, GARBLED, 1, GARBLED]);
+        assert!(drag("AB", "ABC").is_err());
+
+        let settings = "II V III / 08 05 20 / M C U / AQ BT".parse::<Settings>();
+        let ciphertext = Machine::new(&settings.unwrap()).unwrap().encrypt(LONG_TEXT);
+        let crib = "THE SLINGS AND ARROWS OF OUTRAGEOUS FORTUNE";
+        let offset = LONG_TEXT[..LONG_TEXT.find(crib).unwrap()]
+            .bytes()
+            .filter(u8::is_ascii_uppercase)
+            .count();
+
+        let placements = drag(&ciphertext, crib).unwrap();
+        let letters = ciphertext.bytes().filter(u8::is_ascii_uppercase).count();
+        let cribbed = crib.bytes().filter(u8::is_ascii_uppercase).count();
+        assert!(placements.len() < letters - cribbed + 1);
+        let found = placements.iter().find(|p| p.offset == offset).unwrap();
+        assert!(found.loops > 0);
+        assert!(placements
+            .windows(2)
+            .all(|w| (w[0].loops, w[0].links) >= (w[1].loops, w[1].links)));
+    }
+}
@@ -0,0 +1,202 @@
+use std::{fmt, fs, path::Path};
+
+use aho_corasick::{AhoCorasick, MatchKind};
+
+use crate::enigma::{normalize, GARBLE};
+
+// shorter words turn up in garbage decryptions by chance
+pub const MIN_WORD_LEN: usize = 4;
+// the trie also takes THE and AND, too common in plaintext to leave out of its coverage
+const MIN_TRIE_WORD_LEN: usize = 3;
+
+/// Words looked up in decryptions, which have no word breaks: the matches are counted in
+/// the letters alone, longest word first and without overlaps.
+#[derive(Clone, Default)]
+pub struct Dictionary {
+    len: usize,
+    matcher: Option<AhoCorasick>,
+}
+
+impl Dictionary {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> anyhow::Result<Self> {
+        let mut words = words
+            .into_iter()
+            .map(|word| normalize(word.as_ref()).replace(' ', ""))
+            .filter(|word| word.len() >= MIN_WORD_LEN)
+            .collect::<Vec<_>>();
+        words.sort();
+        words.dedup();
+
+        if words.is_empty() {
+            return Ok(Self::default());
+        }
+        let matcher = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&words)?;
+
+        Ok(Self {
+            len: words.len(),
+            matcher: Some(matcher),
+        })
+    }
+
+    // whitespace separated words, e.g. one per line as /usr/share/dict/words
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Self::new(fs::read_to_string(path)?.split_whitespace())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn hits(&self, text: &str) -> usize {
+        match &self.matcher {
+            Some(matcher) => matcher.find_iter(&text.replace(' ', "")).count(),
+            None => 0,
+        }
+    }
+}
+
+// the words themselves would flood logged configurations
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Dictionary({} words)", self.len)
+    }
+}
+
+/// Words in a trie, for the share of the letters of a decryption that words cover: unlike the
+/// count of dictionary hits it grows with every letter a better key puts right, so it can
+/// drive the search, e.g. on a reduced population at the end. A decryption is walked down the
+/// trie from every letter, a few nodes each, and the longest cover without overlaps is put
+/// together by dynamic programming.
+#[derive(Clone, PartialEq)]
+pub struct WordTrie {
+    // the nodes below every node by letter, 0 for none; node 0 is the root
+    children: Vec<[u32; 26]>,
+    // whether a word ends at the node
+    word_end: Vec<bool>,
+    len: usize,
+}
+
+impl WordTrie {
+    pub fn new<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> Self {
+        let mut trie = Self {
+            children: vec![[0; 26]],
+            word_end: vec![false],
+            len: 0,
+        };
+        for word in words {
+            let word = normalize(word.as_ref()).replace(' ', "");
+            if word.len() < MIN_TRIE_WORD_LEN || word.contains(GARBLE as char) {
+                continue;
+            }
+            let mut node = 0;
+            for c in word.bytes().map(|c| (c - b'A') as usize) {
+                if trie.children[node][c] == 0 {
+                    trie.children[node][c] = trie.children.len() as u32;
+                    trie.children.push([0; 26]);
+                    trie.word_end.push(false);
+                }
+                node = trie.children[node][c] as usize;
+            }
+            trie.len += !trie.word_end[node] as usize;
+            trie.word_end[node] = true;
+        }
+        trie
+    }
+
+    // whitespace separated words, e.g. one per line as /usr/share/dict/words
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self::new(fs::read_to_string(path)?.split_whitespace()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // most letters of the text words cover without overlapping, no word spans a garble
+    pub fn covered(&self, text: &str) -> usize {
+        let letters = text
+            .bytes()
+            .filter(|&c| c.is_ascii_uppercase() || c == GARBLE)
+            .map(|c| c.is_ascii_uppercase().then(|| (c - b'A') as usize))
+            .collect::<Vec<_>>();
+
+        // best[i] is the cover of the first i letters
+        let mut best = vec![0; letters.len() + 1];
+        for start in 0..letters.len() {
+            best[start + 1] = best[start + 1].max(best[start]);
+            let mut node = 0;
+            for (end, &c) in letters.iter().enumerate().skip(start) {
+                let Some(c) = c else { break };
+                node = self.children[node][c] as usize;
+                if node == 0 {
+                    break;
+                }
+                if self.word_end[node] {
+                    best[end + 1] = best[end + 1].max(best[start] + end + 1 - start);
+                }
+            }
+        }
+        best[letters.len()]
+    }
+
+    // share of the letters of the text words cover, in [0, 1]
+    pub fn coverage(&self, text: &str) -> f64 {
+        match text.bytes().filter(u8::is_ascii_uppercase).count() {
+            0 => 0.0,
+            n => self.covered(text) as f64 / n as f64,
+        }
+    }
+}
+
+impl fmt::Debug for WordTrie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WordTrie({} words)", self.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hits() {
+        let dictionary =
+            Dictionary::new(["the", "flag", "Secret", "FLAGS", "  ", "secret"]).unwrap();
+        // THE is too short
+        assert_eq!(dictionary.len(), 3);
+        assert_eq!(format!("{:?}", dictionary), "Dictionary(3 words)");
+
+        assert_eq!(dictionary.hits("THE FLAGS ARE SECRET"), 2);
+        assert_eq!(dictionary.hits("THEFLAGXSECRETFLAGX"), 3);
+        assert_eq!(dictionary.hits("QWERTZ"), 0);
+        assert_eq!(Dictionary::default().hits("FLAG"), 0);
+        assert!(Dictionary::new(["a", "bc"]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_trie() {
+        let trie = WordTrie::new(["the", "flag", "Flags", "FLAG", "at", "secret", "sec?"]);
+        // AT is too short, the garbled word unreadable
+        assert_eq!(trie.len(), 4);
+        assert_eq!(format!("{:?}", trie), "WordTrie(4 words)");
+
+        assert_eq!(trie.covered("THE FLAGS ARE SECRET"), 3 + 5 + 6);
+        // FLAGS would leave ECRET uncovered
+        assert_eq!(trie.covered("FLAGSECRET"), 10);
+        assert_eq!(trie.covered("FLA?GSECRET"), 6);
+        assert_eq!(trie.covered("QWERTZ"), 0);
+        assert_eq!(trie.coverage("THEXFLAG"), 7.0 / 8.0);
+        assert_eq!(trie.coverage(""), 0.0);
+        assert!(WordTrie::new(["a", "bc"]).is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+use std::str::FromStr;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::engine::{CrossoverOp, FitnessFunction, GenomeBuilder, MutationOp};
+use crate::enigma::{
+    is_plug_conflicting, is_plug_valid, Reflector, Settings, LETTERS_NUM, MAX_RING_SETTINGS_NUM,
+    MAX_ROTOR_NUM, MAX_ROTOR_POSITIONS_NUM,
+};
+use crate::gen::{Fitness, FitnessCalc};
+
+// every gene holds a value in 0..GENE_VALUES and is decoded modulo the range of its setting,
+// for plugboard letters 0 marks an empty slot
+pub const GENE_VALUES: u8 = LETTERS_NUM + 1;
+const HEADER_LEN: usize = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Settings,
+    FlatDna,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "settings" => Ok(Encoding::Settings),
+            "dna" => Ok(Encoding::FlatDna),
+            _ => Err(format!("unknown encoding {s}, expected settings or dna")),
+        }
+    }
+}
+
+pub fn dna_len(max_plugs: usize) -> usize {
+    HEADER_LEN + 2 * max_plugs
+}
+
+pub fn encode(s: &Settings, max_plugs: usize) -> Vec<u8> {
+    let mut dna = vec![
+        s.rotors.0 - 1,
+        s.rotors.1 - 1,
+        s.rotors.2 - 1,
+        s.ring_settings.0 - 1,
+        s.ring_settings.1 - 1,
+        s.ring_settings.2 - 1,
+        s.rotor_positions.0 - 1,
+        s.rotor_positions.1 - 1,
+        s.rotor_positions.2 - 1,
+    ];
+
+    for &(a, b) in s.plugboard.iter().take(max_plugs) {
+        dna.extend([a, b]);
+    }
+    dna.resize(dna_len(max_plugs), 0);
+
+    dna
+}
+
+// any byte string decodes into valid settings, duplicate rotors and clashing plugs are repaired
+pub fn decode(dna: &[u8], max_plugs: usize) -> Settings {
+    let gene = |i: usize, modulo: u8| 1 + dna.get(i).copied().unwrap_or(0) % modulo;
+
+    let rotors = repair_rotors([
+        gene(0, MAX_ROTOR_NUM),
+        gene(1, MAX_ROTOR_NUM),
+        gene(2, MAX_ROTOR_NUM),
+    ]);
+
+    let mut plugboard = Vec::new();
+    for pair in dna
+        .get(HEADER_LEN..)
+        .unwrap_or(&[])
+        .chunks_exact(2)
+        .take(max_plugs)
+    {
+        let (a, b) = (pair[0] % GENE_VALUES, pair[1] % GENE_VALUES);
+        let plug = (a.min(b), a.max(b));
+
+        if is_plug_valid(plug) && !is_plug_conflicting(&plugboard, plug) {
+            plugboard.push(plug);
+        }
+    }
+
+    Settings {
+        rotors,
+        ring_settings: (
+            gene(3, MAX_RING_SETTINGS_NUM),
+            gene(4, MAX_RING_SETTINGS_NUM),
+            gene(5, MAX_RING_SETTINGS_NUM),
+        ),
+        rotor_positions: (
+            gene(6, MAX_ROTOR_POSITIONS_NUM),
+            gene(7, MAX_ROTOR_POSITIONS_NUM),
+            gene(8, MAX_ROTOR_POSITIONS_NUM),
+        ),
+        plugboard,
+        reflector: Reflector::B,
+    }
+}
+
+fn repair_rotors(mut rotors: [u8; 3]) -> (u8, u8, u8) {
+    for i in 1..rotors.len() {
+        while rotors[..i].contains(&rotors[i]) {
+            rotors[i] = rotors[i] % MAX_ROTOR_NUM + 1;
+        }
+    }
+
+    (rotors[0], rotors[1], rotors[2])
+}
+
+pub struct DnaBuilder {
+    pub max_plugs: usize,
+}
+
+impl GenomeBuilder<Vec<u8>> for DnaBuilder {
+    fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> Vec<u8> {
+        (0..dna_len(self.max_plugs))
+            .map(|_| rng.gen_range(0..GENE_VALUES))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DnaFitnessCalc {
+    pub inner: FitnessCalc,
+    pub max_plugs: usize,
+}
+
+impl FitnessFunction<Vec<u8>> for DnaFitnessCalc {
+    fn fitness_of(&self, dna: &Vec<u8>) -> Fitness {
+        self.inner.fitness_of(&decode(dna, self.max_plugs))
+    }
+
+    fn fitness_of_batch(&self, genomes: &[Vec<u8>]) -> Vec<Fitness> {
+        let keys = genomes
+            .iter()
+            .map(|dna| decode(dna, self.max_plugs))
+            .collect::<Vec<_>>();
+        self.inner.fitness_of_batch(&keys)
+    }
+
+    fn revision(&self) -> u64 {
+        self.inner.revision()
+    }
+
+    fn fingerprint(&self, dna: &Vec<u8>) -> Option<u128> {
+        self.inner.fingerprint(&decode(dna, self.max_plugs))
+    }
+}
+
+// every gene is copied from a parent chosen uniformly at random
+#[derive(Debug, Clone, Copy)]
+pub struct DnaCrossover;
+
+impl CrossoverOp<Vec<u8>> for DnaCrossover {
+    fn crossover<R: Rng>(&self, parents: Vec<Vec<u8>>, rng: &mut R) -> Vec<Vec<u8>> {
+        let len = parents.iter().map(|p| p.len()).min().unwrap_or(0);
+        let child = (0..len).map(|i| parents.choose(rng).unwrap()[i]).collect();
+
+        vec![child]
+    }
+}
+
+// every gene is replaced by a random value with probability mutation_rate
+#[derive(Debug, Clone, Copy)]
+pub struct DnaMutator {
+    pub mutation_rate: f64,
+}
+
+impl MutationOp<Vec<u8>> for DnaMutator {
+    fn mutate<R: Rng>(&self, dna: Vec<u8>, rng: &mut R) -> Vec<u8> {
+        self.mutate_scaled(dna, 1.0, rng)
+    }
+
+    fn mutate_scaled<R: Rng>(&self, mut dna: Vec<u8>, strength: f64, rng: &mut R) -> Vec<u8> {
+        for gene in dna.iter_mut() {
+            if rng.gen_bool(self.mutation_rate * strength) {
+                *gene = rng.gen_range(0..GENE_VALUES);
+            }
+        }
+
+        dna
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use rand::Rng;
+
+    use crate::{enigma::strategy, enigma::MAX_PLUGS_NUM, gen::SettingsBuilder};
+
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let mut rng = rand::thread_rng();
+        let b = SettingsBuilder {
+            max_plugs: 10,
+            reflector: Default::default(),
+            herivel_spread: None,
+            fixed_rings: false,
+        };
+
+        for _ in 0..10000 {
+            let sett = b.build_genome(0, &mut rng);
+            let dna = encode(&sett, 10);
+
+            assert_eq!(dna.len(), dna_len(10));
+            assert_eq!(decode(&dna, 10), sett);
+        }
+    }
+
+    #[test]
+    fn test_decode_repairs() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..10000 {
+            let dna = (0..dna_len(10))
+                .map(|_| rng.gen::<u8>())
+                .collect::<Vec<_>>();
+            assert!(decode(&dna, 10).validate().is_ok());
+        }
+
+        assert_eq!(decode(&[], 0).rotors, (1, 2, 3));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_encode_decode(settings in strategy::settings(MAX_PLUGS_NUM)) {
+            // the genome has no reflector gene, it's always B
+            let settings = Settings { reflector: Reflector::B, ..settings };
+            let dna = encode(&settings, MAX_PLUGS_NUM);
+            prop_assert_eq!(decode(&dna, MAX_PLUGS_NUM), settings);
+        }
+
+        #[test]
+        fn prop_decode_is_valid(dna in prop::collection::vec(any::<u8>(), 0..40), max_plugs in 0..=MAX_PLUGS_NUM) {
+            prop_assert!(decode(&dna, max_plugs).validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_dna_operators() {
+        let mut rng = rand::thread_rng();
+        let b = DnaBuilder { max_plugs: 10 };
+        let m = DnaMutator { mutation_rate: 0.5 };
+
+        for _ in 0..1000 {
+            let parents = vec![b.build_genome(0, &mut rng), b.build_genome(0, &mut rng)];
+            let child = DnaCrossover.crossover(parents.clone(), &mut rng).remove(0);
+
+            assert_eq!(child.len(), dna_len(10));
+            assert!((0..child.len()).all(|i| parents.iter().any(|p| p[i] == child[i])));
+
+            let mutated = m.mutate(child, &mut rng);
+            assert!(mutated.iter().all(|&g| g < GENE_VALUES));
+        }
+    }
+}
@@ -0,0 +1,1183 @@
+use std::{
+    fmt, mem,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    Rng,
+};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+// std's clock panics in the browser
+#[cfg(target_arch = "wasm32")]
+pub(crate) use web_time::Instant;
+
+use crate::bloom::BloomFilter;
+use crate::gen::{self, Fitness};
+use crate::genealogy::{Genealogy, Node, Operator};
+use crate::rng::SearchRng;
+use crate::telemetry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Algorithm {
+    // the in-crate engine below
+    #[default]
+    Ga,
+    // the original genevo based simulation, needs the genevo feature
+    Genevo,
+    // evolution strategy over ring settings and rotor positions, see es.rs
+    Es,
+    // particle swarm, see pso.rs
+    Pso,
+}
+
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ga" => Ok(Algorithm::Ga),
+            "genevo" => Ok(Algorithm::Genevo),
+            "es" => Ok(Algorithm::Es),
+            "pso" => Ok(Algorithm::Pso),
+            _ => Err(format!(
+                "unknown engine {s}, expected ga, genevo, es or pso"
+            )),
+        }
+    }
+}
+
+/// How the parents of the offspring are drawn: uniformly from the best selection_ratio of the
+/// population, or from all of it with odds in proportion to the fitness above the worst's or to
+/// the rank; then selection_ratio is only the share of the population that is bred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectionStrategy {
+    #[default]
+    Truncation,
+    Proportionate,
+    Ranking,
+}
+
+impl SelectionStrategy {
+    // odds of the individuals of a population sorted best first, None for truncation
+    fn odds<G>(&self, population: &[Evaluated<G>]) -> Option<WeightedIndex<f64>> {
+        let weights = match self {
+            SelectionStrategy::Truncation => return None,
+            SelectionStrategy::Proportionate => {
+                let worst = population.last().map_or(0.0, |e| e.fitness.0);
+                population
+                    .iter()
+                    .map(|e| e.fitness.0 - worst)
+                    .collect::<Vec<_>>()
+            }
+            SelectionStrategy::Ranking => (0..population.len())
+                .map(|rank| (population.len() - rank) as f64)
+                .collect(),
+        };
+        // all alike, e.g. a converged population, are drawn uniformly
+        WeightedIndex::new(weights)
+            .or_else(|_| WeightedIndex::new(vec![1.0; population.len()]))
+            .ok()
+    }
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "truncation" => Ok(SelectionStrategy::Truncation),
+            "proportionate" => Ok(SelectionStrategy::Proportionate),
+            "ranking" => Ok(SelectionStrategy::Ranking),
+            _ => Err(format!(
+                "unknown selection {s}, expected truncation, proportionate or ranking"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectionStrategy::Truncation => write!(f, "truncation"),
+            SelectionStrategy::Proportionate => write!(f, "proportionate"),
+            SelectionStrategy::Ranking => write!(f, "ranking"),
+        }
+    }
+}
+
+pub trait FitnessFunction<G>: Sync {
+    fn fitness_of(&self, genome: &G) -> Fitness;
+
+    // a few genomes at once, for fitness functions that share work between them
+    fn fitness_of_batch(&self, genomes: &[G]) -> Vec<Fitness> {
+        genomes
+            .iter()
+            .map(|genome| self.fitness_of(genome))
+            .collect()
+    }
+
+    // changes whenever the fitness of a genome may have, e.g. the ciphertext grew; what was
+    // scored before is scored again
+    fn revision(&self) -> u64 {
+        0
+    }
+
+    // the same number for genomes that score alike, to remember the ones evaluated by; None
+    // remembers nothing
+    fn fingerprint(&self, _genome: &G) -> Option<u128> {
+        None
+    }
+}
+
+pub trait GenomeBuilder<G>: Sync {
+    fn build_genome<R: Rng>(&self, index: usize, rng: &mut R) -> G;
+}
+
+pub trait CrossoverOp<G>: Sync {
+    fn crossover<R: Rng>(&self, parents: Vec<G>, rng: &mut R) -> Vec<G>;
+}
+
+pub trait MutationOp<G>: Sync {
+    fn mutate<R: Rng>(&self, genome: G, rng: &mut R) -> G;
+
+    // mutations at a share of their strength, 1 is the full one, see MutationSchedule;
+    // operators that can't be weakened mutate at full strength
+    fn mutate_scaled<R: Rng>(&self, genome: G, _strength: f64, rng: &mut R) -> G {
+        self.mutate(genome, rng)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Evaluated<G> {
+    // unique within the engine, to trace the individual's descent
+    id: u64,
+    genome: G,
+    fitness: Fitness,
+}
+
+// state of the search after a step, stop_reason is set once a termination condition is met
+#[derive(Debug, Clone)]
+pub struct Progress<G> {
+    pub iteration: u64,
+    pub average_fitness: Fitness,
+    pub median_fitness: Fitness,
+    pub fitness_std_dev: f64,
+    pub best_fitness: Fitness,
+    pub best_genome: G,
+    pub best_found_in: u64,
+    pub duration: Duration,
+    pub processing_time: Duration,
+    pub stop_reason: Option<String>,
+    // sample_size individuals evenly spaced by rank, best first
+    pub sample: Vec<Individual<G>>,
+    // the best top_size individuals, best first
+    pub top: Vec<Individual<G>>,
+    // the best EngineOptions::genealogy individuals of the last generation and all their
+    // ancestors, oldest first; with the step that stops the search only
+    pub ancestry: Vec<Node<G>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Individual<G> {
+    // 0 is the best of the population
+    pub rank: usize,
+    pub genome: G,
+    pub fitness: Fitness,
+}
+
+// genomes built or bred with one random stream, the split doesn't depend on the number of threads
+const CHUNK_SIZE: usize = 1024;
+// genomes handed to the fitness function together, a batch of Enigma machines and the rotor
+// tables they share stay in L1
+const EVAL_BATCH_SIZE: usize = 64;
+// mutations of an offspring that was evaluated before until it's let through anyway
+const REDIRECT_TRIES: usize = 8;
+// how often a paused search looks whether it's resumed
+const PAUSE_POLL: Duration = Duration::from_millis(100);
+
+/// Generational GA: truncation selection, crossover and mutation of the selected parents,
+/// elitist reinsertion of the offspring. Breeding and evaluation run on a rayon thread pool,
+/// the result only depends on the seed.
+pub struct Engine<G, F, C, M> {
+    fitness_calc: F,
+    crossover: C,
+    mutator: M,
+    opts: gen::EngineOptions,
+    termination: gen::TerminationOptions,
+    seed: u64,
+    pool: ThreadPool,
+    // sorted by fitness, best first
+    population: Vec<Evaluated<G>>,
+    best: Option<(Evaluated<G>, u64)>,
+    // of the fitness function the population was scored with
+    revision: u64,
+    next_id: u64,
+    // with EngineOptions::genealogy only
+    genealogy: Option<Genealogy<G>>,
+    // fingerprints of the genomes evaluated, with EngineOptions::explored_memory only
+    explored: Option<BloomFilter>,
+    // offspring mutated again as they were evaluated before
+    redirected: u64,
+    // of the mutations of the generation bred next, see EngineOptions::mutation_schedule
+    strength: f64,
+    // selection_ratio, or as adapted with EngineOptions::selection_adaptation
+    selection_ratio: f64,
+    // best fitness of the first generation, where an annealing by fitness starts
+    first_best: Fitness,
+    generation: u64,
+    started_at: Instant,
+    processing_time: Duration,
+}
+
+impl<G, F, C, M> Engine<G, F, C, M>
+where
+    G: Clone + PartialEq + Send + Sync,
+    F: FitnessFunction<G> + Send,
+    C: CrossoverOp<G> + Send,
+    M: MutationOp<G> + Send,
+{
+    pub fn new<B: GenomeBuilder<G>>(
+        fitness_calc: F,
+        genome_builder: B,
+        crossover: C,
+        mutator: M,
+        opts: &gen::Options,
+    ) -> anyhow::Result<Self> {
+        let mut engine = Self {
+            fitness_calc,
+            crossover,
+            mutator,
+            opts: opts.engine.clone(),
+            termination: opts.termination.clone(),
+            seed: opts.engine.seed.unwrap_or_else(rand::random),
+            pool: thread_pool(&opts.engine)?,
+            population: Vec::new(),
+            best: None,
+            revision: 0,
+            next_id: 0,
+            genealogy: (opts.engine.genealogy > 0).then(Genealogy::default),
+            explored: (opts.engine.explored_memory > 0).then(|| {
+                let expected = opts.engine.population_size as u64
+                    * opts.termination.generation_limit.min(u32::MAX as u64);
+                BloomFilter::new(opts.engine.explored_memory, expected)
+            }),
+            redirected: 0,
+            strength: 1.0,
+            selection_ratio: match opts.engine.selection_adaptation {
+                Some(adaptation) => adaptation.clamp(opts.engine.selection_ratio),
+                None => opts.engine.selection_ratio,
+            },
+            first_best: Fitness(0.0),
+            generation: 0,
+            started_at: Instant::now(),
+            processing_time: Duration::ZERO,
+        };
+        engine.revision = engine.fitness_calc.revision();
+
+        // the initial population is evaluated by the first step
+        let genomes = engine.pool.install(|| {
+            engine.par_chunks(opts.engine.population_size, |i, rng| {
+                genome_builder.build_genome(i, rng)
+            })
+        });
+        engine.population = engine.with_ids(genomes.into_iter().map(|genome| (genome, None)));
+
+        Ok(engine)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // fitness of the best of the current population, the best found so far may be gone
+    pub(crate) fn current_best(&self) -> Fitness {
+        self.population.first().map_or(Fitness(0.0), |e| e.fitness)
+    }
+
+    // exchanges the populations of two engines, the best found so far stays with each
+    pub(crate) fn swap_population(&mut self, other: &mut Self) {
+        mem::swap(&mut self.population, &mut other.population);
+    }
+
+    pub fn step(&mut self) -> Progress<G> {
+        self.started_at += self.opts.pause.wait();
+        let started = Instant::now();
+        let span = telemetry::Span::start("generation");
+        span.set_u64("generation", self.generation + 1);
+        span.set_u64("population_size", self.population.len() as u64);
+
+        let population = mem::take(&mut self.population);
+        let (population, evaluations) = if self.generation == 0 {
+            let evaluations = population.len();
+            let population = self.evaluate(population);
+            self.remember(&population);
+            self.first_best = population[0].fitness;
+            (population, evaluations)
+        } else {
+            let population = self.rescore(population);
+            let best = self
+                .best
+                .as_ref()
+                .map_or(self.first_best, |(b, _)| b.fitness);
+            self.strength = self.opts.mutation_schedule.strength(
+                self.generation + 1,
+                self.first_best,
+                best,
+                self.termination.target_fitness,
+            );
+            span.set_f64("mutation_strength", self.strength);
+            let schedule = &self.opts.population_schedule;
+            let size = schedule.size_at(self.opts.population_size, self.generation + 1);
+            let offspring = self.breed(&population, size);
+            let evaluations = offspring.len();
+            let offspring = self.evaluate(offspring);
+            self.remember(&offspring);
+            (self.reinsert(population, offspring, size), evaluations)
+        };
+        self.population = population;
+        self.generation += 1;
+        if let Some(genealogy) = &mut self.genealogy {
+            genealogy.prune(self.population.iter().map(|e| e.id));
+        }
+
+        let best = &self.population[0];
+        if self
+            .best
+            .as_ref()
+            .is_none_or(|(b, _)| best.fitness > b.fitness)
+        {
+            self.best = Some((best.clone(), self.generation));
+        }
+
+        self.processing_time += started.elapsed();
+
+        let mut progress = self.snapshot();
+        progress.stop_reason = stop_reason(&self.termination, &progress);
+        if let Some(adaptation) = self.opts.selection_adaptation {
+            self.selection_ratio = adaptation.adapt(
+                self.selection_ratio,
+                progress.best_fitness,
+                progress.average_fitness,
+            );
+            span.set_f64("selection_ratio", self.selection_ratio);
+        }
+        if let (Some(genealogy), Some(_)) = (&self.genealogy, &progress.stop_reason) {
+            let best = self.population.iter().take(self.opts.genealogy);
+            progress.ancestry = genealogy.ancestry(best.map(|e| e.id));
+        }
+
+        if self.explored.is_some() {
+            span.set_u64("redirected", self.redirected);
+        }
+        span.set_f64("best_fitness", progress.best_fitness.0);
+        span.set_f64("average_fitness", progress.average_fitness.0);
+        telemetry::record_generation(
+            evaluations as u64,
+            started.elapsed(),
+            progress.best_fitness.0,
+            progress.average_fitness.0,
+        );
+        progress
+    }
+
+    // steps until a termination condition is met
+    pub fn run(&mut self) -> Progress<G> {
+        loop {
+            let progress = self.step();
+            if progress.stop_reason.is_some() {
+                return progress;
+            }
+        }
+    }
+
+    // crossover and mutation of parents drawn from the best selection ratio of the population,
+    // or by the odds of the selection strategy, as many as for a population of the given size,
+    // or as the elites leave room for
+    fn breed(&mut self, population: &[Evaluated<G>], size: usize) -> Vec<Evaluated<G>> {
+        let _span = telemetry::Span::start("breed");
+        let per_parents = self.opts.num_individuals_per_parents;
+        let num_parents = ((size as f64 * self.selection_ratio).round() as usize).max(1);
+        let mating_pool = &population[..num_parents.clamp(per_parents, population.len())];
+        let odds = self.opts.selection.odds(population);
+        let num_offspring = self
+            .opts
+            .elitism
+            .map_or(num_parents, |elites| size.saturating_sub(elites));
+        let traced = self.genealogy.is_some();
+
+        let redirected = AtomicU64::new(0);
+        let offspring = self.pool.install(|| {
+            self.par_chunks(num_offspring, |_, rng| {
+                let parents = match &odds {
+                    Some(odds) => (0..per_parents)
+                        .map(|_| &population[odds.sample(rng)])
+                        .collect::<Vec<_>>(),
+                    None => mating_pool
+                        .choose_multiple(rng, per_parents)
+                        .collect::<Vec<_>>(),
+                };
+                let genomes = parents.iter().map(|e| e.genome.clone()).collect();
+
+                self.crossover
+                    .crossover(genomes, rng)
+                    .into_iter()
+                    .map(|child| {
+                        if !traced {
+                            return (self.mutate(child, rng, &redirected), None);
+                        }
+                        // a child equal to a parent descends from that one alone
+                        let copied = parents.iter().find(|p| p.genome == child).map(|p| p.id);
+                        let mutant = self.mutate(child.clone(), rng, &redirected);
+                        let operator = Operator::of(copied.is_none(), mutant != child);
+                        let parents = copied
+                            .map_or_else(|| parents.iter().map(|p| p.id).collect(), |id| vec![id]);
+                        (mutant, Some((parents, operator)))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+        });
+        self.redirected += redirected.into_inner();
+        self.with_ids(offspring)
+    }
+
+    // mutated again while the memory of explored genomes holds it, up to REDIRECT_TRIES times
+    fn mutate<R: Rng>(&self, child: G, rng: &mut R, redirected: &AtomicU64) -> G {
+        let mut mutant = self.mutator.mutate_scaled(child, self.strength, rng);
+        let Some(explored) = &self.explored else {
+            return mutant;
+        };
+        for _ in 0..REDIRECT_TRIES {
+            match self.fitness_calc.fingerprint(&mutant) {
+                Some(fingerprint) if explored.contains(fingerprint) => {
+                    redirected.fetch_add(1, Ordering::Relaxed);
+                    mutant = self.mutator.mutate_scaled(mutant, self.strength, rng);
+                }
+                _ => break,
+            }
+        }
+        mutant
+    }
+
+    // numbers new genomes of the coming generation and records where they came from, the
+    // initial ones have no origin
+    fn with_ids(
+        &mut self,
+        genomes: impl IntoIterator<Item = (G, Option<(Vec<u64>, Operator)>)>,
+    ) -> Vec<Evaluated<G>> {
+        let generation = self.generation + 1;
+        genomes
+            .into_iter()
+            .map(|(genome, origin)| {
+                let id = self.next_id;
+                self.next_id += 1;
+                if let Some(genealogy) = &mut self.genealogy {
+                    let (parents, operator) = origin.unwrap_or((vec![], Operator::Random));
+                    genealogy.insert(Node {
+                        id,
+                        generation,
+                        parents,
+                        operator,
+                        fitness: Fitness(0.0),
+                        genome: genome.clone(),
+                    });
+                }
+                Evaluated {
+                    id,
+                    genome,
+                    fitness: Fitness(0.0),
+                }
+            })
+            .collect()
+    }
+
+    // the fitness of new individuals for their genealogy, their fingerprints for the memory of
+    // explored genomes
+    fn remember(&mut self, evaluated: &[Evaluated<G>]) {
+        if let Some(genealogy) = &mut self.genealogy {
+            for e in evaluated {
+                genealogy.set_fitness(e.id, e.fitness);
+            }
+        }
+        if let Some(explored) = &mut self.explored {
+            let fitness_calc = &self.fitness_calc;
+            let fingerprints = self.pool.install(|| {
+                evaluated
+                    .par_iter()
+                    .filter_map(|e| fitness_calc.fingerprint(&e.genome))
+                    .collect::<Vec<_>>()
+            });
+            for fingerprint in fingerprints {
+                explored.insert(fingerprint);
+            }
+        }
+    }
+
+    // the population and the best found so far scored again if the fitness function changed
+    fn rescore(&mut self, population: Vec<Evaluated<G>>) -> Vec<Evaluated<G>> {
+        let revision = self.fitness_calc.revision();
+        if revision == self.revision {
+            return population;
+        }
+        self.revision = revision;
+        if let Some((best, _)) = &mut self.best {
+            best.fitness = self.fitness_calc.fitness_of(&best.genome);
+        }
+        self.evaluate(population)
+    }
+
+    fn evaluate(&self, population: Vec<Evaluated<G>>) -> Vec<Evaluated<G>> {
+        let span = telemetry::Span::start("evaluate");
+        span.set_u64("genomes", population.len() as u64);
+
+        let deadline = self.termination.deadline;
+        self.pool
+            .install(|| evaluate(&self.fitness_calc, population, deadline))
+    }
+
+    // the best offspring take replace_ratio of the new population of the given size, the best
+    // parents fill the rest
+    fn reinsert(
+        &self,
+        mut population: Vec<Evaluated<G>>,
+        mut offspring: Vec<Evaluated<G>>,
+        size: usize,
+    ) -> Vec<Evaluated<G>> {
+        let num_offspring = match self.opts.elitism {
+            Some(elites) => size.saturating_sub(elites),
+            None => (size as f64 * self.opts.reinsertion_ratio).round() as usize,
+        }
+        .min(offspring.len())
+        .min(size);
+        offspring.truncate(num_offspring);
+        let num_parents = (size - num_offspring).min(population.len());
+        offspring.extend(population.drain(..num_parents));
+        self.pool.install(|| sort(&mut offspring));
+
+        offspring
+    }
+
+    // calls f for 0..n in parallel, item i always gets the same random stream whatever the threads
+    fn par_chunks<T, Op>(&self, n: usize, f: Op) -> Vec<T>
+    where
+        T: Send,
+        Op: Fn(usize, &mut SearchRng) -> T + Sync,
+    {
+        (0..n.div_ceil(CHUNK_SIZE))
+            .into_par_iter()
+            .flat_map_iter(|chunk| {
+                let mut rng = self.stream(chunk);
+                let end = ((chunk + 1) * CHUNK_SIZE).min(n);
+                (chunk * CHUNK_SIZE..end)
+                    .map(|i| f(i, &mut rng))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // a stream per generation, split in chunks
+    fn stream(&self, chunk: usize) -> SearchRng {
+        SearchRng::stream(self.opts.rng, self.seed, self.generation, chunk)
+    }
+
+    fn snapshot(&self) -> Progress<G> {
+        let fitness_values = self
+            .population
+            .iter()
+            .map(|e| e.fitness)
+            .collect::<Vec<_>>();
+        let stats = gen::fitness_stats(&fitness_values);
+        let (best, best_found_in) = self.best.as_ref().expect("evaluated at least once");
+
+        Progress {
+            iteration: self.generation,
+            average_fitness: Fitness(stats.mean),
+            median_fitness: stats.median,
+            fitness_std_dev: stats.std_dev,
+            best_fitness: best.fitness,
+            best_genome: best.genome.clone(),
+            best_found_in: *best_found_in,
+            duration: self.started_at.elapsed(),
+            processing_time: self.processing_time,
+            stop_reason: None,
+            sample: self.sample(),
+            top: (0..self.opts.top_size.min(self.population.len()))
+                .map(|rank| Individual {
+                    rank,
+                    genome: self.population[rank].genome.clone(),
+                    fitness: self.population[rank].fitness,
+                })
+                .collect(),
+            ancestry: Vec::new(),
+        }
+    }
+
+    fn sample(&self) -> Vec<Individual<G>> {
+        let size = self.population.len();
+        let n = self.opts.sample_size.min(size);
+
+        (0..n)
+            .map(|i| {
+                let rank = i * size / n;
+                Individual {
+                    rank,
+                    genome: self.population[rank].genome.clone(),
+                    fitness: self.population[rank].fitness,
+                }
+            })
+            .collect()
+    }
+}
+
+// best so far, timing and termination of the engines that evaluate a fresh set of keys every
+// step instead of keeping a population
+pub(crate) struct Bookkeeping<G> {
+    termination: gen::TerminationOptions,
+    pause: Pause,
+    sample_size: usize,
+    top_size: usize,
+    best: Option<(G, Fitness, u64)>,
+    generation: u64,
+    started_at: Instant,
+    processing_time: Duration,
+}
+
+impl<G: Clone> Bookkeeping<G> {
+    pub(crate) fn new(opts: &gen::Options) -> Self {
+        Self {
+            termination: opts.termination.clone(),
+            pause: opts.engine.pause.clone(),
+            sample_size: opts.engine.sample_size,
+            top_size: opts.engine.top_size,
+            best: None,
+            generation: 0,
+            started_at: Instant::now(),
+            processing_time: Duration::ZERO,
+        }
+    }
+
+    // to be called before a step, see Pause
+    pub(crate) fn wait_while_paused(&mut self) {
+        self.started_at += self.pause.wait();
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.termination.deadline
+    }
+
+    // the keys of a step in any order, started is when the step began
+    pub(crate) fn record(
+        &mut self,
+        mut evaluated: Vec<(G, Fitness)>,
+        started: Instant,
+    ) -> Progress<G> {
+        // stable, so ties keep their order and the run stays reproducible
+        evaluated.sort_by_key(|e| std::cmp::Reverse(e.1));
+        self.generation += 1;
+        let (genome, fitness) = &evaluated[0];
+        if self.best.as_ref().is_none_or(|best| *fitness > best.1) {
+            self.best = Some((genome.clone(), *fitness, self.generation));
+        }
+        self.processing_time += started.elapsed();
+
+        let fitness_values = evaluated.iter().map(|e| e.1).collect::<Vec<_>>();
+        let stats = gen::fitness_stats(&fitness_values);
+        let (best_genome, best_fitness, best_found_in) = self.best.clone().expect("recorded");
+        let n = self.sample_size.min(evaluated.len());
+
+        let mut progress = Progress {
+            iteration: self.generation,
+            average_fitness: Fitness(stats.mean),
+            median_fitness: stats.median,
+            fitness_std_dev: stats.std_dev,
+            best_fitness,
+            best_genome,
+            best_found_in,
+            duration: self.started_at.elapsed(),
+            processing_time: self.processing_time,
+            stop_reason: None,
+            sample: (0..n)
+                .map(|i| {
+                    let rank = i * evaluated.len() / n;
+                    Individual {
+                        rank,
+                        genome: evaluated[rank].0.clone(),
+                        fitness: evaluated[rank].1,
+                    }
+                })
+                .collect(),
+            top: evaluated
+                .iter()
+                .take(self.top_size)
+                .enumerate()
+                .map(|(rank, (genome, fitness))| Individual {
+                    rank,
+                    genome: genome.clone(),
+                    fitness: *fitness,
+                })
+                .collect(),
+            ancestry: Vec::new(),
+        };
+        progress.stop_reason = stop_reason(&self.termination, &progress);
+        progress
+    }
+}
+
+// the first termination condition the progress meets, shared by the engines
+pub(crate) fn stop_reason<G>(
+    termination: &gen::TerminationOptions,
+    progress: &Progress<G>,
+) -> Option<String> {
+    if progress.iteration >= termination.generation_limit {
+        return Some(format!(
+            "generation limit of {} reached",
+            termination.generation_limit
+        ));
+    }
+
+    if let Ok(limit) = termination.time_limit.to_std() {
+        if progress.duration >= limit {
+            return Some(format!("time limit of {:?} reached", limit));
+        }
+    }
+
+    if termination
+        .deadline
+        .is_some_and(|deadline| Instant::now() >= deadline)
+    {
+        return Some("deadline reached".to_string());
+    }
+
+    let target = termination.target_fitness.unwrap_or(Fitness(1.0));
+    if progress.best_fitness >= target {
+        return Some(format!("fitness limit of {} reached", target));
+    }
+
+    None
+}
+
+/// Holds a search before its next generation while paused, e.g. to free the CPU of a shared
+/// workstation for a while; the population is kept and the paused time doesn't count towards
+/// the time limit. Clones control the same search.
+#[derive(Debug, Clone, Default)]
+pub struct Pause(Arc<AtomicBool>);
+
+impl Pause {
+    pub fn set(&self, paused: bool) {
+        self.0.store(paused, Ordering::Relaxed);
+    }
+
+    // whether the search is paused now; a single atomic operation, so a signal handler may
+    // call it
+    pub fn toggle(&self) -> bool {
+        !self.0.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    // blocks while paused, returns for how long
+    pub(crate) fn wait(&self) -> Duration {
+        let started = Instant::now();
+        while self.is_paused() {
+            thread::sleep(PAUSE_POLL);
+        }
+        started.elapsed()
+    }
+}
+
+/// CPU cores the worker threads are pinned to, parsed from a list like 0-3,8
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cores(pub Vec<usize>);
+
+impl FromStr for Cores {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cores = Vec::new();
+        for part in s.split(',') {
+            let parse = |n: &str| {
+                n.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid core {n} in {s}, expected e.g. 0-3,8"))
+            };
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse(first)?, parse(last)?);
+                    if first > last {
+                        return Err(format!("empty core range {part} in {s}"));
+                    }
+                    cores.extend(first..=last);
+                }
+                None => cores.push(parse(part)?),
+            }
+        }
+
+        cores.sort_unstable();
+        cores.dedup();
+        Ok(Cores(cores))
+    }
+}
+
+impl fmt::Display for Cores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cores = self.0.iter().map(|core| core.to_string());
+        write!(f, "{}", cores.collect::<Vec<_>>().join(","))
+    }
+}
+
+// worker i runs on the i-th of the pinned cores, round robin if there are more workers
+pub(crate) fn thread_pool(opts: &gen::EngineOptions) -> anyhow::Result<ThreadPool> {
+    let builder = ThreadPoolBuilder::new().num_threads(opts.worker_threads());
+    // wasm32 can't spawn threads, the pool runs on the caller like rayon's own fallback
+    #[cfg(target_arch = "wasm32")]
+    let builder = builder.num_threads(1).use_current_thread();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = match &opts.pin_cores {
+        Some(Cores(cores)) => {
+            // only the cores of the process's affinity mask can be pinned to
+            let available = core_affinity::get_core_ids().unwrap_or_default();
+            let core_ids = cores
+                .iter()
+                .map(|&id| {
+                    available
+                        .iter()
+                        .find(|core| core.id == id)
+                        .copied()
+                        .ok_or_else(|| anyhow::anyhow!("core {id} is not available"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            builder.start_handler(move |i| {
+                core_affinity::set_for_current(core_ids[i % core_ids.len()]);
+            })
+        }
+        None => builder,
+    };
+
+    Ok(builder.build()?)
+}
+
+// fitness of every genome in order, in parallel on the current rayon pool; the batches left
+// when the deadline passes aren't evaluated and score 0
+pub(crate) fn fitness_of_all<G, F>(
+    fitness_calc: &F,
+    genomes: &[G],
+    deadline: Option<Instant>,
+) -> Vec<Fitness>
+where
+    G: Sync,
+    F: FitnessFunction<G>,
+{
+    genomes
+        .par_chunks(EVAL_BATCH_SIZE)
+        .flat_map_iter(|batch| {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                vec![Fitness(0.0); batch.len()]
+            } else {
+                fitness_calc.fitness_of_batch(batch)
+            }
+        })
+        .collect()
+}
+
+// scores the population again, best first
+fn evaluate<G, F>(
+    fitness_calc: &F,
+    population: Vec<Evaluated<G>>,
+    deadline: Option<Instant>,
+) -> Vec<Evaluated<G>>
+where
+    G: Send + Sync,
+    F: FitnessFunction<G>,
+{
+    let (ids, genomes): (Vec<_>, Vec<_>) = population.into_iter().map(|e| (e.id, e.genome)).unzip();
+    let fitness = fitness_of_all(fitness_calc, &genomes, deadline);
+    let mut evaluated = ids
+        .into_iter()
+        .zip(genomes)
+        .zip(fitness)
+        .map(|((id, genome), fitness)| Evaluated {
+            id,
+            genome,
+            fitness,
+        })
+        .collect::<Vec<_>>();
+
+    sort(&mut evaluated);
+    evaluated
+}
+
+// best first, the sort is stable so ties keep their order and selection stays reproducible
+fn sort<G: Send>(population: &mut [Evaluated<G>]) {
+    population.par_sort_by(|a, b| b.fitness.cmp(&a.fitness));
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::mpsc;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    use super::*;
+
+    // genome is a bit string, fitness is the fraction of set bits
+    pub(crate) struct OneMax;
+
+    impl FitnessFunction<u32> for OneMax {
+        fn fitness_of(&self, genome: &u32) -> Fitness {
+            Fitness(genome.count_ones() as f64 / 32.0)
+        }
+    }
+
+    impl GenomeBuilder<u32> for OneMax {
+        fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> u32 {
+            rng.gen::<u32>() & rng.gen::<u32>()
+        }
+    }
+
+    impl CrossoverOp<u32> for OneMax {
+        fn crossover<R: Rng>(&self, parents: Vec<u32>, rng: &mut R) -> Vec<u32> {
+            let mask = rng.gen::<u32>();
+            vec![(parents[0] & mask) | (parents[1] & !mask)]
+        }
+    }
+
+    impl MutationOp<u32> for OneMax {
+        fn mutate<R: Rng>(&self, genome: u32, rng: &mut R) -> u32 {
+            genome ^ (1 << rng.gen_range(0..32))
+        }
+    }
+
+    fn one_max(opts: &gen::Options) -> Engine<u32, OneMax, OneMax, OneMax> {
+        Engine::new(OneMax, OneMax, OneMax, OneMax, opts).unwrap()
+    }
+
+    #[test]
+    fn test_engine() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 200;
+        opts.termination.generation_limit = 100;
+        opts.engine.sample_size = 8;
+        opts.engine.top_size = 3;
+        opts.engine.genealogy = 2;
+
+        let mut engine = one_max(&opts);
+
+        let mut best = Fitness(0.0);
+        loop {
+            let progress = engine.step();
+            assert!(progress.best_fitness >= best);
+            assert_eq!(engine.population.len(), 200);
+            assert_eq!(
+                progress.sample.iter().map(|i| i.rank).collect::<Vec<_>>(),
+                [0, 25, 50, 75, 100, 125, 150, 175]
+            );
+            assert!(progress
+                .sample
+                .windows(2)
+                .all(|w| w[0].fitness >= w[1].fitness));
+            assert_eq!(progress.top.len(), 3);
+            assert_eq!(progress.top[0].fitness, progress.sample[0].fitness);
+            assert!(progress
+                .top
+                .windows(2)
+                .all(|w| w[0].fitness >= w[1].fitness));
+            best = progress.best_fitness;
+
+            if progress.stop_reason.is_none() {
+                assert!(progress.ancestry.is_empty());
+            } else {
+                assert!(progress.iteration <= 100);
+                assert_eq!(progress.best_fitness, Fitness(1.0));
+                assert_eq!(progress.best_genome, u32::MAX);
+
+                // the best two trace back to random keys of the first generation
+                let ancestry = &progress.ancestry;
+                let ids = ancestry.iter().map(|n| n.id).collect::<Vec<_>>();
+                assert!(ancestry.iter().any(|n| n.genome == u32::MAX));
+                assert!(ancestry
+                    .iter()
+                    .all(|n| n.parents.iter().all(|p| ids.contains(p))));
+                assert!(ancestry
+                    .iter()
+                    .all(|n| n.parents.is_empty() == (n.operator == Operator::Random)));
+                assert_eq!(ancestry[0].generation, 1);
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_deterministic() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 3000;
+        opts.engine.seed = Some(42);
+
+        let run = |opts: &gen::Options, threads: usize, pin_cores: Option<&str>| {
+            let mut opts = opts.clone();
+            opts.engine.threads = threads;
+            opts.engine.pin_cores = pin_cores.map(|cores| cores.parse().unwrap());
+            let mut engine = one_max(&opts);
+            for _ in 0..5 {
+                engine.step();
+            }
+            engine
+                .population
+                .iter()
+                .map(|e| e.genome)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(&opts, 1, None), run(&opts, 4, None));
+        // core 0 is there on any machine the tests run on
+        assert_eq!(run(&opts, 1, None), run(&opts, 4, Some("0")));
+
+        let mut xoshiro = opts.clone();
+        xoshiro.engine.rng = crate::rng::RngAlgorithm::Xoshiro;
+        assert_eq!(run(&xoshiro, 1, None), run(&xoshiro, 4, None));
+        assert_ne!(run(&xoshiro, 1, None), run(&opts, 1, None));
+    }
+
+    #[test]
+    fn test_population_schedule() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 1000;
+        opts.engine.population_schedule = "3:400,5:2000".parse().unwrap();
+        opts.engine.seed = Some(7);
+
+        let mut engine = one_max(&opts);
+        let sizes = (0..6)
+            .map(|_| {
+                engine.step();
+                engine.population.len()
+            })
+            .collect::<Vec<_>>();
+        // a growing population is limited by the offspring and the parents there are
+        assert_eq!(sizes, [1000, 1000, 400, 400, 1400, 2000]);
+        assert!(engine
+            .population
+            .windows(2)
+            .all(|w| w[0].fitness >= w[1].fitness));
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 200;
+        opts.engine.seed = Some(5);
+
+        for selection in ["truncation", "proportionate", "ranking"] {
+            opts.engine.selection = selection.parse().unwrap();
+            let mut engine = one_max(&opts);
+            let first = engine.step().average_fitness;
+            let last = (0..10).map(|_| engine.step()).last().unwrap();
+            assert!(last.average_fitness > first, "{selection}");
+
+            // the better half is drawn at least as often as the worse, by rank three times as
+            // often; truncation has no odds
+            let odds = opts.engine.selection.odds(&engine.population);
+            if let Some(odds) = odds {
+                let mut rng = ChaCha12Rng::seed_from_u64(5);
+                let top = (0..1000).filter(|_| odds.sample(&mut rng) < 100).count();
+                let expected = if selection == "ranking" { 650 } else { 450 };
+                assert!(top > expected, "{selection} {top}");
+            }
+        }
+        assert!("tournament".parse::<SelectionStrategy>().is_err());
+    }
+
+    #[test]
+    fn test_elitism() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 100;
+        opts.engine.seed = Some(3);
+
+        for elites in [0, 5] {
+            opts.engine.elitism = Some(elites);
+            let mut engine = one_max(&opts);
+            engine.step();
+            for _ in 0..3 {
+                let best = engine
+                    .population
+                    .iter()
+                    .take(elites)
+                    .map(|e| e.id)
+                    .collect::<Vec<_>>();
+                let ids = engine.population.iter().map(|e| e.id).collect::<Vec<_>>();
+                engine.step();
+                let kept = engine
+                    .population
+                    .iter()
+                    .filter(|e| ids.contains(&e.id))
+                    .map(|e| e.id)
+                    .collect::<Vec<_>>();
+                assert_eq!(kept.len(), elites);
+                assert!(best.iter().all(|id| kept.contains(id)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 200;
+        let mut engine = one_max(&opts);
+        engine.step();
+
+        let pause = opts.engine.pause.clone();
+        assert!(pause.toggle() && pause.is_paused());
+        let (sender, receiver) = mpsc::channel();
+        let search = thread::spawn(move || sender.send(engine.step()).unwrap());
+        // held until resumed
+        let held = receiver.recv_timeout(Duration::from_millis(100));
+        assert_eq!(held.unwrap_err(), mpsc::RecvTimeoutError::Timeout);
+        assert!(!pause.toggle() && !pause.is_paused());
+        let progress = receiver.recv().unwrap();
+        search.join().unwrap();
+        assert_eq!(progress.iteration, 2);
+
+        // the reason names the limit, not the time the search took past it
+        opts.termination.time_limit = chrono::Duration::seconds(60);
+        let late = Progress {
+            duration: Duration::from_secs(61),
+            ..progress
+        };
+        let reason = stop_reason(&opts.termination, &late);
+        assert_eq!(reason.as_deref(), Some("time limit of 60s reached"));
+    }
+
+    #[test]
+    fn test_cores() {
+        let cores = "4-6,0,5".parse::<Cores>().unwrap();
+        assert_eq!(cores, Cores(vec![0, 4, 5, 6]));
+        assert_eq!(cores.to_string(), "0,4,5,6");
+        assert!("3-1".parse::<Cores>().is_err());
+        assert!("a".parse::<Cores>().is_err());
+        assert!("".parse::<Cores>().is_err());
+
+        let mut opts = gen::EngineOptions {
+            pin_cores: Some(Cores(vec![1_000_000])),
+            ..Default::default()
+        };
+        assert!(thread_pool(&opts).is_err());
+        assert_eq!(opts.worker_threads(), 1);
+        opts.threads = 3;
+        assert_eq!(opts.worker_threads(), 3);
+    }
+}
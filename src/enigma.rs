@@ -1,40 +1,1326 @@
-use enigma_simulator::{EnigmaBuilder, EnigmaMachine};
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use anyhow::{anyhow, ensure};
+
+pub use crate::enigma_core::{GARBLE, LETTERS_NUM, MAX_ROTOR_NUM};
+use crate::{
+    cache::ScramblerCache,
+    cipher::Cipher,
+    enigma_core::{self, Core, Rotor, Wiring, TURNOVERS},
+};
 
-pub const MAX_ROTOR_NUM: u8 = 6;
 pub const MAX_RING_SETTINGS_NUM: u8 = 26;
 pub const MAX_ROTOR_POSITIONS_NUM: u8 = 26;
+pub const MAX_PLUGS_NUM: usize = LETTERS_NUM as usize / 2;
+pub const PLUGS_NUM: usize = LETTERS_NUM as usize * (LETTERS_NUM as usize - 1) / 2;
+// every legal plug pair, lower letter first
+pub const PLUGS: [(u8, u8); PLUGS_NUM] = all_plugs();
+pub const MODEL_NAME: &str = "Enigma I";
+const ROTOR_NAMES: [&str; MAX_ROTOR_NUM as usize] = ["I", "II", "III", "IV", "V", "VI"];
+// a reflector swaps every letter with another one
+pub const REFLECTOR_PAIRS_NUM: usize = LETTERS_NUM as usize / 2;
+
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reflector {
+    #[default]
+    B,
+    C,
+    // UKW-D, rewired in the field: pairs of letters, A = 1 .. Z = 26
+    D(Vec<(u8, u8)>),
+}
+
+impl Reflector {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Reflector::D(pairs) = self {
+            ensure!(
+                pairs.len() == REFLECTOR_PAIRS_NUM,
+                "UKW-D must wire {} pairs, got {}",
+                REFLECTOR_PAIRS_NUM,
+                pairs.len()
+            );
+            // disjoint pairs covering the alphabet keep the wiring an involution without fixed points
+            for (i, &pair) in pairs.iter().enumerate() {
+                ensure!(is_plug_valid(pair), "invalid UKW-D pair {:?}", pair);
+                ensure!(
+                    !is_plug_conflicting(&pairs[..i], pair),
+                    "UKW-D letters must be unique, got {:?}",
+                    pairs
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // letter i is reflected to wiring[i], A = 0
+    fn wiring(&self) -> [u8; LETTERS_NUM as usize] {
+        match self {
+            Reflector::B => enigma_core::REFLECTOR_B,
+            Reflector::C => enigma_core::REFLECTOR_C,
+            Reflector::D(pairs) => {
+                let mut wiring = [0; LETTERS_NUM as usize];
+                for &(a, b) in pairs {
+                    wiring[a as usize - 1] = b - 1;
+                    wiring[b as usize - 1] = a - 1;
+                }
+                wiring
+            }
+        }
+    }
+}
+
+// B, C or D followed by its pairs, e.g. "D AC BZ ..."
+impl fmt::Display for Reflector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reflector::B => write!(f, "B"),
+            Reflector::C => write!(f, "C"),
+            Reflector::D(pairs) => write!(f, "D {}", pairs_notation(pairs)),
+        }
+    }
+}
+
+impl FromStr for Reflector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut tokens = s.split_whitespace();
+        let reflector = match (tokens.next(), tokens.clone().next()) {
+            (Some("B"), None) => Reflector::B,
+            (Some("C"), None) => Reflector::C,
+            (Some("D"), _) => Reflector::D(tokens.map(parse_plug).collect::<anyhow::Result<_>>()?),
+            _ => {
+                return Err(anyhow!(
+                    "expected reflector B, C or D with its pairs, got {:?}",
+                    s
+                ))
+            }
+        };
+        reflector.validate()?;
+
+        Ok(reflector)
+    }
+}
 
 #[derive(Hash, PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Settings {
     pub rotors: (u8, u8, u8),
     pub ring_settings: (u8, u8, u8),
     pub rotor_positions: (u8, u8, u8),
+    // pairs of swapped letters, A = 1 .. Z = 26
+    pub plugboard: Vec<(u8, u8)>,
+    pub reflector: Reflector,
+}
+
+/// Everything of the settings but the plugboard: keys that share it share the scrambling of
+/// the rotors and reflector at every position.
+#[derive(Hash, PartialEq, Eq, Debug, Clone)]
+pub struct RotorCore {
+    pub rotors: (u8, u8, u8),
+    pub ring_settings: (u8, u8, u8),
+    pub rotor_positions: (u8, u8, u8),
+    pub reflector: Reflector,
 }
 
+impl Settings {
+    pub fn rotor_core(&self) -> RotorCore {
+        RotorCore {
+            rotors: self.rotors,
+            ring_settings: self.ring_settings,
+            rotor_positions: self.rotor_positions,
+            reflector: self.reflector.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let (r0, r1, r2) = self.rotors;
+        ensure!(
+            [r0, r1, r2].iter().all(|r| (1..=MAX_ROTOR_NUM).contains(r)),
+            "rotors must be in 1..={}, got {:?}",
+            MAX_ROTOR_NUM,
+            self.rotors
+        );
+        ensure!(
+            r0 != r1 && r1 != r2 && r2 != r0,
+            "rotors must be unique, got {:?}",
+            self.rotors
+        );
+        ensure!(
+            is_triple_in_range(self.ring_settings, MAX_RING_SETTINGS_NUM),
+            "ring settings must be in 1..={}, got {:?}",
+            MAX_RING_SETTINGS_NUM,
+            self.ring_settings
+        );
+        ensure!(
+            is_triple_in_range(self.rotor_positions, MAX_ROTOR_POSITIONS_NUM),
+            "rotor positions must be in 1..={}, got {:?}",
+            MAX_ROTOR_POSITIONS_NUM,
+            self.rotor_positions
+        );
+        ensure!(
+            self.plugboard.len() <= MAX_PLUGS_NUM,
+            "plugboard can have at most {} pairs, got {}",
+            MAX_PLUGS_NUM,
+            self.plugboard.len()
+        );
+
+        for (i, &plug) in self.plugboard.iter().enumerate() {
+            ensure!(is_plug_valid(plug), "invalid plugboard pair {:?}", plug);
+            ensure!(
+                !is_plug_conflicting(&self.plugboard[..i], plug),
+                "plugboard letters must be unique, got {:?}",
+                self.plugboard
+            );
+        }
+        self.reflector.validate()
+    }
+
+    /// The one of the keys that encrypt every text alike reported and cached for all of them:
+    /// plugboard and UKW-D pairs lower letter first and in order; the ring setting of the left
+    /// rotor at A with its position turned along, as the left rotor never carries and only the
+    /// difference of the two counts; a middle rotor at its turnover turned past it with the
+    /// left rotor, as the double step does at the first key press anyway, unless the right
+    /// rotor carries then too. Invalid settings are returned as they are.
+    pub fn canonicalize(&self) -> Settings {
+        if self.validate().is_err() {
+            return self.clone();
+        }
+        let turn = |position: u8, by: i16| {
+            ((position as i16 - 1 + by).rem_euclid(LETTERS_NUM as i16) + 1) as u8
+        };
+        let at_turnover =
+            |rotor: u8, position: u8| TURNOVERS[rotor as usize - 1] >> (position - 1) & 1 != 0;
+        let sorted = |pairs: &[(u8, u8)]| {
+            let mut pairs = pairs
+                .iter()
+                .map(|&(a, b)| (a.min(b), a.max(b)))
+                .collect::<Vec<_>>();
+            pairs.sort_unstable();
+            pairs
+        };
+
+        let (ring, middle_ring, right_ring) = self.ring_settings;
+        let (left, mut middle, right) = self.rotor_positions;
+        let mut left = turn(left, 1 - ring as i16);
+        let (_, middle_rotor, right_rotor) = self.rotors;
+        if at_turnover(middle_rotor, middle)
+            && !at_turnover(middle_rotor, turn(middle, 1))
+            && !at_turnover(right_rotor, right)
+        {
+            middle = turn(middle, 1);
+            left = turn(left, 1);
+        }
+
+        Settings {
+            rotors: self.rotors,
+            ring_settings: (1, middle_ring, right_ring),
+            rotor_positions: (left, middle, right),
+            plugboard: sorted(&self.plugboard),
+            reflector: match &self.reflector {
+                Reflector::D(pairs) => Reflector::D(sorted(pairs)),
+                reflector => reflector.clone(),
+            },
+        }
+    }
+
+    /// Whether the keys encrypt every message of up to `message_len` key presses alike: the
+    /// same letter comes out for every key at each of the first `message_len` positions. Keys
+    /// that canonicalize alike are equivalent at any length; shorter messages tell fewer apart,
+    /// e.g. keys whose middle rotor only steps differently later on. Invalid settings are only
+    /// equivalent to themselves.
+    pub fn is_equivalent_to(&self, other: &Settings, message_len: usize) -> bool {
+        if self.canonicalize() == other.canonicalize() {
+            return true;
+        }
+        let (Ok(first), Ok(second)) = (Machine::new(self), Machine::new(other)) else {
+            return false;
+        };
+        let (mut a, mut b) = (first.core.positions(), second.core.positions());
+        (0..message_len).all(|_| {
+            first.step(&mut a);
+            second.step(&mut b);
+            (0..LETTERS_NUM).all(|c| first.press(c, &a) == second.press(c, &b))
+        })
+    }
+
+    /// The key as a number, the same for keys that only differ in the order of their pairs;
+    /// canonicalize first to number keys that encrypt alike the same. Mixed radix over the
+    /// rotors, rings, positions, plugboard and reflector, a wiring ranked among all the sets of
+    /// letter pairs it could be, so every valid key fits.
+    pub fn pack(&self) -> u128 {
+        let (r0, r1, r2) = self.rotors;
+        let (s0, s1, s2) = self.ring_settings;
+        let (p0, p1, p2) = self.rotor_positions;
+        let reflector = match &self.reflector {
+            Reflector::B => 0,
+            Reflector::C => 1,
+            Reflector::D(pairs) => 2 + pairs_rank(pairs, true),
+        };
+        let digit = |n: u8, radix: u8| (n.saturating_sub(1) % radix) as u128;
+
+        [
+            (digit(r0, MAX_ROTOR_NUM), MAX_ROTOR_NUM as u128),
+            (digit(r1, MAX_ROTOR_NUM), MAX_ROTOR_NUM as u128),
+            (digit(r2, MAX_ROTOR_NUM), MAX_ROTOR_NUM as u128),
+            (
+                digit(s0, MAX_RING_SETTINGS_NUM),
+                MAX_RING_SETTINGS_NUM as u128,
+            ),
+            (
+                digit(s1, MAX_RING_SETTINGS_NUM),
+                MAX_RING_SETTINGS_NUM as u128,
+            ),
+            (
+                digit(s2, MAX_RING_SETTINGS_NUM),
+                MAX_RING_SETTINGS_NUM as u128,
+            ),
+            (
+                digit(p0, MAX_ROTOR_POSITIONS_NUM),
+                MAX_ROTOR_POSITIONS_NUM as u128,
+            ),
+            (
+                digit(p1, MAX_ROTOR_POSITIONS_NUM),
+                MAX_ROTOR_POSITIONS_NUM as u128,
+            ),
+            (
+                digit(p2, MAX_ROTOR_POSITIONS_NUM),
+                MAX_ROTOR_POSITIONS_NUM as u128,
+            ),
+            (
+                pairs_rank(&self.plugboard, false),
+                PAIRINGS[0][LETTERS_NUM as usize],
+            ),
+            (reflector, 2 + PAIRINGS[1][LETTERS_NUM as usize]),
+        ]
+        .into_iter()
+        .fold(0, |packed, (digit, radix)| packed * radix + digit)
+    }
+}
+
+// number of sets of disjoint pairs of n letters, [1] of those that pair every letter
+const PAIRINGS: [[u128; LETTERS_NUM as usize + 1]; 2] = pairings();
+
+const fn pairings() -> [[u128; LETTERS_NUM as usize + 1]; 2] {
+    let mut table = [[1; LETTERS_NUM as usize + 1]; 2];
+    table[1][1] = 0;
+    let mut n = 2;
+    while n <= LETTERS_NUM as usize {
+        // the first letter is left alone or pairs with one of the others
+        table[0][n] = table[0][n - 1] + (n as u128 - 1) * table[0][n - 2];
+        table[1][n] = (n as u128 - 1) * table[1][n - 2];
+        n += 1;
+    }
+    table
+}
+
+// rank of a set of disjoint letter pairs among all the sets of them, or among those that pair
+// every letter: the lowest letter left is either alone or paired with the j-th of the others
+fn pairs_rank(pairs: &[(u8, u8)], every_letter: bool) -> u128 {
+    let mut partners = [None; LETTERS_NUM as usize];
+    for &(a, b) in pairs.iter().filter(|&&pair| is_plug_valid(pair)) {
+        partners[a as usize - 1] = Some(b - 1);
+        partners[b as usize - 1] = Some(a - 1);
+    }
+
+    let table = &PAIRINGS[every_letter as usize];
+    let mut left = (1_u32 << LETTERS_NUM) - 1;
+    let mut rank = 0;
+    while left != 0 {
+        let letter = left.trailing_zeros();
+        left &= !(1 << letter);
+        let n = left.count_ones() as usize;
+        match partners[letter as usize] {
+            Some(partner) if left & (1 << partner) != 0 => {
+                let j = (left & ((1 << partner) - 1)).count_ones() as u128;
+                let alone = if every_letter { 0 } else { table[n] };
+                rank += alone + j * table[n - 1];
+                left &= !(1 << partner);
+            }
+            _ => {}
+        }
+    }
+    rank
+}
+
+pub(crate) fn pairs_notation(pairs: &[(u8, u8)]) -> String {
+    pairs
+        .iter()
+        .map(|&(a, b)| format!("{}{}", letter(a), letter(b)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// key sheet notation: [reflector /] rotors / ring settings / rotor positions / plugboard,
+// e.g. "II V III / 08 05 20 / M C U / AB CZ", the reflector is only written when it isn't B
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (r0, r1, r2) = self.rotors;
+        let (s0, s1, s2) = self.ring_settings;
+        let (p0, p1, p2) = self.rotor_positions;
+
+        if self.reflector != Reflector::B {
+            write!(f, "{} / ", self.reflector)?;
+        }
+        write!(
+            f,
+            "{} {} {} / {:02} {:02} {:02} / {} {} {}",
+            rotor_name(r0),
+            rotor_name(r1),
+            rotor_name(r2),
+            s0,
+            s1,
+            s2,
+            letter(p0),
+            letter(p1),
+            letter(p2)
+        )?;
+
+        if !self.plugboard.is_empty() {
+            write!(f, " / {}", pairs_notation(&self.plugboard))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Settings {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut sections = s.split('/').map(str::trim).collect::<Vec<_>>();
+
+        // rotor names are roman numerals, so a leading B, C or D is a reflector
+        let reflector = match sections.first().and_then(|s| s.split_whitespace().next()) {
+            Some("B" | "C" | "D") => sections.remove(0).parse()?,
+            _ => Reflector::B,
+        };
+        ensure!(
+            (3..=4).contains(&sections.len()),
+            "expected `[reflector /] rotors / ring settings / rotor positions [/ plugboard]`, got {:?}",
+            s
+        );
+
+        let settings = Settings {
+            rotors: parse_triple(sections[0], parse_rotor)?,
+            ring_settings: parse_triple(sections[1], |v| Ok(v.parse::<u8>()?))?,
+            rotor_positions: parse_triple(sections[2], parse_letter)?,
+            plugboard: sections.get(3).map_or(Ok(Vec::new()), |p| {
+                p.split_whitespace().map(parse_plug).collect()
+            })?,
+            reflector,
+        };
+        settings.validate()?;
+
+        Ok(settings)
+    }
+}
+
+fn parse_triple(
+    section: &str,
+    parse: impl Fn(&str) -> anyhow::Result<u8>,
+) -> anyhow::Result<(u8, u8, u8)> {
+    let values = section
+        .split_whitespace()
+        .map(parse)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    match values[..] {
+        [a, b, c] => Ok((a, b, c)),
+        _ => Err(anyhow!("expected 3 values, got {:?}", section)),
+    }
+}
+
+fn parse_rotor(name: &str) -> anyhow::Result<u8> {
+    ROTOR_NAMES
+        .iter()
+        .position(|&r| r == name)
+        .map(|i| i as u8 + 1)
+        .ok_or_else(|| anyhow!("unknown rotor {:?}", name))
+}
+
+fn parse_letter(s: &str) -> anyhow::Result<u8> {
+    match s.as_bytes() {
+        &[c] if c.is_ascii_alphabetic() => Ok(c.to_ascii_uppercase() - b'A' + 1),
+        _ => Err(anyhow!("expected a letter, got {:?}", s)),
+    }
+}
+
+pub(crate) fn parse_plug(s: &str) -> anyhow::Result<(u8, u8)> {
+    let (a, b) = s
+        .char_indices()
+        .nth(1)
+        .map(|(i, _)| s.split_at(i))
+        .ok_or_else(|| anyhow!("expected a pair of letters, got {:?}", s))?;
+    let (a, b) = (parse_letter(a)?, parse_letter(b)?);
+
+    Ok((a.min(b), a.max(b)))
+}
+
+pub(crate) fn rotor_name(r: u8) -> String {
+    match ROTOR_NAMES.get((r as usize).wrapping_sub(1)) {
+        Some(name) => name.to_string(),
+        None => format!("?{}", r),
+    }
+}
+
+// '?' for values out of 1..=26 so that displaying invalid settings never panics
+pub(crate) fn letter(n: u8) -> char {
+    if (1..=LETTERS_NUM).contains(&n) {
+        (b'A' + n - 1) as char
+    } else {
+        '?'
+    }
+}
+
+const fn all_plugs() -> [(u8, u8); PLUGS_NUM] {
+    let mut plugs = [(0, 0); PLUGS_NUM];
+    let mut i = 0;
+    let mut a = 1;
+    while a <= LETTERS_NUM {
+        let mut b = a + 1;
+        while b <= LETTERS_NUM {
+            plugs[i] = (a, b);
+            i += 1;
+            b += 1;
+        }
+        a += 1;
+    }
+    plugs
+}
+
+pub fn is_plug_valid(plug: (u8, u8)) -> bool {
+    (1..=LETTERS_NUM).contains(&plug.0) && (1..=LETTERS_NUM).contains(&plug.1) && plug.0 != plug.1
+}
+
+pub fn is_plug_conflicting(plugboard: &[(u8, u8)], plug: (u8, u8)) -> bool {
+    plugboard
+        .iter()
+        .any(|&(a, b)| a == plug.0 || a == plug.1 || b == plug.0 || b == plug.1)
+}
+
+fn is_triple_in_range(t: (u8, u8, u8), max: u8) -> bool {
+    [t.0, t.1, t.2].iter().all(|v| (1..=max).contains(v))
+}
+
+// letter each letter is plugged to, A = 0
+fn plugboard_wiring(pairs: &[(u8, u8)]) -> [u8; LETTERS_NUM as usize] {
+    let mut wiring = std::array::from_fn(|i| i as u8);
+    for &(a, b) in pairs {
+        wiring.swap(a as usize - 1, b as usize - 1);
+    }
+    wiring
+}
+
+// Enigma I on the core of enigma_core, from the key notation
+#[derive(Debug, Clone)]
 pub struct Machine {
-    internal: EnigmaMachine,
+    core: Core,
 }
 
 impl Machine {
     pub fn new(s: &Settings) -> anyhow::Result<Self> {
-        Ok(Self {
-            internal: EnigmaMachine::new()
-                .reflector("B")
-                .rotors(s.rotors.0, s.rotors.1, s.rotors.2)
-                .ring_positions(
-                    s.rotor_positions.0,
-                    s.rotor_positions.1,
-                    s.rotor_positions.2,
-                )
-                .ring_settings(s.ring_settings.0, s.ring_settings.1, s.ring_settings.2)?,
-        })
+        s.validate()?;
+
+        let (r0, r1, r2) = s.rotors;
+        let (s0, s1, s2) = s.ring_settings;
+        let (p0, p1, p2) = s.rotor_positions;
+        let core = Core::new(
+            [r0, r1, r2],
+            [s0, s1, s2],
+            [p0, p1, p2],
+            &s.plugboard,
+            s.reflector.wiring(),
+        )?;
+        Ok(Self { core })
     }
 
     pub fn decrypt(&self, text: &str) -> String {
-        self.internal.decrypt(text)
+        self.apply(text)
     }
 
     pub fn encrypt(&self, text: &str) -> String {
-        self.internal.encrypt(text)
+        self.apply(text)
+    }
+
+    fn apply(&self, text: &str) -> String {
+        let mut text = normalize(text).into_bytes();
+        self.core.apply_in_place(&mut text);
+        String::from_utf8(text).expect("A..Z, garbles and spaces")
+    }
+
+    /// The letter every letter becomes at key press `offset` of a message, from 0 and A = 0:
+    /// the rotors step before every key press, garbles count as key presses and spaces don't.
+    /// The machine is reciprocal, so the alphabet is its own inverse.
+    pub fn mapping_at(&self, offset: usize) -> Wiring {
+        let mut positions = self.core.positions();
+        for _ in 0..=offset {
+            self.core.step(&mut positions);
+        }
+        std::array::from_fn(|c| self.core.press(c as u8, &positions))
+    }
+
+    pub(crate) fn step(&self, positions: &mut [u8; 3]) {
+        self.core.step(positions)
+    }
+
+    pub(crate) fn press(&self, c: u8, positions: &[u8; 3]) -> u8 {
+        self.core.press(c, positions)
+    }
+}
+
+// the rotor passes of the batch decryption, worked out at compile time as well
+static PASS_TABLES: (PassTables, PassTables) = (
+    pass_table(&enigma_core::rotor_wirings(false)),
+    pass_table(&enigma_core::rotor_wirings(true)),
+);
+
+// a rotor pass for every rotor, shift of the rotor (position less ring setting) and letter
+type PassTables = [[[u8; LETTERS_NUM as usize]; LETTERS_NUM as usize]; MAX_ROTOR_NUM as usize];
+
+const fn pass_table(wirings: &[Wiring; MAX_ROTOR_NUM as usize]) -> PassTables {
+    let mut table = [[[0; LETTERS_NUM as usize]; LETTERS_NUM as usize]; MAX_ROTOR_NUM as usize];
+    let mut r = 0;
+    while r < table.len() {
+        let mut shift = 0;
+        while shift < LETTERS_NUM {
+            let mut c = 0;
+            while c < LETTERS_NUM {
+                table[r][shift as usize][c as usize] = Rotor::pass(&wirings[r], c, shift);
+                c += 1;
+            }
+            shift += 1;
+        }
+        r += 1;
+    }
+    table
+}
+
+// the state of one machine of a batch, small enough for a few cache lines
+struct Lane {
+    rotors: [u8; 3],
+    // position less ring setting, the turnovers are kept in the same terms
+    shifts: [u8; 3],
+    turnovers: [u32; 3],
+    plugboard: [u8; LETTERS_NUM as usize],
+    reflector: [u8; LETTERS_NUM as usize],
+}
+
+/// Decrypts the ciphertext under every key at once: the machines are stepped in lockstep,
+/// one letter of all of them at a time, through rotor passes looked up in tables shared by
+/// the whole batch, so the per-candidate state is a few contiguous bytes and the inner loop
+/// over candidates has no branches or divisions.
+pub fn decrypt_batch(keys: &[Settings], ciphertext: &str) -> anyhow::Result<Vec<String>> {
+    decrypt_lanes(keys, ciphertext)
+}
+
+fn decrypt_lanes<'a>(
+    keys: impl IntoIterator<Item = &'a Settings>,
+    ciphertext: &str,
+) -> anyhow::Result<Vec<String>> {
+    let (forward, backward) = &PASS_TABLES;
+    let text = normalize(ciphertext);
+    let l = LETTERS_NUM;
+
+    let mut lanes = keys
+        .into_iter()
+        .map(|key| {
+            let machine = Machine::new(key)?.core;
+            let (r0, r1, r2) = key.rotors;
+            let rings = machine.rotors.each_ref().map(|r| r.ring_setting);
+            Ok(Lane {
+                rotors: [r0 - 1, r1 - 1, r2 - 1],
+                shifts: std::array::from_fn(|r| (l + machine.positions[r] - rings[r]) % l),
+                turnovers: std::array::from_fn(|r| {
+                    (0..l)
+                        .filter(|&t| machine.rotors[r].is_at_turnover(t))
+                        .fold(0, |mask, t| mask | 1 << ((l + t - rings[r]) % l))
+                }),
+                plugboard: machine.plugboard,
+                reflector: machine.reflector,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let advance = |s: u8, by: u8| {
+        let s = s + by;
+        s - l * (s >= l) as u8
+    };
+    let mut plaintexts = vec![Vec::with_capacity(text.len()); lanes.len()];
+    for c in text.bytes() {
+        if !c.is_ascii_uppercase() && c != GARBLE {
+            plaintexts.iter_mut().for_each(|p| p.push(c));
+            continue;
+        }
+
+        for (lane, plaintext) in lanes.iter_mut().zip(&mut plaintexts) {
+            // the double step of Machine::step without branches
+            let [left, middle, right] = lane.shifts;
+            let middle_turns = (lane.turnovers[1] >> middle) as u8 & 1;
+            let right_turns = (lane.turnovers[2] >> right) as u8 & 1;
+            lane.shifts = [
+                advance(left, middle_turns),
+                advance(middle, middle_turns | right_turns),
+                advance(right, 1),
+            ];
+            if c == GARBLE {
+                plaintext.push(GARBLE);
+                continue;
+            }
+
+            let [r0, r1, r2] = lane.rotors.map(usize::from);
+            let [s0, s1, s2] = lane.shifts.map(usize::from);
+            let mut x = lane.plugboard[(c - b'A') as usize] as usize;
+            x = forward[r2][s2][x] as usize;
+            x = forward[r1][s1][x] as usize;
+            x = forward[r0][s0][x] as usize;
+            x = lane.reflector[x] as usize;
+            x = backward[r0][s0][x] as usize;
+            x = backward[r1][s1][x] as usize;
+            x = backward[r2][s2][x] as usize;
+            plaintext.push(b'A' + lane.plugboard[x]);
+        }
+    }
+
+    Ok(plaintexts
+        .into_iter()
+        .map(|p| String::from_utf8(p).expect("A..Z, garbles and spaces"))
+        .collect())
+}
+
+// unknown entry of a scrambler
+const UNSCRAMBLED: u8 = u8::MAX;
+
+/// The letter swaps of the rotors and reflector at every position of a message, the machine
+/// without its plugboard: with plugboard P the letter c at position i becomes P(S_i(P(c))).
+/// The swaps are worked out as keys look them up, both letters of a pair at once, and are
+/// shared between threads, so keys that only differ in the plugboard mostly pay a lookup
+/// per letter instead of the rotor stepping and wiring.
+pub struct Scrambler {
+    machine: Machine,
+    // rotor positions when the letter at that position is pressed
+    positions: Vec<[u8; 3]>,
+    swaps: Vec<[AtomicU8; LETTERS_NUM as usize]>,
+}
+
+impl Scrambler {
+    // for messages of up to `letters` letters, the plugboard of the settings is ignored
+    pub fn new(settings: &Settings, letters: usize) -> anyhow::Result<Self> {
+        let machine = Machine::new(&Settings {
+            plugboard: vec![],
+            ..settings.clone()
+        })?;
+
+        let mut position = machine.core.positions();
+        let positions = (0..letters)
+            .map(|_| {
+                machine.step(&mut position);
+                position
+            })
+            .collect();
+
+        Ok(Self {
+            machine,
+            positions,
+            swaps: (0..letters)
+                .map(|_| std::array::from_fn(|_| AtomicU8::new(UNSCRAMBLED)))
+                .collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // the letter c becomes at the i-th letter of the message, A = 0
+    pub fn swap(&self, i: usize, c: u8) -> u8 {
+        // another thread filling the same pair writes the same letters
+        let entry = &self.swaps[i][c as usize];
+        match entry.load(Ordering::Relaxed) {
+            UNSCRAMBLED => {
+                let s = self.machine.press(c, &self.positions[i]);
+                entry.store(s, Ordering::Relaxed);
+                self.swaps[i][s as usize].store(c, Ordering::Relaxed);
+                s
+            }
+            s => s,
+        }
+    }
+
+    /// Decrypts a message of at most `len()` letters with the plugboard of the settings.
+    pub fn decrypt(&self, text: &str, plugboard: &[(u8, u8)]) -> String {
+        let plugboard = plugboard_wiring(plugboard);
+        let mut i = 0;
+
+        normalize(text)
+            .bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' => {
+                    let s = self.swap(i, plugboard[(c - b'A') as usize]);
+                    i += 1;
+                    (b'A' + plugboard[s as usize]) as char
+                }
+                GARBLE => {
+                    i += 1;
+                    GARBLE as char
+                }
+                c => c as char,
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Scrambler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scrambler({} letters)", self.len())
+    }
+}
+
+impl Cipher for Machine {
+    type Key = Settings;
+
+    const MODEL_NAME: &'static str = MODEL_NAME;
+
+    fn new(key: &Settings) -> anyhow::Result<Self> {
+        Machine::new(key)
+    }
+
+    fn encrypt(&self, text: &str) -> String {
+        Machine::encrypt(self, text)
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        Machine::decrypt(self, text)
+    }
+
+    // the valid keys are decrypted in lockstep
+    fn decrypt_batch(keys: &[&Settings], ciphertext: &str) -> Vec<anyhow::Result<String>> {
+        let valid = keys.iter().copied().filter(|key| key.validate().is_ok());
+        let mut plaintexts = match decrypt_lanes(valid, ciphertext) {
+            Ok(plaintexts) => plaintexts.into_iter(),
+            Err(err) => return keys.iter().map(|_| Err(anyhow!("{}", err))).collect(),
+        };
+
+        keys.iter()
+            .map(|key| {
+                key.validate()?;
+                Ok(plaintexts.next().expect("a plaintext for every valid key"))
+            })
+            .collect()
+    }
+
+    // a scrambler costs a few decryptions to fill, only worth it when it's kept for others
+    fn decrypt_shared(
+        key: &Settings,
+        ciphertext: &str,
+        scramblers: &ScramblerCache,
+    ) -> anyhow::Result<String> {
+        if key.plugboard.is_empty() || !scramblers.is_enabled() {
+            return Ok(Machine::new(key)?.decrypt(ciphertext));
+        }
+        key.validate()?;
+
+        let letters = keystrokes(ciphertext);
+        let scrambler =
+            scramblers.get_or_insert(key.rotor_core(), || Scrambler::new(key, letters))?;
+        Ok(scrambler.decrypt(ciphertext, &key.plugboard))
+    }
+
+    fn canonical(key: &Settings) -> Settings {
+        key.canonicalize()
+    }
+
+    fn pack(key: &Settings) -> u128 {
+        key.pack()
+    }
+
+    fn keyspace_size() -> u128 {
+        keyspace_size(MAX_PLUGS_NUM)
+    }
+}
+
+// the machine only has A..Z keys: letters are uppercased, whitespace becomes a space
+// and is passed through without stepping the rotors, the garble markers ? and * become a
+// garble, anything else is dropped
+pub fn normalize(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            'a'..='z' | 'A'..='Z' => Some(c.to_ascii_uppercase()),
+            '?' | '*' => Some(GARBLE as char),
+            c if c.is_whitespace() => Some(' '),
+            _ => None,
+        })
+        .collect()
+}
+
+// letters and garbles of the text, one key press each
+pub fn keystrokes(text: &str) -> usize {
+    normalize(text).bytes().filter(|&c| c != b' ').count()
+}
+
+pub fn keyspace_size(max_plugs: usize) -> u128 {
+    let rotors = MAX_ROTOR_NUM as u128;
+    let rotor_orders = rotors * (rotors - 1) * (rotors - 2);
+
+    rotor_orders
+        * (MAX_RING_SETTINGS_NUM as u128).pow(3)
+        * (MAX_ROTOR_POSITIONS_NUM as u128).pow(3)
+        * plugboards_num(max_plugs)
+}
+
+// keys left with the left and right ring at A: a left ring is matched by a position that
+// encrypts alike, a right ring only moves where the middle rotor steps, and a sweep over it
+// recovers that once the rest of the key is found
+pub fn fixed_rings_keyspace_size(max_plugs: usize) -> u128 {
+    keyspace_size(max_plugs) / (MAX_RING_SETTINGS_NUM as u128).pow(2)
+}
+
+// number of ways to choose 0..=max_plugs disjoint letter pairs
+fn plugboards_num(max_plugs: usize) -> u128 {
+    let mut with_k_plugs = 1_u128;
+    let mut total = 1_u128;
+
+    for k in 1..=max_plugs.min(MAX_PLUGS_NUM) as u128 {
+        let free_letters = 26 - 2 * (k - 1);
+        with_k_plugs = with_k_plugs * free_letters * (free_letters - 1) / 2 / k;
+        total += with_k_plugs;
+    }
+
+    total
+}
+
+#[cfg(test)]
+pub(crate) mod strategy {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    // B, C or any UKW-D wiring
+    pub fn reflector() -> impl Strategy<Value = Reflector> {
+        let letters = Just((1..=LETTERS_NUM).collect::<Vec<_>>()).prop_shuffle();
+
+        prop_oneof![
+            Just(Reflector::B),
+            Just(Reflector::C),
+            letters.prop_map(|letters| Reflector::D(
+                letters
+                    .chunks_exact(2)
+                    .map(|p| (p[0].min(p[1]), p[0].max(p[1])))
+                    .collect()
+            )),
+        ]
+    }
+
+    // any valid settings with up to max_plugs disjoint plug pairs
+    pub fn settings(max_plugs: usize) -> impl Strategy<Value = Settings> {
+        let triple = |max: u8| (1..=max, 1..=max, 1..=max);
+        let letters = Just((1..=LETTERS_NUM).collect::<Vec<_>>()).prop_shuffle();
+        let rotors = Just((1..=MAX_ROTOR_NUM).collect::<Vec<_>>()).prop_shuffle();
+
+        (
+            rotors,
+            triple(MAX_RING_SETTINGS_NUM),
+            triple(MAX_ROTOR_POSITIONS_NUM),
+            letters,
+            0..=max_plugs.min(MAX_PLUGS_NUM),
+            reflector(),
+        )
+            .prop_map(
+                |(rotors, ring_settings, rotor_positions, letters, num_plugs, reflector)| {
+                    Settings {
+                        rotors: (rotors[0], rotors[1], rotors[2]),
+                        ring_settings,
+                        rotor_positions,
+                        plugboard: letters
+                            .chunks_exact(2)
+                            .take(num_plugs)
+                            .map(|p| (p[0].min(p[1]), p[0].max(p[1])))
+                            .collect(),
+                        reflector,
+                    }
+                },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use enigma_simulator::{EnigmaBuilder, EnigmaMachine};
+    use proptest::prelude::*;
+
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    #[test]
+    fn test_keyspace_size() {
+        assert_eq!(keyspace_size(0), 37_069_893_120);
+        assert_eq!(fixed_rings_keyspace_size(0), 120 * 26 * 26_u128.pow(3));
+        assert_eq!(plugboards_num(1), 1 + 325);
+        assert_eq!(plugboards_num(10) - plugboards_num(9), 150_738_274_937_250);
+    }
+
+    #[test]
+    fn test_plugs() {
+        assert!(PLUGS.iter().all(|&plug| is_plug_valid(plug)));
+        assert!(PLUGS.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(PLUGS.last(), Some(&(25, 26)));
+    }
+
+    #[test]
+    fn test_wiring_tables() {
+        let (forward, backward) = &PASS_TABLES;
+        for r in 0..MAX_ROTOR_NUM as usize {
+            // at ring setting A the shift is the position
+            let rotor = Rotor::new(r as u8 + 1, 1);
+            for shift in 0..LETTERS_NUM {
+                for c in 0..LETTERS_NUM {
+                    let (s, i) = (shift as usize, c as usize);
+                    assert_eq!(forward[r][s][i], rotor.forward(c, shift));
+                    assert_eq!(backward[r][s][i], rotor.backward(c, shift));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_plugboard_roundtrip() {
+        let settings = Settings {
+            rotors: (2, 5, 3),
+            ring_settings: (8, 5, 20),
+            rotor_positions: (13, 3, 21),
+            plugboard: vec![(1, 2), (3, 26), (10, 11)],
+            reflector: Reflector::B,
+        };
+        let machine = Machine::new(&settings).unwrap();
+
+        let ciphertext = machine.encrypt("HELLO WORLD");
+        assert_eq!(machine.decrypt(&ciphertext), "HELLO WORLD");
+
+        let invalid = Settings {
+            plugboard: vec![(1, 2), (2, 3)],
+            ..settings
+        };
+        assert!(Machine::new(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_batch() {
+        let valid = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        let invalid = Settings {
+            rotors: (1, 1, 2),
+            ..valid.clone()
+        };
+        assert!(decrypt_batch(&[valid.clone(), invalid.clone()], "BDZGO").is_err());
+
+        let plaintexts = <Machine as Cipher>::decrypt_batch(&[&invalid, &valid], "BDZGO");
+        assert!(plaintexts[0].is_err());
+        assert_eq!(plaintexts[1].as_ref().unwrap(), "AAAAA");
+    }
+
+    #[test]
+    fn test_garbles() {
+        // the letters after a garble still decrypt at their positions
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        assert_eq!(Machine::new(&settings).unwrap().decrypt("BD?G*"), "AA?A?");
+        assert_eq!(decrypt_batch(&[settings], "B*ZG?").unwrap(), ["A?AA?"]);
+    }
+
+    #[test]
+    fn test_mapping_at() {
+        let machine = |s: &str| Machine::new(&s.parse::<Settings>().unwrap()).unwrap();
+        let key = machine("II V III / 08 05 20 / M C U / AQ BT");
+        let ciphertext = key.encrypt("HELLOWORLD");
+        for (i, c) in ciphertext.bytes().enumerate() {
+            let mapping = key.mapping_at(i);
+            assert_eq!(b'A' + mapping[(c - b'A') as usize], b"HELLOWORLD"[i]);
+            assert!((0..LETTERS_NUM).all(|c| c != mapping[c as usize]));
+            assert!((0..LETTERS_NUM).all(|c| mapping[mapping[c as usize] as usize] == c));
+        }
+
+        // II double steps at E: the third key press is the first of B F W
+        let stepping = machine("I II III / 01 01 01 / A D U");
+        let stepped = machine("I II III / 01 01 01 / B F W");
+        assert_eq!(stepping.mapping_at(2), stepped.mapping_at(0));
+        assert_ne!(stepping.mapping_at(2), stepping.mapping_at(1));
+    }
+
+    #[test]
+    fn test_reflector() {
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        assert_eq!(Machine::new(&settings).unwrap().encrypt("AAAAA"), "BDZGO");
+
+        // UKW-D wired like B is the same machine
+        let b = Reflector::B.wiring();
+        let pairs = (0..LETTERS_NUM)
+            .filter(|&i| i < b[i as usize])
+            .map(|i| (i + 1, b[i as usize] + 1))
+            .collect::<Vec<_>>();
+        let rewired = Settings {
+            reflector: Reflector::D(pairs),
+            ..settings.clone()
+        };
+        assert_eq!(
+            Machine::new(&rewired).unwrap().encrypt(LONG_TEXT),
+            Machine::new(&settings).unwrap().encrypt(LONG_TEXT)
+        );
+
+        let d = "D AB CD EF GH IJ KL MN OP QR ST UV WX YZ";
+        let reflector = d.parse::<Reflector>().unwrap();
+        assert_eq!(reflector.to_string(), d);
+        assert_eq!(
+            format!("{d} / II V III / 08 05 20 / M C U")
+                .parse::<Settings>()
+                .unwrap()
+                .reflector,
+            reflector
+        );
+        assert_eq!(
+            "C / II V III / 08 05 20 / M C U"
+                .parse::<Settings>()
+                .unwrap()
+                .to_string(),
+            "C / II V III / 08 05 20 / M C U"
+        );
+
+        for invalid in [
+            "A",
+            "B AB",
+            "D",
+            "D AB CD EF GH IJ KL MN OP QR ST UV WX",
+            "D AB CD EF GH IJ KL MN OP QR ST UV WX YA",
+            "D AA CD EF GH IJ KL MN OP QR ST UV WX YZ",
+        ] {
+            assert!(invalid.parse::<Reflector>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Hello,\tworld!"), "HELLO WORLD");
+        assert_eq!(normalize("ÄÖ 42"), " ");
+        assert_eq!(normalize("ab?c*"), "AB?C?");
+        assert_eq!(keystrokes("ab?c *"), 5);
+    }
+
+    #[test]
+    fn test_key_notation() {
+        let settings = "II V III / 08 05 20 / M C U / AB CZ"
+            .parse::<Settings>()
+            .unwrap();
+        assert_eq!(settings.rotors, (2, 5, 3));
+        assert_eq!(settings.ring_settings, (8, 5, 20));
+        assert_eq!(settings.rotor_positions, (13, 3, 21));
+        assert_eq!(settings.plugboard, vec![(1, 2), (3, 26)]);
+        assert_eq!(settings.to_string(), "II V III / 08 05 20 / M C U / AB CZ");
+
+        for invalid in [
+            "",
+            "II V III / 08 05 20",
+            "II V VII / 08 05 20 / M C U",
+            "II V III / 08 05 27 / M C U",
+            "II V III / 08 05 20 / M C UU",
+            "II V III / 08 05 20 / M C U / AB BC",
+            "II V III / 08 05 20 / M C U / AÄ",
+        ] {
+            assert!(invalid.parse::<Settings>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        // II at its turnover E double steps at the first key press
+        let settings = "I II III / 05 05 05 / A E B / ZA CB".parse::<Settings>();
+        let canonical = settings.unwrap().canonicalize();
+        assert_eq!(canonical.to_string(), "I II III / 01 05 05 / X F B / AZ BC");
+        // unless III carries at once
+        let settings = "I II III / 05 05 05 / A E V".parse::<Settings>();
+        let canonical = settings.unwrap().canonicalize();
+        assert_eq!(canonical.to_string(), "I II III / 01 05 05 / W E V");
+
+        let invalid = Settings {
+            ring_settings: (0, 1, 1),
+            ..canonical.clone()
+        };
+        assert_eq!(invalid.canonicalize(), invalid);
+    }
+
+    #[test]
+    fn test_is_equivalent_to() {
+        let key = |s: &str| s.parse::<Settings>().unwrap();
+        let settings = key("I II III / 05 05 05 / A E B / ZA CB");
+        assert!(settings.is_equivalent_to(&settings.canonicalize(), 10_000));
+
+        // the middle ring and position turned alike part once V carries the left rotor
+        let settings = key("II V III / 08 05 20 / M C U");
+        let turned = key("II V III / 08 06 20 / M D U");
+        assert!(settings.is_equivalent_to(&turned, 500));
+        assert!(!settings.is_equivalent_to(&turned, 1000));
+        assert!(!settings.is_equivalent_to(&key("II V III / 08 05 20 / M C V"), 1));
+        assert!(settings.is_equivalent_to(&key("II V III / 08 05 20 / M C V"), 0));
+
+        let invalid = Settings {
+            ring_settings: (0, 1, 1),
+            ..settings.clone()
+        };
+        assert!(invalid.is_equivalent_to(&invalid, 100));
+        assert!(!invalid.is_equivalent_to(&settings, 100));
+    }
+
+    #[test]
+    fn test_pack() {
+        let settings = "II V III / 08 05 20 / M C U / AQ BT"
+            .parse::<Settings>()
+            .unwrap();
+        let reordered = "II V III / 08 05 20 / M C U / TB QA"
+            .parse::<Settings>()
+            .unwrap();
+        assert_eq!(settings.pack(), reordered.pack());
+        let other = "II V III / 08 05 20 / M C U / AQ BZ"
+            .parse::<Settings>()
+            .unwrap();
+        assert_ne!(settings.pack(), other.pack());
+
+        // every set of up to two plugs has its own rank below the number of plugboards
+        assert_eq!(
+            PAIRINGS[0][LETTERS_NUM as usize],
+            plugboards_num(MAX_PLUGS_NUM)
+        );
+        let mut ranks = vec![pairs_rank(&[], false)];
+        for (i, &a) in PLUGS.iter().enumerate() {
+            ranks.push(pairs_rank(&[a], false));
+            for &b in PLUGS[i + 1..]
+                .iter()
+                .filter(|&&b| !is_plug_conflicting(&[a], b))
+            {
+                ranks.push(pairs_rank(&[a, b], false));
+            }
+        }
+        let count = ranks.len();
+        ranks.sort_unstable();
+        ranks.dedup();
+        assert_eq!(ranks.len(), count);
+        assert!(ranks.iter().all(|&rank| rank < PAIRINGS[0][26]));
+
+        // the largest digits still fit
+        let all_pairs = (1..=13).map(|i| (27 - i, i)).collect::<Vec<_>>();
+        let last = Settings {
+            rotors: (6, 5, 4),
+            ring_settings: (26, 26, 26),
+            rotor_positions: (26, 26, 26),
+            plugboard: all_pairs.clone(),
+            reflector: Reflector::D(all_pairs.clone()),
+        };
+        assert_eq!(pairs_rank(&all_pairs, true), PAIRINGS[1][26] - 1);
+        assert!(last.pack() > settings.pack());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_matches_simulator(
+            settings in strategy::settings(MAX_PLUGS_NUM),
+            text in "[A-Z ]{0,200}",
+        ) {
+            let reflector = match settings.reflector {
+                Reflector::B => "B",
+                Reflector::C => "C",
+                Reflector::D(_) => return Ok(()),
+            };
+            let simulator = EnigmaMachine::new()
+                .reflector(reflector)
+                .plugboard(&pairs_notation(&settings.plugboard))
+                .rotors(settings.rotors.0, settings.rotors.1, settings.rotors.2)
+                .ring_positions(
+                    settings.rotor_positions.0,
+                    settings.rotor_positions.1,
+                    settings.rotor_positions.2,
+                )
+                .ring_settings(
+                    settings.ring_settings.0,
+                    settings.ring_settings.1,
+                    settings.ring_settings.2,
+                )
+                .unwrap();
+
+            prop_assert_eq!(
+                Machine::new(&settings).unwrap().encrypt(&text),
+                simulator.encrypt(&text)
+            );
+        }
+
+        #[test]
+        fn prop_batch_matches_machine(
+            keys in prop::collection::vec(strategy::settings(MAX_PLUGS_NUM), 0..8),
+            text in "[a-zA-Z .,!?*\t]{0,200}",
+        ) {
+            let plaintexts = decrypt_batch(&keys, &text).unwrap();
+
+            prop_assert_eq!(plaintexts.len(), keys.len());
+            for (key, plaintext) in keys.iter().zip(&plaintexts) {
+                prop_assert_eq!(plaintext, &Machine::new(key).unwrap().decrypt(&text));
+            }
+        }
+
+        #[test]
+        fn prop_scrambler_matches_machine(
+            settings in strategy::settings(MAX_PLUGS_NUM),
+            other in strategy::settings(MAX_PLUGS_NUM),
+            text in "[A-Z ?]{0,200}",
+        ) {
+            let scrambler = Scrambler::new(&other, text.len()).unwrap();
+            let core = other.rotor_core();
+            let settings = Settings { plugboard: settings.plugboard, ..other };
+            let machine = Machine::new(&settings).unwrap();
+
+            // twice, the second time from the filled swaps
+            for _ in 0..2 {
+                prop_assert_eq!(scrambler.decrypt(&text, &settings.plugboard), machine.decrypt(&text));
+            }
+            prop_assert_eq!(settings.rotor_core(), core);
+        }
+
+        #[test]
+        fn prop_canonical_encrypts_alike(
+            settings in strategy::settings(MAX_PLUGS_NUM),
+            text in "[A-Z ?]{0,200}",
+        ) {
+            let canonical = settings.canonicalize();
+            prop_assert_eq!(
+                Machine::new(&canonical).unwrap().encrypt(&text),
+                Machine::new(&settings).unwrap().encrypt(&text)
+            );
+            prop_assert_eq!(canonical.ring_settings.0, 1);
+            prop_assert_eq!(canonical.canonicalize(), canonical);
+        }
+
+        #[test]
+        fn prop_key_notation_roundtrip(settings in strategy::settings(MAX_PLUGS_NUM)) {
+            prop_assert_eq!(settings.to_string().parse::<Settings>().unwrap(), settings);
+        }
+
+        #[test]
+        fn prop_roundtrip(settings in strategy::settings(MAX_PLUGS_NUM), text in "[a-zA-Z .,!?*\t]{0,200}") {
+            let machine = Machine::new(&settings).unwrap();
+            let ciphertext = machine.encrypt(&text);
+
+            prop_assert_eq!(machine.decrypt(&ciphertext), normalize(&text));
+        }
+
+        #[test]
+        fn prop_no_letter_maps_to_itself(settings in strategy::settings(MAX_PLUGS_NUM), text in "[A-Z ]{1,200}") {
+            let ciphertext = Machine::new(&settings).unwrap().encrypt(&text);
+
+            prop_assert_eq!(ciphertext.len(), text.len());
+            for (p, c) in text.chars().zip(ciphertext.chars()) {
+                prop_assert!(p == ' ' && c == ' ' || p != ' ' && p != c);
+            }
+        }
     }
 }
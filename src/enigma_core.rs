@@ -0,0 +1,304 @@
+// the Enigma I machine alone: rotors, reflector, plugboard and stepping, without the standard
+// library or an allocator, for embedded targets and constrained WASM hosts; the key notation,
+// the search and everything else build on it behind the `std` feature
+use core::fmt;
+
+pub const LETTERS_NUM: u8 = 26;
+pub const MAX_ROTOR_NUM: u8 = 6;
+// an unreadable letter of an intercept, written ? or *: the rotors step past it
+pub const GARBLE: u8 = b'?';
+// wiring and turnover letters of rotors I..VI
+pub(crate) const ROTORS: [(&str, &str); MAX_ROTOR_NUM as usize] = [
+    ("EKMFLGDQVZNTOWYHXUSPAIBRCJ", "Q"),
+    ("AJDKSIRUXBLHWTMCQGZNPYFVOE", "E"),
+    ("BDFHJLCPRTXVZNYEIWGAKMUSQO", "V"),
+    ("ESOVPZJAYQUIRHXLNFTGKDCMWB", "J"),
+    ("VZBRGITYUPSDNHLXAWMJQOFECK", "Z"),
+    ("JPGVOUMFYQBENHZRDKASXLICTW", "ZM"),
+];
+// the wirings of the built-in rotors and reflectors are worked out at compile time
+pub type Wiring = [u8; LETTERS_NUM as usize];
+static FORWARD: [Wiring; MAX_ROTOR_NUM as usize] = rotor_wirings(false);
+static BACKWARD: [Wiring; MAX_ROTOR_NUM as usize] = rotor_wirings(true);
+pub(crate) static TURNOVERS: [u32; MAX_ROTOR_NUM as usize] = rotor_turnovers();
+pub const REFLECTOR_B: Wiring = permutation("YRUHQSLDPXNGOKMIEBFZCWVJAT");
+pub const REFLECTOR_C: Wiring = permutation("FVPJIAOYEDRZXWGCTKUQSBNMHL");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreError {
+    Rotor(u8),
+    RepeatedRotor(u8),
+    RingSetting(u8),
+    Position(u8),
+    Plug(u8, u8),
+    // a letter the reflector leaves alone or doesn't swap back
+    Reflector(u8),
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreError::Rotor(r) => write!(f, "rotor {} out of range 1..={}", r, MAX_ROTOR_NUM),
+            CoreError::RepeatedRotor(r) => write!(f, "rotor {} used twice", r),
+            CoreError::RingSetting(s) => write!(f, "ring setting {} out of range 1..=26", s),
+            CoreError::Position(p) => write!(f, "rotor position {} out of range 1..=26", p),
+            CoreError::Plug(a, b) => write!(f, "invalid or conflicting plug ({}, {})", a, b),
+            CoreError::Reflector(c) => write!(f, "reflector doesn't swap letter {}", c),
+        }
+    }
+}
+
+impl core::error::Error for CoreError {}
+
+// letter i is wired to permutation[i], A = 0
+const fn permutation(wiring: &str) -> Wiring {
+    let wiring = wiring.as_bytes();
+    let mut permutation = [0; LETTERS_NUM as usize];
+    let mut i = 0;
+    while i < permutation.len() {
+        permutation[i] = wiring[i] - b'A';
+        i += 1;
+    }
+    permutation
+}
+
+// forward wirings of rotors I..VI, or the backward ones, the inverse permutations
+pub(crate) const fn rotor_wirings(backward: bool) -> [Wiring; MAX_ROTOR_NUM as usize] {
+    let mut wirings = [[0; LETTERS_NUM as usize]; MAX_ROTOR_NUM as usize];
+    let mut r = 0;
+    while r < wirings.len() {
+        let forward = permutation(ROTORS[r].0);
+        let mut i = 0;
+        while i < forward.len() {
+            match backward {
+                true => wirings[r][forward[i] as usize] = i as u8,
+                false => wirings[r][i] = forward[i],
+            }
+            i += 1;
+        }
+        r += 1;
+    }
+    wirings
+}
+
+// a bit for every position at which a rotor carries the next one to the left, A = bit 0
+const fn rotor_turnovers() -> [u32; MAX_ROTOR_NUM as usize] {
+    let mut turnovers = [0; MAX_ROTOR_NUM as usize];
+    let mut r = 0;
+    while r < turnovers.len() {
+        let letters = ROTORS[r].1.as_bytes();
+        let mut i = 0;
+        while i < letters.len() {
+            turnovers[r] |= 1 << (letters[i] - b'A');
+            i += 1;
+        }
+        r += 1;
+    }
+    turnovers
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Rotor {
+    forward: &'static Wiring,
+    backward: &'static Wiring,
+    // see rotor_turnovers
+    turnovers: u32,
+    pub(crate) ring_setting: u8,
+}
+
+impl Rotor {
+    // rotor 1..=6, ring setting 1..=26
+    pub(crate) fn new(rotor: u8, ring_setting: u8) -> Self {
+        let r = rotor as usize - 1;
+        Self {
+            forward: &FORWARD[r],
+            backward: &BACKWARD[r],
+            turnovers: TURNOVERS[r],
+            ring_setting: ring_setting - 1,
+        }
+    }
+
+    pub(crate) fn is_at_turnover(&self, position: u8) -> bool {
+        self.turnovers >> position & 1 != 0
+    }
+
+    pub(crate) const fn pass(wiring: &Wiring, c: u8, shift: u8) -> u8 {
+        let n = LETTERS_NUM;
+        (wiring[((c + shift) % n) as usize] + n - shift) % n
+    }
+
+    fn shift(&self, position: u8) -> u8 {
+        (LETTERS_NUM + position - self.ring_setting) % LETTERS_NUM
+    }
+
+    pub(crate) fn forward(&self, c: u8, position: u8) -> u8 {
+        Self::pass(self.forward, c, self.shift(position))
+    }
+
+    pub(crate) fn backward(&self, c: u8, position: u8) -> u8 {
+        Self::pass(self.backward, c, self.shift(position))
+    }
+}
+
+/// Enigma I: plugboard, three rotors stepping with the middle rotor double step and a
+/// reflector, a few dozen bytes on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Core {
+    // letter each letter is plugged to, A = 0
+    pub(crate) plugboard: Wiring,
+    // left, middle, right
+    pub(crate) rotors: [Rotor; 3],
+    // start positions, A = 0
+    pub(crate) positions: [u8; 3],
+    pub(crate) reflector: Wiring,
+}
+
+impl Core {
+    /// Rotors 1..=6 (I..VI), ring settings and positions 1..=26 from left to right, plug pairs
+    /// with A = 1 and the reflector wiring with A = 0, e.g. `REFLECTOR_B`.
+    pub fn new(
+        rotors: [u8; 3],
+        ring_settings: [u8; 3],
+        positions: [u8; 3],
+        plugs: &[(u8, u8)],
+        reflector: Wiring,
+    ) -> Result<Self, CoreError> {
+        for (i, &r) in rotors.iter().enumerate() {
+            if !(1..=MAX_ROTOR_NUM).contains(&r) {
+                return Err(CoreError::Rotor(r));
+            }
+            if rotors[..i].contains(&r) {
+                return Err(CoreError::RepeatedRotor(r));
+            }
+        }
+        if let Some(&s) = ring_settings
+            .iter()
+            .find(|s| !(1..=LETTERS_NUM).contains(s))
+        {
+            return Err(CoreError::RingSetting(s));
+        }
+        if let Some(&p) = positions.iter().find(|p| !(1..=LETTERS_NUM).contains(p)) {
+            return Err(CoreError::Position(p));
+        }
+        let mut plugboard: Wiring = core::array::from_fn(|i| i as u8);
+        for &(a, b) in plugs {
+            let valid =
+                |x: u8| (1..=LETTERS_NUM).contains(&x) && plugboard[x as usize - 1] == x - 1;
+            if a == b || !valid(a) || !valid(b) {
+                return Err(CoreError::Plug(a, b));
+            }
+            plugboard.swap(a as usize - 1, b as usize - 1);
+        }
+        for (c, &o) in reflector.iter().enumerate() {
+            if o >= LETTERS_NUM || o as usize == c || reflector[o as usize] as usize != c {
+                return Err(CoreError::Reflector(c as u8 + 1));
+            }
+        }
+
+        let [r0, r1, r2] = rotors;
+        let [s0, s1, s2] = ring_settings;
+        Ok(Self {
+            plugboard,
+            rotors: [Rotor::new(r0, s0), Rotor::new(r1, s1), Rotor::new(r2, s2)],
+            positions: positions.map(|p| p - 1),
+            reflector,
+        })
+    }
+
+    /// Enciphers normalized text in place from the start positions: the machine is
+    /// reciprocal, so this decrypts as well. A garble steps the rotors and stays one, anything
+    /// else than A..Z passes through without stepping them.
+    pub fn apply_in_place(&self, text: &mut [u8]) {
+        let mut positions = self.positions;
+        for c in text {
+            match *c {
+                b'A'..=b'Z' => {
+                    self.step(&mut positions);
+                    *c = b'A' + self.press(*c - b'A', &positions);
+                }
+                GARBLE => self.step(&mut positions),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn step(&self, positions: &mut [u8; 3]) {
+        let [_, middle, right] = &self.rotors;
+        let advance = |p: &mut u8| *p = (*p + 1) % LETTERS_NUM;
+
+        if middle.is_at_turnover(positions[1]) {
+            advance(&mut positions[1]);
+            advance(&mut positions[0]);
+        } else if right.is_at_turnover(positions[2]) {
+            advance(&mut positions[1]);
+        }
+        advance(&mut positions[2]);
+    }
+
+    // letter c, A = 0, pressed with the rotors at the positions
+    pub fn press(&self, c: u8, positions: &[u8; 3]) -> u8 {
+        let rotors = self.rotors.iter().zip(positions);
+
+        let c = self.plugboard[c as usize];
+        let c = rotors.clone().rev().fold(c, |c, (r, &p)| r.forward(c, p));
+        let c = self.reflector[c as usize];
+        let c = rotors.fold(c, |c, (r, &p)| r.backward(c, p));
+        self.plugboard[c as usize]
+    }
+
+    pub fn positions(&self) -> [u8; 3] {
+        self.positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core() {
+        for r in 0..MAX_ROTOR_NUM as usize {
+            assert!((0..LETTERS_NUM).all(|c| BACKWARD[r][FORWARD[r][c as usize] as usize] == c));
+        }
+        // rotor I turns the next one over at Q, VI at M and Z
+        assert_eq!(FORWARD[0][0], b'E' - b'A');
+        assert_eq!(TURNOVERS[0], 1 << 16);
+        assert_eq!(TURNOVERS[5], 1 << 12 | 1 << 25);
+        assert_eq!(REFLECTOR_B[0], b'Y' - b'A');
+
+        // II V III / 08 05 20 / M C U / AQ BT on a buffer of the stack
+        let core = Core::new(
+            [2, 5, 3],
+            [8, 5, 20],
+            [13, 3, 21],
+            &[(1, 17), (2, 20)],
+            REFLECTOR_B,
+        )
+        .unwrap();
+        let mut text = *b"HELLO WORLD";
+        core.apply_in_place(&mut text);
+        assert_ne!(&text, b"HELLO WORLD");
+        assert_eq!(text[5], b' ');
+        core.apply_in_place(&mut text);
+        assert_eq!(&text, b"HELLO WORLD");
+
+        let new = |rotors, plugs: &[(u8, u8)], reflector| {
+            Core::new(rotors, [1; 3], [1; 3], plugs, reflector)
+        };
+        assert_eq!(
+            new([1, 1, 2], &[], REFLECTOR_B),
+            Err(CoreError::RepeatedRotor(1))
+        );
+        assert_eq!(new([1, 2, 7], &[], REFLECTOR_B), Err(CoreError::Rotor(7)));
+        assert_eq!(
+            new([1, 2, 3], &[(1, 2), (2, 3)], REFLECTOR_B),
+            Err(CoreError::Plug(2, 3))
+        );
+        let mut broken = REFLECTOR_C;
+        broken.swap(0, 5);
+        assert!(matches!(
+            new([1, 2, 3], &[], broken),
+            Err(CoreError::Reflector(_))
+        ));
+    }
+}
@@ -0,0 +1,336 @@
+use std::f64::consts::TAU;
+
+use rand::{seq::SliceRandom, Rng};
+use rayon::ThreadPool;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use crate::{
+    engine::{self, Bookkeeping, FitnessFunction, Progress},
+    enigma::{Reflector, Settings, LETTERS_NUM, MAX_ROTOR_NUM},
+    gen::{self, ReflectorSearch},
+    rng::SearchRng,
+};
+
+// ring settings and rotor positions, each a point on a circle of 26 letters
+const DIM: usize = 6;
+const CIRCLE: f64 = LETTERS_NUM as f64;
+const INITIAL_SIGMA: f64 = CIRCLE / 4.0;
+// a strand whose samples all round to about the same key has converged and is restarted
+const MIN_STEP: f64 = 0.2;
+const MIN_LAMBDA: usize = 8;
+
+/// Separable CMA-ES over the ring settings and rotor positions: the six of them are relaxed
+/// to points on a circle of 26 and every rotor order and reflector runs its own strand with a
+/// mean, a global step size and a step size per coordinate. Samples are rounded to the
+/// nearest letters for the fitness and the mean moves towards the best of them; strands that
+/// converged restart at random. The plugboard is left empty, see climb.rs.
+pub struct EvolutionStrategy<F> {
+    fitness_calc: F,
+    strands: Vec<Strand>,
+    params: Params,
+    rng: SearchRng,
+    pool: ThreadPool,
+    bookkeeping: Bookkeeping<Settings>,
+}
+
+// the search of one rotor order and reflector
+#[derive(Debug, Clone)]
+struct Strand {
+    rotors: (u8, u8, u8),
+    reflector: Reflector,
+    mean: [f64; DIM],
+    sigma: f64,
+    // per coordinate scale of the steps, the square root of the diagonal covariance
+    scale: [f64; DIM],
+    // evolution path of the step size
+    path: [f64; DIM],
+}
+
+// learning rates of the standard (mu/mu_w, lambda) strategy
+#[derive(Debug, Clone)]
+struct Params {
+    lambda: usize,
+    weights: Vec<f64>,
+    mu_eff: f64,
+    c_sigma: f64,
+    d_sigma: f64,
+    c_mu: f64,
+    // expected length of a standard normal vector
+    chi: f64,
+}
+
+impl Params {
+    fn new(lambda: usize) -> Self {
+        let mu = lambda / 2;
+        let weights = (1..=mu)
+            .map(|i| (mu as f64 + 0.5).ln() - (i as f64).ln())
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+        let weights = weights.iter().map(|w| w / total).collect::<Vec<_>>();
+        let mu_eff = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+
+        let n = DIM as f64;
+        let c_sigma = (mu_eff + 2.0) / (n + mu_eff + 5.0);
+        let d_sigma = 1.0 + 2.0 * (((mu_eff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + c_sigma;
+        // a diagonal covariance learns (n + 2) / 3 times faster than a full one
+        let c_mu = ((n + 2.0) / 3.0 * 2.0 * (mu_eff - 2.0 + 1.0 / mu_eff)
+            / ((n + 2.0).powi(2) + mu_eff))
+            .clamp(0.0, 1.0);
+        let chi = n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n));
+
+        Self {
+            lambda,
+            weights,
+            mu_eff,
+            c_sigma,
+            d_sigma,
+            c_mu,
+            chi,
+        }
+    }
+}
+
+impl Strand {
+    fn new<R: Rng>(rotors: (u8, u8, u8), reflector: Reflector, rng: &mut R) -> Self {
+        let mut strand = Self {
+            rotors,
+            reflector,
+            mean: [0.0; DIM],
+            sigma: 0.0,
+            scale: [0.0; DIM],
+            path: [0.0; DIM],
+        };
+        strand.restart(rng);
+        strand
+    }
+
+    fn restart<R: Rng>(&mut self, rng: &mut R) {
+        self.mean = [(); DIM].map(|_| rng.gen_range(0.0..CIRCLE));
+        self.sigma = INITIAL_SIGMA;
+        self.scale = [1.0; DIM];
+        self.path = [0.0; DIM];
+    }
+
+    // a standard normal step and the key of the mean moved by it
+    fn sample<R: Rng>(&self, rng: &mut R) -> ([f64; DIM], Settings) {
+        let z = [(); DIM].map(|_| normal(rng));
+        let x = (0..DIM).map(|i| self.mean[i] + self.sigma * self.scale[i] * z[i]);
+        (z, self.settings(x))
+    }
+
+    // rounds each coordinate to the nearest letter, wrapped around the circle
+    fn settings(&self, x: impl Iterator<Item = f64>) -> Settings {
+        let letters = x
+            .map(|x| (x.round().rem_euclid(CIRCLE) as u8) + 1)
+            .collect::<Vec<_>>();
+        Settings {
+            rotors: self.rotors,
+            ring_settings: (letters[0], letters[1], letters[2]),
+            rotor_positions: (letters[3], letters[4], letters[5]),
+            plugboard: vec![],
+            reflector: self.reflector.clone(),
+        }
+    }
+
+    // moves towards the weighted best steps, ranked is best first
+    fn update<R: Rng>(&mut self, params: &Params, ranked: &[[f64; DIM]], rng: &mut R) {
+        let mut z_w = [0.0; DIM];
+        let mut y_squared = [0.0; DIM];
+        for (w, z) in params.weights.iter().zip(ranked) {
+            for i in 0..DIM {
+                z_w[i] += w * z[i];
+                y_squared[i] += w * (self.scale[i] * z[i]).powi(2);
+            }
+        }
+
+        let c = params.c_sigma;
+        let norm = (c * (2.0 - c) * params.mu_eff).sqrt();
+        for i in 0..DIM {
+            // the steps are relative to the mean, the circle only matters for rounding
+            self.mean[i] = (self.mean[i] + self.sigma * self.scale[i] * z_w[i]).rem_euclid(CIRCLE);
+            self.path[i] = (1.0 - c) * self.path[i] + norm * z_w[i];
+            self.scale[i] =
+                ((1.0 - params.c_mu) * self.scale[i].powi(2) + params.c_mu * y_squared[i]).sqrt();
+        }
+
+        let path_length = self.path.iter().map(|p| p * p).sum::<f64>().sqrt();
+        self.sigma *= ((c / params.d_sigma) * (path_length / params.chi - 1.0)).exp();
+        self.sigma = self.sigma.min(CIRCLE / 2.0);
+
+        let widest = self.scale.iter().fold(0.0, |a: f64, &b| a.max(b));
+        if self.sigma * widest < MIN_STEP {
+            self.restart(rng);
+        }
+    }
+}
+
+impl<F: FitnessFunction<Settings>> EvolutionStrategy<F> {
+    // population_size is the number of samples of a generation over all strands
+    pub fn new(fitness_calc: F, opts: &gen::Options) -> anyhow::Result<Self> {
+        let seed = opts.engine.seed.unwrap_or_else(rand::random);
+        let mut rng = SearchRng::new(opts.engine.rng, seed);
+        let reflectors = match &opts.engine.reflector {
+            ReflectorSearch::Known(reflector) => vec![reflector.clone()],
+            ReflectorSearch::Standard => vec![Reflector::B, Reflector::C],
+            ReflectorSearch::Rewirable => anyhow::bail!("the es engine needs a known reflector"),
+        };
+
+        let mut strands = Vec::new();
+        for reflector in reflectors {
+            for rotors in rotor_orders() {
+                strands.push(Strand::new(rotors, reflector.clone(), &mut rng));
+            }
+        }
+        strands.shuffle(&mut rng);
+        let lambda = (opts.engine.population_size / strands.len()).max(MIN_LAMBDA);
+
+        Ok(Self {
+            fitness_calc,
+            strands,
+            params: Params::new(lambda),
+            rng,
+            pool: engine::thread_pool(&opts.engine)?,
+            bookkeeping: Bookkeeping::new(opts),
+        })
+    }
+
+    pub fn step(&mut self) -> Progress<Settings> {
+        self.bookkeeping.wait_while_paused();
+        let started = Instant::now();
+        let lambda = self.params.lambda;
+
+        let (steps, keys): (Vec<_>, Vec<_>) = self
+            .strands
+            .iter()
+            .flat_map(|strand| {
+                (0..lambda)
+                    .map(|_| strand.sample(&mut self.rng))
+                    .collect::<Vec<_>>()
+            })
+            .unzip();
+        let fitness = self.pool.install(|| {
+            engine::fitness_of_all(&self.fitness_calc, &keys, self.bookkeeping.deadline())
+        });
+
+        for (s, strand) in self.strands.iter_mut().enumerate() {
+            let range = s * lambda..(s + 1) * lambda;
+            let mut order = range.collect::<Vec<_>>();
+            // stable, so ties keep the sampling order and the run stays reproducible
+            order.sort_by(|&a, &b| fitness[b].cmp(&fitness[a]));
+            let ranked = order.iter().map(|&i| steps[i]).collect::<Vec<_>>();
+            strand.update(&self.params, &ranked, &mut self.rng);
+        }
+
+        let evaluated = keys.into_iter().zip(fitness).collect();
+        self.bookkeeping.record(evaluated, started)
+    }
+}
+
+// every ordered choice of three different rotors
+fn rotor_orders() -> impl Iterator<Item = (u8, u8, u8)> {
+    let rotors = 1..=MAX_ROTOR_NUM;
+    rotors.clone().flat_map(move |a| {
+        rotors.clone().flat_map(move |b| {
+            (1..=MAX_ROTOR_NUM)
+                .filter(move |&c| a != b && b != c && a != c)
+                .map(move |c| (a, b, c))
+        })
+    })
+}
+
+// Box-Muller, the ES only needs a few normal numbers per sample
+fn normal<R: Rng>(rng: &mut R) -> f64 {
+    let u = 1.0 - rng.gen::<f64>();
+    let v = rng.gen::<f64>();
+    (-2.0 * u.ln()).sqrt() * (TAU * v).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    use crate::{
+        enigma::Machine,
+        gen::Fitness,
+        metrics::tests::LONG_TEXT,
+        sim::{Simulation, StepResult},
+    };
+
+    use super::*;
+
+    // how close the ring settings and positions are to a target, on the circle
+    struct Distance(Settings);
+
+    impl FitnessFunction<Settings> for Distance {
+        fn fitness_of(&self, key: &Settings) -> Fitness {
+            let letters = |s: &Settings| {
+                let (r, p) = (s.ring_settings, s.rotor_positions);
+                [r.0, r.1, r.2, p.0, p.1, p.2]
+            };
+            let distance = letters(key)
+                .iter()
+                .zip(letters(&self.0))
+                .map(|(&a, b)| {
+                    let d = (a as i32 - b as i32).rem_euclid(26);
+                    d.min(26 - d) as f64
+                })
+                .sum::<f64>();
+            Fitness(1.0 - distance / (6.0 * 13.0))
+        }
+    }
+
+    #[test]
+    fn test_es() {
+        assert_eq!(rotor_orders().count(), 120);
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let normals = (0..10_000).map(|_| normal(&mut rng)).collect::<Vec<_>>();
+        let mean = normals.iter().sum::<f64>() / 10_000.0;
+        let variance = normals.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 10_000.0;
+        assert!(mean.abs() < 0.05 && (variance - 1.0).abs() < 0.05);
+
+        let target = "I II III / 25 01 13 / Z B M".parse::<Settings>().unwrap();
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 120 * 12;
+        opts.engine.seed = Some(5);
+        opts.engine.sample_size = 4;
+        opts.engine.top_size = 2;
+        opts.termination.generation_limit = 60;
+
+        let mut es = EvolutionStrategy::new(Distance(target), &opts).unwrap();
+        let progress = loop {
+            let progress = es.step();
+            assert_eq!(progress.sample.len(), 4);
+            assert_eq!(progress.top.len(), 2);
+            if progress.stop_reason.is_some() {
+                break progress;
+            }
+        };
+        assert!(progress.iteration < 60);
+        assert_eq!(progress.best_fitness, Fitness(1.0));
+        assert_eq!(progress.best_genome.rotor_positions, (26, 2, 13));
+    }
+
+    #[test]
+    fn test_es_simulation() {
+        let settings = "II IV I / 03 17 09 / K E Y".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let mut opts = gen::Options::default();
+        opts.engine.algorithm = engine::Algorithm::Es;
+        opts.engine.population_size = 120 * 8;
+        opts.engine.seed = Some(1);
+        opts.termination.generation_limit = 3;
+
+        let last = Simulation::new(&ciphertext, opts.clone()).unwrap().last();
+        assert!(
+            matches!(last, Some(Ok(StepResult::Final { generation, .. }))
+            if generation.iteration == 3 && generation.best_settings.plugboard.is_empty())
+        );
+
+        opts.engine.max_plugs = 2;
+        assert!(Simulation::new(&ciphertext, opts).is_err());
+    }
+}
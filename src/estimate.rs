@@ -0,0 +1,219 @@
+// how hard a ciphertext is to break, before the search runs, to triage a pile of intercepts:
+// how far a decryption with the right rotors and none of the plugs stands out of the scores of
+// random keys, and how long the search takes to come across one. The search is taken as no
+// better than drawing keys at random until it hits the right rotors, from where the plugs are
+// climbed; crossover of partly right keys only makes it faster
+use std::fmt;
+
+use rand::Rng;
+
+use crate::{
+    confidence::Baseline,
+    engine::GenomeBuilder,
+    enigma::{self, normalize, Machine, Settings},
+    gen::{self, SettingsBuilder},
+    language::{Language, GERMAN_SAMPLE},
+    metrics::{Metric, ENGLISH_SAMPLE},
+};
+
+// random keys the scores of the wrong rotors are drawn from
+const BASELINE_SAMPLES: usize = 500;
+// messages of the same length encrypted with random keys, whose signal is averaged
+const TRIALS: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outlook {
+    // found within the budget 9 times out of 10
+    Likely,
+    Possible,
+    Unlikely,
+    // the right rotors score no better than the best wrong ones by chance
+    Hopeless,
+}
+
+impl fmt::Display for Outlook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outlook::Likely => write!(f, "likely"),
+            Outlook::Possible => write!(f, "possible"),
+            Outlook::Unlikely => write!(f, "unlikely"),
+            Outlook::Hopeless => write!(f, "hopeless"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    // letters of the ciphertext
+    pub letters: usize,
+    // standard deviations above random keys a decryption with the right rotors and none of the
+    // max_plugs plugs scores
+    pub signal: f64,
+    // standard deviations the best of the wrong rotors reaches by chance
+    pub chance: f64,
+    // rotor settings the search chooses from; the right ring is taken along by the right
+    // position and the left one hardly matters, so only the middle ring counts
+    pub rotor_settings: u128,
+    pub expected_generations: f64,
+    // of hitting the right rotors within the budget of generations
+    pub probability: f64,
+    pub outlook: Outlook,
+}
+
+/// Estimates how hard the search with the options has it on the ciphertext in a budget of
+/// `generations`. Its plugs are taken to be max_plugs, its language that of the metric,
+/// English if it has none.
+pub fn estimate<R: Rng>(
+    ciphertext: &str,
+    opts: &gen::Options,
+    generations: u64,
+    rng: &mut R,
+) -> anyhow::Result<Estimate> {
+    let metric = opts
+        .fitness
+        .metric
+        .clone()
+        .unwrap_or(Metric::IndexOfCoincidence);
+    let letters = normalize(ciphertext).replace(' ', "").len();
+    let max_plugs = opts.engine.max_plugs;
+    let baseline = Baseline::sample(
+        ciphertext,
+        &metric,
+        &opts.engine.reflector,
+        max_plugs,
+        BASELINE_SAMPLES,
+        rng,
+    )?;
+
+    let score = (0..TRIALS)
+        .map(|_| unplugged_score(letters, max_plugs, &metric, rng))
+        .sum::<anyhow::Result<f64>>()?
+        / TRIALS as f64;
+    let rotor_settings = enigma::fixed_rings_keyspace_size(0) * opts.engine.reflector.choices();
+    let confidence = Baseline {
+        keyspace: rotor_settings,
+        ..baseline
+    }
+    .confidence(score);
+
+    // a hit is kept if it outscores the best of the wrong rotors, which lies about at chance
+    let recognized = normal_cdf(confidence.sigmas - confidence.chance);
+    let per_generation = opts.engine.population_size as f64 * recognized / rotor_settings as f64;
+    let probability = -(-per_generation * generations as f64).exp_m1();
+    let outlook = match probability {
+        _ if confidence.sigmas < confidence.chance => Outlook::Hopeless,
+        p if p >= 0.9 => Outlook::Likely,
+        p if p >= 0.1 => Outlook::Possible,
+        _ => Outlook::Unlikely,
+    };
+
+    Ok(Estimate {
+        letters,
+        signal: confidence.sigmas,
+        chance: confidence.chance,
+        rotor_settings,
+        expected_generations: 1.0 / per_generation,
+        probability,
+        outlook,
+    })
+}
+
+// score of a passage of the language of the metric encrypted with a random key of max_plugs
+// plugs and decrypted with its rotors but without them
+fn unplugged_score<R: Rng>(
+    letters: usize,
+    max_plugs: usize,
+    metric: &Metric,
+    rng: &mut R,
+) -> anyhow::Result<f64> {
+    let sample = match metric {
+        Metric::Sinkov(Language::German)
+        | Metric::Bigram(Language::German)
+        | Metric::Trimmed(Language::German, _) => GERMAN_SAMPLE,
+        _ => ENGLISH_SAMPLE,
+    };
+    let sample = normalize(sample);
+    let words = sample.split_whitespace().collect::<Vec<_>>();
+    let start = rng.gen_range(0..words.len());
+    let plaintext = words
+        .iter()
+        .cycle()
+        .skip(start)
+        .flat_map(|word| word.chars())
+        .take(letters)
+        .collect::<String>();
+
+    let mut key = SettingsBuilder {
+        max_plugs: 0,
+        reflector: gen::ReflectorSearch::default(),
+        herivel_spread: None,
+        fixed_rings: false,
+    }
+    .build_genome(0, rng);
+    for _ in 0..max_plugs {
+        gen::add_plug(&mut key.plugboard, rng);
+    }
+    let ciphertext = Machine::new(&key)?.encrypt(&plaintext);
+    let unplugged = Settings {
+        plugboard: vec![],
+        ..key
+    };
+    Ok(metric.score(&Machine::new(&unplugged)?.decrypt(&ciphertext)))
+}
+
+// Abramowitz and Stegun 7.1.26, off by less than 1.5e-7
+fn normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * z);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let key = "II V III / 08 05 20 / M C U / AQ BT"
+            .parse::<Settings>()
+            .unwrap();
+        let machine = Machine::new(&key).unwrap();
+        let mut opts = gen::Options::default();
+        opts.engine.max_plugs = 2;
+        opts.fitness.metric = Some(Metric::Bigram(Language::English));
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        let long = estimate(&machine.encrypt(LONG_TEXT), &opts, 100, &mut rng).unwrap();
+        assert!(long.signal > long.chance, "{long:?}");
+        let choices = opts.engine.reflector.choices();
+        assert_eq!(long.rotor_settings, 120 * 26u128.pow(4) * choices);
+        let short = estimate(&machine.encrypt(&LONG_TEXT[..40]), &opts, 100, &mut rng);
+        let short = short.unwrap();
+        assert!(short.signal < long.signal);
+        assert!(short.probability < long.probability);
+        assert!(short.expected_generations > long.expected_generations);
+
+        // ten unknown plugs drown the rotors of a short message
+        opts.engine.max_plugs = 10;
+        let plugged = estimate(&machine.encrypt(&LONG_TEXT[..40]), &opts, 100, &mut rng);
+        let plugged = plugged.unwrap();
+        assert_eq!(plugged.outlook, Outlook::Hopeless, "{plugged:?}");
+
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-7);
+        assert!((normal_cdf(1.96) - 0.975).abs() < 1e-4);
+        assert!((normal_cdf(-1.96) - 0.025).abs() < 1e-4);
+    }
+}
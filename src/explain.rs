@@ -0,0 +1,299 @@
+// a narrative of the search in plain language, for teaching: what the genetic algorithm does
+// between generations, why the score rose and which parts of the key the population agrees on
+use std::fmt;
+
+use crate::{
+    enigma::{self, Settings},
+    gen,
+    metrics::Metric,
+    sim::{Generation, StepResult},
+};
+
+/// Best keys of a generation to read the convergence of the population from.
+pub const TOP: usize = 20;
+
+// best keys a part of the key must agree in to count as converged, and how many of them
+const CONVERGED_SHARE: f64 = 0.9;
+const MIN_TOP: usize = 5;
+// generations without a better key that are told as a standstill
+const STALL: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Reflector,
+    RotorOrder,
+    RingSettings,
+    RotorPositions,
+    Plugboard,
+}
+
+impl Component {
+    pub const ALL: [Component; 5] = [
+        Component::Reflector,
+        Component::RotorOrder,
+        Component::RingSettings,
+        Component::RotorPositions,
+        Component::Plugboard,
+    ];
+
+    // the part of the key in key sheet notation
+    pub fn of(&self, settings: &Settings) -> String {
+        match self {
+            Component::Reflector => settings.reflector.to_string(),
+            Component::RotorOrder => {
+                let (a, b, c) = settings.rotors;
+                [a, b, c].map(enigma::rotor_name).join(" ")
+            }
+            Component::RingSettings => {
+                let (a, b, c) = settings.ring_settings;
+                format!("{a:02} {b:02} {c:02}")
+            }
+            Component::RotorPositions => {
+                let (a, b, c) = settings.rotor_positions;
+                [a, b, c].map(|p| enigma::letter(p).to_string()).join(" ")
+            }
+            Component::Plugboard if settings.plugboard.is_empty() => "no plugs".to_string(),
+            // the same plugs in any order
+            Component::Plugboard => {
+                let mut plugs = settings
+                    .plugboard
+                    .iter()
+                    .map(|&(a, b)| (a.min(b), a.max(b)))
+                    .collect::<Vec<_>>();
+                plugs.sort();
+                enigma::pairs_notation(&plugs)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Component::Reflector => write!(f, "reflector"),
+            Component::RotorOrder => write!(f, "rotor order"),
+            Component::RingSettings => write!(f, "ring settings"),
+            Component::RotorPositions => write!(f, "rotor positions"),
+            Component::Plugboard => write!(f, "plugboard"),
+        }
+    }
+}
+
+/// Tells the steps of a search one after the other. The convergence of the population is read
+/// from the best keys of a generation, which are only there with EngineOptions::top_size.
+#[derive(Debug)]
+pub struct Narrator {
+    previous: Option<Generation>,
+    // parts of the key the search doesn't choose, or that were told as converged
+    settled: Vec<Component>,
+    // generation of the best key the last standstill was told for
+    stalled_at: Option<u64>,
+}
+
+impl Narrator {
+    pub fn new(opts: &gen::EngineOptions) -> Self {
+        let mut settled = Vec::new();
+        if !opts.reflector.is_searched() {
+            settled.push(Component::Reflector);
+        }
+        if opts.max_plugs == 0 {
+            settled.push(Component::Plugboard);
+        }
+        Self {
+            previous: None,
+            settled,
+            stalled_at: None,
+        }
+    }
+
+    pub fn step(&mut self, step: &StepResult) -> Vec<String> {
+        let mut lines = self.generation(step.generation());
+        if let StepResult::Final { generation, reason } = step {
+            lines.push(format!(
+                "The search stopped after {} generations: {}. Its best key, {}, was found in \
+                 generation {}.",
+                generation.iteration, reason, generation.best_settings, generation.best_found_in
+            ));
+        }
+        lines
+    }
+
+    fn generation(&mut self, generation: &Generation) -> Vec<String> {
+        let i = generation.iteration;
+        let mut lines = Vec::new();
+        match &self.previous {
+            None => lines.push(format!(
+                "Generation {i}: the population starts as random keys. Each is scored by how \
+                 much its decryption looks like language; random letters score low, so the best \
+                 of them, {:.5}, stands only a little above the average {:.5}.",
+                generation.best_fitness, generation.average_fitness
+            )),
+            Some(previous) if generation.best_fitness > previous.best_fitness => {
+                let changed = Component::ALL
+                    .into_iter()
+                    .filter(|c| c.of(&previous.best_settings) != c.of(&generation.best_settings))
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>();
+                let how = match changed.as_slice() {
+                    [] => "an equivalent key".to_string(),
+                    [one] => format!("a key with another {one}"),
+                    [init @ .., last] => format!("a key with other {} and {last}", init.join(", ")),
+                };
+                let mut line = format!(
+                    "Generation {i}: the best score rose from {:.5} to {:.5} with {how}.",
+                    previous.best_fitness, generation.best_fitness
+                );
+                // told at the first rise only
+                if previous.best_found_in == 1 {
+                    line += " A decryption with more of the key right has more of the letters of \
+                             the language, so crossover and mutation that hit on a better part \
+                             are kept by selection.";
+                }
+                lines.push(line);
+            }
+            Some(_) => {}
+        }
+
+        let since = i.saturating_sub(generation.best_found_in);
+        if since >= STALL && self.stalled_at != Some(generation.best_found_in) {
+            self.stalled_at = Some(generation.best_found_in);
+            lines.push(format!(
+                "Generation {i}: no better key for {since} generations. The population has \
+                 gathered on a local optimum, a key that is partly right; only mutation can \
+                 carry it off to a better one."
+            ));
+        }
+
+        let top = &generation.top;
+        for component in Component::ALL {
+            if top.len() < MIN_TOP || self.settled.contains(&component) {
+                continue;
+            }
+            let value = component.of(&generation.best_settings);
+            let agreeing = top
+                .iter()
+                .filter(|individual| component.of(&individual.genome) == value)
+                .count();
+            if agreeing as f64 >= CONVERGED_SHARE * top.len() as f64 {
+                self.settled.push(component);
+                lines.push(format!(
+                    "Generation {i}: the population converged on the {component}, {agreeing} of \
+                     the {} best keys have {value}. Selection breeds from keys that agree on it, \
+                     so the search now varies the rest of the key.",
+                    top.len()
+                ));
+            }
+        }
+
+        self.previous = Some(generation.clone());
+        lines
+    }
+}
+
+// told before the plugboard climb after the search
+pub fn climb(metric: &Metric) -> String {
+    format!(
+        "The genetic search is over and a hill climb takes the plugboard on: it tries adding, \
+         removing and swapping one plug at a time and keeps every change that raises the {} \
+         score. A plug only changes the few letters it swaps, too little for the score to tell \
+         the GA, but enough for a climb from rotors that are right.",
+        metric
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{engine::Individual, gen::Fitness};
+
+    use super::*;
+
+    fn generation(iteration: u64, best: f64, settings: &str, top: &[&str]) -> Generation {
+        let settings = settings.parse::<Settings>().unwrap();
+        Generation {
+            iteration,
+            average_fitness: Fitness(0.04),
+            median_fitness: Fitness(0.04),
+            fitness_std_dev: 0.01,
+            best_fitness: Fitness(best),
+            best_settings: settings,
+            best_found_in: iteration,
+            duration: Duration::ZERO,
+            processing_time: Duration::ZERO,
+            sample: vec![],
+            top: top
+                .iter()
+                .enumerate()
+                .map(|(rank, key)| Individual {
+                    rank,
+                    genome: key.parse().unwrap(),
+                    fitness: Fitness(best),
+                })
+                .collect(),
+            ancestry: vec![],
+            coverage: None,
+            invalid_genomes: None,
+        }
+    }
+
+    #[test]
+    fn test_narrator() {
+        let opts = gen::EngineOptions {
+            reflector: gen::ReflectorSearch::Standard,
+            max_plugs: 2,
+            ..Default::default()
+        };
+        let mut narrator = Narrator::new(&opts);
+        let first = generation(1, 0.045, "I II III / 01 01 01 / A A A", &[]);
+        let lines = narrator.step(&StepResult::Intermediate(first.clone()));
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("Generation 1: the population starts as random keys"));
+
+        let top = ["II V III / 01 01 01 / A B C"; 5];
+        let better = generation(2, 0.05, "II V III / 01 01 01 / A B C", &top);
+        let lines = narrator.step(&StepResult::Intermediate(better.clone()));
+        assert!(
+            lines[0].contains(
+                "rose from 0.04500 to 0.05000 with a key with other rotor order and rotor positions"
+            ),
+            "{lines:?}"
+        );
+        assert!(lines[0].contains("A decryption with more of the key right"));
+        // all five best keys agree on every part
+        assert_eq!(lines.len(), 1 + Component::ALL.len());
+        assert!(lines[1].contains("converged on the reflector, 5 of the 5 best keys have B"));
+
+        // a standstill is told once
+        let stalled = Generation {
+            iteration: 30,
+            best_found_in: 2,
+            ..better.clone()
+        };
+        let lines = narrator.step(&StepResult::Intermediate(stalled.clone()));
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("no better key for 28 generations"));
+        let lines = narrator.step(&StepResult::Final {
+            generation: Generation {
+                iteration: 31,
+                ..stalled
+            },
+            reason: "generation limit".to_string(),
+        });
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("The search stopped after 31 generations: generation limit"));
+
+        // a part the search doesn't choose doesn't converge
+        let opts = gen::EngineOptions {
+            reflector: gen::ReflectorSearch::default(),
+            max_plugs: 0,
+            ..Default::default()
+        };
+        let lines = Narrator::new(&opts).step(&StepResult::Intermediate(better));
+        assert_eq!(lines.len(), 1 + 3);
+
+        let key = "C / II V III / 08 05 20 / M C U / AQ BT".parse().unwrap();
+        let parts = Component::ALL.map(|c| c.of(&key));
+        assert_eq!(parts, ["C", "II V III", "08 05 20", "M C U", "AQ BT"]);
+    }
+}
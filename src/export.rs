@@ -0,0 +1,271 @@
+use std::{fs, fs::File, path::Path, sync::Arc};
+
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt64Array, UInt8Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use crate::{
+    enigma::Settings,
+    sim::{Generation, StepResult},
+};
+
+pub const GENERATIONS_FILE: &str = "generations.parquet";
+pub const INDIVIDUALS_FILE: &str = "individuals.parquet";
+
+/// Writes the statistics of every generation and the sampled individuals (see
+/// `EngineOptions::sample_size`) as Parquet files into a directory, for pandas or Polars.
+/// Generations are written when the final step is recorded, individuals as they come.
+pub struct ParquetExport {
+    generations: Vec<Generation>,
+    generations_writer: ArrowWriter<File>,
+    individuals_writer: ArrowWriter<File>,
+}
+
+impl ParquetExport {
+    pub fn create(dir: &Path) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = |name: &str, schema: SchemaRef| {
+            anyhow::Ok(ArrowWriter::try_new(
+                File::create(dir.join(name))?,
+                schema,
+                Some(props.clone()),
+            )?)
+        };
+
+        Ok(Self {
+            generations: Vec::new(),
+            generations_writer: writer(GENERATIONS_FILE, generations_schema())?,
+            individuals_writer: writer(INDIVIDUALS_FILE, individuals_schema())?,
+        })
+    }
+
+    pub fn record(&mut self, step: &StepResult) -> anyhow::Result<()> {
+        let generation = step.generation();
+        if !generation.sample.is_empty() {
+            self.individuals_writer
+                .write(&individuals_batch(generation)?)?;
+        }
+        self.generations.push(generation.clone());
+
+        if let StepResult::Final { .. } = step {
+            self.generations_writer
+                .write(&generations_batch(&self.generations)?)?;
+            self.generations_writer.finish()?;
+            self.individuals_writer.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn generations_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("iteration", DataType::UInt64, false),
+        Field::new("average_fitness", DataType::Float64, false),
+        Field::new("median_fitness", DataType::Float64, false),
+        Field::new("fitness_std_dev", DataType::Float64, false),
+        Field::new("best_fitness", DataType::Float64, false),
+        Field::new("best_found_in", DataType::UInt64, false),
+        Field::new("best_key", DataType::Utf8, false),
+        Field::new("duration_ms", DataType::UInt64, false),
+        Field::new("processing_time_ms", DataType::UInt64, false),
+    ]))
+}
+
+fn generations_batch(generations: &[Generation]) -> anyhow::Result<RecordBatch> {
+    let u64s = |f: fn(&Generation) -> u64| -> ArrayRef {
+        Arc::new(generations.iter().map(f).collect::<UInt64Array>())
+    };
+    let f64s = |f: fn(&Generation) -> f64| -> ArrayRef {
+        Arc::new(generations.iter().map(f).collect::<Float64Array>())
+    };
+
+    Ok(RecordBatch::try_new(
+        generations_schema(),
+        vec![
+            u64s(|g| g.iteration),
+            f64s(|g| g.average_fitness.0),
+            f64s(|g| g.median_fitness.0),
+            f64s(|g| g.fitness_std_dev),
+            f64s(|g| g.best_fitness.0),
+            u64s(|g| g.best_found_in),
+            Arc::new(
+                generations
+                    .iter()
+                    .map(|g| Some(g.best_settings.to_string()))
+                    .collect::<StringArray>(),
+            ),
+            u64s(|g| g.duration.as_millis() as u64),
+            u64s(|g| g.processing_time.as_millis() as u64),
+        ],
+    )?)
+}
+
+// settings split into columns, so the population can be grouped by rotor order or position
+fn individuals_schema() -> SchemaRef {
+    let mut fields = vec![
+        Field::new("iteration", DataType::UInt64, false),
+        Field::new("rank", DataType::UInt64, false),
+        Field::new("fitness", DataType::Float64, false),
+        Field::new("key", DataType::Utf8, false),
+    ];
+    for setting in ["rotor", "ring_setting", "rotor_position"] {
+        for slot in 1..=3 {
+            fields.push(Field::new(
+                format!("{}_{}", setting, slot),
+                DataType::UInt8,
+                false,
+            ));
+        }
+    }
+    fields.push(Field::new("plugs", DataType::UInt8, false));
+    fields.push(Field::new("reflector", DataType::Utf8, false));
+
+    Arc::new(Schema::new(fields))
+}
+
+fn individuals_batch(generation: &Generation) -> anyhow::Result<RecordBatch> {
+    let sample = &generation.sample;
+    let strings = |f: &dyn Fn(usize) -> String| -> ArrayRef {
+        Arc::new(
+            (0..sample.len())
+                .map(|i| Some(f(i)))
+                .collect::<StringArray>(),
+        )
+    };
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt64Array::from(vec![generation.iteration; sample.len()])),
+        Arc::new(
+            sample
+                .iter()
+                .map(|i| i.rank as u64)
+                .collect::<UInt64Array>(),
+        ),
+        Arc::new(sample.iter().map(|i| i.fitness.0).collect::<Float64Array>()),
+        strings(&|i| sample[i].genome.to_string()),
+    ];
+    let settings = sample.iter().map(|i| &i.genome).collect::<Vec<_>>();
+    let u8s = |f: &dyn Fn(&Settings) -> u8| -> ArrayRef {
+        Arc::new(settings.iter().map(|s| f(s)).collect::<UInt8Array>())
+    };
+    columns.extend([
+        u8s(&|s| s.rotors.0),
+        u8s(&|s| s.rotors.1),
+        u8s(&|s| s.rotors.2),
+        u8s(&|s| s.ring_settings.0),
+        u8s(&|s| s.ring_settings.1),
+        u8s(&|s| s.ring_settings.2),
+        u8s(&|s| s.rotor_positions.0),
+        u8s(&|s| s.rotor_positions.1),
+        u8s(&|s| s.rotor_positions.2),
+        u8s(&|s| s.plugboard.len() as u8),
+    ]);
+    columns.push(strings(&|i| settings[i].reflector.to_string()));
+
+    Ok(RecordBatch::try_new(individuals_schema(), columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::{engine::Individual, gen::Fitness};
+
+    use super::*;
+
+    fn generation(iteration: u64) -> Generation {
+        let settings = "C / II V III / 08 05 20 / M C U / AB CZ"
+            .parse::<Settings>()
+            .unwrap();
+        Generation {
+            iteration,
+            average_fitness: Fitness(0.04),
+            median_fitness: Fitness(0.039),
+            fitness_std_dev: 0.002,
+            best_fitness: Fitness(0.05),
+            best_settings: settings.clone(),
+            best_found_in: 1,
+            duration: Duration::from_millis(1200),
+            processing_time: Duration::from_millis(800),
+            sample: (0..3)
+                .map(|rank| Individual {
+                    rank: rank * 10,
+                    genome: settings.clone(),
+                    fitness: Fitness(0.05 - rank as f64 / 100.0),
+                })
+                .collect(),
+            top: Vec::new(),
+            ancestry: Vec::new(),
+            coverage: None,
+            invalid_genomes: None,
+        }
+    }
+
+    fn read(path: &Path) -> Vec<RecordBatch> {
+        ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_export() {
+        let dir = std::env::temp_dir().join(format!("enigmagen-export-{}", std::process::id()));
+        let mut export = ParquetExport::create(&dir).unwrap();
+        export
+            .record(&StepResult::Intermediate(generation(1)))
+            .unwrap();
+        export
+            .record(&StepResult::Final {
+                generation: generation(2),
+                reason: "generation limit of 2 reached".to_string(),
+            })
+            .unwrap();
+
+        let generations = read(&dir.join(GENERATIONS_FILE));
+        assert_eq!(generations.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        assert_eq!(
+            generations[0].schema().fields(),
+            generations_schema().fields()
+        );
+
+        let individuals = read(&dir.join(INDIVIDUALS_FILE));
+        assert_eq!(individuals.iter().map(|b| b.num_rows()).sum::<usize>(), 6);
+        let batch = &individuals[0];
+        let column = |name: &str| batch.column_by_name(name).unwrap();
+        assert_eq!(
+            column("rank")
+                .as_any()
+                .downcast_ref::<UInt64Array>()
+                .unwrap()
+                .values(),
+            &[0, 10, 20, 0, 10, 20]
+        );
+        assert_eq!(
+            column("rotor_2")
+                .as_any()
+                .downcast_ref::<UInt8Array>()
+                .unwrap()
+                .value(0),
+            5
+        );
+        assert_eq!(
+            column("reflector")
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .value(0),
+            "C"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
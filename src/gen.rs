@@ -1,122 +1,1313 @@
-use std::sync::Arc;
+use std::{
+    cmp::Ordering,
+    fmt, mem,
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
+};
 
+use anyhow::ensure;
 use chrono::Duration;
-use genevo::{
-    genetic::{Children, Parents},
-    operator::{CrossoverOp, GeneticOperator, MutationOp},
-    prelude::{FitnessFunction, GenomeBuilder, Genotype},
-    random::Rng,
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+use regex::Regex;
+
+use crate::cache::{CacheBackend, FitnessCache, ScramblerCache};
+use crate::cipher::Cipher;
+use crate::coverage::Coverage;
+use crate::dictionary::Dictionary;
+use crate::dna::Encoding;
+use crate::engine::{
+    Algorithm, Cores, CrossoverOp, FitnessFunction, GenomeBuilder, Instant, MutationOp, Pause,
+    SelectionStrategy,
 };
-use moka::sync::Cache;
-use rand::{distributions, prelude::Distribution, seq::IteratorRandom};
-
 use crate::enigma::{
-    Machine, Settings, MAX_RING_SETTINGS_NUM, MAX_ROTOR_NUM, MAX_ROTOR_POSITIONS_NUM,
+    is_plug_conflicting, Machine, Reflector, Settings, LETTERS_NUM, MAX_PLUGS_NUM,
+    MAX_RING_SETTINGS_NUM, MAX_ROTOR_NUM, MAX_ROTOR_POSITIONS_NUM, REFLECTOR_PAIRS_NUM,
 };
+pub use crate::metrics::index_of_coincidence;
+use crate::metrics::Metric;
+use crate::rng::RngAlgorithm;
+use crate::tempering::Tempering;
+use crate::termination::{self, Criterion};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Options {
-    pub fitness_scale: usize,
+    pub engine: EngineOptions,
+    pub fitness: FitnessOptions,
+    pub termination: TerminationOptions,
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineOptions {
     pub population_size: usize,
-    pub generation_limit: u64,
-    pub time_limit: Duration,
+    pub num_individuals_per_parents: usize,
     pub selection_ratio: f64,
+    pub selection: SelectionStrategy,
+    // bounds the selection ratio is adapted within to how far the average trails the best
+    pub selection_adaptation: Option<SelectionAdaptation>,
     pub mutation_rate: f64,
+    // mutation_rate and how far rings and positions move, weakened as the run goes on
+    pub mutation_schedule: MutationSchedule,
+    // odds of the parts of a key to be the one a mutation changes
+    pub mutation_weights: MutationWeights,
+    pub crossover: CrossoverMode,
+    pub rotor_crossover: RotorCrossover,
+    pub plugboard_crossover: PlugboardCrossover,
     pub reinsertion_ratio: f64,
-    pub cache_size: usize,
+    // best individuals copied unchanged into the next generation, the best offspring fill the
+    // rest; in place of reinsertion_ratio, which keeps the best (1 - ratio) of the population
+    pub elitism: Option<usize>,
+    pub max_plugs: usize,
+    pub encoding: Encoding,
+    pub reflector: ReflectorSearch,
+    pub algorithm: Algorithm,
+    // same seed gives the same search at any number of threads, random if not set
+    pub seed: Option<u64>,
+    // generator of the random numbers of the engines and their operators
+    pub rng: RngAlgorithm,
+    // population of the later generations, population_size is the first
+    pub population_schedule: PopulationSchedule,
+    // GA islands at several temperatures instead of a single population
+    pub tempering: Option<Tempering>,
+    // 0 uses all cores, or one thread per pinned core
+    pub threads: usize,
+    // cores the worker threads are pinned to, e.g. to keep off the others on a shared server
+    pub pin_cores: Option<Cores>,
+    // rotor positions of the initial keys within this many letters of their ring settings
+    // (the Herivel tip), anywhere if not set
+    pub herivel_spread: Option<u8>,
+    // ring settings of the left and right rotor held at A in the search, 26² times fewer keys:
+    // a ring turns the wiring against the letters like the position does, exactly so for the
+    // left rotor and up to where the middle rotor steps for the right one, which the
+    // simulation recovers with the middle ring once the search is over
+    pub fixed_rings: bool,
+    // individuals reported with every generation, for analysis of the population
+    pub sample_size: usize,
+    // best individuals reported with every generation, e.g. to rescore them
+    pub top_size: usize,
+    // best individuals of the last generation whose ancestry is recorded, 0 records none
+    pub genealogy: usize,
+    // bytes of a Bloom filter of every key evaluated in the run, offspring it already holds
+    // are mutated again; 0 remembers none, each tempering island keeps its own
+    pub explored_memory: usize,
+    pub pause: Pause,
+}
+
+// whether the reflector is a gene, for traffic where it isn't known a priori
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectorSearch {
+    Known(Reflector),
+    // B or C
+    Standard,
+    // any UKW-D wiring
+    Rewirable,
 }
 
-impl Genotype for Settings {
-    type Dna = u8;
+impl ReflectorSearch {
+    pub fn is_searched(&self) -> bool {
+        !matches!(self, ReflectorSearch::Known(_))
+    }
+
+    // number of reflectors the search chooses from, UKW-D has 25!! wirings
+    pub fn choices(&self) -> u128 {
+        match self {
+            ReflectorSearch::Known(_) => 1,
+            ReflectorSearch::Standard => 2,
+            ReflectorSearch::Rewirable => (1..LETTERS_NUM as u128).step_by(2).product(),
+        }
+    }
+}
+
+impl Default for ReflectorSearch {
+    fn default() -> Self {
+        ReflectorSearch::Known(Reflector::B)
+    }
+}
+
+impl fmt::Display for ReflectorSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReflectorSearch::Known(reflector) => write!(f, "{}", reflector),
+            ReflectorSearch::Standard => write!(f, "standard (B or C)"),
+            ReflectorSearch::Rewirable => write!(f, "rewirable (any UKW-D wiring)"),
+        }
+    }
+}
+
+// standard, rewirable or a known reflector in key sheet notation
+impl FromStr for ReflectorSearch {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "standard" => Ok(ReflectorSearch::Standard),
+            "rewirable" => Ok(ReflectorSearch::Rewirable),
+            s => Ok(ReflectorSearch::Known(s.parse()?)),
+        }
+    }
+}
+
+/// Population size by generation: a large random population explores in the first generations,
+/// a smaller one refines the best of it later for fewer evaluations in total.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PopulationSchedule {
+    #[default]
+    Constant,
+    // (first generation, size) in generation order, population_size before the first stage
+    Stages(Vec<(u64, usize)>),
+    // geometric decline from population_size to final_size in generation `by`
+    Decay {
+        final_size: usize,
+        by: u64,
+    },
+}
+
+impl PopulationSchedule {
+    // population of the generation, the first generation is 1
+    pub fn size_at(&self, initial: usize, generation: u64) -> usize {
+        match self {
+            PopulationSchedule::Constant => initial,
+            PopulationSchedule::Stages(stages) => stages
+                .iter()
+                .rev()
+                .find(|(from, _)| *from <= generation)
+                .map_or(initial, |&(_, size)| size),
+            PopulationSchedule::Decay { final_size, by } if generation < *by => {
+                let progress = generation.saturating_sub(1) as f64 / (by - 1) as f64;
+                let ratio = *final_size as f64 / initial as f64;
+                (initial as f64 * ratio.powf(progress)).round() as usize
+            }
+            PopulationSchedule::Decay { final_size, .. } => *final_size,
+        }
+    }
+
+    // the largest population of any generation
+    pub fn max_size(&self, initial: usize) -> usize {
+        match self {
+            PopulationSchedule::Stages(stages) => stages
+                .iter()
+                .map(|&(_, size)| size)
+                .fold(initial, usize::max),
+            PopulationSchedule::Decay { final_size, .. } => initial.max(*final_size),
+            PopulationSchedule::Constant => initial,
+        }
+    }
+
+    pub(crate) fn sizes_mut(&mut self) -> Vec<&mut usize> {
+        match self {
+            PopulationSchedule::Constant => vec![],
+            PopulationSchedule::Stages(stages) => stages.iter_mut().map(|(_, size)| size).collect(),
+            PopulationSchedule::Decay { final_size, .. } => vec![final_size],
+        }
+    }
+
+    fn sizes(&self) -> Vec<usize> {
+        self.clone()
+            .sizes_mut()
+            .into_iter()
+            .map(|size| *size)
+            .collect()
+    }
+}
+
+impl fmt::Display for PopulationSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopulationSchedule::Constant => write!(f, "constant"),
+            PopulationSchedule::Stages(stages) => {
+                let stages = stages
+                    .iter()
+                    .map(|(from, size)| format!("{from}:{size}"))
+                    .collect::<Vec<_>>();
+                write!(f, "{}", stages.join(","))
+            }
+            PopulationSchedule::Decay { final_size, by } => write!(f, "decay:{final_size}@{by}"),
+        }
+    }
+}
+
+// constant, decay:<final size>@<generation> or stages <first generation>:<size>,...
+impl FromStr for PopulationSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!("invalid population schedule {s}, expected constant, decay:50000@40 or 20:200000,50:50000")
+        };
+
+        if s == "constant" {
+            return Ok(PopulationSchedule::Constant);
+        }
+        if let Some(decay) = s.strip_prefix("decay:") {
+            let (final_size, by) = decay.split_once('@').ok_or_else(invalid)?;
+            return Ok(PopulationSchedule::Decay {
+                final_size: final_size.parse().map_err(|_| invalid())?,
+                by: by.parse().map_err(|_| invalid())?,
+            });
+        }
+
+        let stages = s
+            .split(',')
+            .map(|stage| {
+                let (from, size) = stage.split_once(':').ok_or_else(invalid)?;
+                Ok((
+                    from.parse().map_err(|_| invalid())?,
+                    size.parse().map_err(|_| invalid())?,
+                ))
+            })
+            .collect::<Result<Vec<(u64, usize)>, String>>()?;
+        Ok(PopulationSchedule::Stages(stages))
+    }
+}
+
+/// Bounds of a selection ratio adapted every generation to the ratio of the best to the average
+/// fitness: when the average closes in on the best, the descendants of a few keys are taking
+/// over the population and more of it may mate; when it trails far behind, selection hardly
+/// tells keys apart and the search drifts, so fewer may.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SelectionAdaptation {
+    pub min: f64,
+    pub max: f64,
+}
+
+// best to average fitness below which the population is taken over, above which it drifts
+const TAKEOVER_RATIO: f64 = 1.05;
+const DRIFT_RATIO: f64 = 1.3;
+// factor the selection ratio changes by in a generation
+const ADAPTATION_STEP: f64 = 1.1;
+
+impl SelectionAdaptation {
+    pub fn clamp(&self, selection_ratio: f64) -> f64 {
+        selection_ratio.clamp(self.min, self.max)
+    }
+
+    // selection ratio of the next generation; unchanged for fitness that isn't positive, whose
+    // ratios mean nothing
+    pub fn adapt(&self, selection_ratio: f64, best: Fitness, average: Fitness) -> f64 {
+        if average.0 <= 0.0 || best.0 <= 0.0 {
+            return selection_ratio;
+        }
+        let spread = best.0 / average.0;
+        let adapted = if spread < TAKEOVER_RATIO {
+            selection_ratio * ADAPTATION_STEP
+        } else if spread > DRIFT_RATIO {
+            selection_ratio / ADAPTATION_STEP
+        } else {
+            selection_ratio
+        };
+        self.clamp(adapted)
+    }
+}
+
+impl fmt::Display for SelectionAdaptation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.min, self.max)
+    }
+}
+
+// <min>:<max>, e.g. 0.1:0.8
+impl FromStr for SelectionAdaptation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid selection bounds {s}, expected e.g. 0.1:0.8");
+        let (min, max) = s.split_once(':').ok_or_else(invalid)?;
+        Ok(SelectionAdaptation {
+            min: min.parse().map_err(|_| invalid())?,
+            max: max.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+/// Strength of the mutations over a run, the share of mutation_rate and of how far rings and
+/// positions move: full all along, or annealed down to `to` so the late generations refine the
+/// best keys rather than jump away from them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MutationSchedule {
+    #[default]
+    Constant,
+    // geometric decline to `to` in generation `by`
+    Decay {
+        to: f64,
+        by: u64,
+    },
+    // linear decline to `to` as the best fitness climbs from the best of the first generation
+    // to target_fitness
+    Fitness {
+        to: f64,
+    },
+}
+
+impl MutationSchedule {
+    // strength of the mutations breeding the generation, the first generation is 1
+    pub fn strength(
+        &self,
+        generation: u64,
+        first_best: Fitness,
+        best: Fitness,
+        target: Option<Fitness>,
+    ) -> f64 {
+        match *self {
+            MutationSchedule::Constant => 1.0,
+            MutationSchedule::Decay { to, by } if generation < by => {
+                to.powf(generation.saturating_sub(1) as f64 / (by - 1) as f64)
+            }
+            MutationSchedule::Decay { to, .. } => to,
+            MutationSchedule::Fitness { to } => {
+                let progress = match target {
+                    Some(target) if target.0 > first_best.0 => {
+                        ((best.0 - first_best.0) / (target.0 - first_best.0)).clamp(0.0, 1.0)
+                    }
+                    _ => 0.0,
+                };
+                1.0 - (1.0 - to) * progress
+            }
+        }
+    }
+}
+
+impl fmt::Display for MutationSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MutationSchedule::Constant => write!(f, "constant"),
+            MutationSchedule::Decay { to, by } => write!(f, "decay:{to}@{by}"),
+            MutationSchedule::Fitness { to } => write!(f, "fitness:{to}"),
+        }
+    }
+}
+
+// constant, decay:<strength>@<generation> or fitness:<strength>
+impl FromStr for MutationSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid mutation schedule {s}, expected constant, decay:0.2@100 or fitness:0.2"
+            )
+        };
+
+        if s == "constant" {
+            return Ok(MutationSchedule::Constant);
+        }
+        if let Some(decay) = s.strip_prefix("decay:") {
+            let (to, by) = decay.split_once('@').ok_or_else(invalid)?;
+            return Ok(MutationSchedule::Decay {
+                to: to.parse().map_err(|_| invalid())?,
+                by: by.parse().map_err(|_| invalid())?,
+            });
+        }
+        match s.strip_prefix("fitness:") {
+            Some(to) => Ok(MutationSchedule::Fitness {
+                to: to.parse().map_err(|_| invalid())?,
+            }),
+            None => Err(invalid()),
+        }
+    }
+}
+
+/// Relative odds of the parts of a key to be changed by a mutation, all alike by default: the
+/// rotors and rings have far fewer values than the positions and the plugboard, and a wrong
+/// rotor spoils the whole decryption. The plugboard and the reflector only count when they're
+/// searched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutationWeights {
+    pub rotors: f64,
+    pub rings: f64,
+    pub positions: f64,
+    pub plugboard: f64,
+    pub reflector: f64,
+    // a ring and the position of its rotor turned together, see shift_ring
+    pub shift: f64,
+}
+
+impl MutationWeights {
+    const NAMES: [&'static str; 6] = [
+        "rotors",
+        "rings",
+        "positions",
+        "plugboard",
+        "reflector",
+        "shift",
+    ];
+
+    fn weights(&self) -> [f64; 6] {
+        [
+            self.rotors,
+            self.rings,
+            self.positions,
+            self.plugboard,
+            self.reflector,
+            self.shift,
+        ]
+    }
+
+    // index of the part a mutation changes, in the order of NAMES
+    fn pick<R: Rng>(&self, plugs: bool, reflector: bool, rng: &mut R) -> usize {
+        let mut weights = self.weights();
+        if !plugs {
+            weights[3] = 0.0;
+        }
+        if !reflector {
+            weights[4] = 0.0;
+        }
+
+        let mut x = rng.gen::<f64>() * weights.iter().sum::<f64>();
+        for (i, &w) in weights.iter().enumerate() {
+            if x < w {
+                return i;
+            }
+            x -= w;
+        }
+        // rounding past the end
+        weights.iter().rposition(|&w| w > 0.0).unwrap_or(2)
+    }
+}
+
+impl Default for MutationWeights {
+    fn default() -> Self {
+        Self {
+            rotors: 1.0,
+            rings: 1.0,
+            positions: 1.0,
+            plugboard: 1.0,
+            reflector: 1.0,
+            shift: 1.0,
+        }
+    }
+}
+
+impl fmt::Display for MutationWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let weights = Self::NAMES
+            .iter()
+            .zip(self.weights())
+            .map(|(name, w)| format!("{name}:{w}"))
+            .collect::<Vec<_>>();
+        write!(f, "{}", weights.join(","))
+    }
+}
+
+// <part>:<weight>,... of rotors, rings, positions, plugboard, reflector and shift, the parts left
+// out are never mutated, e.g. rotors:10,rings:10,positions:40,plugboard:40
+impl FromStr for MutationWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = [0.0; 6];
+        for part in s.split(',') {
+            let invalid = || format!("invalid mutation weight {part}, expected e.g. positions:40");
+            let (name, weight) = part.split_once(':').ok_or_else(invalid)?;
+            let i = Self::NAMES
+                .iter()
+                .position(|n| *n == name.trim())
+                .ok_or_else(|| {
+                    format!(
+                        "unknown part of the key {name}, expected one of {}",
+                        Self::NAMES.join(", ")
+                    )
+                })?;
+            weights[i] = weight.trim().parse().map_err(|_| invalid())?;
+        }
+
+        let [rotors, rings, positions, plugboard, reflector, shift] = weights;
+        Ok(Self {
+            rotors,
+            rings,
+            positions,
+            plugboard,
+            reflector,
+            shift,
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct FitnessCalc {
-    pub ciphertext: Arc<String>,
-    pub max_value: usize,
-    pub cache: Cache<Settings, usize>,
+pub struct FitnessOptions {
+    pub cache_size: usize,
+    pub cache_backend: CacheBackend,
+    // rotor configurations whose scramblers are kept for keys with other plugboards,
+    // about 30 bytes per ciphertext letter each; 0 decrypts every key on its own, as
+    // filling a scrambler only pays off once the rotors of the population converge
+    pub scrambler_cache_size: usize,
+    // the metric of the cipher when None, e.g. the index of coincidence for Enigma
+    pub metric: Option<Metric>,
+    // count the distinct keys evaluated, see Coverage
+    pub coverage: bool,
 }
 
-impl FitnessFunction<Settings, usize> for FitnessCalc {
-    fn fitness_of(&self, s: &Settings) -> usize {
-        if let Some(norm_metric) = self.cache.get(s) {
-            return norm_metric;
+#[derive(Debug, Clone)]
+pub struct TerminationOptions {
+    pub generation_limit: u64,
+    pub time_limit: Duration,
+    // stop as soon as the best solution reaches it, e.g. fitness of the known plaintext
+    pub target_fitness: Option<Fitness>,
+    // CTF mode: stop as soon as the best decryption contains `dictionary_hits` words of the
+    // dictionary or matches the pattern, checked once per generation
+    pub dictionary: Dictionary,
+    pub dictionary_hits: usize,
+    pub pattern: Option<Regex>,
+    // hard stop for callers with a latency bound: the generation running when it passes skips
+    // the evaluations left, which score 0, and is the last
+    pub deadline: Option<Instant>,
+    // replaces the generation, time and fitness limits when set, see termination
+    pub criterion: Option<Criterion>,
+}
+
+impl TerminationOptions {
+    // the generation, time and fitness limits as a criterion, to add conditions to
+    pub fn limits(&self) -> Criterion {
+        let time_limit = self.time_limit.to_std().unwrap_or_default();
+        termination::generation_limit(self.generation_limit)
+            .or(termination::time_limit(time_limit))
+            .or(termination::fitness_limit(
+                self.target_fitness.unwrap_or(Fitness(1.0)),
+            ))
+    }
+}
+
+impl EngineOptions {
+    // number of threads the evaluation pool runs
+    pub fn worker_threads(&self) -> usize {
+        match (self.threads, &self.pin_cores) {
+            (0, Some(Cores(cores))) => cores.len(),
+            (0, None) => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            (threads, _) => threads,
+        }
+    }
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        Self {
+            population_size: 1_500_000,
+            num_individuals_per_parents: 2,
+            selection_ratio: 0.5,
+            selection: SelectionStrategy::Truncation,
+            selection_adaptation: None,
+            mutation_rate: 0.05,
+            mutation_schedule: MutationSchedule::Constant,
+            mutation_weights: MutationWeights::default(),
+            crossover: CrossoverMode::default(),
+            rotor_crossover: RotorCrossover::default(),
+            plugboard_crossover: PlugboardCrossover::default(),
+            reinsertion_ratio: 0.7,
+            elitism: None,
+            max_plugs: 0,
+            encoding: Encoding::Settings,
+            reflector: ReflectorSearch::default(),
+            algorithm: Algorithm::Ga,
+            seed: None,
+            rng: RngAlgorithm::default(),
+            population_schedule: PopulationSchedule::Constant,
+            tempering: None,
+            threads: 0,
+            pin_cores: None,
+            herivel_spread: None,
+            fixed_rings: false,
+            sample_size: 0,
+            top_size: 0,
+            genealogy: 0,
+            explored_memory: 0,
+            pause: Pause::default(),
+        }
+    }
+}
+
+impl Default for FitnessOptions {
+    fn default() -> Self {
+        Self {
+            cache_size: 3_000_000,
+            cache_backend: CacheBackend::default(),
+            scrambler_cache_size: 0,
+            metric: None,
+            coverage: false,
+        }
+    }
+}
+
+impl Default for TerminationOptions {
+    fn default() -> Self {
+        Self {
+            generation_limit: 300,
+            time_limit: Duration::minutes(15),
+            target_fitness: None,
+            dictionary: Dictionary::default(),
+            dictionary_hits: 5,
+            pattern: None,
+            deadline: None,
+            criterion: None,
+        }
+    }
+}
+
+// rough per-entry bookkeeping of moka (hash, timestamps, frequency sketch, deque nodes)
+const CACHE_ENTRY_OVERHEAD_BYTES: usize = 96;
+// of a dashmap entry (control byte, spare buckets at 7/8 load)
+const SHARDED_CACHE_ENTRY_OVERHEAD_BYTES: usize = 16;
+
+/// A number of bytes with an optional binary unit, e.g. 512M, 1.5G or 8GiB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteSize(pub usize);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_ascii_uppercase();
+        let number = upper
+            .strip_suffix("IB")
+            .or_else(|| upper.strip_suffix('B'))
+            .unwrap_or(&upper);
+        let (number, unit) = match number.char_indices().last() {
+            Some((i, c)) if c.is_ascii_alphabetic() => (&number[..i], c),
+            _ => (number, ' '),
+        };
+        let shift = match unit {
+            ' ' => 0,
+            'K' => 10,
+            'M' => 20,
+            'G' => 30,
+            'T' => 40,
+            _ => return Err(format!("unknown unit in {s}, expected K, M, G or T")),
+        };
+
+        let bytes = match number.trim().parse::<f64>() {
+            Ok(n) if n >= 0.0 && n.is_finite() => n * (1u64 << shift) as f64,
+            _ => return Err(format!("invalid size {s}, expected e.g. 512M or 8G")),
+        };
+        // 2^BITS, usize::MAX rounds up to it as a float
+        match bytes < 2f64.powi(usize::BITS as i32) {
+            true => Ok(ByteSize(bytes as usize)),
+            false => Err(format!("size {s} is more than this platform addresses")),
+        }
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the largest unit the size is a whole number of, in u64 as 1T overflows a 32-bit usize
+        let (bytes, units) = (self.0 as u64, [(40, "T"), (30, "G"), (20, "M"), (10, "K")]);
+        match units
+            .iter()
+            .find(|(shift, _)| bytes > 0 && bytes.is_multiple_of(1u64 << shift))
+        {
+            Some((shift, unit)) => write!(f, "{}{}", bytes >> shift, unit),
+            None => write!(f, "{}B", self.0),
+        }
+    }
+}
+
+impl Options {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let engine = &self.engine;
+        ensure!(
+            engine.population_size >= 2,
+            "population_size must be at least 2"
+        );
+        ensure!(
+            engine.num_individuals_per_parents >= 2
+                && engine.num_individuals_per_parents <= engine.population_size,
+            "num_individuals_per_parents must be in 2..=population_size"
+        );
+        match &engine.population_schedule {
+            PopulationSchedule::Constant => {}
+            PopulationSchedule::Stages(stages) => ensure!(
+                stages.windows(2).all(|w| w[0].0 < w[1].0),
+                "population schedule stages must be in generation order"
+            ),
+            PopulationSchedule::Decay { by, .. } => ensure!(
+                *by > 1,
+                "population schedule must decay by generation 2 or later"
+            ),
+        }
+        ensure!(
+            engine
+                .population_schedule
+                .sizes()
+                .iter()
+                .all(|&size| size >= engine.num_individuals_per_parents.max(2)),
+            "scheduled population sizes must be at least num_individuals_per_parents"
+        );
+        ensure!(
+            engine.population_schedule == PopulationSchedule::Constant
+                || engine.algorithm == Algorithm::Ga,
+            "a population schedule requires the ga engine"
+        );
+        if let Some(tempering) = &engine.tempering {
+            ensure!(
+                engine.algorithm == Algorithm::Ga,
+                "tempering requires the ga engine"
+            );
+            tempering.validate(self)?;
+        }
+        ensure!(
+            engine.explored_memory == 0 || engine.algorithm == Algorithm::Ga,
+            "a memory of explored keys requires the ga engine"
+        );
+        ensure!(
+            engine.genealogy == 0
+                || engine.algorithm == Algorithm::Ga && engine.tempering.is_none(),
+            "genealogy requires the ga engine without tempering"
+        );
+        ensure!(
+            engine.selection_ratio > 0.0 && engine.selection_ratio <= 1.0,
+            "selection_ratio must be in (0, 1]"
+        );
+        if let Some(SelectionAdaptation { min, max }) = engine.selection_adaptation {
+            ensure!(
+                min > 0.0 && min <= max && max <= 1.0,
+                "selection adaptation bounds must be in (0, 1] and in order"
+            );
+            ensure!(
+                engine.algorithm == Algorithm::Ga && engine.tempering.is_none(),
+                "selection adaptation requires the ga engine without tempering"
+            );
+            ensure!(
+                engine.selection == SelectionStrategy::Truncation,
+                "selection adaptation requires truncation selection"
+            );
+        }
+        ensure!(
+            engine.selection == SelectionStrategy::Truncation || engine.algorithm == Algorithm::Ga,
+            "{} selection requires the ga engine",
+            engine.selection
+        );
+        ensure!(
+            (0.0..=1.0).contains(&engine.mutation_rate),
+            "mutation_rate must be in [0, 1]"
+        );
+        match engine.mutation_schedule {
+            MutationSchedule::Constant => {}
+            MutationSchedule::Decay { to, by } => ensure!(
+                to > 0.0 && to <= 1.0 && by > 1,
+                "mutation schedule must decay to a strength in (0, 1] by generation 2 or later"
+            ),
+            MutationSchedule::Fitness { to } => ensure!(
+                to > 0.0 && to <= 1.0 && self.termination.target_fitness.is_some(),
+                "mutation schedule by fitness needs a strength in (0, 1] and a target fitness"
+            ),
+        }
+        ensure!(
+            engine.mutation_schedule == MutationSchedule::Constant
+                || engine.algorithm == Algorithm::Ga,
+            "a mutation schedule requires the ga engine"
+        );
+        let weights = engine.mutation_weights;
+        ensure!(
+            weights.weights().iter().all(|w| w.is_finite() && *w >= 0.0),
+            "mutation weights must be finite and not negative"
+        );
+        ensure!(
+            weights.rotors + weights.rings + weights.positions + weights.shift > 0.0
+                || weights.plugboard > 0.0 && engine.max_plugs > 0
+                || weights.reflector > 0.0 && engine.reflector.is_searched(),
+            "mutation weights must give a searched part of the key a chance"
+        );
+        ensure!(
+            (0.0..=1.0).contains(&engine.reinsertion_ratio),
+            "reinsertion_ratio must be in [0, 1]"
+        );
+        ensure!(
+            engine.elitism.is_none() || engine.algorithm == Algorithm::Ga,
+            "an elitism count requires the ga engine"
+        );
+        ensure!(
+            engine
+                .elitism
+                .is_none_or(|elites| elites < engine.population_size),
+            "elitism must leave room for offspring in the population"
+        );
+        ensure!(
+            engine.max_plugs <= MAX_PLUGS_NUM,
+            "max_plugs must be at most {}",
+            MAX_PLUGS_NUM
+        );
+        if let ReflectorSearch::Known(reflector) = &engine.reflector {
+            reflector.validate()?;
+        }
+        ensure!(
+            engine.encoding == Encoding::Settings
+                || engine.reflector == ReflectorSearch::Known(Reflector::B),
+            "the dna encoding only supports the known reflector B"
+        );
+        if let Some(spread) = engine.herivel_spread {
+            ensure!(
+                spread < LETTERS_NUM / 2,
+                "herivel_spread must be under {}, which allows any position",
+                LETTERS_NUM / 2
+            );
+            ensure!(
+                engine.encoding == Encoding::Settings && engine.algorithm != Algorithm::Es,
+                "the herivel tip needs the settings encoding and the ga, pso or genevo engine"
+            );
         }
+        if engine.fixed_rings {
+            ensure!(
+                engine.encoding == Encoding::Settings
+                    && matches!(engine.algorithm, Algorithm::Ga | Algorithm::Genevo),
+                "fixed rings need the settings encoding and the ga or genevo engine"
+            );
+            ensure!(
+                engine.herivel_spread.is_none(),
+                "the herivel tip places the positions near the rings, which fixed rings don't search"
+            );
+        }
+        ensure!(
+            engine.algorithm != Algorithm::Genevo || cfg!(feature = "genevo"),
+            "the genevo engine requires the genevo feature"
+        );
+        ensure!(
+            self.fitness.cache_backend != CacheBackend::Moka || cfg!(feature = "cache"),
+            "the moka cache backend requires the cache feature"
+        );
+        if engine.algorithm == Algorithm::Pso {
+            ensure!(
+                engine.encoding == Encoding::Settings,
+                "the pso engine only supports the settings encoding"
+            );
+            ensure!(
+                engine.reflector != ReflectorSearch::Rewirable,
+                "the pso engine needs a known reflector or the standard ones"
+            );
+        }
+        if engine.algorithm == Algorithm::Es {
+            ensure!(
+                engine.encoding == Encoding::Settings,
+                "the es engine only supports the settings encoding"
+            );
+            ensure!(
+                engine.reflector != ReflectorSearch::Rewirable,
+                "the es engine needs a known reflector or the standard ones"
+            );
+            ensure!(
+                engine.max_plugs == 0,
+                "the es engine searches the rotors without plugs, recover the plugboard with --climb"
+            );
+        }
+
+        let termination = &self.termination;
+        ensure!(
+            termination.generation_limit > 0,
+            "generation_limit must be positive"
+        );
+        ensure!(
+            termination.time_limit > Duration::zero(),
+            "time_limit must be positive"
+        );
+        ensure!(
+            termination.dictionary.is_empty() || termination.dictionary_hits > 0,
+            "dictionary_hits must be positive"
+        );
+        Ok(())
+    }
+
+    // current population, offspring and the evaluated copy are alive at the same time
+    pub fn estimated_population_bytes(&self) -> usize {
+        let engine = &self.engine;
+        3 * engine.population_schedule.max_size(engine.population_size)
+            * (self.estimated_genome_bytes() + mem::size_of::<Fitness>())
+    }
 
-        let machine = Machine::new(s).expect("Wrong machine settings");
-        let plaintext = machine.decrypt(&self.ciphertext);
-        let norm_metric = index_of_coincidence_norm(&plaintext, self.max_value);
+    fn estimated_genome_bytes(&self) -> usize {
+        let reflector_pairs = match self.engine.reflector {
+            ReflectorSearch::Known(Reflector::D(_)) | ReflectorSearch::Rewirable => {
+                REFLECTOR_PAIRS_NUM
+            }
+            _ => 0,
+        };
+        mem::size_of::<Settings>()
+            + (self.engine.max_plugs + reflector_pairs) * mem::size_of::<(u8, u8)>()
+    }
 
-        self.cache.insert(s.clone(), norm_metric);
-        norm_metric
+    pub fn estimated_cache_bytes(&self) -> usize {
+        self.fitness.cache_size * self.estimated_cache_entry_bytes()
     }
 
-    fn average(&self, fitness_values: &[usize]) -> usize {
-        fitness_values.iter().sum::<usize>() / fitness_values.len()
+    fn estimated_cache_entry_bytes(&self) -> usize {
+        let overhead = match self.fitness.cache_backend {
+            CacheBackend::Moka => CACHE_ENTRY_OVERHEAD_BYTES,
+            CacheBackend::Sharded => SHARDED_CACHE_ENTRY_OVERHEAD_BYTES,
+            CacheBackend::None => return 0,
+        };
+        self.estimated_genome_bytes() + mem::size_of::<Fitness>() + overhead
     }
 
-    fn highest_possible_fitness(&self) -> usize {
-        self.max_value
+    // scales the population and the fitness cache down by the same factor until their
+    // estimates fit in the budget, false if they already do
+    pub fn fit_memory(&mut self, budget: ByteSize) -> anyhow::Result<bool> {
+        let total = self.estimated_population_bytes() + self.estimated_cache_bytes();
+        if total <= budget.0 {
+            return Ok(false);
+        }
+
+        let factor = budget.0 as f64 / total as f64;
+        let per_individual = self.estimated_population_bytes()
+            / self
+                .engine
+                .population_schedule
+                .max_size(self.engine.population_size);
+        let engine = &mut self.engine;
+        let min_size = engine.num_individuals_per_parents.max(2);
+        let scaled = |size: usize| (size as f64 * factor) as usize;
+        ensure!(
+            scaled(engine.population_size) >= min_size
+                && engine
+                    .population_schedule
+                    .sizes()
+                    .iter()
+                    .all(|&size| scaled(size) >= min_size),
+            "a memory budget of {} is too small for a population, {} per individual",
+            budget,
+            ByteSize(per_individual)
+        );
+        engine.population_size = scaled(engine.population_size);
+        for size in engine.population_schedule.sizes_mut() {
+            *size = scaled(*size);
+        }
+        self.fitness.cache_size = (self.fitness.cache_size as f64 * factor) as usize;
+        Ok(true)
     }
+}
 
-    fn lowest_possible_fitness(&self) -> usize {
-        0
+// total order over f64 so that the exact metric can be ranked without scaling and rounding
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fitness(pub f64);
+
+impl PartialEq for Fitness {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
     }
 }
 
-pub fn index_of_coincidence_norm(text: &str, max_value: usize) -> usize {
-    let metric = index_of_coincidence(&text);
-    (metric * (max_value as f64)).round() as usize
+impl Eq for Fitness {}
+
+impl PartialOrd for Fitness {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-fn index_of_coincidence(text: &str) -> f64 {
-    debug_assert!(
-        text.chars()
-            .all(|c| c.is_ascii_uppercase() || c.is_whitespace()),
-        "only A..Z and whitespace are supported"
-    );
+impl Ord for Fitness {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl fmt::Display for Fitness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Ciphertext shared by the clones of a fitness function, so that all of them score keys on
+/// the longer text when an intercept grows during the search.
+#[derive(Clone)]
+pub struct Ciphertext(Arc<RwLock<(Arc<String>, u64)>>);
+
+impl Ciphertext {
+    pub fn new(text: String) -> Self {
+        Self(Arc::new(RwLock::new((Arc::new(text), 0))))
+    }
+
+    pub fn get(&self) -> Arc<String> {
+        self.get_revision().0
+    }
+
+    // counts the replacements
+    pub fn revision(&self) -> u64 {
+        self.get_revision().1
+    }
+
+    // both at once, so a score is never cached under the revision of another text
+    pub fn get_revision(&self) -> (Arc<String>, u64) {
+        self.0.read().expect("not poisoned").clone()
+    }
+
+    pub fn replace(&self, text: String) {
+        let mut current = self.0.write().expect("not poisoned");
+        *current = (Arc::new(text), current.1 + 1);
+    }
+}
+
+impl fmt::Debug for Ciphertext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), f)
+    }
+}
+
+/// Genomes no key could be built of, scored 0 rather than aborting the run: how many and the
+/// first with its error, for the caller to report. Clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct InvalidGenomes(Arc<Mutex<Option<(u64, String)>>>);
+
+impl InvalidGenomes {
+    pub fn record(&self, key: &impl fmt::Debug, err: &anyhow::Error) {
+        let mut invalid = self.0.lock().expect("not poisoned");
+        match invalid.as_mut() {
+            Some((count, _)) => *count += 1,
+            None => *invalid = Some((1, format!("{:?}: {}", key, err))),
+        }
+    }
+
+    pub fn get(&self) -> Option<(u64, String)> {
+        self.0.lock().expect("not poisoned").clone()
+    }
+}
+
+// scores a key by the metric of its cipher on the decrypted ciphertext
+pub struct CipherFitness<C: Cipher> {
+    pub ciphertext: Ciphertext,
+    pub cache: FitnessCache<C::Key>,
+    pub scramblers: ScramblerCache,
+    // overrides the metric of the cipher
+    pub metric: Option<Metric>,
+    pub coverage: Coverage,
+    pub invalid: InvalidGenomes,
+}
+
+pub type FitnessCalc = CipherFitness<Machine>;
+
+impl<C: Cipher> Clone for CipherFitness<C> {
+    fn clone(&self) -> Self {
+        Self {
+            ciphertext: self.ciphertext.clone(),
+            cache: self.cache.clone(),
+            scramblers: self.scramblers.clone(),
+            metric: self.metric.clone(),
+            coverage: self.coverage.clone(),
+            invalid: self.invalid.clone(),
+        }
+    }
+}
+
+impl<C: Cipher> fmt::Debug for CipherFitness<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CipherFitness")
+            .field("model", &C::MODEL_NAME)
+            .field("ciphertext", &self.ciphertext)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<C: Cipher> FitnessFunction<C::Key> for CipherFitness<C> {
+    // equivalent keys share a cache entry
+    fn fitness_of(&self, key: &C::Key) -> Fitness {
+        let canonical = C::canonical(key);
+        if self.coverage.is_enabled() {
+            self.coverage.insert([C::pack(&canonical)]);
+        }
+        let (ciphertext, revision) = self.ciphertext.get_revision();
+        if let Some(fitness) = self.cache.get(&canonical, revision) {
+            return fitness;
+        }
+
+        let fitness = self.score(key, C::decrypt_shared(key, &ciphertext, &self.scramblers));
+        self.cache.insert(canonical, fitness, revision);
+        fitness
+    }
+
+    fn fitness_of_batch(&self, keys: &[C::Key]) -> Vec<Fitness> {
+        // the scramblers serve plugged keys one at a time
+        if self.scramblers.is_enabled() {
+            return keys.iter().map(|key| self.fitness_of(key)).collect();
+        }
+
+        let (ciphertext, revision) = self.ciphertext.get_revision();
+        let canonical = keys.iter().map(C::canonical).collect::<Vec<_>>();
+        if self.coverage.is_enabled() {
+            self.coverage.insert(canonical.iter().map(C::pack));
+        }
+        let cached = canonical
+            .iter()
+            .map(|key| self.cache.get(key, revision))
+            .collect::<Vec<_>>();
+        let missing = keys
+            .iter()
+            .zip(&cached)
+            .filter(|(_, fitness)| fitness.is_none())
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        let mut plaintexts = C::decrypt_batch(&missing, &ciphertext).into_iter();
+
+        keys.iter()
+            .zip(canonical)
+            .zip(cached)
+            .map(|((key, canonical), cached)| {
+                cached.unwrap_or_else(|| {
+                    let plaintext = plaintexts.next().expect("a decryption for every miss");
+                    let fitness = self.score(key, plaintext);
+                    self.cache.insert(canonical, fitness, revision);
+                    fitness
+                })
+            })
+            .collect()
+    }
+
+    fn revision(&self) -> u64 {
+        self.ciphertext.revision()
+    }
+
+    fn fingerprint(&self, key: &C::Key) -> Option<u128> {
+        Some(C::pack(&C::canonical(key)))
+    }
+}
 
-    let mut hist = [0; 26];
-    let mut n = 0;
+impl<C: Cipher> CipherFitness<C> {
+    /// Scores keys on another ciphertext from now on, in every clone. The scores cached for the
+    /// old one are left to be scored again when a key comes up again, or to be evicted.
+    pub fn replace_ciphertext(&self, ciphertext: String) {
+        self.ciphertext.replace(ciphertext);
+        // a scrambler covers as many letters as the text it was built for
+        self.scramblers.clear();
+    }
 
-    text.chars().for_each(|c| {
-        if c.is_ascii_uppercase() {
-            let idx = c as usize - 'A' as usize;
-            hist[idx] += 1;
-            n += 1;
+    fn score(&self, key: &C::Key, plaintext: anyhow::Result<String>) -> Fitness {
+        // an invalid genome must not abort the whole run, it just never survives selection
+        match plaintext {
+            Ok(plaintext) => Fitness(match &self.metric {
+                Some(metric) => metric.score(&plaintext),
+                None => C::score(&plaintext),
+            }),
+            Err(err) => {
+                self.invalid.record(key, &err);
+                Fitness(0.0)
+            }
         }
-    });
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FitnessStats {
+    pub mean: f64,
+    pub median: Fitness,
+    pub std_dev: f64,
+}
 
-    if n <= 1 {
-        return 0.0;
+pub fn fitness_stats(fitness_values: &[Fitness]) -> FitnessStats {
+    if fitness_values.is_empty() {
+        return FitnessStats {
+            mean: 0.0,
+            median: Fitness(0.0),
+            std_dev: 0.0,
+        };
     }
 
-    let numerator = hist
-        .into_iter()
-        .filter(|&freq| freq > 0)
-        .map(|freq| freq * (freq - 1))
-        .sum::<usize>();
+    // Welford's streaming mean and variance, doesn't lose precision on huge populations
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+    for (i, &Fitness(v)) in fitness_values.iter().enumerate() {
+        let delta = v - mean;
+        mean += delta / (i + 1) as f64;
+        m2 += delta * (v - mean);
+    }
 
-    let denominator = n * (n - 1);
+    let mut values = fitness_values.to_vec();
+    let mid = values.len() / 2;
+    let median = *values.select_nth_unstable(mid).1;
 
-    numerator as f64 / denominator as f64
+    FitnessStats {
+        mean,
+        median,
+        std_dev: (m2 / fitness_values.len() as f64).sqrt(),
+    }
 }
 
-pub struct SettingsBuilder;
+pub struct SettingsBuilder {
+    pub max_plugs: usize,
+    pub reflector: ReflectorSearch,
+    pub herivel_spread: Option<u8>,
+    // see EngineOptions::fixed_rings
+    pub fixed_rings: bool,
+}
 
 impl GenomeBuilder<Settings> for SettingsBuilder {
-    fn build_genome<R>(&self, _: usize, rng: &mut R) -> Settings
-    where
-        R: Rng + Sized,
-    {
+    fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> Settings {
+        let rotors = gen_triple_unique(1, MAX_ROTOR_NUM, rng);
+        let mut ring_settings = gen_triple(1, MAX_RING_SETTINGS_NUM, rng);
+        if self.fixed_rings {
+            (ring_settings.0, ring_settings.2) = (1, 1);
+        }
+        let rotor_positions = match self.herivel_spread {
+            Some(spread) => gen_near(ring_settings, spread, rng),
+            None => gen_triple(1, MAX_ROTOR_POSITIONS_NUM, rng),
+        };
+
         Settings {
-            rotors: gen_triple_unique(1, MAX_ROTOR_NUM, rng),
-            ring_settings: gen_triple(1, MAX_RING_SETTINGS_NUM, rng),
-            rotor_positions: gen_triple(1, MAX_ROTOR_POSITIONS_NUM, rng),
+            rotors,
+            ring_settings,
+            rotor_positions,
+            plugboard: gen_plugboard(self.max_plugs, rng),
+            reflector: gen_reflector(&self.reflector, rng),
         }
     }
 }
 
+// Herivel's tip: operators set the rings with the rotors in the machine, ring letter on top,
+// and turned the rotors only a little for the first message, so its positions lie within
+// `spread` letters of the ring settings either way
+fn gen_near<R: Rng>(ring_settings: (u8, u8, u8), spread: u8, rng: &mut R) -> (u8, u8, u8) {
+    let mut near = |ring: u8| {
+        let spread = spread as i16;
+        turn_letter(ring, rng.gen_range(-spread..=spread))
+    };
+    let (left, middle, right) = ring_settings;
+    (near(left), near(middle), near(right))
+}
+
+fn gen_reflector<R: Rng>(search: &ReflectorSearch, rng: &mut R) -> Reflector {
+    match search {
+        ReflectorSearch::Known(reflector) => reflector.clone(),
+        ReflectorSearch::Standard => [Reflector::B, Reflector::C].choose(rng).unwrap().clone(),
+        ReflectorSearch::Rewirable => Reflector::D(complete_wiring(Vec::new(), rng)),
+    }
+}
+
+// pairs the letters left free by the given pairs at random
+fn complete_wiring<R: Rng>(mut pairs: Vec<(u8, u8)>, rng: &mut R) -> Vec<(u8, u8)> {
+    let mut free = (1..=LETTERS_NUM)
+        .filter(|&l| !is_plug_conflicting(&pairs, (l, l)))
+        .collect::<Vec<_>>();
+    free.shuffle(rng);
+
+    pairs.extend(
+        free.chunks_exact(2)
+            .map(|p| (p[0].min(p[1]), p[0].max(p[1]))),
+    );
+    pairs
+}
+
+fn gen_plugboard<R: Rng>(max_plugs: usize, rng: &mut R) -> Vec<(u8, u8)> {
+    let num_plugs = rng.gen_range(0..=max_plugs.min(MAX_PLUGS_NUM));
+    let mut plugboard = Vec::with_capacity(num_plugs);
+
+    for _ in 0..num_plugs {
+        if !add_plug(&mut plugboard, rng) {
+            break;
+        }
+    }
+
+    plugboard
+}
+
+// picks two distinct free letters, which is a uniformly random legal pair, false if none is left
+pub(crate) fn add_plug<R: Rng>(plugboard: &mut Vec<(u8, u8)>, rng: &mut R) -> bool {
+    let free = (1..=LETTERS_NUM)
+        .filter(|&l| !is_plug_conflicting(plugboard, (l, l)))
+        .choose_multiple(rng, 2);
+
+    match free[..] {
+        [a, b] => {
+            plugboard.push((a.min(b), a.max(b)));
+            true
+        }
+        _ => false,
+    }
+}
+
 fn gen_triple_unique<R: Rng>(from: u8, to: u8, rng: &mut R) -> (u8, u8, u8) {
     let r = (from..=to).choose_multiple(rng, 3);
     (r[0], r[1], r[2])
@@ -130,106 +1321,436 @@ fn gen_triple<R: Rng>(from: u8, to: u8, rng: &mut R) -> (u8, u8, u8) {
     (r[0], r[1], r[2])
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct SettingsCrossover;
+/// How the rotors, rings and positions of the parents are mixed: every gene on its own, or the
+/// rotor of a slot with its ring setting and start position, as together they decide what the
+/// slot does to a letter and a good rotor is worth nothing at the wrong ring or position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CrossoverMode {
+    #[default]
+    Genes,
+    Slots,
+}
+
+impl FromStr for CrossoverMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "genes" => Ok(CrossoverMode::Genes),
+            "slots" => Ok(CrossoverMode::Slots),
+            _ => Err(format!("unknown crossover {s}, expected genes or slots")),
+        }
+    }
+}
+
+impl fmt::Display for CrossoverMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrossoverMode::Genes => write!(f, "genes"),
+            CrossoverMode::Slots => write!(f, "slots"),
+        }
+    }
+}
+
+/// How the rotor order of a child is bred when genes are crossed: every slot from any parent,
+/// drawn again until the rotors differ, or by partially mapped (PMX) or order (OX) crossover of
+/// two parents, which treat the order as a partial permutation of the six rotors and always
+/// give a valid one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotorCrossover {
+    #[default]
+    Retry,
+    Pmx,
+    Ox,
+}
+
+impl FromStr for RotorCrossover {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "retry" => Ok(RotorCrossover::Retry),
+            "pmx" => Ok(RotorCrossover::Pmx),
+            "ox" => Ok(RotorCrossover::Ox),
+            _ => Err(format!(
+                "unknown rotor crossover {s}, expected retry, pmx or ox"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RotorCrossover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RotorCrossover::Retry => write!(f, "retry"),
+            RotorCrossover::Pmx => write!(f, "pmx"),
+            RotorCrossover::Ox => write!(f, "ox"),
+        }
+    }
+}
+
+/// How the plugboard of a child is bred: the pairs at the same index of the parents, which loses
+/// pairs whenever the parents list them in another order; the union of the parents' pairs pruned
+/// at random to the size of one of them; the pairs all parents share, filled with random pairs to
+/// that size; or the board of one parent with a pair of another exchanged into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlugboardCrossover {
+    #[default]
+    Positional,
+    Union,
+    Intersection,
+    Exchange,
+}
+
+impl FromStr for PlugboardCrossover {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "positional" => Ok(PlugboardCrossover::Positional),
+            "union" => Ok(PlugboardCrossover::Union),
+            "intersection" => Ok(PlugboardCrossover::Intersection),
+            "exchange" => Ok(PlugboardCrossover::Exchange),
+            _ => Err(format!(
+                "unknown plugboard crossover {s}, expected positional, union, intersection or exchange"
+            )),
+        }
+    }
+}
 
-impl GeneticOperator for SettingsCrossover {
-    fn name() -> String {
-        "Settings-Crossover".to_string()
+impl fmt::Display for PlugboardCrossover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlugboardCrossover::Positional => write!(f, "positional"),
+            PlugboardCrossover::Union => write!(f, "union"),
+            PlugboardCrossover::Intersection => write!(f, "intersection"),
+            PlugboardCrossover::Exchange => write!(f, "exchange"),
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SettingsCrossover {
+    pub mode: CrossoverMode,
+    pub rotors: RotorCrossover,
+    pub plugboard: PlugboardCrossover,
+}
+
 impl CrossoverOp<Settings> for SettingsCrossover {
-    fn crossover<R>(&self, parents: Parents<Settings>, rng: &mut R) -> Children<Settings>
-    where
-        R: Rng + Sized,
-    {
-        debug_assert_eq!(parents.len(), 2, "crossover should use 2 parents");
-        vec![cross_settings(&parents[0], &parents[1], rng)]
+    fn crossover<R: Rng>(&self, parents: Vec<Settings>, rng: &mut R) -> Vec<Settings> {
+        debug_assert!(
+            parents.len() >= 2,
+            "crossover should use at least 2 parents"
+        );
+        vec![cross_settings(&parents, self, rng)]
     }
 }
 
-fn cross_settings<R: Rng>(sett1: &Settings, sett2: &Settings, rng: &mut R) -> Settings {
-    let bernoulli = distributions::Bernoulli::new(0.5).unwrap();
+// every gene is inherited from a parent chosen uniformly at random, for 2 parents it's a coin flip,
+// or every slot with CrossoverMode::Slots
+fn cross_settings<R: Rng>(parents: &[Settings], op: &SettingsCrossover, rng: &mut R) -> Settings {
+    let (rotors, ring_settings, rotor_positions) = match op.mode {
+        CrossoverMode::Genes => {
+            let rotors = parents.iter().map(|s| s.rotors).collect::<Vec<_>>();
+            let ring_settings = parents.iter().map(|s| s.ring_settings).collect::<Vec<_>>();
+            let rotor_positions = parents
+                .iter()
+                .map(|s| s.rotor_positions)
+                .collect::<Vec<_>>();
+            let rotors = match op.rotors {
+                RotorCrossover::Retry => cross_rotors(&rotors, rng),
+                RotorCrossover::Pmx | RotorCrossover::Ox => {
+                    let (i, j) = two_parents(rotors.len(), rng);
+                    let order = |(a, b, c): (u8, u8, u8)| [a, b, c];
+                    let [a, b, c] =
+                        cross_orders(order(rotors[i]), order(rotors[j]), op.rotors, rng);
+                    (a, b, c)
+                }
+            };
+            (
+                rotors,
+                cross_circular(&ring_settings, rng),
+                cross_circular(&rotor_positions, rng),
+            )
+        }
+        CrossoverMode::Slots => {
+            let [left, middle, right] = cross_slots(parents, rng);
+            (
+                (left.rotors.0, middle.rotors.1, right.rotors.2),
+                (
+                    left.ring_settings.0,
+                    middle.ring_settings.1,
+                    right.ring_settings.2,
+                ),
+                (
+                    left.rotor_positions.0,
+                    middle.rotor_positions.1,
+                    right.rotor_positions.2,
+                ),
+            )
+        }
+    };
+    let plugboards = parents
+        .iter()
+        .map(|s| s.plugboard.as_slice())
+        .collect::<Vec<_>>();
+    let reflectors = parents.iter().map(|s| &s.reflector).collect::<Vec<_>>();
 
     Settings {
-        rotors: cross_rotors(sett1.rotors, sett2.rotors, bernoulli, rng),
-        ring_settings: cross_positionally(sett1.ring_settings, sett2.ring_settings, bernoulli, rng),
-        rotor_positions: cross_positionally(
-            sett1.rotor_positions,
-            sett2.rotor_positions,
-            bernoulli,
-            rng,
-        ),
+        rotors,
+        ring_settings,
+        rotor_positions,
+        plugboard: cross_plugboards_with(&plugboards, op.plugboard, rng),
+        reflector: cross_reflectors(&reflectors, rng),
     }
 }
 
-fn cross_rotors<R: Rng>(
-    rotors1: (u8, u8, u8),
-    rotors2: (u8, u8, u8),
-    bernoulli: distributions::Bernoulli,
+// indices of two different parents in random order
+fn two_parents<R: Rng>(len: usize, rng: &mut R) -> (usize, usize) {
+    let i = rng.gen_range(0..len);
+    (i, (i + rng.gen_range(1..len)) % len)
+}
+
+fn cross_plugboards_with<R: Rng>(
+    plugboards: &[&[(u8, u8)]],
+    op: PlugboardCrossover,
     rng: &mut R,
-) -> (u8, u8, u8) {
-    loop {
-        let r = cross_positionally(rotors1, rotors2, bernoulli, rng);
+) -> Vec<(u8, u8)> {
+    let sorted = |&(a, b): &(u8, u8)| (a.min(b), a.max(b));
+    // as many pairs as a parent chosen at random
+    let size = |rng: &mut R| plugboards.choose(rng).unwrap().len();
+
+    match op {
+        PlugboardCrossover::Positional => cross_plugboards(plugboards, rng),
+        PlugboardCrossover::Union => {
+            let mut union = plugboards
+                .iter()
+                .flat_map(|p| p.iter().map(sorted))
+                .collect::<Vec<_>>();
+            union.shuffle(rng);
+
+            let size = size(rng);
+            let mut plugboard = Vec::new();
+            for plug in union {
+                if plugboard.len() < size && !is_plug_conflicting(&plugboard, plug) {
+                    plugboard.push(plug);
+                }
+            }
+            plugboard
+        }
+        PlugboardCrossover::Intersection => {
+            let mut plugboard = plugboards[0]
+                .iter()
+                .map(sorted)
+                .filter(|&plug| {
+                    plugboards[1..]
+                        .iter()
+                        .all(|p| p.iter().any(|q| sorted(q) == plug))
+                })
+                .collect::<Vec<_>>();
+            let size = size(rng);
+            while plugboard.len() < size && add_plug(&mut plugboard, rng) {}
+            plugboard
+        }
+        PlugboardCrossover::Exchange => {
+            let (i, j) = two_parents(plugboards.len(), rng);
+            let mut plugboard = plugboards[i].to_vec();
+
+            if let Some(&plug) = plugboards[j].choose(rng) {
+                let len = plugboard.len();
+                plugboard.retain(|&p| !is_plug_conflicting(&[p], plug));
+                // a pair it doesn't clash with takes the place of one at random
+                if plugboard.len() == len && len > 0 {
+                    plugboard.swap_remove(rng.gen_range(0..len));
+                }
+                plugboard.push(plug);
+            }
+            plugboard
+        }
+    }
+}
+
+// UKW-D wirings are crossed like plugboards and the letters left over are paired at random,
+// any other reflector is inherited as a whole
+fn cross_reflectors<R: Rng>(reflectors: &[&Reflector], rng: &mut R) -> Reflector {
+    let wirings = reflectors
+        .iter()
+        .filter_map(|r| match r {
+            Reflector::D(pairs) => Some(pairs.as_slice()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    match reflectors.choose(rng).unwrap() {
+        Reflector::D(_) => Reflector::D(complete_wiring(cross_plugboards(&wirings, rng), rng)),
+        reflector => (*reflector).clone(),
+    }
+}
+
+fn cross_plugboards<R: Rng>(plugboards: &[&[(u8, u8)]], rng: &mut R) -> Vec<(u8, u8)> {
+    let mut plugboard = Vec::new();
+    let max_len = plugboards.iter().map(|p| p.len()).max().unwrap_or(0);
+
+    for i in 0..max_len {
+        let plug = plugboards.choose(rng).unwrap().get(i);
+
+        if let Some(&plug) = plug {
+            if !is_plug_conflicting(&plugboard, plug) {
+                plugboard.push(plug);
+            }
+        }
+    }
+
+    plugboard
+}
+
+// retries are bounded, parents with rotors in clashing positions fall back to the first parent
+const MAX_RETRIES: usize = 100;
+
+// the parent every slot is inherited from whole, its rotor, ring setting and start position,
+// chosen at random
+fn cross_slots<'a, R: Rng>(parents: &'a [Settings], rng: &mut R) -> [&'a Settings; 3] {
+    for _ in 0..MAX_RETRIES {
+        let [left, middle, right] = std::array::from_fn(|_| parents.choose(rng).unwrap());
+
+        if is_triple_unique((left.rotors.0, middle.rotors.1, right.rotors.2)) {
+            return [left, middle, right];
+        }
+    }
+
+    [&parents[0]; 3]
+}
+
+fn cross_rotors<R: Rng>(rotors: &[(u8, u8, u8)], rng: &mut R) -> (u8, u8, u8) {
+    for _ in 0..MAX_RETRIES {
+        let r = cross_positionally(rotors, rng);
 
         if is_triple_unique(r) {
             return r;
         }
     }
+
+    rotors[0]
+}
+
+// the rotors of a random run of slots are copied from the first parent, the other slots are
+// filled from the second: PMX keeps the rotor of the second parent in a slot unless the run
+// took it, then follows the run's mapping from the first parent's rotor to the second's until
+// it lands on one the run didn't; OX takes the second parent's rotors that the run left over in
+// their order, starting after the run
+fn cross_orders<R: Rng>(
+    first: [u8; 3],
+    second: [u8; 3],
+    op: RotorCrossover,
+    rng: &mut R,
+) -> [u8; 3] {
+    let start = rng.gen_range(0..3);
+    let end = rng.gen_range(start + 1..=3);
+    let run = &first[start..end];
+    let mut child = first;
+
+    match op {
+        RotorCrossover::Ox => {
+            let mut rest = (0..3)
+                .map(|i| second[(end + i) % 3])
+                .filter(|r| !run.contains(r));
+            for i in (end..3).chain(0..start) {
+                child[i] = rest.next().unwrap();
+            }
+        }
+        _ => {
+            for i in (0..start).chain(end..3) {
+                let mut rotor = second[i];
+                while let Some(j) = run.iter().position(|&r| r == rotor) {
+                    rotor = second[start + j];
+                }
+                child[i] = rotor;
+            }
+        }
+    }
+
+    child
 }
 
 fn is_triple_unique(t: (u8, u8, u8)) -> bool {
     t.0 != t.1 && t.1 != t.2 && t.2 != t.0
 }
 
-fn cross_positionally<R: Rng>(
-    x: (u8, u8, u8),
-    y: (u8, u8, u8),
-    bernoulli: distributions::Bernoulli,
-    rng: &mut R,
-) -> (u8, u8, u8) {
+// every letter is taken from the short arc between the letters of two parents drawn at random,
+// both ends included: parents at Z and A give Z or A and never a letter halfway round
+fn cross_circular<R: Rng>(xs: &[(u8, u8, u8)], rng: &mut R) -> (u8, u8, u8) {
+    let mut cross = |letter: fn(&(u8, u8, u8)) -> u8| {
+        let a = letter(xs.choose(rng).unwrap());
+        let b = letter(xs.choose(rng).unwrap());
+        // b - a the short way around, in -13..13
+        let d = (b as i16 - a as i16 + 13).rem_euclid(LETTERS_NUM as i16) - 13;
+        turn_letter(a, rng.gen_range(d.min(0)..=d.max(0)))
+    };
+
+    (cross(|t| t.0), cross(|t| t.1), cross(|t| t.2))
+}
+
+fn cross_positionally<R: Rng>(xs: &[(u8, u8, u8)], rng: &mut R) -> (u8, u8, u8) {
     (
-        if bernoulli.sample(rng) { x.0 } else { y.0 },
-        if bernoulli.sample(rng) { x.1 } else { y.1 },
-        if bernoulli.sample(rng) { x.2 } else { y.2 },
+        xs.choose(rng).unwrap().0,
+        xs.choose(rng).unwrap().1,
+        xs.choose(rng).unwrap().2,
     )
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct SettingsMutator {
     pub mutation_rate: f64,
+    pub max_plugs: usize,
+    // the reflector is only mutated when it's searched for
+    pub search_reflector: bool,
+    // only the middle ring is mutated, see EngineOptions::fixed_rings
+    pub fixed_rings: bool,
+    pub weights: MutationWeights,
 }
 
-impl GeneticOperator for SettingsMutator {
-    fn name() -> String {
-        "Settings-Mutator".to_string()
+impl SettingsMutator {
+    pub fn new(engine: &EngineOptions) -> Self {
+        SettingsMutator {
+            mutation_rate: engine.mutation_rate,
+            max_plugs: engine.max_plugs,
+            search_reflector: engine.reflector.is_searched(),
+            fixed_rings: engine.fixed_rings,
+            weights: engine.mutation_weights,
+        }
     }
 }
 
 impl MutationOp<Settings> for SettingsMutator {
-    fn mutate<R>(&self, sett: Settings, rng: &mut R) -> Settings
-    where
-        R: Rng + Sized,
-    {
-        let num_mutations = ((9_f64 * self.mutation_rate) + rng.gen::<f64>()).floor() as usize;
+    fn mutate<R: Rng>(&self, sett: Settings, rng: &mut R) -> Settings {
+        self.mutate_scaled(sett, 1.0, rng)
+    }
+
+    // fewer mutations and rings and positions that move less far
+    fn mutate_scaled<R: Rng>(&self, sett: Settings, strength: f64, rng: &mut R) -> Settings {
+        let rate = self.mutation_rate * strength;
+        let num_mutations = ((9_f64 * rate) + rng.gen::<f64>()).floor() as usize;
 
         if num_mutations == 0 {
             return sett;
         }
 
         let mut mutated = sett.clone();
+        let plugs = self.max_plugs > 0;
 
         for _ in 0..num_mutations {
-            match rng.gen_range(0..3) {
+            match self.weights.pick(plugs, self.search_reflector, rng) {
                 0 => mutated.rotors = mutate_triple_unique(sett.rotors, 1, MAX_ROTOR_NUM, rng),
-                1 => {
-                    mutated.ring_settings =
-                        mutate_triple(sett.ring_settings, 1, MAX_RING_SETTINGS_NUM, rng)
+                1 if self.fixed_rings => {
+                    mutated.ring_settings = change_letter(sett.ring_settings, 1, strength, rng)
                 }
-                2 => {
-                    mutated.rotor_positions =
-                        mutate_triple(sett.rotor_positions, 1, MAX_ROTOR_POSITIONS_NUM, rng)
+                1 => mutated.ring_settings = mutate_letters(sett.ring_settings, strength, rng),
+                2 => mutated.rotor_positions = mutate_letters(sett.rotor_positions, strength, rng),
+                3 => mutated.plugboard = mutate_plugboard(&sett.plugboard, self.max_plugs, rng),
+                4 => mutated.reflector = mutate_reflector(&sett.reflector, rng),
+                5 => {
+                    (mutated.ring_settings, mutated.rotor_positions) =
+                        shift_ring(&sett, self.fixed_rings, rng)
                 }
                 _ => panic!("out of settings range"),
             }
@@ -239,50 +1760,141 @@ impl MutationOp<Settings> for SettingsMutator {
     }
 }
 
+// turns the ring and the position of the middle or right rotor by the same number of letters:
+// the wiring keeps its place against the letters and only the turnover moves, so the
+// decryption stays the same but for the letters after the rotor would have stepped the next
+// one; the left ring is left alone, as nothing steps it the key would be the same
+fn shift_ring<R: Rng>(
+    sett: &Settings,
+    fixed_rings: bool,
+    rng: &mut R,
+) -> ((u8, u8, u8), (u8, u8, u8)) {
+    let pos = if fixed_rings { 1 } else { rng.gen_range(1..3) };
+    let by = rng.gen_range(1..LETTERS_NUM as i16);
+    let turn = |letter| turn_letter(letter, by);
+    let (rings, positions) = (sett.ring_settings, sett.rotor_positions);
+
+    match pos {
+        1 => (
+            (rings.0, turn(rings.1), rings.2),
+            (positions.0, turn(positions.1), positions.2),
+        ),
+        _ => (
+            (rings.0, rings.1, turn(rings.2)),
+            (positions.0, positions.1, turn(positions.2)),
+        ),
+    }
+}
+
+// removes a random pair, adds a new one, or replaces one with the other
+fn mutate_plugboard<R: Rng>(
+    plugboard: &[(u8, u8)],
+    max_plugs: usize,
+    rng: &mut R,
+) -> Vec<(u8, u8)> {
+    let mut mutated = plugboard.to_vec();
+
+    let remove = !mutated.is_empty() && (mutated.len() >= max_plugs || rng.gen_bool(0.5));
+    if remove {
+        let idx = rng.gen_range(0..mutated.len());
+        mutated.swap_remove(idx);
+    }
+
+    if mutated.len() < max_plugs && (!remove || rng.gen_bool(0.5)) {
+        add_plug(&mut mutated, rng);
+    }
+
+    mutated
+}
+
+// B and C swap, a UKW-D wiring exchanges the partners of two of its pairs
+fn mutate_reflector<R: Rng>(reflector: &Reflector, rng: &mut R) -> Reflector {
+    match reflector {
+        Reflector::B => Reflector::C,
+        Reflector::C => Reflector::B,
+        Reflector::D(pairs) => {
+            let mut pairs = pairs.clone();
+            let picked = (0..pairs.len()).choose_multiple(rng, 2);
+
+            if let [i, j] = picked[..] {
+                let ((a, b), (c, d)) = (pairs[i], pairs[j]);
+                let (x, y) = if rng.gen_bool(0.5) { (c, d) } else { (d, c) };
+                pairs[i] = (a.min(x), a.max(x));
+                pairs[j] = (b.min(y), b.max(y));
+            }
+            Reflector::D(pairs)
+        }
+    }
+}
+
+// the new value is sampled from the ones not used by the other two positions
 fn mutate_triple_unique<R: Rng>(t: (u8, u8, u8), from: u8, to: u8, rng: &mut R) -> (u8, u8, u8) {
     let pos = rng.gen_range(0..3);
+    let mut values = [t.0, t.1, t.2];
 
-    loop {
-        let next = change_triple(t, pos, from, to, rng);
-        if is_triple_unique(next) {
-            return next;
-        }
+    let free = (from..=to)
+        .filter(|v| (0..3).all(|i| i == pos || values[i] != *v))
+        .choose(rng);
+    if let Some(v) = free {
+        values[pos] = v;
     }
+
+    (values[0], values[1], values[2])
 }
 
-fn mutate_triple<R: Rng>(t: (u8, u8, u8), from: u8, to: u8, rng: &mut R) -> (u8, u8, u8) {
+// ring settings and rotor positions are letters on a circle, Z next to A
+fn turn_letter(letter: u8, by: i16) -> u8 {
+    ((letter as i16 - 1 + by).rem_euclid(LETTERS_NUM as i16) + 1) as u8
+}
+
+// farthest a mutation moves a ring or position rather than drawing it anew
+const NUDGE_LETTERS: i16 = 3;
+
+fn mutate_letters<R: Rng>(t: (u8, u8, u8), strength: f64, rng: &mut R) -> (u8, u8, u8) {
     let pos = rng.gen_range(0..3);
 
-    change_triple(t, pos, from, to, rng)
+    change_letter(t, pos, strength, rng)
 }
 
-fn change_triple<R: Rng>(t: (u8, u8, u8), pos: u8, from: u8, to: u8, rng: &mut R) -> (u8, u8, u8) {
-    let v = rng.gen_range(from..=to);
-    match pos {
-        0 => (v, t.1, t.2),
-        1 => (t.0, v, t.2),
-        2 => (t.0, t.1, v),
-        _ => panic!("out of triple range"),
-    }
+// half the time the letter moves up to NUDGE_LETTERS either way around the circle, across Z to
+// A as readily as anywhere else, to refine a key that is nearly right; otherwise it's drawn anew.
+// At a lower strength it's drawn anew less often and moves less far
+fn change_letter<R: Rng>(t: (u8, u8, u8), pos: usize, strength: f64, rng: &mut R) -> (u8, u8, u8) {
+    let mut letters = [t.0, t.1, t.2];
+    letters[pos] = if !rng.gen_bool(0.5 * strength) {
+        let reach = (NUDGE_LETTERS as f64 * strength).round().max(1.0) as i16;
+        let by = rng.gen_range(1..=reach);
+        turn_letter(letters[pos], if rng.gen() { by } else { -by })
+    } else {
+        rng.gen_range(1..=LETTERS_NUM)
+    };
+
+    (letters[0], letters[1], letters[2])
 }
 
 #[cfg(test)]
 mod tests {
     use approx::assert_relative_eq;
+    use proptest::prelude::*;
+    use rand::{rngs::StdRng, SeedableRng};
 
-    use crate::enigma;
+    use crate::enigma::{self, strategy};
+    use crate::metrics::tests::LONG_TEXT;
 
     use super::*;
 
-    const LONG_TEXT: &str = "TO BE OR NOT TO BE THAT IS THE QUESTION WHETHER TIS NOBLER IN THE MIND TO SUFFER THE SLINGS AND ARROWS OF OUTRAGEOUS FORTUNE OR TO TAKE ARMS AGAINST A SEA OF TROUBLES AND BY OPPOSING END THEMTO DIETO SLEEP NO MORE AND BY A SLEEP TO SAY WE END THE HEARTACHE AND THE THOUSAND NATURAL SHOCKS THAT FLESH IS HEIR TOTIS A CONSUMMATION DEVOUTLY TO BE WISHD TO DIETO SLEEP TO SLEEP PERCHANCE TO DREAMAY THERES THE RUB FOR IN THAT SLEEP OF DEATH WHAT DREAMS MAY COME WHEN WE HAVE SHUFFLED OFF THIS MORTAL COIL MUST GIVE US PAUSE THERES THE RESPECT THAT MAKES CALAMITY OF SO LONG LIFE";
-
     #[test]
-    fn test_ioc() {
-        assert_relative_eq!(index_of_coincidence(""), 0.0);
-        assert_relative_eq!(index_of_coincidence("A"), 0.0);
-        assert_relative_eq!(index_of_coincidence("AB"), 0.0);
-        assert_relative_eq!(index_of_coincidence("ABAA"), 0.5);
-        assert_relative_eq!(index_of_coincidence(LONG_TEXT), 0.0700307611754696);
+    fn test_fitness_stats() {
+        assert_eq!(fitness_stats(&[]).median, Fitness(0.0));
+        assert_relative_eq!(
+            fitness_stats(&[Fitness(f64::MAX), Fitness(f64::MAX)]).mean,
+            f64::MAX
+        );
+
+        let stats = fitness_stats(&[4.0, 1.0, 2.0, 5.0, 3.0].map(Fitness));
+        assert_relative_eq!(stats.mean, 3.0);
+        assert_eq!(stats.median, Fitness(3.0));
+        assert_relative_eq!(stats.std_dev, 2.0_f64.sqrt());
     }
 
     #[test]
@@ -291,15 +1903,20 @@ mod tests {
             rotors: (2, 5, 3),
             ring_settings: (8, 5, 20),
             rotor_positions: (13, 3, 21),
+            plugboard: vec![],
+            reflector: Reflector::B,
         };
 
         let machine = Machine::new(&settings).unwrap();
         let ciphertext = machine.encrypt(LONG_TEXT);
 
         let calc = FitnessCalc {
-            ciphertext: Arc::new(ciphertext),
-            max_value: 1000000,
-            cache: Cache::new(100),
+            ciphertext: Ciphertext::new(ciphertext),
+            cache: FitnessCache::new(100),
+            scramblers: ScramblerCache::new(100),
+            coverage: Coverage::default(),
+            invalid: Default::default(),
+            metric: None,
         };
 
         let mut closer_settings = settings.clone();
@@ -309,29 +1926,285 @@ mod tests {
             rotors: (1, 2, 3),
             ring_settings: (1, 1, 1),
             rotor_positions: (1, 1, 1),
+            plugboard: vec![],
+            reflector: Reflector::B,
+        };
+
+        assert_relative_eq!(calc.fitness_of(&settings).0, 0.070031, epsilon = 5e-7);
+        assert_relative_eq!(
+            calc.fitness_of(&closer_settings).0,
+            0.038868,
+            epsilon = 5e-7
+        );
+        assert_relative_eq!(calc.fitness_of(&wrong_settings).0, 0.037764, epsilon = 5e-7);
+        assert!(calc.fitness_of(&closer_settings) > calc.fitness_of(&wrong_settings));
+
+        // keys that only differ in the plugboard share the scrambler of their rotors
+        for plugboard in [vec![(1, 2)], vec![(1, 2), (3, 4)], vec![(5, 26)]] {
+            let plugged = enigma::Settings {
+                plugboard,
+                ..settings.clone()
+            };
+            let plaintext = Machine::new(&plugged)
+                .unwrap()
+                .decrypt(&calc.ciphertext.get());
+            assert_eq!(
+                calc.fitness_of(&plugged).0,
+                crate::metrics::index_of_coincidence(&plaintext)
+            );
+        }
+
+        let invalid_settings = enigma::Settings {
+            rotors: (1, 1, 3),
+            ring_settings: (1, 1, 1),
+            rotor_positions: (1, 1, 1),
+            plugboard: vec![],
+            reflector: Reflector::B,
         };
+        assert_eq!(calc.invalid.get(), None);
+        assert_eq!(calc.fitness_of(&invalid_settings), Fitness(0.0));
+        assert_eq!(calc.invalid.get().map(|(count, _)| count), Some(1));
+
+        // the score cached for the whole text isn't served for another one
+        calc.replace_ciphertext(machine.encrypt(&LONG_TEXT[..50]));
+        let plaintext = machine.decrypt(&calc.ciphertext.get());
+        assert_eq!(
+            calc.fitness_of(&settings).0,
+            crate::metrics::index_of_coincidence(&plaintext)
+        );
+    }
+
+    #[test]
+    fn test_options_validate() {
+        let mut opts = Options::default();
+        assert!(opts.validate().is_ok());
+
+        opts.engine.max_plugs = 10;
+        assert!(opts.validate().is_ok());
+
+        opts.engine.reflector = ReflectorSearch::Rewirable;
+        assert!(opts.validate().is_ok());
+        opts.engine.encoding = Encoding::FlatDna;
+        assert!(opts.validate().is_err());
+        opts.engine.encoding = Encoding::Settings;
+
+        opts.engine.herivel_spread = Some(3);
+        assert!(opts.validate().is_ok());
+        opts.engine.herivel_spread = Some(13);
+        assert!(opts.validate().is_err());
+        opts.engine.herivel_spread = Some(3);
+        opts.engine.fixed_rings = true;
+        assert!(opts.validate().is_err());
+        opts.engine.herivel_spread = None;
+        assert!(opts.validate().is_ok());
+        opts.engine.algorithm = Algorithm::Es;
+        assert!(opts.validate().is_err());
+        opts.engine.algorithm = Algorithm::Ga;
+        opts.engine.fixed_rings = false;
+
+        opts.engine.selection_ratio = 0.0;
+        assert!(opts.validate().is_err());
+
+        opts.engine.selection_ratio = 0.5;
+        opts.termination.time_limit = Duration::zero();
+        assert!(opts.validate().is_err());
+    }
+
+    #[test]
+    fn test_population_schedule() {
+        let decay = "decay:1000@5".parse::<PopulationSchedule>().unwrap();
+        assert_eq!(
+            (1..=6).map(|g| decay.size_at(16000, g)).collect::<Vec<_>>(),
+            [16000, 8000, 4000, 2000, 1000, 1000]
+        );
+        let stages = "10:500,20:100".parse::<PopulationSchedule>().unwrap();
+        assert_eq!(
+            [1, 9, 10, 19, 20, 100].map(|g| stages.size_at(1000, g)),
+            [1000, 1000, 500, 500, 100, 100]
+        );
+        assert_eq!(stages.max_size(50), 500);
+        assert_eq!(PopulationSchedule::Constant.size_at(1000, 50), 1000);
+        for s in ["constant", "decay:1000@5", "10:500,20:100"] {
+            assert_eq!(s.parse::<PopulationSchedule>().unwrap().to_string(), s);
+        }
+        assert!("decay:1000".parse::<PopulationSchedule>().is_err());
+        assert!("10-500".parse::<PopulationSchedule>().is_err());
+
+        let mut opts = Options::default();
+        opts.engine.population_schedule = "20:100,10:500".parse().unwrap();
+        assert!(opts.validate().is_err());
+        opts.engine.population_schedule = "10:1".parse().unwrap();
+        assert!(opts.validate().is_err());
+        opts.engine.population_schedule = stages;
+        assert!(opts.validate().is_ok());
+
+        let total = opts.estimated_population_bytes() + opts.estimated_cache_bytes();
+        assert!(opts.fit_memory(ByteSize(total / 2)).unwrap());
+        assert_eq!(
+            opts.engine.population_schedule,
+            PopulationSchedule::Stages(vec![(10, 250), (20, 50)])
+        );
+    }
+
+    #[test]
+    fn test_mutation_schedule() {
+        let (first, target) = (Fitness(0.04), Some(Fitness(0.07)));
+        let decay = "decay:0.25@3".parse::<MutationSchedule>().unwrap();
+        assert_eq!(
+            (1..=4)
+                .map(|g| decay.strength(g, first, first, target))
+                .collect::<Vec<_>>(),
+            [1.0, 0.5, 0.25, 0.25]
+        );
+        let fitness = "fitness:0.2".parse::<MutationSchedule>().unwrap();
+        assert_relative_eq!(fitness.strength(9, first, Fitness(0.055), target), 0.6);
+        assert_relative_eq!(fitness.strength(9, first, Fitness(0.09), target), 0.2);
+        for s in ["constant", "decay:0.25@3", "fitness:0.2"] {
+            assert_eq!(s.parse::<MutationSchedule>().unwrap().to_string(), s);
+        }
+        assert!("decay:0.25".parse::<MutationSchedule>().is_err());
+
+        let mut opts = Options::default();
+        opts.engine.mutation_schedule = fitness;
+        assert!(opts.validate().is_err());
+        opts.termination.target_fitness = target;
+        assert!(opts.validate().is_ok());
+
+        // a weak mutation leaves most keys alone and moves a position a letter at most
+        let m = SettingsMutator {
+            mutation_rate: 0.5,
+            max_plugs: 0,
+            search_reflector: false,
+            fixed_rings: false,
+            weights: "positions:1".parse().unwrap(),
+        };
+        let mut rng = rand::thread_rng();
+        let sett = SettingsBuilder {
+            max_plugs: 0,
+            reflector: ReflectorSearch::default(),
+            herivel_spread: None,
+            fixed_rings: false,
+        }
+        .build_genome(0, &mut rng);
+        let unchanged = (0..1000)
+            .filter(|_| m.mutate_scaled(sett.clone(), 0.01, &mut rng) == sett)
+            .count();
+        assert!(unchanged > 800, "{unchanged}");
+    }
+
+    #[test]
+    fn test_selection_adaptation() {
+        let bounds = "0.1:0.8".parse::<SelectionAdaptation>().unwrap();
+        assert_eq!(bounds.to_string(), "0.1:0.8");
+        // taken over, drifting and in between
+        assert_relative_eq!(bounds.adapt(0.5, Fitness(1.0), Fitness(0.99)), 0.55);
+        assert_relative_eq!(bounds.adapt(0.5, Fitness(1.0), Fitness(0.5)), 0.5 / 1.1);
+        assert_relative_eq!(bounds.adapt(0.5, Fitness(1.0), Fitness(0.9)), 0.5);
+        assert_relative_eq!(bounds.adapt(0.8, Fitness(1.0), Fitness(1.0)), 0.8);
+        assert_relative_eq!(bounds.adapt(0.1, Fitness(1.0), Fitness(0.1)), 0.1);
+        assert_relative_eq!(bounds.adapt(0.5, Fitness(-1.0), Fitness(-2.0)), 0.5);
+        assert!("0.1".parse::<SelectionAdaptation>().is_err());
+
+        let mut opts = Options::default();
+        opts.engine.selection_adaptation = Some(SelectionAdaptation { min: 0.8, max: 0.1 });
+        assert!(opts.validate().is_err());
+        opts.engine.selection_adaptation = Some(bounds);
+        assert!(opts.validate().is_ok());
+    }
 
-        assert_eq!(calc.fitness_of(&settings), 70031);
-        assert_eq!(calc.fitness_of(&closer_settings), 38868);
-        assert_eq!(calc.fitness_of(&wrong_settings), 37764);
+    #[test]
+    fn test_fit_memory() {
+        for (s, bytes) in [
+            ("512", 512),
+            ("4K", 4096),
+            ("1.5g", 3 << 29),
+            ("8GiB", 8 << 30),
+        ] {
+            assert_eq!(s.parse::<ByteSize>(), Ok(ByteSize(bytes)));
+        }
+        assert_eq!(ByteSize(8 << 30).to_string(), "8G");
+        assert_eq!(ByteSize(1000).to_string(), "1000B");
+        assert!("8X".parse::<ByteSize>().is_err());
+        assert!("-1G".parse::<ByteSize>().is_err());
+        assert!("16777216T".parse::<ByteSize>().is_err());
+
+        let mut opts = Options::default();
+        let total = opts.estimated_population_bytes() + opts.estimated_cache_bytes();
+        assert!(!opts.fit_memory(ByteSize(total)).unwrap());
+
+        assert!(opts.fit_memory(ByteSize(total / 4)).unwrap());
+        assert!(opts.estimated_population_bytes() + opts.estimated_cache_bytes() <= total / 4);
+        assert_eq!(opts.engine.population_size, 1_500_000 / 4);
+        assert_eq!(opts.fitness.cache_size, 3_000_000 / 4);
+
+        assert!(opts.fit_memory(ByteSize(100)).is_err());
     }
 
     #[test]
     fn test_settings_builder() {
         let mut rng = rand::thread_rng();
-        let b = SettingsBuilder {};
+        let b = SettingsBuilder {
+            max_plugs: 10,
+            reflector: ReflectorSearch::default(),
+            herivel_spread: None,
+            fixed_rings: false,
+        };
 
         for _ in 0..10000 {
             let sett = b.build_genome(0, &mut rng);
             assert!(is_settings_valid(&sett))
         }
+
+        // within 2 letters of the ring settings, across Z to A
+        let b = SettingsBuilder {
+            herivel_spread: Some(2),
+            ..b
+        };
+        let distance = |ring: u8, position: u8| {
+            let d = (position as i16 - ring as i16).rem_euclid(26);
+            d.min(26 - d)
+        };
+        for _ in 0..10000 {
+            let sett = b.build_genome(0, &mut rng);
+            assert!(is_settings_valid(&sett));
+            let (r, p) = (sett.ring_settings, sett.rotor_positions);
+            assert!([(r.0, p.0), (r.1, p.1), (r.2, p.2)]
+                .iter()
+                .all(|&(ring, position)| distance(ring, position) <= 2));
+        }
+    }
+
+    #[test]
+    fn test_add_plug() {
+        let mut rng = rand::thread_rng();
+        let mut plugboard = Vec::new();
+
+        for _ in 0..MAX_PLUGS_NUM {
+            assert!(add_plug(&mut plugboard, &mut rng));
+        }
+        assert!(!add_plug(&mut plugboard, &mut rng));
+
+        let sett = Settings {
+            rotors: (1, 2, 3),
+            ring_settings: (1, 1, 1),
+            rotor_positions: (1, 1, 1),
+            plugboard,
+            reflector: Reflector::B,
+        };
+        assert!(sett.validate().is_ok());
+        assert!(gen_plugboard(100, &mut rng).len() <= MAX_PLUGS_NUM);
     }
 
     #[test]
     fn test_settings_crossover() {
         let mut rng = rand::thread_rng();
-        let b = SettingsBuilder {};
-        let c = SettingsCrossover {};
+        let b = SettingsBuilder {
+            max_plugs: 10,
+            reflector: ReflectorSearch::default(),
+            herivel_spread: None,
+            fixed_rings: false,
+        };
+        let c = SettingsCrossover::default();
 
         for _ in 0..10000 {
             let sett1 = b.build_genome(0, &mut rng);
@@ -343,11 +2216,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_settings_crossover_many_parents() {
+        let mut rng = rand::thread_rng();
+        let b = SettingsBuilder {
+            max_plugs: 10,
+            reflector: ReflectorSearch::default(),
+            herivel_spread: None,
+            fixed_rings: false,
+        };
+        let c = SettingsCrossover::default();
+
+        for _ in 0..10000 {
+            let parents = (0..3)
+                .map(|_| b.build_genome(0, &mut rng))
+                .collect::<Vec<_>>();
+
+            let offsprings = c.crossover(parents.clone(), &mut rng);
+
+            // on the short arc between the letters of two of the parents
+            let between = |letter: fn(&Settings) -> u8, child: u8| {
+                parents.iter().any(|a| {
+                    parents.iter().any(|b| {
+                        let arc = (letter(b) as i16 - letter(a) as i16 + 13).rem_euclid(26) - 13;
+                        let d = (child as i16 - letter(a) as i16 + 13).rem_euclid(26) - 13;
+                        d * arc >= 0 && d.abs() <= arc.abs()
+                    })
+                })
+            };
+            for child in offsprings {
+                assert!(is_settings_valid(&child));
+                assert!(between(|s| s.ring_settings.0, child.ring_settings.0));
+                assert!(between(|s| s.rotor_positions.2, child.rotor_positions.2));
+            }
+
+            // the rotor, ring and position of a slot stay together
+            let slots = SettingsCrossover {
+                mode: CrossoverMode::Slots,
+                ..c
+            };
+            for child in slots.crossover(parents.clone(), &mut rng) {
+                assert!(is_settings_valid(&child));
+                let slot = |s: &Settings| (s.rotors.1, s.ring_settings.1, s.rotor_positions.1);
+                assert!(parents.iter().any(|p| slot(p) == slot(&child)));
+            }
+        }
+        assert_eq!("slots".parse(), Ok(CrossoverMode::Slots));
+    }
+
     #[test]
     fn test_settings_mutator() {
         let mut rng = rand::thread_rng();
-        let b = SettingsBuilder {};
-        let m = SettingsMutator { mutation_rate: 0.9 };
+        let b = SettingsBuilder {
+            max_plugs: 10,
+            reflector: ReflectorSearch::default(),
+            herivel_spread: None,
+            fixed_rings: false,
+        };
+        let m = SettingsMutator {
+            mutation_rate: 0.9,
+            max_plugs: 10,
+            search_reflector: false,
+            fixed_rings: false,
+            weights: MutationWeights::default(),
+        };
 
         for _ in 0..10000 {
             let sett = b.build_genome(0, &mut rng);
@@ -355,10 +2287,247 @@ mod tests {
 
             assert!(is_settings_valid(&mutated_sett));
         }
+
+        let b = SettingsBuilder {
+            fixed_rings: true,
+            ..b
+        };
+        let m = SettingsMutator {
+            fixed_rings: true,
+            ..m
+        };
+        for _ in 0..10000 {
+            let mutated_sett = m.mutate(b.build_genome(0, &mut rng), &mut rng);
+            let (left, _, right) = mutated_sett.ring_settings;
+            assert!(is_settings_valid(&mutated_sett) && left == 1 && right == 1);
+        }
+
+        let weights: MutationWeights = "positions:40, plugboard:0".parse().unwrap();
+        assert_eq!(
+            weights.to_string(),
+            "rotors:0,rings:0,positions:40,plugboard:0,reflector:0,shift:0"
+        );
+        let m = SettingsMutator { weights, ..m };
+        for _ in 0..1000 {
+            let sett = b.build_genome(0, &mut rng);
+            let mutated_sett = m.mutate(sett.clone(), &mut rng);
+            assert_eq!(
+                Settings {
+                    rotor_positions: sett.rotor_positions,
+                    ..mutated_sett
+                },
+                sett
+            );
+        }
+        // the ring and the position turn together, the wiring stays where it was
+        let m = SettingsMutator {
+            weights: "shift:1".parse().unwrap(),
+            ..m
+        };
+        let offsets = |s: &Settings| {
+            let (rings, positions) = (s.ring_settings, s.rotor_positions);
+            [
+                (26 + positions.1 - rings.1) % 26,
+                (26 + positions.2 - rings.2) % 26,
+            ]
+        };
+        for _ in 0..1000 {
+            let sett = b.build_genome(0, &mut rng);
+            let mutated_sett = m.mutate(sett.clone(), &mut rng);
+            assert!(is_settings_valid(&mutated_sett));
+            assert_eq!(offsets(&mutated_sett), offsets(&sett));
+            assert_eq!(mutated_sett.ring_settings.0, sett.ring_settings.0);
+            assert_eq!(mutated_sett.rotor_positions.0, sett.rotor_positions.0);
+        }
+        assert!("gears:1".parse::<MutationWeights>().is_err());
+        let mut opts = Options::default();
+        opts.engine.mutation_weights = "plugboard:1".parse().unwrap();
+        assert!(opts.validate().is_err());
+        opts.engine.max_plugs = 5;
+        assert!(opts.validate().is_ok());
+    }
+
+    #[test]
+    fn test_rotor_crossover() {
+        let mut rng = rand::thread_rng();
+        let gen_rotors = |rng: &mut rand::rngs::ThreadRng| {
+            let (a, b, c) = gen_triple_unique(1, MAX_ROTOR_NUM, rng);
+            [a, b, c]
+        };
+        for op in [RotorCrossover::Pmx, RotorCrossover::Ox] {
+            for _ in 0..1000 {
+                let first = gen_rotors(&mut rng);
+                let second = gen_rotors(&mut rng);
+                let child = cross_orders(first, second, op, &mut rng);
+
+                assert!(is_triple_unique((child[0], child[1], child[2])), "{op}");
+                assert!(child
+                    .iter()
+                    .all(|r| first.contains(r) || second.contains(r)));
+                // at least one slot of the first parent is kept
+                assert!((0..3).any(|i| child[i] == first[i]));
+            }
+        }
+
+        // a run of the left slot alone: PMX keeps V and moves IV to where the second parent has
+        // I, OX fills in V then IV, the order after the run
+        for (op, expected) in [
+            (RotorCrossover::Pmx, [1, 4, 5]),
+            (RotorCrossover::Ox, [1, 5, 4]),
+        ] {
+            let left_runs = (0..100)
+                .map(|_| cross_orders([1, 2, 3], [4, 1, 5], op, &mut rng))
+                .filter(|c| c[0] == 1 && c[1] != 2)
+                .collect::<Vec<_>>();
+            assert!(
+                !left_runs.is_empty() && left_runs.iter().all(|c| *c == expected),
+                "{op}"
+            );
+        }
+        assert_eq!("pmx".parse(), Ok(RotorCrossover::Pmx));
+    }
+
+    #[test]
+    fn test_plugboard_crossover() {
+        let mut rng = rand::thread_rng();
+        // the same pairs listed in another order and with the letters swapped
+        let first = [(1, 2), (3, 4), (5, 6)];
+        let second = [(6, 5), (1, 2), (4, 3)];
+        let third = [(7, 8), (9, 10), (11, 12), (13, 14)];
+        let sorted = |mut p: Vec<(u8, u8)>| {
+            p.sort_unstable();
+            p
+        };
+
+        for _ in 0..100 {
+            for op in [PlugboardCrossover::Union, PlugboardCrossover::Intersection] {
+                let child = cross_plugboards_with(&[&first, &second], op, &mut rng);
+                assert_eq!(sorted(child), first, "{op}");
+            }
+
+            let child =
+                cross_plugboards_with(&[&first, &third], PlugboardCrossover::Union, &mut rng);
+            assert!([3, 4].contains(&child.len()));
+            assert!(child.iter().all(|p| first.contains(p) || third.contains(p)));
+
+            let child = cross_plugboards_with(
+                &[&first, &third],
+                PlugboardCrossover::Intersection,
+                &mut rng,
+            );
+            assert!([3, 4].contains(&child.len()) && !is_plug_conflicting(&child[1..], child[0]));
+
+            // one pair of the other parent in place of one of the parent's
+            let child =
+                cross_plugboards_with(&[&first, &third], PlugboardCrossover::Exchange, &mut rng);
+            let (own, other) = match third.contains(&child[child.len() - 1]) {
+                true => (&first[..], &third[..]),
+                false => (&third[..], &first[..]),
+            };
+            assert_eq!(child.len(), own.len());
+            assert_eq!(child.iter().filter(|p| other.contains(p)).count(), 1);
+        }
+        assert_eq!("exchange".parse(), Ok(PlugboardCrossover::Exchange));
+    }
+
+    #[test]
+    fn test_circular_genes() {
+        let mut rng = rand::thread_rng();
+        assert_eq!(turn_letter(26, 1), 1);
+        assert_eq!(turn_letter(2, -3), 25);
+
+        // Z and A are neighbours, the letters between them the long way round are never bred
+        for _ in 0..1000 {
+            let child = cross_circular(&[(26, 5, 1), (1, 9, 1)], &mut rng);
+            assert!([26, 1].contains(&child.0), "{child:?}");
+            assert!((5..=9).contains(&child.1) && child.2 == 1, "{child:?}");
+        }
+
+        let mut across = false;
+        for _ in 0..1000 {
+            let (_, letter, _) = change_letter((1, 26, 1), 1, 1.0, &mut rng);
+            assert!((1..=26).contains(&letter));
+            across |= (1..=2).contains(&letter);
+        }
+        assert!(across);
+    }
+
+    #[test]
+    fn test_reflector_operators() {
+        let mut rng = rand::thread_rng();
+        let m = SettingsMutator {
+            mutation_rate: 0.9,
+            max_plugs: 10,
+            search_reflector: true,
+            fixed_rings: false,
+            weights: MutationWeights::default(),
+        };
+
+        for search in [ReflectorSearch::Standard, ReflectorSearch::Rewirable] {
+            let b = SettingsBuilder {
+                max_plugs: 10,
+                reflector: search.clone(),
+                herivel_spread: None,
+                fixed_rings: false,
+            };
+
+            for _ in 0..1000 {
+                let parents = (0..3)
+                    .map(|_| b.build_genome(0, &mut rng))
+                    .collect::<Vec<_>>();
+
+                for child in SettingsCrossover::default().crossover(parents, &mut rng) {
+                    assert!(is_settings_valid(&child));
+                    assert_eq!(
+                        matches!(child.reflector, Reflector::D(_)),
+                        search == ReflectorSearch::Rewirable
+                    );
+                    assert!(is_settings_valid(&m.mutate(child, &mut rng)));
+                }
+            }
+        }
+
+        let wiring = gen_reflector(&ReflectorSearch::Rewirable, &mut rng);
+        let rewired = mutate_reflector(&wiring, &mut rng);
+        assert!(rewired.validate().is_ok());
+        assert_ne!(rewired, wiring);
+        assert_eq!(mutate_reflector(&Reflector::B, &mut rng), Reflector::C);
+
+        assert_eq!(ReflectorSearch::Rewirable.choices(), 7_905_853_580_625);
+        assert_eq!(
+            "C".parse::<ReflectorSearch>().unwrap(),
+            ReflectorSearch::Known(Reflector::C)
+        );
+        assert!("E".parse::<ReflectorSearch>().is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_operators_keep_genomes_valid(
+            parents in prop::collection::vec(strategy::settings(10), 2..5),
+            mutation_rate in 0.0..=1.0,
+            seed: u64,
+        ) {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let m = SettingsMutator {
+                mutation_rate,
+                max_plugs: 10,
+                search_reflector: true,
+                fixed_rings: false,
+                weights: MutationWeights::default(),
+            };
+
+            for child in SettingsCrossover::default().crossover(parents, &mut rng) {
+                prop_assert!(is_settings_valid(&child));
+                prop_assert!(is_settings_valid(&m.mutate(child, &mut rng)));
+            }
+        }
     }
 
     fn is_settings_valid(sett: &Settings) -> bool {
-        is_triple_unique(sett.rotors)
+        sett.validate().is_ok()
+            && sett.plugboard.len() <= 10
+            && is_triple_unique(sett.rotors)
             && is_triple_in_range(sett.rotors, 1, MAX_ROTOR_NUM)
             && is_triple_in_range(sett.ring_settings, 1, MAX_RING_SETTINGS_NUM)
             && is_triple_in_range(sett.rotor_positions, 1, MAX_ROTOR_POSITIONS_NUM)
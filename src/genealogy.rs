@@ -0,0 +1,186 @@
+// where the best keys of a search came from: every individual the GA breeds is recorded with
+// its parents and how it was made, and what no living individual descends from is forgotten
+// after each generation, so the record stays about as large as the population
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::gen::Fitness;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    // a key of the initial population
+    Random,
+    Crossover,
+    Mutation,
+    CrossoverMutation,
+    // crossover handed a parent over unchanged and mutation left it so
+    Copy,
+}
+
+impl Operator {
+    pub(crate) fn of(crossed: bool, mutated: bool) -> Self {
+        match (crossed, mutated) {
+            (true, true) => Operator::CrossoverMutation,
+            (true, false) => Operator::Crossover,
+            (false, true) => Operator::Mutation,
+            (false, false) => Operator::Copy,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Random => write!(f, "random"),
+            Operator::Crossover => write!(f, "crossover"),
+            Operator::Mutation => write!(f, "mutation"),
+            Operator::CrossoverMutation => write!(f, "crossover + mutation"),
+            Operator::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<G> {
+    pub id: u64,
+    // the generation the individual was bred in, 1 for the initial population
+    pub generation: u64,
+    pub parents: Vec<u64>,
+    pub operator: Operator,
+    pub fitness: Fitness,
+    pub genome: G,
+}
+
+#[derive(Debug)]
+pub(crate) struct Genealogy<G> {
+    nodes: HashMap<u64, Node<G>>,
+}
+
+// a derived Default would require G: Default
+impl<G> Default for Genealogy<G> {
+    fn default() -> Self {
+        Self {
+            nodes: HashMap::new(),
+        }
+    }
+}
+
+impl<G: Clone> Genealogy<G> {
+    pub(crate) fn insert(&mut self, node: Node<G>) {
+        self.nodes.insert(node.id, node);
+    }
+
+    pub(crate) fn set_fitness(&mut self, id: u64, fitness: Fitness) {
+        if let Some(node) = self.nodes.get_mut(&id) {
+            node.fitness = fitness;
+        }
+    }
+
+    // the individuals and all their ancestors, oldest first
+    pub(crate) fn ancestry(&self, ids: impl IntoIterator<Item = u64>) -> Vec<Node<G>> {
+        let mut nodes = self
+            .reachable(ids)
+            .into_iter()
+            .filter_map(|id| self.nodes.get(&id).cloned())
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| (node.generation, node.id));
+        nodes
+    }
+
+    // forgets whom none of the living descend from
+    pub(crate) fn prune(&mut self, alive: impl IntoIterator<Item = u64>) {
+        let reachable = self.reachable(alive);
+        self.nodes.retain(|id, _| reachable.contains(id));
+    }
+
+    fn reachable(&self, ids: impl IntoIterator<Item = u64>) -> HashSet<u64> {
+        let mut reachable = HashSet::new();
+        let mut pending = ids.into_iter().collect::<Vec<_>>();
+        while let Some(id) = pending.pop() {
+            if reachable.insert(id) {
+                if let Some(node) = self.nodes.get(&id) {
+                    pending.extend(&node.parents);
+                }
+            }
+        }
+        reachable
+    }
+}
+
+/// Graphviz DOT of an ancestry, parents pointing to their offspring; each node tells the
+/// generation, key, fitness and operator of an individual, those without offspring in the
+/// graph (the best of the last generation) are drawn doubled.
+pub fn dot<G: fmt::Display>(ancestry: &[Node<G>]) -> String {
+    let parents = ancestry
+        .iter()
+        .flat_map(|node| node.parents.iter().copied())
+        .collect::<HashSet<_>>();
+
+    let mut dot = "digraph genealogy {\n    node [shape=box, fontname=monospace];\n".to_string();
+    for node in ancestry {
+        let shape = if parents.contains(&node.id) {
+            ""
+        } else {
+            ", peripheries=2"
+        };
+        dot += &format!(
+            "    n{} [label=\"generation {}\\n{}\\n{:.5}\\n{}\"{}];\n",
+            node.id, node.generation, node.genome, node.fitness.0, node.operator, shape
+        );
+    }
+    for node in ancestry {
+        for parent in &node.parents {
+            dot += &format!("    n{} -> n{};\n", parent, node.id);
+        }
+    }
+    dot += "}\n";
+    dot
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u64, generation: u64, parents: &[u64], operator: Operator) -> Node<u32> {
+        Node {
+            id,
+            generation,
+            parents: parents.to_vec(),
+            operator,
+            fitness: Fitness(id as f64 / 10.0),
+            genome: id as u32,
+        }
+    }
+
+    #[test]
+    fn test_genealogy() {
+        let mut genealogy = Genealogy::default();
+        for id in 0..4 {
+            genealogy.insert(node(id, 1, &[], Operator::Random));
+        }
+        genealogy.insert(node(4, 2, &[0, 1], Operator::Crossover));
+        genealogy.insert(node(5, 2, &[2], Operator::Mutation));
+        genealogy.insert(node(6, 3, &[4, 5], Operator::CrossoverMutation));
+        genealogy.set_fitness(6, Fitness(0.9));
+
+        let ancestry = genealogy.ancestry([6]);
+        let ids = ancestry.iter().map(|n| n.id).collect::<Vec<_>>();
+        assert_eq!(ids, [0, 1, 2, 4, 5, 6]);
+        assert_eq!(ancestry[5].fitness, Fitness(0.9));
+
+        // 3 has no descendants among the living
+        genealogy.prune([6, 5]);
+        assert_eq!(genealogy.nodes.len(), 6);
+        assert!(!genealogy.nodes.contains_key(&3));
+
+        let dot = dot(&ancestry);
+        assert!(dot.starts_with("digraph genealogy {\n"));
+        assert!(dot.contains("    n4 [label=\"generation 2\\n4\\n0.40000\\ncrossover\"];\n"));
+        assert!(dot.contains("\\ncrossover + mutation\", peripheries=2];\n"));
+        assert!(dot.contains("    n0 -> n4;\n    n1 -> n4;\n"));
+        assert!(dot.ends_with("    n5 -> n6;\n}\n"));
+        assert_eq!(Operator::of(false, false), Operator::Copy);
+    }
+}
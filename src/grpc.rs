@@ -0,0 +1,188 @@
+use std::net::SocketAddr;
+
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    enigma::Machine,
+    gen::{self, Fitness},
+    sim::{Simulation, StepResult},
+};
+
+pub mod proto {
+    tonic::include_proto!("enigmagen");
+}
+
+use proto::{
+    solver_server::{Solver, SolverServer},
+    CrackProgress, CrackRequest,
+};
+
+// generations the search may run ahead of a slow client before it blocks
+const PROGRESS_BUFFER: usize = 4;
+
+#[derive(Debug, Default)]
+pub struct SolverService;
+
+#[tonic::async_trait]
+impl Solver for SolverService {
+    type CrackStream = ReceiverStream<Result<CrackProgress, Status>>;
+
+    async fn crack(
+        &self,
+        request: Request<CrackRequest>,
+    ) -> Result<Response<Self::CrackStream>, Status> {
+        let request = request.into_inner();
+        let opts = options(&request).map_err(|err| Status::invalid_argument(err.to_string()))?;
+        let ciphertext = request.ciphertext;
+
+        let (tx, rx) = mpsc::channel(PROGRESS_BUFFER);
+        // the search is CPU bound and runs its own thread pool, it stays off the async workers
+        tokio::task::spawn_blocking(move || {
+            let sim = match Simulation::new(&ciphertext, opts) {
+                Ok(sim) => sim,
+                Err(err) => {
+                    let _ = tx.blocking_send(Err(Status::internal(err.to_string())));
+                    return;
+                }
+            };
+
+            for step in sim {
+                let progress = step
+                    .and_then(|step| progress(&ciphertext, &step))
+                    .map_err(|err| Status::internal(err.to_string()));
+                // waits while the client is behind, a closed stream cancels the search
+                if tx.blocking_send(progress).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+fn options(request: &CrackRequest) -> anyhow::Result<gen::Options> {
+    let mut opts = gen::Options::default();
+    let engine = &mut opts.engine;
+    let termination = &mut opts.termination;
+
+    engine.seed = Some(request.seed.unwrap_or_else(rand::random));
+    engine.max_plugs = request.max_plugs as usize;
+    engine.threads = request.threads as usize;
+    if !request.reflector.is_empty() {
+        engine.reflector = request.reflector.parse()?;
+    }
+    if request.population_size > 0 {
+        engine.population_size = request.population_size as usize;
+    }
+    if request.generation_limit > 0 {
+        termination.generation_limit = request.generation_limit;
+    }
+    if request.time_limit_secs > 0 {
+        termination.time_limit = chrono::Duration::seconds(request.time_limit_secs as i64);
+    }
+    termination.target_fitness = request.target_fitness.map(Fitness);
+
+    opts.validate()?;
+    Ok(opts)
+}
+
+fn progress(ciphertext: &str, step: &StepResult) -> anyhow::Result<CrackProgress> {
+    let generation = step.generation();
+    let mut progress = CrackProgress {
+        generation: generation.iteration,
+        average_fitness: generation.average_fitness.0,
+        median_fitness: generation.median_fitness.0,
+        fitness_std_dev: generation.fitness_std_dev,
+        best_fitness: generation.best_fitness.0,
+        best_key: generation.best_settings.to_string(),
+        duration_ms: generation.duration.as_millis() as u64,
+        ..Default::default()
+    };
+
+    if let StepResult::Final { generation, reason } = step {
+        progress.finished = true;
+        progress.reason = reason.clone();
+        progress.plaintext = Machine::new(&generation.best_settings)?.decrypt(ciphertext);
+    }
+
+    Ok(progress)
+}
+
+// runs until the process is stopped
+pub async fn serve(addr: SocketAddr) -> anyhow::Result<()> {
+    Server::builder()
+        .add_service(SolverServer::new(SolverService))
+        .serve(addr)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::{wrappers::TcpListenerStream, StreamExt};
+
+    use crate::{enigma::Settings, metrics::tests::LONG_TEXT};
+
+    use super::{proto::solver_client::SolverClient, *};
+
+    #[test]
+    fn test_crack() {
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(
+                Server::builder()
+                    .add_service(SolverServer::new(SolverService))
+                    .serve_with_incoming(TcpListenerStream::new(listener)),
+            );
+            let mut client = SolverClient::connect(format!("http://{}", addr))
+                .await
+                .unwrap();
+
+            let request = CrackRequest {
+                ciphertext: ciphertext.clone(),
+                seed: Some(3),
+                population_size: 500,
+                generation_limit: 3,
+                ..Default::default()
+            };
+            let progress = client
+                .crack(request.clone())
+                .await
+                .unwrap()
+                .into_inner()
+                .collect::<Result<Vec<_>, _>>()
+                .await
+                .unwrap();
+
+            assert_eq!(
+                progress.iter().map(|p| p.generation).collect::<Vec<_>>(),
+                [1, 2, 3]
+            );
+            assert!(progress[..2].iter().all(|p| !p.finished));
+            let last = &progress[2];
+            assert!(last.finished);
+            let key = last.best_key.parse::<Settings>().unwrap();
+            assert_eq!(
+                last.plaintext,
+                Machine::new(&key).unwrap().decrypt(&ciphertext)
+            );
+
+            let invalid = CrackRequest {
+                reflector: "E".to_string(),
+                ..request
+            };
+            let status = client.crack(invalid).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        });
+    }
+}
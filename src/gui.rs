@@ -0,0 +1,413 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::anyhow;
+use eframe::egui;
+use egui_plot::{Legend, Line, Plot};
+
+use crate::{
+    enigma::{self, Machine, Settings},
+    gen,
+    sim::{Generation, Simulation, StepResult},
+};
+
+// options as they are typed in, parsed when the search is started
+#[derive(Debug, Clone)]
+struct Form {
+    ciphertext: String,
+    reflector: String,
+    max_plugs: usize,
+    population_size: usize,
+    mutation_rate: f64,
+    generation_limit: u64,
+    time_limit_secs: i64,
+    threads: usize,
+    // empty for a random seed
+    seed: String,
+}
+
+impl Default for Form {
+    fn default() -> Self {
+        let opts = gen::Options::default();
+        Self {
+            ciphertext: String::new(),
+            reflector: opts.engine.reflector.to_string(),
+            max_plugs: opts.engine.max_plugs,
+            // the default population takes minutes per generation, too slow to watch
+            population_size: 50_000,
+            mutation_rate: opts.engine.mutation_rate,
+            generation_limit: opts.termination.generation_limit,
+            time_limit_secs: opts.termination.time_limit.num_seconds(),
+            threads: opts.engine.threads,
+            seed: String::new(),
+        }
+    }
+}
+
+impl Form {
+    fn options(&self) -> anyhow::Result<gen::Options> {
+        let mut opts = gen::Options::default();
+        let engine = &mut opts.engine;
+        let termination = &mut opts.termination;
+
+        engine.reflector = self.reflector.trim().parse()?;
+        engine.max_plugs = self.max_plugs;
+        engine.population_size = self.population_size;
+        engine.mutation_rate = self.mutation_rate;
+        engine.threads = self.threads;
+        engine.seed = Some(match self.seed.trim() {
+            "" => rand::random(),
+            seed => seed.parse().map_err(|_| anyhow!("invalid seed {}", seed))?,
+        });
+        termination.generation_limit = self.generation_limit;
+        termination.time_limit = chrono::Duration::seconds(self.time_limit_secs);
+
+        opts.validate()?;
+        Ok(opts)
+    }
+}
+
+#[derive(Debug)]
+enum Event {
+    Generation(Generation),
+    Finished(Generation, String),
+    Cancelled,
+    Failed(String),
+}
+
+// the search runs on its own thread and sends every generation to the window
+struct Search {
+    cancel: Arc<AtomicBool>,
+    events: mpsc::Receiver<Event>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Search {
+    fn start(ciphertext: String, opts: gen::Options, repaint: impl Fn() + Send + 'static) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, events) = mpsc::channel();
+
+        let cancelled = cancel.clone();
+        let worker = thread::spawn(move || {
+            let send = |event| {
+                let _ = tx.send(event);
+                repaint();
+            };
+
+            let mut sim = match Simulation::new(&ciphertext, opts) {
+                Ok(sim) => sim,
+                Err(err) => return send(Event::Failed(err.to_string())),
+            };
+            loop {
+                if cancelled.load(Ordering::Relaxed) {
+                    return send(Event::Cancelled);
+                }
+                match sim.step() {
+                    Ok(StepResult::Intermediate(generation)) => send(Event::Generation(generation)),
+                    Ok(StepResult::Final { generation, reason }) => {
+                        return send(Event::Finished(generation, reason))
+                    }
+                    Err(err) => return send(Event::Failed(err.to_string())),
+                }
+            }
+        });
+
+        Self {
+            cancel,
+            events,
+            worker: Some(worker),
+        }
+    }
+
+    fn stop(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Search {
+    fn drop(&mut self) {
+        self.stop();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[derive(Default)]
+struct App {
+    form: Form,
+    search: Option<Search>,
+    // ciphertext of the current search, the form may have been edited since
+    ciphertext: String,
+    generations: Vec<Generation>,
+    best: Option<(Settings, String)>,
+    status: String,
+}
+
+impl App {
+    fn start(&mut self, ctx: &egui::Context) {
+        let ciphertext = enigma::normalize(&self.form.ciphertext);
+        let opts = match self.form.options() {
+            Ok(opts) => opts,
+            Err(err) => {
+                self.status = format!("Invalid options: {}", err);
+                return;
+            }
+        };
+
+        self.status = format!("Running, seed {}", opts.engine.seed.unwrap_or_default());
+        self.generations.clear();
+        self.best = None;
+        let ctx = ctx.clone();
+        self.search = Some(Search::start(ciphertext.clone(), opts, move || {
+            ctx.request_repaint()
+        }));
+        self.ciphertext = ciphertext;
+    }
+
+    fn running(&self) -> bool {
+        self.search.is_some()
+    }
+
+    fn receive(&mut self) {
+        let Some(search) = &self.search else {
+            return;
+        };
+
+        let events = search.events.try_iter().collect::<Vec<_>>();
+        for event in events {
+            match event {
+                Event::Generation(generation) => self.show(generation),
+                Event::Finished(generation, reason) => {
+                    self.status = format!(
+                        "Finished after {} generations: {}",
+                        generation.iteration, reason
+                    );
+                    self.show(generation);
+                    self.search = None;
+                }
+                Event::Cancelled => {
+                    self.status = "Stopped".to_string();
+                    self.search = None;
+                }
+                Event::Failed(err) => {
+                    self.status = format!("Failed: {}", err);
+                    self.search = None;
+                }
+            }
+        }
+    }
+
+    fn show(&mut self, generation: Generation) {
+        let improved = match &self.best {
+            Some((settings, _)) => *settings != generation.best_settings,
+            None => true,
+        };
+        if improved {
+            if let Ok(machine) = Machine::new(&generation.best_settings) {
+                let plaintext = machine.decrypt(&self.ciphertext);
+                self.best = Some((generation.best_settings.clone(), plaintext));
+            }
+        }
+        self.generations.push(generation);
+    }
+
+    fn options_panel(&mut self, ui: &mut egui::Ui) {
+        let form = &mut self.form;
+        ui.add_enabled_ui(self.search.is_none(), |ui| {
+            egui::Grid::new("options").num_columns(2).show(ui, |ui| {
+                ui.label("Model");
+                ui.label(enigma::MODEL_NAME);
+                ui.end_row();
+
+                ui.label("Reflector")
+                    .on_hover_text("B, C, \"D AB CD ...\", standard (B or C) or rewirable");
+                ui.text_edit_singleline(&mut form.reflector);
+                ui.end_row();
+
+                ui.label("Plugboard pairs");
+                ui.add(egui::DragValue::new(&mut form.max_plugs).range(0..=13));
+                ui.end_row();
+
+                ui.label("Population");
+                ui.add(
+                    egui::DragValue::new(&mut form.population_size)
+                        .range(2..=10_000_000)
+                        .speed(1000),
+                );
+                ui.end_row();
+
+                ui.label("Mutation rate");
+                ui.add(
+                    egui::DragValue::new(&mut form.mutation_rate)
+                        .range(0.0..=1.0)
+                        .speed(0.001),
+                );
+                ui.end_row();
+
+                ui.label("Generations");
+                ui.add(egui::DragValue::new(&mut form.generation_limit).range(1..=100_000));
+                ui.end_row();
+
+                ui.label("Time limit (s)");
+                ui.add(egui::DragValue::new(&mut form.time_limit_secs).range(1..=86_400));
+                ui.end_row();
+
+                ui.label("Threads").on_hover_text("0 uses all cores");
+                ui.add(egui::DragValue::new(&mut form.threads).range(0..=1024));
+                ui.end_row();
+
+                ui.label("Seed").on_hover_text("empty for a random seed");
+                ui.text_edit_singleline(&mut form.seed);
+                ui.end_row();
+            });
+        });
+    }
+
+    fn chart(&self, ui: &mut egui::Ui) {
+        let line = |name: &str, fitness: fn(&Generation) -> f64| {
+            let points = self
+                .generations
+                .iter()
+                .map(|g| [g.iteration as f64, fitness(g)])
+                .collect::<Vec<_>>();
+            Line::new(name, points)
+        };
+
+        Plot::new("convergence")
+            .legend(Legend::default())
+            .height(260.0)
+            .x_axis_label("generation")
+            .y_axis_label("fitness")
+            .show(ui, |plot| {
+                plot.line(line("best", |g| g.best_fitness.0));
+                plot.line(line("average", |g| g.average_fitness.0));
+                plot.line(line("median", |g| g.median_fitness.0));
+            });
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.receive();
+
+        egui::SidePanel::left("options_panel").show(ctx, |ui| {
+            ui.heading("Options");
+            self.options_panel(ui);
+            ui.separator();
+            ui.horizontal(|ui| {
+                let ready = !self.running() && !self.form.ciphertext.trim().is_empty();
+                if ui.add_enabled(ready, egui::Button::new("Start")).clicked() {
+                    self.start(ctx);
+                }
+                if let Some(search) = &self.search {
+                    if ui.button("Stop").clicked() {
+                        search.stop();
+                    }
+                }
+            });
+        });
+
+        egui::TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.label(&self.status);
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Ciphertext");
+            ui.add_enabled(
+                !self.running(),
+                egui::TextEdit::multiline(&mut self.form.ciphertext)
+                    .desired_rows(4)
+                    .desired_width(f32::INFINITY)
+                    .hint_text("paste the intercept, anything but letters is dropped"),
+            );
+
+            ui.heading("Convergence");
+            self.chart(ui);
+
+            ui.heading("Best decryption");
+            if let Some((settings, plaintext)) = &self.best {
+                let fitness = self.generations.last().map_or(0.0, |g| g.best_fitness.0);
+                ui.monospace(format!("{}  (fitness {:.6})", settings, fitness));
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    ui.add(egui::Label::new(egui::RichText::new(plaintext).monospace()).wrap());
+                });
+            }
+        });
+    }
+}
+
+/// Opens the desktop window and returns when it's closed, stopping a running search.
+pub fn run() -> anyhow::Result<()> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("enigmagen")
+            .with_inner_size([1100.0, 720.0]),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "enigmagen",
+        options,
+        Box::new(|_| Ok(Box::<App>::default())),
+    )
+    .map_err(|err| anyhow!("gui failed: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    fn start(form: &Form) -> Search {
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        Search::start(ciphertext, form.options().unwrap(), || {})
+    }
+
+    #[test]
+    fn test_search() {
+        let form = Form {
+            population_size: 500,
+            generation_limit: 3,
+            seed: "3".to_string(),
+            ..Default::default()
+        };
+        let events = start(&form).events.iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(&events[2], Event::Finished(g, _) if g.iteration == 3));
+
+        let form = Form {
+            reflector: "E".to_string(),
+            ..Default::default()
+        };
+        assert!(form.options().is_err());
+        let form = Form {
+            seed: "x".to_string(),
+            ..Default::default()
+        };
+        assert!(form.options().is_err());
+    }
+
+    #[test]
+    fn test_stop() {
+        let search = start(&Form {
+            population_size: 500,
+            generation_limit: 100_000,
+            ..Default::default()
+        });
+        assert!(matches!(
+            search.events.recv().unwrap(),
+            Event::Generation(_)
+        ));
+        search.stop();
+        let last = search.events.iter().last().unwrap();
+        assert!(matches!(last, Event::Cancelled));
+    }
+}
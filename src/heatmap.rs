@@ -0,0 +1,264 @@
+// how the population spreads over the rotors and positions of each slot, generation by
+// generation, to watch it converge on parts of the key in a plotting tool
+use std::{fmt, fs, path::PathBuf, str::FromStr};
+
+use anyhow::ensure;
+
+use crate::{
+    enigma::{self, LETTERS_NUM, MAX_ROTOR_NUM},
+    sim::{Generation, StepResult},
+};
+
+const SLOTS: [&str; 3] = ["left", "middle", "right"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeatmapFormat {
+    // one row per slot and value, the long format pandas and ggplot pivot from
+    #[default]
+    Csv,
+    Json,
+}
+
+impl FromStr for HeatmapFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(HeatmapFormat::Csv),
+            "json" => Ok(HeatmapFormat::Json),
+            _ => Err(format!("unknown heatmap format {s}, expected csv or json")),
+        }
+    }
+}
+
+impl fmt::Display for HeatmapFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeatmapFormat::Csv => write!(f, "csv"),
+            HeatmapFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// How many of the sampled individuals of a generation (see `EngineOptions::sample_size`) have
+/// each rotor and each position in the left, middle and right slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    pub generation: u64,
+    pub keys: usize,
+    pub rotors: [[usize; MAX_ROTOR_NUM as usize]; 3],
+    pub positions: [[usize; LETTERS_NUM as usize]; 3],
+}
+
+impl Heatmap {
+    pub fn new(generation: &Generation) -> Self {
+        let mut rotors = [[0; MAX_ROTOR_NUM as usize]; 3];
+        let mut positions = [[0; LETTERS_NUM as usize]; 3];
+        for individual in &generation.sample {
+            let settings = &individual.genome;
+            let (r0, r1, r2) = settings.rotors;
+            let (p0, p1, p2) = settings.rotor_positions;
+            for (slot, (r, p)) in [(r0, p0), (r1, p1), (r2, p2)].into_iter().enumerate() {
+                rotors[slot][r as usize - 1] += 1;
+                positions[slot][p as usize - 1] += 1;
+            }
+        }
+        Self {
+            generation: generation.iteration,
+            keys: generation.sample.len(),
+            rotors,
+            positions,
+        }
+    }
+
+    // (component, slot, value, count) for every cell
+    fn cells(&self) -> impl Iterator<Item = (&'static str, &'static str, String, usize)> + '_ {
+        let rotors = SLOTS
+            .into_iter()
+            .zip(&self.rotors)
+            .flat_map(|(slot, counts)| {
+                counts
+                    .iter()
+                    .enumerate()
+                    .map(move |(r, &count)| ("rotor", slot, enigma::rotor_name(r as u8 + 1), count))
+            });
+        let positions = SLOTS
+            .into_iter()
+            .zip(&self.positions)
+            .flat_map(|(slot, counts)| {
+                counts.iter().enumerate().map(move |(p, &count)| {
+                    (
+                        "position",
+                        slot,
+                        enigma::letter(p as u8 + 1).to_string(),
+                        count,
+                    )
+                })
+            });
+        rotors.chain(positions)
+    }
+
+    pub fn csv(&self) -> String {
+        let mut csv = "generation,component,slot,value,count\n".to_string();
+        for (component, slot, value, count) in self.cells() {
+            csv += &format!("{},{component},{slot},{value},{count}\n", self.generation);
+        }
+        csv
+    }
+
+    // counts by slot, in the order of the values
+    pub fn json(&self) -> String {
+        fn matrix<const N: usize>(values: &[String], counts: &[[usize; N]; 3]) -> String {
+            let values = values
+                .iter()
+                .map(|v| format!("\"{v}\""))
+                .collect::<Vec<_>>();
+            let rows = counts
+                .iter()
+                .map(|row| format!("{row:?}").replace(' ', ""))
+                .collect::<Vec<_>>();
+            format!(
+                "{{\"slots\":[\"left\",\"middle\",\"right\"],\"values\":[{}],\"counts\":[{}]}}",
+                values.join(","),
+                rows.join(",")
+            )
+        }
+        let rotors = (1..=MAX_ROTOR_NUM)
+            .map(enigma::rotor_name)
+            .collect::<Vec<_>>();
+        let positions = (1..=LETTERS_NUM)
+            .map(|p| enigma::letter(p).to_string())
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"generation\":{},\"keys\":{},\"rotors\":{},\"positions\":{}}}\n",
+            self.generation,
+            self.keys,
+            matrix(&rotors, &self.rotors),
+            matrix(&positions, &self.positions)
+        )
+    }
+}
+
+/// Writes the heatmap of every `every`th generation and of the last one into a directory, a
+/// file per generation.
+#[derive(Debug)]
+pub struct HeatmapExport {
+    dir: PathBuf,
+    every: u64,
+    format: HeatmapFormat,
+}
+
+impl HeatmapExport {
+    pub fn create(dir: PathBuf, every: u64, format: HeatmapFormat) -> anyhow::Result<Self> {
+        ensure!(
+            every > 0,
+            "heatmaps are written every 1 or more generations"
+        );
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, every, format })
+    }
+
+    pub fn record(&mut self, step: &StepResult) -> anyhow::Result<()> {
+        let generation = step.generation();
+        let last = matches!(step, StepResult::Final { .. });
+        if !last && !generation.iteration.is_multiple_of(self.every) {
+            return Ok(());
+        }
+
+        let heatmap = Heatmap::new(generation);
+        let contents = match self.format {
+            HeatmapFormat::Csv => heatmap.csv(),
+            HeatmapFormat::Json => heatmap.json(),
+        };
+        let name = format!("generation-{:06}.{}", generation.iteration, self.format);
+        fs::write(self.dir.join(name), contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{engine::Individual, enigma::Settings, gen::Fitness};
+
+    use super::*;
+
+    #[test]
+    fn test_heatmap() {
+        let sample = [
+            "II V III / 01 01 01 / A B C",
+            "II V I / 01 01 01 / A B Z",
+            "IV V III / 01 01 01 / A D C",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(rank, key)| Individual {
+            rank,
+            genome: key.parse::<Settings>().unwrap(),
+            fitness: Fitness(0.05),
+        })
+        .collect::<Vec<_>>();
+        let generation = |iteration| Generation {
+            iteration,
+            average_fitness: Fitness(0.04),
+            median_fitness: Fitness(0.04),
+            fitness_std_dev: 0.01,
+            best_fitness: Fitness(0.05),
+            best_settings: "II V III / 01 01 01 / A B C".parse().unwrap(),
+            best_found_in: 1,
+            duration: Duration::ZERO,
+            processing_time: Duration::ZERO,
+            sample: sample.clone(),
+            top: vec![],
+            ancestry: vec![],
+            coverage: None,
+            invalid_genomes: None,
+        };
+
+        let heatmap = Heatmap::new(&generation(10));
+        assert_eq!(heatmap.keys, 3);
+        assert_eq!(heatmap.rotors[0], [0, 2, 0, 1, 0, 0]);
+        assert_eq!(heatmap.rotors[1], [0, 0, 0, 0, 3, 0]);
+        assert_eq!(heatmap.positions[0][0], 3);
+        assert_eq!(heatmap.positions[2][2], 2);
+        assert_eq!(heatmap.positions[2][25], 1);
+
+        let csv = heatmap.csv();
+        assert_eq!(csv.lines().count(), 1 + 3 * (6 + 26));
+        assert!(csv.contains("\n10,rotor,left,II,2\n"), "{csv}");
+        assert!(csv.contains("\n10,position,right,Z,1\n"));
+        let json = heatmap.json();
+        assert!(json.starts_with("{\"generation\":10,\"keys\":3,\"rotors\":{\"slots\":"));
+        assert!(json.contains("\"values\":[\"I\",\"II\",\"III\",\"IV\",\"V\",\"VI\"]"));
+        assert!(json.contains("\"counts\":[[0,2,0,1,0,0],[0,0,0,0,3,0],[1,0,2,0,0,0]]"));
+
+        // every other generation and the last one
+        let dir = std::env::temp_dir().join(format!("heatmap-{}", std::process::id()));
+        let mut export = HeatmapExport::create(dir.clone(), 2, HeatmapFormat::Json).unwrap();
+        for iteration in 1..=4 {
+            let step = StepResult::Intermediate(generation(iteration));
+            export.record(&step).unwrap();
+        }
+        let step = StepResult::Final {
+            generation: generation(5),
+            reason: "generation limit".to_string(),
+        };
+        export.record(&step).unwrap();
+        let mut files = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "generation-000002.json",
+                "generation-000004.json",
+                "generation-000005.json"
+            ]
+        );
+        fs::remove_dir_all(dir).unwrap();
+        assert!(HeatmapExport::create(std::env::temp_dir(), 0, HeatmapFormat::Csv).is_err());
+    }
+}
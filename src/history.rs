@@ -0,0 +1,290 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{
+    gen::{Fitness, Options},
+    sim::{Generation, StepResult},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        started_at TEXT NOT NULL,
+        ciphertext TEXT NOT NULL,
+        seed INTEGER,
+        population_size INTEGER NOT NULL,
+        max_plugs INTEGER NOT NULL,
+        reflector TEXT NOT NULL,
+        config TEXT NOT NULL,
+        finished_at TEXT,
+        reason TEXT,
+        generations INTEGER,
+        best_fitness REAL,
+        best_key TEXT
+    );
+    CREATE TABLE IF NOT EXISTS generations (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        iteration INTEGER NOT NULL,
+        average_fitness REAL NOT NULL,
+        median_fitness REAL NOT NULL,
+        fitness_std_dev REAL NOT NULL,
+        best_fitness REAL NOT NULL,
+        best_key TEXT NOT NULL,
+        duration_ms INTEGER NOT NULL,
+        PRIMARY KEY (run_id, iteration)
+    );
+";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub id: i64,
+    pub started_at: String,
+    pub ciphertext: String,
+    pub seed: Option<u64>,
+    pub config: String,
+    // empty while the run is going or if it was interrupted
+    pub finished_at: Option<String>,
+    pub reason: Option<String>,
+    pub generations: Option<u64>,
+    pub best_fitness: Option<Fitness>,
+    pub best_key: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationRecord {
+    pub iteration: u64,
+    pub average_fitness: Fitness,
+    pub median_fitness: Fitness,
+    pub fitness_std_dev: f64,
+    pub best_fitness: Fitness,
+    pub best_key: String,
+    pub duration_ms: u64,
+}
+
+/// Run history kept in a SQLite database: configuration and seed of every run, its
+/// per-generation statistics and the final result.
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    pub fn start_run(&self, ciphertext: &str, opts: &Options) -> anyhow::Result<i64> {
+        self.conn.execute(
+            "INSERT INTO runs (started_at, ciphertext, seed, population_size, max_plugs, reflector, config)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                now(),
+                ciphertext,
+                // sqlite integers are signed, the seed is stored bit for bit
+                opts.engine.seed.map(|seed| seed as i64),
+                opts.engine.population_size as i64,
+                opts.engine.max_plugs as i64,
+                opts.engine.reflector.to_string(),
+                format!("{:?}", opts),
+            ],
+        )?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn record(&self, run: i64, step: &StepResult) -> anyhow::Result<()> {
+        self.record_generation(run, step.generation())?;
+
+        if let StepResult::Final { generation, reason } = step {
+            self.conn.execute(
+                "UPDATE runs SET finished_at = ?2, reason = ?3, generations = ?4, best_fitness = ?5, best_key = ?6
+                 WHERE id = ?1",
+                params![
+                    run,
+                    now(),
+                    reason,
+                    generation.iteration as i64,
+                    generation.best_fitness.0,
+                    generation.best_settings.to_string(),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn record_generation(&self, run: i64, generation: &Generation) -> anyhow::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO generations
+             (run_id, iteration, average_fitness, median_fitness, fitness_std_dev, best_fitness, best_key, duration_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                run,
+                generation.iteration as i64,
+                generation.average_fitness.0,
+                generation.median_fitness.0,
+                generation.fitness_std_dev,
+                generation.best_fitness.0,
+                generation.best_settings.to_string(),
+                generation.duration.as_millis() as i64,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    // most recent first
+    pub fn runs(&self, limit: usize) -> anyhow::Result<Vec<Run>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM runs ORDER BY id DESC LIMIT ?1",
+            RUN_COLUMNS
+        ))?;
+        let runs = stmt
+            .query_map([limit as i64], run_from_row)?
+            .collect::<Result<_, _>>()?;
+
+        Ok(runs)
+    }
+
+    pub fn run(&self, id: i64) -> anyhow::Result<Run> {
+        self.conn
+            .query_row(
+                &format!("SELECT {} FROM runs WHERE id = ?1", RUN_COLUMNS),
+                [id],
+                run_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| anyhow!("no run {} in the history", id))
+    }
+
+    pub fn generations(&self, run: i64) -> anyhow::Result<Vec<GenerationRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT iteration, average_fitness, median_fitness, fitness_std_dev, best_fitness, best_key, duration_ms
+             FROM generations WHERE run_id = ?1 ORDER BY iteration",
+        )?;
+        let generations = stmt
+            .query_map([run], |row| {
+                Ok(GenerationRecord {
+                    iteration: row.get::<_, i64>(0)? as u64,
+                    average_fitness: Fitness(row.get(1)?),
+                    median_fitness: Fitness(row.get(2)?),
+                    fitness_std_dev: row.get(3)?,
+                    best_fitness: Fitness(row.get(4)?),
+                    best_key: row.get(5)?,
+                    duration_ms: row.get::<_, i64>(6)? as u64,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(generations)
+    }
+}
+
+const RUN_COLUMNS: &str =
+    "id, started_at, ciphertext, seed, config, finished_at, reason, generations, best_fitness, best_key";
+
+fn run_from_row(row: &rusqlite::Row) -> rusqlite::Result<Run> {
+    Ok(Run {
+        id: row.get(0)?,
+        started_at: row.get(1)?,
+        ciphertext: row.get(2)?,
+        seed: row.get::<_, Option<i64>>(3)?.map(|seed| seed as u64),
+        config: row.get(4)?,
+        finished_at: row.get(5)?,
+        reason: row.get(6)?,
+        generations: row.get::<_, Option<i64>>(7)?.map(|n| n as u64),
+        best_fitness: row.get::<_, Option<f64>>(8)?.map(Fitness),
+        best_key: row.get(9)?,
+    })
+}
+
+fn now() -> String {
+    chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::enigma::Settings;
+
+    use super::*;
+
+    fn generation(iteration: u64, best: f64) -> Generation {
+        Generation {
+            iteration,
+            average_fitness: Fitness(best / 2.0),
+            median_fitness: Fitness(best / 3.0),
+            fitness_std_dev: 0.01,
+            best_fitness: Fitness(best),
+            best_settings: "II V III / 08 05 20 / M C U / AB CZ"
+                .parse::<Settings>()
+                .unwrap(),
+            best_found_in: iteration,
+            duration: Duration::from_millis(1500),
+            processing_time: Duration::from_millis(700),
+            sample: Vec::new(),
+            top: Vec::new(),
+            ancestry: Vec::new(),
+            coverage: None,
+            invalid_genomes: None,
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let history = History::open_in_memory().unwrap();
+        let mut opts = Options::default();
+        opts.engine.seed = Some(u64::MAX);
+
+        let first = history.start_run("ABC", &opts).unwrap();
+        history
+            .record(first, &StepResult::Intermediate(generation(1, 0.04)))
+            .unwrap();
+        history
+            .record(
+                first,
+                &StepResult::Final {
+                    generation: generation(2, 0.06),
+                    reason: "target fitness reached".to_string(),
+                },
+            )
+            .unwrap();
+        let second = history.start_run("DEF", &Options::default()).unwrap();
+
+        let runs = history.runs(10).unwrap();
+        assert_eq!(
+            runs.iter().map(|r| r.id).collect::<Vec<_>>(),
+            [second, first]
+        );
+        assert_eq!(runs[0].reason, None);
+
+        let run = history.run(first).unwrap();
+        assert_eq!(run.seed, Some(u64::MAX));
+        assert_eq!(run.ciphertext, "ABC");
+        assert_eq!(run.generations, Some(2));
+        assert_eq!(run.best_fitness, Some(Fitness(0.06)));
+        assert_eq!(
+            run.best_key.as_deref(),
+            Some("II V III / 08 05 20 / M C U / AB CZ")
+        );
+
+        let generations = history.generations(first).unwrap();
+        assert_eq!(generations.len(), 2);
+        assert_eq!(generations[0].median_fitness, Fitness(0.04 / 3.0));
+        assert_eq!(generations[1].duration_ms, 1500);
+
+        assert!(history.run(second + 1).is_err());
+        assert_eq!(history.runs(1).unwrap().len(), 1);
+    }
+}
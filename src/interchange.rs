@@ -0,0 +1,236 @@
+use std::{collections::HashMap, fmt::Write as _, str::FromStr};
+
+use anyhow::{anyhow, bail, ensure};
+
+use crate::enigma::{letter, pairs_notation, parse_plug, rotor_name, Reflector, Settings};
+
+// how a recovered key is written out for other tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyFormat {
+    // "II V III / 08 05 20 / M C U / AB CZ"
+    #[default]
+    Sheet,
+    // one setting per line, ring settings and positions as letters, as CrypTool 2
+    // and online simulators take them
+    CrypTool,
+    // the builder call of the enigma-simulator crate
+    EnigmaSimulator,
+}
+
+impl FromStr for KeyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sheet" => Ok(KeyFormat::Sheet),
+            "cryptool" => Ok(KeyFormat::CrypTool),
+            "enigma-simulator" => Ok(KeyFormat::EnigmaSimulator),
+            _ => Err(format!(
+                "unknown key format {s}, expected sheet, cryptool or enigma-simulator"
+            )),
+        }
+    }
+}
+
+pub fn export(settings: &Settings, format: KeyFormat) -> anyhow::Result<String> {
+    settings.validate()?;
+
+    match format {
+        KeyFormat::Sheet => Ok(settings.to_string()),
+        KeyFormat::CrypTool => Ok(export_cryptool(settings)),
+        KeyFormat::EnigmaSimulator => export_enigma_simulator(settings),
+    }
+}
+
+pub fn import(key: &str, format: KeyFormat) -> anyhow::Result<Settings> {
+    let settings = match format {
+        KeyFormat::Sheet => key.parse()?,
+        KeyFormat::CrypTool => import_cryptool(key)?,
+        KeyFormat::EnigmaSimulator => import_enigma_simulator(key)?,
+    };
+    settings.validate()?;
+
+    Ok(settings)
+}
+
+fn export_cryptool(s: &Settings) -> String {
+    let (r0, r1, r2) = s.rotors;
+    let (s0, s1, s2) = s.ring_settings;
+    let (p0, p1, p2) = s.rotor_positions;
+
+    let mut out = String::new();
+    let _ = writeln!(out, "Model = Enigma I");
+    let _ = writeln!(out, "Reflector = UKW {}", s.reflector);
+    let _ = writeln!(out, "Rotors = {}", [r0, r1, r2].map(rotor_name).join(", "));
+    let _ = writeln!(out, "Rings = {}", letters([s0, s1, s2]));
+    let _ = writeln!(out, "Key = {}", letters([p0, p1, p2]));
+    let _ = writeln!(out, "Plugboard = {}", pairs_notation(&s.plugboard));
+    out
+}
+
+fn import_cryptool(key: &str) -> anyhow::Result<Settings> {
+    let fields = key
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim()))
+        .collect::<HashMap<_, _>>();
+    let field = |name: &str| {
+        fields
+            .get(name)
+            .copied()
+            .ok_or_else(|| anyhow!("missing field {:?}", name))
+    };
+
+    let reflector = field("reflector")?;
+    let reflector = reflector.strip_prefix("UKW").unwrap_or(reflector).trim();
+    let sheet = format!(
+        "{} / {} / {} / {} / {}",
+        reflector,
+        field("rotors")?.replace(',', " "),
+        parse_letters(field("rings")?)?
+            .map(|n| format!("{:02}", n))
+            .join(" "),
+        field("key")?.replace(',', " "),
+        fields.get("plugboard").copied().unwrap_or_default()
+    );
+
+    sheet.trim_end_matches(['/', ' ']).parse()
+}
+
+// the simulator takes rotors and letters as 1-based numbers and only the fixed reflectors
+fn export_enigma_simulator(s: &Settings) -> anyhow::Result<String> {
+    let reflector = match s.reflector {
+        Reflector::B => "B",
+        Reflector::C => "C",
+        Reflector::D(_) => bail!("enigma-simulator has no rewirable reflector"),
+    };
+    let (r0, r1, r2) = s.rotors;
+    let (s0, s1, s2) = s.ring_settings;
+    let (p0, p1, p2) = s.rotor_positions;
+
+    Ok(format!(
+        "EnigmaMachine::new().reflector({:?}).plugboard({:?}).rotors({}, {}, {})\
+         .ring_settings({}, {}, {}).ring_positions({}, {}, {})",
+        reflector,
+        pairs_notation(&s.plugboard),
+        r0,
+        r1,
+        r2,
+        s0,
+        s1,
+        s2,
+        p0,
+        p1,
+        p2
+    ))
+}
+
+fn import_enigma_simulator(key: &str) -> anyhow::Result<Settings> {
+    let call = |method: &str| {
+        let start = key
+            .find(&format!(".{}(", method))
+            .ok_or_else(|| anyhow!("missing call to {}", method))?
+            + method.len()
+            + 2;
+        let end = key[start..]
+            .find(')')
+            .ok_or_else(|| anyhow!("unclosed call to {}", method))?;
+        anyhow::Ok(&key[start..start + end])
+    };
+    let numbers = |method: &str| -> anyhow::Result<(u8, u8, u8)> {
+        let values = call(method)?
+            .split(',')
+            .map(|v| Ok(v.trim().parse::<u8>()?))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        match values[..] {
+            [a, b, c] => Ok((a, b, c)),
+            _ => Err(anyhow!("{} takes 3 numbers, got {:?}", method, values)),
+        }
+    };
+    let string = |method: &str| -> anyhow::Result<&str> {
+        let arg = call(method)?.trim();
+        ensure!(
+            arg.len() >= 2 && arg.starts_with('"') && arg.ends_with('"'),
+            "{} takes a string, got {:?}",
+            method,
+            arg
+        );
+        Ok(&arg[1..arg.len() - 1])
+    };
+
+    Ok(Settings {
+        rotors: numbers("rotors")?,
+        ring_settings: numbers("ring_settings")?,
+        rotor_positions: numbers("ring_positions")?,
+        plugboard: string("plugboard")?
+            .split_whitespace()
+            .map(parse_plug)
+            .collect::<anyhow::Result<_>>()?,
+        reflector: string("reflector")?.parse()?,
+    })
+}
+
+fn letters(values: [u8; 3]) -> String {
+    values.map(|v| letter(v).to_string()).join(", ")
+}
+
+fn parse_letters(s: &str) -> anyhow::Result<[u8; 3]> {
+    let values = s
+        .split([',', ' '])
+        .filter(|v| !v.is_empty())
+        .map(|v| match v.as_bytes() {
+            &[c] if c.is_ascii_alphabetic() => Ok(c.to_ascii_uppercase() - b'A' + 1),
+            _ => Err(anyhow!("expected a letter, got {:?}", v)),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    values
+        .try_into()
+        .map_err(|values| anyhow!("expected 3 letters, got {:?}", values))
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::enigma::{strategy, MAX_PLUGS_NUM};
+
+    use super::*;
+
+    const KEY: &str = "II V III / 08 05 20 / M C U / AB CZ";
+
+    #[test]
+    fn test_export() {
+        let settings = KEY.parse::<Settings>().unwrap();
+
+        assert_eq!(
+            export(&settings, KeyFormat::CrypTool).unwrap(),
+            "Model = Enigma I\nReflector = UKW B\nRotors = II, V, III\n\
+             Rings = H, E, T\nKey = M, C, U\nPlugboard = AB CZ\n"
+        );
+        assert_eq!(
+            export(&settings, KeyFormat::EnigmaSimulator).unwrap(),
+            "EnigmaMachine::new().reflector(\"B\").plugboard(\"AB CZ\").rotors(2, 5, 3)\
+             .ring_settings(8, 5, 20).ring_positions(13, 3, 21)"
+        );
+
+        let rewired = Settings {
+            reflector: "D AB CD EF GH IJ KL MN OP QR ST UV WX YZ".parse().unwrap(),
+            ..settings
+        };
+        assert!(export(&rewired, KeyFormat::EnigmaSimulator).is_err());
+        assert!(import("Rotors = II, V, III", KeyFormat::CrypTool).is_err());
+        assert!(import("EnigmaMachine::new()", KeyFormat::EnigmaSimulator).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_roundtrip(settings in strategy::settings(MAX_PLUGS_NUM)) {
+            for format in [KeyFormat::Sheet, KeyFormat::CrypTool, KeyFormat::EnigmaSimulator] {
+                if let Ok(key) = export(&settings, format) {
+                    prop_assert_eq!(&import(&key, format).unwrap(), &settings);
+                }
+            }
+        }
+    }
+}
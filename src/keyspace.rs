@@ -0,0 +1,369 @@
+// every key of a part of the Enigma I keyspace in a fixed order, so that an index names a key:
+// index ranges split a scan between workers or machines without overlap, and a scan cut short
+// resumes from the index it reached
+use std::ops::Range;
+
+use anyhow::{anyhow, ensure};
+
+use crate::{
+    enigma::{Reflector, Settings, LETTERS_NUM, MAX_PLUGS_NUM},
+    gen::ReflectorSearch,
+    pipeline,
+};
+
+const LETTERS: usize = LETTERS_NUM as usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Plugboards {
+    // the plugboard of every key, e.g. from a recovered daily key
+    Fixed(Vec<(u8, u8)>),
+    // any pairs up to this many
+    UpTo(usize),
+}
+
+/// The choices of every part of the key, the keyspace holds all their combinations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraints {
+    pub rotor_orders: Vec<(u8, u8, u8)>,
+    pub ring_settings: Vec<(u8, u8, u8)>,
+    pub rotor_positions: Vec<(u8, u8, u8)>,
+    pub plugboards: Plugboards,
+}
+
+impl Default for Constraints {
+    // the keyspace of a search without plugs
+    fn default() -> Self {
+        Self {
+            rotor_orders: pipeline::rotor_orders(),
+            ring_settings: letter_triples(),
+            rotor_positions: letter_triples(),
+            plugboards: Plugboards::UpTo(0),
+        }
+    }
+}
+
+// all 26³ ring settings or rotor positions, AAA to ZZZ
+pub fn letter_triples() -> Vec<(u8, u8, u8)> {
+    let letters = 1..=LETTERS_NUM;
+    letters
+        .clone()
+        .flat_map(|a| letters.clone().map(move |b| (a, b)))
+        .flat_map(|(a, b)| letters.clone().map(move |c| (a, b, c)))
+        .collect()
+}
+
+/// Keys with the reflectors the search chooses from and the constraints, numbered from 0 in
+/// the order of the rotor order, reflector, ring settings, rotor positions and plugboard, the
+/// plugboard changing fastest. Plugboards are ordered by their number of pairs, then by their
+/// pairs, lower letter first.
+#[derive(Debug, Clone)]
+pub struct Keyspace {
+    model: ReflectorSearch,
+    constraints: Constraints,
+    // number of ways to wire k disjoint pairs among n letters, at [n][k]
+    matchings: Vec<Vec<u128>>,
+    len: u128,
+}
+
+impl Keyspace {
+    pub fn new(model: &ReflectorSearch, constraints: Constraints) -> anyhow::Result<Self> {
+        if let ReflectorSearch::Known(reflector) = model {
+            reflector.validate()?;
+        }
+        let base = Settings {
+            rotors: (1, 2, 3),
+            ring_settings: (1, 1, 1),
+            rotor_positions: (1, 1, 1),
+            plugboard: vec![],
+            reflector: Reflector::B,
+        };
+        for &rotors in &constraints.rotor_orders {
+            Settings {
+                rotors,
+                ..base.clone()
+            }
+            .validate()?;
+        }
+        for &ring_settings in &constraints.ring_settings {
+            Settings {
+                ring_settings,
+                ..base.clone()
+            }
+            .validate()?;
+        }
+        for &rotor_positions in &constraints.rotor_positions {
+            Settings {
+                rotor_positions,
+                ..base.clone()
+            }
+            .validate()?;
+        }
+        match &constraints.plugboards {
+            Plugboards::Fixed(plugboard) => Settings {
+                plugboard: plugboard.clone(),
+                ..base
+            }
+            .validate()?,
+            &Plugboards::UpTo(max_plugs) => ensure!(
+                max_plugs <= MAX_PLUGS_NUM,
+                "max_plugs must be at most {}",
+                MAX_PLUGS_NUM
+            ),
+        }
+
+        let mut keyspace = Self {
+            model: model.clone(),
+            constraints,
+            matchings: matchings(),
+            len: 0,
+        };
+        let c = &keyspace.constraints;
+        keyspace.len = [
+            c.rotor_orders.len() as u128,
+            keyspace.reflectors(),
+            c.ring_settings.len() as u128,
+            c.rotor_positions.len() as u128,
+            keyspace.plugboards(),
+        ]
+        .into_iter()
+        .try_fold(1_u128, u128::checked_mul)
+        .ok_or_else(|| anyhow!("the keyspace has more than 2^128 keys"))?;
+        Ok(keyspace)
+    }
+
+    pub fn len(&self) -> u128 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The key at the index, none past the end.
+    pub fn get(&self, index: u128) -> Option<Settings> {
+        if index >= self.len {
+            return None;
+        }
+        let c = &self.constraints;
+        let mut index = index;
+        let mut digit = |radix: u128| {
+            let digit = index % radix;
+            index /= radix;
+            digit
+        };
+        let plugboard = digit(self.plugboards());
+        let rotor_positions = digit(c.rotor_positions.len() as u128) as usize;
+        let ring_settings = digit(c.ring_settings.len() as u128) as usize;
+        let reflector = digit(self.reflectors());
+        let rotors = digit(c.rotor_orders.len() as u128) as usize;
+
+        Some(Settings {
+            rotors: c.rotor_orders[rotors],
+            ring_settings: c.ring_settings[ring_settings],
+            rotor_positions: c.rotor_positions[rotor_positions],
+            plugboard: self.plugboard(plugboard),
+            reflector: self.reflector(reflector),
+        })
+    }
+
+    pub fn iter(&self) -> Keys<'_> {
+        self.range(0..self.len)
+    }
+
+    pub fn range(&self, range: Range<u128>) -> Keys<'_> {
+        let next = range.start.min(self.len);
+        Keys {
+            keyspace: self,
+            next,
+            end: range.end.clamp(next, self.len),
+        }
+    }
+
+    /// Shard `index` of `count` near-equal ranges that cover the keyspace in order.
+    pub fn shard(&self, index: u128, count: u128) -> Keys<'_> {
+        assert!(index < count, "shard {} of {}", index, count);
+        let (size, rest) = (self.len / count, self.len % count);
+        let start = index * size + index.min(rest);
+        let end = start + size + (index < rest) as u128;
+        self.range(start..end)
+    }
+
+    fn reflectors(&self) -> u128 {
+        match self.model {
+            ReflectorSearch::Rewirable => self.matchings[LETTERS][MAX_PLUGS_NUM],
+            ref model => model.choices(),
+        }
+    }
+
+    fn reflector(&self, index: u128) -> Reflector {
+        match &self.model {
+            ReflectorSearch::Known(reflector) => reflector.clone(),
+            ReflectorSearch::Standard => [Reflector::B, Reflector::C][index as usize].clone(),
+            ReflectorSearch::Rewirable => Reflector::D(self.matching(MAX_PLUGS_NUM, index)),
+        }
+    }
+
+    fn plugboards(&self) -> u128 {
+        match self.constraints.plugboards {
+            Plugboards::Fixed(_) => 1,
+            Plugboards::UpTo(max_plugs) => self.matchings[LETTERS][..=max_plugs].iter().sum(),
+        }
+    }
+
+    fn plugboard(&self, mut index: u128) -> Vec<(u8, u8)> {
+        let max_plugs = match &self.constraints.plugboards {
+            Plugboards::Fixed(plugboard) => return plugboard.clone(),
+            &Plugboards::UpTo(max_plugs) => max_plugs,
+        };
+        for plugs in 0..=max_plugs {
+            let with_plugs = self.matchings[LETTERS][plugs];
+            if index < with_plugs {
+                return self.matching(plugs, index);
+            }
+            index -= with_plugs;
+        }
+        unreachable!("the index is within the plugboards")
+    }
+
+    // the pairs at the index among the ways to wire that many: the lowest free letter is paired
+    // with every higher free letter in turn, then left unpaired
+    fn matching(&self, mut pairs: usize, mut index: u128) -> Vec<(u8, u8)> {
+        let mut free = (1..=LETTERS_NUM).collect::<Vec<_>>();
+        let mut matching = Vec::with_capacity(pairs);
+        while pairs > 0 {
+            let n = free.len();
+            let per_partner = self.matchings[n - 2][pairs - 1];
+            let paired = (n as u128 - 1) * per_partner;
+            if index >= paired {
+                index -= paired;
+                free.remove(0);
+                continue;
+            }
+            let partner = free.remove(1 + (index / per_partner) as usize);
+            matching.push((free.remove(0), partner));
+            index %= per_partner;
+            pairs -= 1;
+        }
+        matching
+    }
+}
+
+fn matchings() -> Vec<Vec<u128>> {
+    let mut matchings = vec![vec![0_u128; MAX_PLUGS_NUM + 1]; LETTERS + 1];
+    for n in 0..=LETTERS {
+        matchings[n][0] = 1;
+        for k in 1..=MAX_PLUGS_NUM.min(n / 2) {
+            matchings[n][k] = matchings[n - 1][k] + (n as u128 - 1) * matchings[n - 2][k - 1];
+        }
+    }
+    matchings
+}
+
+/// Keys of a range of the keyspace in order.
+#[derive(Debug, Clone)]
+pub struct Keys<'a> {
+    keyspace: &'a Keyspace,
+    next: u128,
+    end: u128,
+}
+
+impl Keys<'_> {
+    // index of the next key, where a scan stopped here resumes
+    pub fn position(&self) -> u128 {
+        self.next
+    }
+}
+
+impl Iterator for Keys<'_> {
+    type Item = Settings;
+
+    fn next(&mut self) -> Option<Settings> {
+        if self.next >= self.end {
+            return None;
+        }
+        self.next += 1;
+        self.keyspace.get(self.next - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.end - self.next;
+        (
+            left.min(usize::MAX as u128) as usize,
+            usize::try_from(left).ok(),
+        )
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Settings> {
+        self.next = self.next.saturating_add(n as u128).min(self.end);
+        self.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::enigma;
+
+    use super::*;
+
+    #[test]
+    fn test_keyspace() {
+        let keyspace = Keyspace::new(&ReflectorSearch::default(), Constraints::default());
+        assert_eq!(keyspace.unwrap().len(), enigma::keyspace_size(0));
+        let constraints = Constraints {
+            plugboards: Plugboards::UpTo(MAX_PLUGS_NUM),
+            ..Default::default()
+        };
+        let keyspace = Keyspace::new(&ReflectorSearch::Standard, constraints).unwrap();
+        assert_eq!(keyspace.len(), enigma::keyspace_size(MAX_PLUGS_NUM) * 2);
+        let last = keyspace.get(keyspace.len() - 1).unwrap();
+        let last_plugboard = "AZ BY CX DW EV FU GT HS IR JQ KP LO MN";
+        assert_eq!(
+            last.to_string(),
+            format!("C / VI V IV / 26 26 26 / Z Z Z / {last_plugboard}")
+        );
+        assert!(keyspace.get(keyspace.len()).is_none());
+
+        let constraints = Constraints {
+            rotor_orders: vec![(2, 5, 3)],
+            ring_settings: vec![(1, 5, 20)],
+            rotor_positions: vec![(13, 3, 21), (1, 1, 1)],
+            plugboards: Plugboards::UpTo(2),
+        };
+        let keyspace = Keyspace::new(&ReflectorSearch::Standard, constraints.clone()).unwrap();
+        assert_eq!(keyspace.len(), 2 * 2 * (1 + 325 + 325 * 276 / 2));
+        let keys = keyspace.iter().collect::<Vec<_>>();
+        assert_eq!(keys.len() as u128, keyspace.len());
+        assert!(keys.iter().all(|key| key.validate().is_ok()));
+        assert_eq!(keys.iter().collect::<HashSet<_>>().len(), keys.len());
+        assert_eq!(keys[0].to_string(), "II V III / 01 05 20 / M C U");
+        assert_eq!(keys[1].plugboard, [(1, 2)]);
+
+        // shards cover the keyspace in order, a scan resumes where it stopped
+        let shards = (0..7)
+            .flat_map(|i| keyspace.shard(i, 7))
+            .collect::<Vec<_>>();
+        assert_eq!(shards, keys);
+        let mut scan = keyspace.range(100..200);
+        assert_eq!(scan.nth(9), Some(keys[109].clone()));
+        assert_eq!(scan.position(), 110);
+        assert_eq!(keyspace.range(110..200).collect::<Vec<_>>(), keys[110..200]);
+
+        let rewirable = Keyspace::new(&ReflectorSearch::Rewirable, constraints).unwrap();
+        assert_eq!(
+            rewirable.len(),
+            keyspace.len() / 2 * ReflectorSearch::Rewirable.choices()
+        );
+        let wirings = (0..1000)
+            .map(|i| rewirable.get(i * rewirable.len() / 1000).unwrap())
+            .collect::<Vec<_>>();
+        assert!(wirings.iter().all(|key| key.validate().is_ok()));
+
+        let invalid = Constraints {
+            rotor_orders: vec![(1, 1, 2)],
+            ..Default::default()
+        };
+        assert!(Keyspace::new(&ReflectorSearch::default(), invalid).is_err());
+    }
+}
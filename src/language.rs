@@ -0,0 +1,384 @@
+// letter statistics of the plaintext languages and telling them apart
+use std::{fmt, str::FromStr, sync::OnceLock};
+
+use crate::{
+    enigma::normalize,
+    metrics::{self, ENGLISH_FREQUENCIES, ENGLISH_SAMPLE},
+};
+
+// public domain German prose, umlauts spelled out as AE, OE, UE and SS as in wartime traffic
+pub(crate) const GERMAN_SAMPLE: &str = include_str!("data/german.txt");
+
+// relative letter frequencies of German text with umlauts spelled out, A..Z
+pub const GERMAN_FREQUENCIES: [f64; 26] = [
+    0.0651, 0.0189, 0.0306, 0.0508, 0.1740, 0.0166, 0.0301, 0.0476, 0.0755, 0.0027, 0.0121, 0.0344,
+    0.0253, 0.0978, 0.0251, 0.0079, 0.0002, 0.0700, 0.0727, 0.0615, 0.0435, 0.0067, 0.0189, 0.0003,
+    0.0004, 0.0113,
+];
+
+// fewer letter pairs say little about the language
+const MIN_DETECTION_BIGRAMS: usize = 20;
+
+// expectation maximization steps of the corruption rate, converged well before
+const CORRUPTION_ITERATIONS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::German];
+
+    pub fn frequencies(&self) -> &'static [f64; 26] {
+        match self {
+            Language::English => &ENGLISH_FREQUENCIES,
+            Language::German => &GERMAN_FREQUENCIES,
+        }
+    }
+
+    // ln of the probability of every letter pair, add-one smoothed
+    fn bigrams(&self) -> &'static [[f64; 26]; 26] {
+        static ENGLISH: OnceLock<[[f64; 26]; 26]> = OnceLock::new();
+        static GERMAN: OnceLock<[[f64; 26]; 26]> = OnceLock::new();
+
+        let (table, sample) = match self {
+            Language::English => (&ENGLISH, ENGLISH_SAMPLE),
+            Language::German => (&GERMAN, GERMAN_SAMPLE),
+        };
+        table.get_or_init(|| {
+            let mut counts = [[1.0; 26]; 26];
+            metrics::for_each_bigram(&normalize(sample), |a, b| counts[a][b] += 1.0);
+
+            let total = counts.iter().flatten().sum::<f64>();
+            counts.map(|row| row.map(|count| (count / total).ln()))
+        })
+    }
+
+    // ln of the probability of the letter pair a, b, A = 0
+    pub(crate) fn bigram_log_prob(&self, a: usize, b: usize) -> f64 {
+        self.bigrams()[a][b]
+    }
+
+    // chi-squared distance of the letter frequencies from the language, 0 for a perfect match
+    pub fn chi_squared(&self, text: &str) -> f64 {
+        let (hist, n) = metrics::letter_counts(text);
+        if n == 0 {
+            return f64::INFINITY;
+        }
+
+        hist.iter()
+            .zip(self.frequencies())
+            .map(|(&count, &expected)| {
+                let observed = count as f64 / n as f64;
+                (observed - expected).powi(2) / expected
+            })
+            .sum()
+    }
+
+    // chi_squared mapped to (0, 1], higher is closer to the language
+    pub fn similarity(&self, text: &str) -> f64 {
+        1.0 / (1.0 + self.chi_squared(text))
+    }
+
+    // Sinkov's statistic: the mean ln of the probability of the letters, as their geometric
+    // mean in (0, 1); a letter that is rare in the language costs, where the IoC only sees repeats
+    pub fn sinkov(&self, text: &str) -> f64 {
+        let (hist, n) = metrics::letter_counts(text);
+        self.sinkov_of(&hist, n)
+    }
+
+    pub(crate) fn sinkov_of(&self, hist: &[usize; 26], n: usize) -> f64 {
+        if n == 0 {
+            return 0.0;
+        }
+
+        let log_likelihood = hist
+            .iter()
+            .zip(self.frequencies())
+            .map(|(&count, &p)| count as f64 * p.ln())
+            .sum::<f64>();
+        (log_likelihood / n as f64).exp()
+    }
+
+    // Sinkov's statistic of evenly spread letters and of text of the language
+    pub(crate) fn sinkov_range(&self) -> (f64, f64) {
+        let frequencies = self.frequencies();
+        let random = frequencies.iter().map(|p| p.ln()).sum::<f64>() / 26.0;
+        let language = frequencies.iter().map(|p| p * p.ln()).sum::<f64>();
+        (random.exp(), language.exp())
+    }
+
+    // bigram_score of evenly spread letter pairs and of text of the language
+    pub(crate) fn bigram_range(&self) -> (f64, f64) {
+        let bigrams = self.bigrams().iter().flatten();
+        let random = bigrams.clone().sum::<f64>() / (26.0 * 26.0);
+        let language = bigrams.map(|&ln_p| ln_p.exp() * ln_p).sum::<f64>();
+        (random.exp(), language.exp())
+    }
+
+    // ln of the probability of the letter pairs of the text and their number
+    fn log_likelihood(&self, text: &str) -> (f64, usize) {
+        let bigrams = self.bigrams();
+        let (mut log_likelihood, mut n) = (0.0, 0);
+
+        metrics::for_each_bigram(text, |a, b| {
+            log_likelihood += bigrams[a][b];
+            n += 1;
+        });
+
+        (log_likelihood, n)
+    }
+
+    // geometric mean of the probabilities of the letter pairs, in (0, 1), higher is better;
+    // unlike the index of coincidence it tells apart texts with the same letter frequencies
+    pub fn bigram_score(&self, text: &str) -> f64 {
+        match self.log_likelihood(text) {
+            (_, 0) => 0.0,
+            (log_likelihood, n) => (log_likelihood / n as f64).exp(),
+        }
+    }
+
+    /// bigram_score of the trusted letter pairs of a text with a share `corrupted` of its
+    /// letters wrong: a wrong letter spoils the pairs it is in, so the least likely
+    /// 1 - (1 - corrupted)^2 of the pairs are left out.
+    pub fn trimmed_bigram_score(&self, text: &str, corrupted: f64) -> f64 {
+        let bigrams = self.bigrams();
+        let mut log_probs = Vec::new();
+        metrics::for_each_bigram(text, |a, b| log_probs.push(bigrams[a][b]));
+        log_probs.sort_by(|a, b| b.total_cmp(a));
+
+        match trusted(log_probs.len(), corrupted) {
+            0 => 0.0,
+            n => (log_probs[..n].iter().sum::<f64>() / n as f64).exp(),
+        }
+    }
+
+    // trimmed_bigram_score of evenly spread letter pairs and of text of the language: the
+    // most likely pairs up to the trusted share of them, or of their probability
+    pub(crate) fn trimmed_bigram_range(&self, corrupted: f64) -> (f64, f64) {
+        let mut log_probs = self.bigrams().iter().flatten().copied().collect::<Vec<_>>();
+        log_probs.sort_by(|a, b| b.total_cmp(a));
+
+        let n = trusted(log_probs.len(), corrupted);
+        let random = log_probs[..n].iter().sum::<f64>() / n as f64;
+        let share = (1.0 - corrupted).powi(2);
+        let (mut mass, mut log_likelihood) = (0.0, 0.0);
+        for &ln_p in &log_probs {
+            let p = ln_p.exp().min(share - mass);
+            if p <= 0.0 {
+                break;
+            }
+            mass += p;
+            log_likelihood += p * ln_p;
+        }
+        (random.exp(), (log_likelihood / mass).exp())
+    }
+
+    /// Estimated share of wrong letters in a decryption: every letter is taken as either
+    /// following the one before as in the language or as any letter at all, and the share of
+    /// the latter is fitted by expectation maximization.
+    pub fn corruption_rate(&self, text: &str) -> f64 {
+        let bigrams = self.bigrams();
+        let first = bigrams.map(|row| row.iter().map(|ln_p| ln_p.exp()).sum::<f64>());
+        let mut probs = Vec::new();
+        metrics::for_each_bigram(text, |a, b| probs.push(bigrams[a][b].exp() / first[a]));
+        if probs.is_empty() {
+            return 0.0;
+        }
+
+        let even = 1.0 / 26.0;
+        let mut noise = 0.5;
+        for _ in 0..CORRUPTION_ITERATIONS {
+            let posterior = |p: f64| noise * even / ((1.0 - noise) * p + noise * even);
+            noise = probs.iter().map(|&p| posterior(p)).sum::<f64>() / probs.len() as f64;
+        }
+        noise
+    }
+}
+
+// letter pairs out of n that don't touch a wrong letter
+fn trusted(n: usize, corrupted: f64) -> usize {
+    let share = (1.0 - corrupted).powi(2);
+    ((n as f64 * share).ceil() as usize).min(n)
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Language::English => write!(f, "english"),
+            Language::German => write!(f, "german"),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "english" => Ok(Language::English),
+            "german" => Ok(Language::German),
+            _ => Err(format!("unknown language {s}, expected english or german")),
+        }
+    }
+}
+
+/// The plaintext language a solver scores with; in auto mode the candidates of every
+/// language are tried and the one that reads best in its own language wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageMode {
+    Known(Language),
+    Auto,
+}
+
+impl LanguageMode {
+    pub fn languages(&self) -> Vec<Language> {
+        match self {
+            LanguageMode::Known(language) => vec![*language],
+            LanguageMode::Auto => Language::ALL.to_vec(),
+        }
+    }
+}
+
+impl Default for LanguageMode {
+    fn default() -> Self {
+        LanguageMode::Known(Language::default())
+    }
+}
+
+impl fmt::Display for LanguageMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LanguageMode::Known(language) => write!(f, "{}", language),
+            LanguageMode::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+impl FromStr for LanguageMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(LanguageMode::Auto),
+            s => Ok(LanguageMode::Known(s.parse()?)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub language: Language,
+    // posterior probability of the language, with random letters as the other alternative
+    pub confidence: f64,
+}
+
+/// Identifies the language of a decryption by its letter pairs, None when the text is too
+/// short or looks more like random letters than any of the languages.
+pub fn detect(text: &str) -> Option<Detection> {
+    let scores = Language::ALL.map(|language| language.log_likelihood(text));
+    let n = scores[0].1;
+    if n < MIN_DETECTION_BIGRAMS {
+        return None;
+    }
+
+    let random = -(n as f64) * (26.0f64 * 26.0).ln();
+    let (best, &(best_log_likelihood, _)) = scores
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))?;
+    if best_log_likelihood <= random {
+        return None;
+    }
+
+    // uniform prior over the languages and random letters, shifted by the best for precision
+    let total = scores
+        .iter()
+        .map(|&(log_likelihood, _)| log_likelihood)
+        .chain([random])
+        .map(|log_likelihood| (log_likelihood - best_log_likelihood).exp())
+        .sum::<f64>();
+
+    Some(Detection {
+        language: Language::ALL[best],
+        confidence: 1.0 / total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    const GERMAN_TEXT: &str = "DER HUND UND DIE KATZE GINGEN MIT DEM ESEL IN DEN WALD UND \
+                               SUCHTEN EIN HAUS FUER DIE NACHT WEIL ES SCHON DUNKEL WAR";
+
+    #[test]
+    fn test_detect() {
+        assert_relative_eq!(GERMAN_FREQUENCIES.iter().sum::<f64>(), 1.0, epsilon = 1e-3);
+
+        let english = detect(LONG_TEXT).unwrap();
+        assert_eq!(english.language, Language::English);
+        assert!(english.confidence > 0.99);
+        let german = detect(GERMAN_TEXT).unwrap();
+        assert_eq!(german.language, Language::German);
+        assert!(german.confidence > 0.99);
+
+        assert_eq!(detect("SHORT TEXT"), None);
+        assert_eq!(detect("QXZJV WKQPF XJZQV BKXQJ ZVPWQ XJKZQ"), None);
+    }
+
+    #[test]
+    fn test_scores() {
+        assert!(
+            Language::German.similarity(GERMAN_TEXT) > Language::English.similarity(GERMAN_TEXT)
+        );
+        assert!(Language::English.similarity(LONG_TEXT) > Language::German.similarity(LONG_TEXT));
+        assert!(
+            Language::German.bigram_score(GERMAN_TEXT)
+                > Language::English.bigram_score(GERMAN_TEXT)
+        );
+        assert_eq!(Language::German.bigram_score("A"), 0.0);
+
+        for mode in ["english", "german", "auto"] {
+            assert_eq!(mode.parse::<LanguageMode>().unwrap().to_string(), mode);
+        }
+        assert_eq!(LanguageMode::Auto.languages(), Language::ALL);
+        assert!("french".parse::<LanguageMode>().is_err());
+    }
+
+    #[test]
+    fn test_corruption() {
+        // a fifth of the letters replaced by any letter
+        let mut rng = StdRng::seed_from_u64(5);
+        let corrupted = LONG_TEXT
+            .bytes()
+            .map(|c| match c.is_ascii_uppercase() && rng.gen_bool(0.2) {
+                true => (b'A' + rng.gen_range(0..26)) as char,
+                false => c as char,
+            })
+            .collect::<String>();
+
+        let english = Language::English;
+        assert!(english.corruption_rate(LONG_TEXT) < 0.03);
+        let rate = english.corruption_rate(&corrupted);
+        assert!((0.12..0.28).contains(&rate), "{rate}");
+        assert_eq!(english.corruption_rate("A"), 0.0);
+
+        // the trusted pairs of the corrupted text read about as well as the clean text
+        let (trimmed, clean) = (
+            english.trimmed_bigram_score(&corrupted, 0.2),
+            english.bigram_score(LONG_TEXT),
+        );
+        assert!(trimmed > 0.9 * clean, "{trimmed} {clean}");
+        assert!(english.bigram_score(&corrupted) < 0.8 * clean);
+        assert_relative_eq!(english.trimmed_bigram_score(LONG_TEXT, 0.0), clean);
+    }
+}
@@ -0,0 +1,102 @@
+// without the `std` feature only the machine of enigma_core is built, for no_std targets
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "std")]
+pub mod beam;
+#[cfg(feature = "std")]
+pub mod bloom;
+#[cfg(feature = "std")]
+pub mod bombe;
+#[cfg(feature = "std")]
+pub mod cache;
+#[cfg(feature = "std")]
+pub mod challenge;
+#[cfg(feature = "std")]
+pub mod cipher;
+#[cfg(feature = "std")]
+pub mod climb;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "genevo")]
+mod compat;
+#[cfg(feature = "std")]
+pub mod confidence;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod crib;
+#[cfg(feature = "std")]
+pub mod dictionary;
+#[cfg(feature = "std")]
+pub mod dna;
+#[cfg(feature = "std")]
+pub mod engine;
+#[cfg(feature = "std")]
+pub mod enigma;
+pub mod enigma_core;
+#[cfg(feature = "std")]
+pub mod es;
+#[cfg(feature = "std")]
+pub mod estimate;
+#[cfg(feature = "std")]
+pub mod explain;
+#[cfg(feature = "parquet")]
+pub mod export;
+#[cfg(feature = "std")]
+pub mod gen;
+#[cfg(feature = "std")]
+pub mod genealogy;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "gui")]
+pub mod gui;
+#[cfg(feature = "std")]
+pub mod heatmap;
+#[cfg(feature = "history")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod interchange;
+#[cfg(feature = "std")]
+pub mod keyspace;
+#[cfg(feature = "std")]
+pub mod language;
+#[cfg(feature = "std")]
+pub mod lorenz;
+#[cfg(feature = "std")]
+pub mod message_key;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "std")]
+pub mod morse;
+#[cfg(feature = "std")]
+pub mod ngrams;
+#[cfg(feature = "std")]
+pub mod pareto;
+#[cfg(feature = "std")]
+pub mod pipeline;
+#[cfg(feature = "std")]
+pub mod pso;
+#[cfg(feature = "serde")]
+pub mod report;
+#[cfg(feature = "std")]
+pub mod rescore;
+#[cfg(feature = "std")]
+pub mod rng;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+#[cfg(feature = "std")]
+pub mod sim;
+#[cfg(feature = "std")]
+pub mod substitution;
+#[cfg(feature = "std")]
+pub mod telemetry;
+#[cfg(feature = "std")]
+pub mod tempering;
+#[cfg(feature = "std")]
+pub mod termination;
+#[cfg(feature = "std")]
+pub mod traffic;
+#[cfg(feature = "std")]
+pub mod typex;
+#[cfg(feature = "std")]
+pub mod vigenere;
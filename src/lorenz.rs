@@ -0,0 +1,378 @@
+use std::{fmt, sync::OnceLock};
+
+use anyhow::ensure;
+use rand::Rng;
+
+use crate::{
+    cipher::Cipher, engine::FitnessFunction, enigma::normalize, gen::Fitness,
+    metrics::ENGLISH_SAMPLE,
+};
+
+// chi 1..5, psi 1..5, mu 61 and mu 37
+pub const WHEEL_SIZES: [usize; WHEELS_NUM] = [41, 31, 29, 26, 23, 43, 47, 51, 53, 59, 61, 37];
+pub const WHEELS_NUM: usize = 12;
+pub const IMPULSES: usize = 5;
+pub const MODEL_NAME: &str = "Lorenz SZ40/42";
+const CHI: usize = 0;
+const PSI: usize = 5;
+const MU61: usize = 10;
+const MU37: usize = 11;
+
+// Bletchley Park teleprinter notation of the 32 ITA2 codes, index is the code;
+// 9 is space, / 3 4 5 8 are null, carriage return, line feed, figure and letter shift
+const TELEPRINTER: &[u8; 32] = b"/T3O9HNM4LRGIPCVEZDBSYFXAWJ5UQK8";
+
+// wheel patterns (cams) and start positions; the patterns changed rarely and were
+// recovered by rectangling, the daily key is the start positions
+#[derive(Hash, PartialEq, Eq, Debug, Clone)]
+pub struct LorenzKey {
+    pub patterns: Vec<Vec<bool>>,
+    pub positions: [usize; WHEELS_NUM],
+}
+
+impl LorenzKey {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.patterns.len() == WHEELS_NUM,
+            "expected {} wheel patterns, got {}",
+            WHEELS_NUM,
+            self.patterns.len()
+        );
+        for (i, (pattern, &size)) in self.patterns.iter().zip(&WHEEL_SIZES).enumerate() {
+            ensure!(
+                pattern.len() == size,
+                "wheel {} must have {} cams, got {}",
+                i + 1,
+                size,
+                pattern.len()
+            );
+            ensure!(
+                self.positions[i] < size,
+                "wheel {} position must be in 0..{}, got {}",
+                i + 1,
+                size,
+                self.positions[i]
+            );
+        }
+        Ok(())
+    }
+
+    // random patterns with about half of the cams raised, the psi motor stands still more often
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        let patterns = WHEEL_SIZES
+            .iter()
+            .enumerate()
+            .map(|(i, &size)| {
+                let raised = match i {
+                    MU61 => 0.8,
+                    MU37 => 0.35,
+                    _ => 0.5,
+                };
+                (0..size).map(|_| rng.gen_bool(raised)).collect()
+            })
+            .collect();
+
+        Self {
+            patterns,
+            positions: WHEEL_SIZES.map(|size| rng.gen_range(0..size)),
+        }
+    }
+}
+
+impl fmt::Display for LorenzKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positions = self.positions.map(|p| (p + 1).to_string()).join(" ");
+        write!(f, "{} {}", MODEL_NAME, positions)
+    }
+}
+
+pub struct Lorenz {
+    key: LorenzKey,
+}
+
+impl Lorenz {
+    pub fn new(key: &LorenzKey) -> anyhow::Result<Self> {
+        key.validate()?;
+        Ok(Self { key: key.clone() })
+    }
+
+    // the key stream: chi XOR extended psi, one 5 bit code per character
+    fn key_stream(&self, len: usize) -> Vec<u8> {
+        let patterns = &self.key.patterns;
+        let mut positions = self.key.positions;
+        let cam =
+            |wheel: usize, positions: &[usize; WHEELS_NUM]| patterns[wheel][positions[wheel]] as u8;
+
+        (0..len)
+            .map(|_| {
+                let code = (0..IMPULSES).fold(0, |code, i| {
+                    code | ((cam(CHI + i, &positions) ^ cam(PSI + i, &positions)) << i)
+                });
+
+                // chi and mu 61 step every time, mu 37 if mu 61 shows a raised cam
+                // and the psi wheels together if mu 37 does
+                let psi_steps = cam(MU37, &positions) == 1;
+                let mu37_steps = cam(MU61, &positions) == 1;
+                for wheel in 0..WHEELS_NUM {
+                    let steps = match wheel {
+                        MU37 => mu37_steps,
+                        w if (PSI..PSI + IMPULSES).contains(&w) => psi_steps,
+                        _ => true,
+                    };
+                    if steps {
+                        positions[wheel] = (positions[wheel] + 1) % WHEEL_SIZES[wheel];
+                    }
+                }
+
+                code
+            })
+            .collect()
+    }
+
+    fn add_key(&self, codes: Vec<u8>) -> Vec<u8> {
+        let key_stream = self.key_stream(codes.len());
+        codes
+            .into_iter()
+            .zip(key_stream)
+            .map(|(c, k)| c ^ k)
+            .collect()
+    }
+}
+
+pub fn to_codes(text: &str) -> Vec<u8> {
+    text.bytes()
+        .filter_map(|c| {
+            let c = match c {
+                b' ' => b'9',
+                c => c.to_ascii_uppercase(),
+            };
+            TELEPRINTER.iter().position(|&t| t == c).map(|i| i as u8)
+        })
+        .collect()
+}
+
+pub fn from_codes(codes: &[u8]) -> String {
+    codes
+        .iter()
+        .map(|&c| TELEPRINTER[(c & 0b11111) as usize] as char)
+        .collect()
+}
+
+impl Cipher for Lorenz {
+    type Key = LorenzKey;
+
+    const MODEL_NAME: &'static str = MODEL_NAME;
+
+    fn new(key: &LorenzKey) -> anyhow::Result<Self> {
+        Lorenz::new(key)
+    }
+
+    // plaintext letters and spaces to teleprinter notation
+    fn encrypt(&self, text: &str) -> String {
+        from_codes(&self.add_key(to_codes(&normalize(text))))
+    }
+
+    // teleprinter notation back to text, 9 becomes a space again
+    fn decrypt(&self, text: &str) -> String {
+        from_codes(&self.add_key(to_codes(text))).replace('9', " ")
+    }
+
+    // start positions only, the patterns are assumed known
+    fn keyspace_size() -> u128 {
+        WHEEL_SIZES.iter().map(|&size| size as u128).product()
+    }
+}
+
+fn delta(codes: &[u8]) -> Vec<u8> {
+    codes.windows(2).map(|w| w[0] ^ w[1]).collect()
+}
+
+fn delta_chi(pattern: &[bool], position: usize, len: usize) -> Vec<u8> {
+    let stream = (0..=len)
+        .map(|i| pattern[(position + i) % pattern.len()] as u8)
+        .collect::<Vec<_>>();
+    delta(&stream)
+}
+
+// how often two impulses of English plaintext agree in delta, for every pair of impulses
+fn english_delta_agreement() -> &'static [[f64; IMPULSES]; IMPULSES] {
+    static AGREEMENT: OnceLock<[[f64; IMPULSES]; IMPULSES]> = OnceLock::new();
+
+    AGREEMENT.get_or_init(|| {
+        let delta_p = delta(&to_codes(&normalize(ENGLISH_SAMPLE)));
+        let mut agreement = [[0.0; IMPULSES]; IMPULSES];
+        for (a, row) in agreement.iter_mut().enumerate() {
+            for (b, value) in row.iter_mut().enumerate() {
+                let agreeing = delta_p
+                    .iter()
+                    .filter(|&&p| ((p >> a) ^ (p >> b)) & 1 == 0)
+                    .count();
+                *value = agreeing as f64 / delta_p.len() as f64;
+            }
+        }
+        agreement
+    })
+}
+
+// scores chi settings by the "1+2" statistic: in de-chi'd text dZ1 + dZ2 + dChi1 + dChi2 leans
+// the way it does in the plaintext, the psi wheels often stand still and add nothing to the delta;
+// German traffic leaned towards dot (0), English without repeated shifts leans towards cross (1)
+pub struct ChiFitness {
+    delta_z: Vec<u8>,
+    chi_patterns: Vec<Vec<bool>>,
+}
+
+impl ChiFitness {
+    pub fn new(ciphertext: &str, patterns: &[Vec<bool>]) -> Self {
+        Self {
+            delta_z: delta(&to_codes(ciphertext)),
+            chi_patterns: patterns[CHI..CHI + IMPULSES].to_vec(),
+        }
+    }
+
+    // fraction of characters where impulses a and b of the de-chi'd text lean like English
+    fn bulge(&self, a: usize, position_a: usize, b: usize, position_b: usize) -> f64 {
+        let len = self.delta_z.len();
+        if len == 0 {
+            return 0.0;
+        }
+        let chi_a = delta_chi(&self.chi_patterns[a], position_a, len);
+        let chi_b = delta_chi(&self.chi_patterns[b], position_b, len);
+
+        let agreeing = (0..len)
+            .filter(|&i| {
+                let z = self.delta_z[i];
+                ((z >> a) ^ (z >> b) ^ chi_a[i] ^ chi_b[i]) & 1 == 0
+            })
+            .count();
+        let agreement = agreeing as f64 / len as f64;
+
+        match english_delta_agreement()[a][b] >= 0.5 {
+            true => agreement,
+            false => 1.0 - agreement,
+        }
+    }
+
+    // excess of the bulge over chance, scaled by how strongly English leans for the pair
+    fn weighted_bulge(&self, a: usize, position_a: usize, b: usize, position_b: usize) -> f64 {
+        let lean = (english_delta_agreement()[a][b] - 0.5).abs();
+        lean * (self.bulge(a, position_a, b, position_b) - 0.5)
+    }
+}
+
+impl FitnessFunction<[usize; IMPULSES]> for ChiFitness {
+    // mean weighted bulge over every pair of wheels
+    fn fitness_of(&self, positions: &[usize; IMPULSES]) -> Fitness {
+        let pairs = (0..IMPULSES).flat_map(|a| (a + 1..IMPULSES).map(move |b| (a, b)));
+        let (sum, n) = pairs.fold((0.0, 0), |(sum, n), (a, b)| {
+            (
+                sum + self.weighted_bulge(a, positions[a], b, positions[b]),
+                n + 1,
+            )
+        });
+        Fitness(0.5 + sum / n as f64)
+    }
+}
+
+// the best settings of chi 1 and 2 kept from the first stage of the attack
+const CHI_CANDIDATES: usize = 10;
+// ties may keep a wheel moving, the refinement stops after this many passes anyway
+const REFINE_PASSES: usize = 10;
+
+// chi wheel setting attack: chi 1 and 2 are set together by trying every pair of positions,
+// the others one by one against the wheels set before them; every candidate is then refined
+// a wheel at a time until no single wheel improves it and the best one wins;
+// the statistic is weak, it takes several thousand characters to set the wheels reliably
+pub fn break_chi(ciphertext: &str, patterns: &[Vec<bool>]) -> [usize; IMPULSES] {
+    let fitness = ChiFitness::new(ciphertext, patterns);
+
+    let mut pairs = (0..WHEEL_SIZES[CHI])
+        .flat_map(|a| (0..WHEEL_SIZES[CHI + 1]).map(move |b| (a, b)))
+        .map(|(a, b)| (fitness.bulge(0, a, 1, b), a, b))
+        .collect::<Vec<_>>();
+    pairs.sort_by(|x, y| y.0.total_cmp(&x.0));
+
+    pairs
+        .iter()
+        .take(CHI_CANDIDATES)
+        .map(|&(_, a, b)| {
+            let mut positions = [a, b, 0, 0, 0];
+            for wheel in 2..IMPULSES {
+                positions[wheel] = best_position(wheel, |position| {
+                    (0..wheel)
+                        .map(|set| fitness.weighted_bulge(set, positions[set], wheel, position))
+                        .sum()
+                });
+            }
+            refine(&fitness, positions)
+        })
+        .max_by(|a, b| fitness.fitness_of(a).cmp(&fitness.fitness_of(b)))
+        .unwrap()
+}
+
+fn refine(fitness: &ChiFitness, mut positions: [usize; IMPULSES]) -> [usize; IMPULSES] {
+    for _ in 0..REFINE_PASSES {
+        let before = positions;
+        for wheel in 0..IMPULSES {
+            positions[wheel] = best_position(wheel, |position| {
+                let mut candidate = positions;
+                candidate[wheel] = position;
+                fitness.fitness_of(&candidate).0
+            });
+        }
+        if positions == before {
+            break;
+        }
+    }
+    positions
+}
+
+fn best_position(wheel: usize, score: impl Fn(usize) -> f64) -> usize {
+    (0..WHEEL_SIZES[CHI + wheel])
+        .map(|position| (score(position), position))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap()
+        .1
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    #[test]
+    fn test_machine() {
+        let key = LorenzKey::random(&mut StdRng::seed_from_u64(42));
+        let machine = Lorenz::new(&key).unwrap();
+
+        let ciphertext = machine.encrypt(LONG_TEXT);
+        assert_eq!(ciphertext.len(), LONG_TEXT.len());
+        assert!(ciphertext.bytes().all(|c| TELEPRINTER.contains(&c)));
+        assert_eq!(machine.decrypt(&ciphertext), LONG_TEXT);
+        assert_eq!(from_codes(&to_codes("HELLO WORLD")), "HELLO9WORLD");
+
+        let mut invalid = key.clone();
+        invalid.positions[3] = 26;
+        assert!(Lorenz::new(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_break_chi() {
+        let key = LorenzKey::random(&mut StdRng::seed_from_u64(7));
+        let plaintext = LONG_TEXT.repeat(5);
+        let ciphertext = Lorenz::new(&key).unwrap().encrypt(&plaintext);
+
+        let mut chi = [0; IMPULSES];
+        chi.copy_from_slice(&key.positions[CHI..CHI + IMPULSES]);
+
+        let fitness = ChiFitness::new(&ciphertext, &key.patterns);
+        let mut wrong = chi;
+        wrong[0] = (wrong[0] + 1) % WHEEL_SIZES[0];
+        assert!(fitness.fitness_of(&chi) > fitness.fitness_of(&wrong));
+
+        assert_eq!(break_chi(&ciphertext, &key.patterns), chi);
+    }
+}
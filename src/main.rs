@@ -1,135 +1,1715 @@
-use std::sync::Arc;
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
 
-use anyhow::anyhow;
-use chrono::Duration;
-use gen::index_of_coincidence_norm;
-use genevo::operator::prelude::{ElitistReinserter, MaximizeSelector};
-use genevo::prelude::*;
-use genevo::types::fmt::Display;
-use moka::sync::Cache;
+use anyhow::{anyhow, bail, ensure};
+use clap::Parser;
+#[cfg(feature = "parquet")]
+use enigmagen_rs::export::ParquetExport;
+#[cfg(feature = "grpc")]
+use enigmagen_rs::grpc;
+#[cfg(feature = "history")]
+use enigmagen_rs::history::History;
+#[cfg(feature = "serde")]
+use enigmagen_rs::report::{ProgressEvent, ProgressFormat, ReportFormat, RunReport};
+use enigmagen_rs::{
+    beam,
+    bombe::{Bombe, Candidate, Menu},
+    cache::{CacheBackend, FitnessCache, ScramblerCache},
+    challenge::{self, Challenge, Difficulty},
+    climb::{PlugboardClimb, PlugboardSearch},
+    compare,
+    confidence::Baseline,
+    coverage::Coverage,
+    crib,
+    dictionary::Dictionary,
+    dna::Encoding,
+    engine::{Algorithm, Cores, FitnessFunction, GenomeBuilder, SelectionStrategy},
+    enigma, estimate,
+    explain::{self, Narrator},
+    gen::{self, ByteSize, Fitness},
+    genealogy,
+    heatmap::{HeatmapExport, HeatmapFormat},
+    interchange::{self, KeyFormat},
+    language::{self, Language},
+    message_key::{self, Indicator},
+    metrics::Metric,
+    morse,
+    ngrams::NgramTable,
+    pareto,
+    pipeline::{Gillogly, Strategy},
+    rescore::Rescorer,
+    rng::RngAlgorithm,
+    sim,
+    tempering::Tempering,
+    termination::{self, StopTrigger},
+    traffic::{self, Procedure, Traffic},
+};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 
-pub mod enigma;
-pub mod gen;
+// with --progress-format ndjson stdout carries the JSON lines alone, the rest printed along a
+// search goes to stderr
+static NOTES_TO_STDERR: AtomicBool = AtomicBool::new(false);
 
-fn main() {
-    let plaintext = "TO BE OR NOT TO BE THAT IS THE QUESTION WHETHER TIS NOBLER IN THE MIND TO SUFFER THE SLINGS AND ARROWS OF OUTRAGEOUS FORTUNE OR TO TAKE ARMS AGAINST A SEA OF TROUBLES AND BY OPPOSING END THEM TO DIE TO SLEEP NO MORE AND BY A SLEEP TO SAY WE END THE HEARTACHE AND THE THOUSAND NATURAL SHOCKS THAT FLESH IS HEIR TO TIS A CONSUMMATION DEVOUTLY TO BE WISHD TO DIE TO SLEEP TO SLEEP PERCHANCE TO DREAM AY THERES THE RUB FOR IN THAT SLEEP OF DEATH WHAT DREAMS MAY COME WHEN WE HAVE SHUFFLED OFF THIS MORTAL COIL MUST GIVE US PAUSE THERES THE RESPECT THAT MAKES CALAMITY OF SO LONG LIFE";
+macro_rules! note {
+    ($($arg:tt)*) => {
+        match NOTES_TO_STDERR.load(Ordering::Relaxed) {
+            true => eprintln!($($arg)*),
+            false => println!($($arg)*),
+        }
+    };
+}
+
+// an output of the run: run history, statistics files
+type Recorder = Box<dyn FnMut(&sim::StepResult) -> anyhow::Result<()>>;
+
+#[derive(Parser, Debug)]
+#[command(about = "Cracking the Enigma machine using a genetic algorithm")]
+struct Args {
+    /// Validate the configuration and print the search plan without running it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Maximum number of plugboard pairs to search for, wartime procedure used 10
+    #[arg(long, default_value_t = 0)]
+    max_plugs: usize,
+
+    /// Genome encoding: settings (hand-written operators) or dna (flat bytes, uniform operators)
+    #[arg(long, default_value = "settings")]
+    encoding: Encoding,
+
+    /// Reflector: a known one (B, C or "D AB CD ..."), standard (search B or C)
+    /// or rewirable (search UKW-D wirings)
+    #[arg(long, default_value = "B")]
+    reflector: gen::ReflectorSearch,
+
+    /// Search engine: ga (built-in), es (evolution strategy over ring settings and rotor
+    /// positions, no plugs), pso (particle swarm) or genevo (requires the genevo feature)
+    #[arg(long, default_value = "ga")]
+    engine: Algorithm,
+
+    /// Attack: search (whole keys with --engine) or gillogly (index of coincidence over all
+    /// rotor orders and positions, then the ring settings, then the plugboard climbed with
+    /// --climb, bigram by default, with no generations for --db, --report, --parquet,
+    /// --heatmap, --genealogy, --explain or --diff to follow)
+    #[arg(long, default_value = "search")]
+    strategy: Strategy,
+
+    /// Fitness metric: ioc (index of coincidence), sinkov[:english|german] (log-likelihood
+    /// of the letters, more forgiving of ring settings a few positions off), entropy
+    /// (of the letter distribution), bigram[:english|german] (letter pair likelihood),
+    /// trimmed:<share>[:english|german] (letter pair likelihood of the pairs a share of
+    /// garbled letters leaves trusted, e.g. trimmed:0.1), ngrams:<file> (a table built with
+    /// train-ngrams, or english and german built in with their features), words:<file> (share
+    /// of the letters covered by the words of a word list), or a weighted sum of them
+    /// normalized to a common scale, e.g. 0.5*ioc+0.5*bigram
+    #[arg(long, default_value = "ioc")]
+    metric: Metric,
+
+    /// Multi-objective mode: search keys scoring well on all these metrics at once, e.g.
+    /// ioc,bigram, and print the Pareto front of the trade-offs instead of a single best key,
+    /// without --db, --report, --parquet, --heatmap, --genealogy, --explain or --diff
+    #[arg(long, value_delimiter = ',')]
+    pareto: Vec<Metric>,
+
+    /// Population of the multi-objective search, its sorting is quadratic in it
+    #[arg(long, default_value_t = 2000)]
+    pareto_population: usize,
+
+    /// Rotor configurations whose letter swaps are cached for keys that only differ in the
+    /// plugboard, pays off with plugs once the population converges; 0 turns it off
+    #[arg(long, default_value_t = 0)]
+    scrambler_cache: usize,
+
+    /// Fitness cache backend: moka, sharded (a dashmap, less bookkeeping per lookup, for short
+    /// ciphertexts on many threads) or none
+    #[arg(long, default_value = "moka")]
+    cache_backend: CacheBackend,
+
+    /// Hill-climb the plugboard of the best key with this metric after the search, e.g.
+    /// bigram, up to --max-plugs plugs; candidates are scored by their changed letters only
+    #[arg(long)]
+    climb: Option<Metric>,
+
+    /// How --climb refines the plugboard: hill, or tabu:<tenure>:<iterations> to keep going
+    /// past the hill climb's optimum with recently changed plugs forbidden for tenure steps
+    #[arg(long, default_value = "hill")]
+    climb_search: PlugboardSearch,
+
+    /// Random keys scored by the metric (or --climb's) to tell how far the score of the found
+    /// key stands out from chance; 0 skips the confidence report
+    #[arg(long, default_value_t = 1000)]
+    confidence_samples: usize,
+
+    /// Count the distinct keys the search evaluates and report them as a share of the keys it
+    /// chooses from when it finishes; every key is kept, 16 bytes and more each
+    #[arg(long)]
+    coverage: bool,
+
+    /// Tell what the search does in plain language as it goes: why the score rose, which
+    /// parts of the key the population agrees on, when the plugboard climb takes over
+    #[arg(long)]
+    explain: bool,
+
+    /// Show the best decryption of every reported generation against the known plaintext of
+    /// the demo, the letters it gets right in green and the others in red (lowercased instead off
+    /// a terminal or with NO_COLOR set)
+    #[arg(long)]
+    diff: bool,
+
+    /// Rank the best keys of the last generation again with a stronger model before the
+    /// climb: a metric, e.g. ngrams:quintgrams.bin, or command:<program and arguments> that
+    /// reads a decryption per line and writes a score per line, higher is better
+    #[arg(long)]
+    rescore: Option<Rescorer>,
+
+    /// Number of best keys of the last generation --rescore ranks
+    #[arg(long, default_value_t = 20)]
+    rescore_top: usize,
+
+    /// Seed of the built-in engine, the same seed reproduces the run at any number of threads
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Random number generator of the search: chacha, reproducible across releases, or
+    /// xoshiro, faster to draw from
+    #[arg(long, default_value = "chacha")]
+    rng: RngAlgorithm,
+
+    /// Odds of the parts of a key to be changed by a mutation, e.g.
+    /// rotors:10,rings:10,positions:40,plugboard:40; the parts left out aren't mutated
+    #[arg(long)]
+    mutation_weights: Option<gen::MutationWeights>,
+
+    /// Mutation strength over the run: constant, decay:<strength>@<generation> for a geometric
+    /// decline of the mutation rate and the jumps of rings and positions, or fitness:<strength>
+    /// as the best fitness approaches the target
+    #[arg(long, default_value = "constant")]
+    mutation_schedule: gen::MutationSchedule,
+
+    /// Best keys copied unchanged into the next generation, the best offspring fill the rest;
+    /// 0 replaces the whole population every generation. Without it the best 30% are kept
+    #[arg(long)]
+    elitism: Option<usize>,
+
+    /// Adapt the share of the population that mates within <min>:<max>, e.g. 0.1:0.8: more when
+    /// the average fitness closes in on the best, fewer when it trails far behind
+    #[arg(long)]
+    adapt_selection: Option<gen::SelectionAdaptation>,
+
+    /// How parents are drawn: truncation, uniformly from the best half, or from the whole
+    /// population with odds by fitness (proportionate) or by rank (ranking)
+    #[arg(long, default_value = "truncation")]
+    selection: SelectionStrategy,
+
+    /// How parents are crossed: genes, each rotor, ring and position from any parent, or slots,
+    /// the rotor of a slot with its ring and position from the same parent
+    #[arg(long, default_value = "genes")]
+    crossover: gen::CrossoverMode,
+
+    /// How the rotor order of a child is bred from its parents: retry, any rotor of a slot drawn
+    /// again until they differ, or pmx or ox, the order crossovers of permutations
+    #[arg(long, default_value = "retry")]
+    rotor_crossover: gen::RotorCrossover,
+
+    /// How the plugboard of a child is bred from its parents: positional, the pairs at the same
+    /// index, union, all their pairs pruned to the size of one, intersection, the pairs they
+    /// share filled up at random, or exchange, a pair of one parent swapped into the other's
+    #[arg(long, default_value = "positional")]
+    plugboard_crossover: gen::PlugboardCrossover,
+
+    /// Number of worker threads, 0 uses all cores or one per --pin-cores core
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
+
+    /// Population of the later generations: constant, decay:<size>@<generation> for a geometric
+    /// decline to the size by that generation, or stages like 20:200000,50:50000
+    #[arg(long, default_value = "constant")]
+    population_schedule: gen::PopulationSchedule,
+
+    /// Start the search from keys whose rotor positions are within this many letters of their
+    /// ring settings (the Herivel tip), for the first message of a day an operator keyed in
+    /// after setting the rings
+    #[arg(long)]
+    herivel: Option<u8>,
+
+    /// Hold the rings of the left and right rotor at A in the search, 26² times fewer keys,
+    /// and recover the right ring with the middle one after it
+    #[arg(long)]
+    fix_rings: bool,
+
+    /// Parallel tempering: <islands>[:<max temperature>[:<swap interval>]], e.g. 4:4:5 for four
+    /// islands sharing the population, the hottest mutating four times as much, whose
+    /// populations may swap with their neighbours every five generations
+    #[arg(long)]
+    tempering: Option<Tempering>,
+
+    /// Memory budget of the population and the fitness cache, e.g. 8G; both are scaled down
+    /// by the same factor when their estimated size doesn't fit
+    #[arg(long)]
+    max_memory: Option<ByteSize>,
+
+    /// Memory of a Bloom filter of every key evaluated in the run, e.g. 64M; offspring it
+    /// already holds are mutated again instead of being evaluated once more
+    #[arg(long)]
+    explored_memory: Option<ByteSize>,
+
+    /// Pin the worker threads to these cores, e.g. 0-3,8, to keep the search off the rest of
+    /// a shared machine
+    #[arg(long)]
+    pin_cores: Option<Cores>,
+
+    /// File with the ciphertext to crack, - for stdin
+    #[arg(long, conflicts_with = "morse")]
+    ciphertext: Option<PathBuf>,
+
+    /// Crack a Morse intercept read from this file
+    #[arg(long)]
+    morse: Option<PathBuf>,
+
+    /// Garbled Morse codes up to this many elements off are read as the nearest letter
+    #[arg(long, default_value_t = 1)]
+    morse_tolerance: usize,
+
+    /// Keep reading the --morse file while the search runs: Morse appended to it, e.g. of an
+    /// intercept still coming in, is scored by the following generations
+    #[arg(long, requires = "morse")]
+    follow: bool,
+
+    /// CTF mode: stop as soon as the decryption contains --dictionary-hits words of this
+    /// file (whitespace separated, words under 4 letters are ignored)
+    #[arg(long)]
+    dictionary: Option<PathBuf>,
+
+    /// Number of dictionary words that stops the search
+    #[arg(long, default_value_t = 5)]
+    dictionary_hits: usize,
+
+    /// CTF mode: stop as soon as the decryption matches this regex, the machine only has
+    /// the letters A..Z so a flag is e.g. FLAG[A-Z]+
+    #[arg(long)]
+    pattern: Option<regex::Regex>,
+
+    /// Stop gracefully when this file appears, or when this http:// URL answers "stop" (asked
+    /// once a second in the background); repeatable
+    #[arg(long)]
+    stop: Vec<StopTrigger>,
+
+    /// Format of the printed key: sheet, cryptool or enigma-simulator
+    #[arg(long, default_value = "sheet")]
+    key_format: KeyFormat,
+
+    /// Speak JSON-RPC on stdin and stdout (start, pause, resume, cancel, status, result) instead
+    /// of running
+    #[cfg(feature = "rpc")]
+    #[arg(long)]
+    rpc: bool,
+
+    /// Serve the Solver gRPC service (proto/enigmagen.proto) on this address instead of running
+    #[cfg(feature = "grpc")]
+    #[arg(long)]
+    serve: Option<std::net::SocketAddr>,
+
+    /// Export spans and metrics of the search over OTLP/HTTP, the collector is set with
+    /// OTEL_EXPORTER_OTLP_ENDPOINT (http://localhost:4318 by default)
+    #[cfg(feature = "otel")]
+    #[arg(long)]
+    otlp: bool,
+
+    /// Record the run (configuration, seed, generations, result) in this SQLite database
+    #[cfg(feature = "history")]
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+
+    /// Write generation statistics and sampled individuals as Parquet files into this directory
+    #[cfg(feature = "parquet")]
+    #[arg(long)]
+    parquet: Option<PathBuf>,
+
+    /// Write the result and the last sampled population to this file when the run finishes
+    #[cfg(feature = "serde")]
+    #[arg(long)]
+    report: Option<PathBuf>,
+
+    /// Format of --report: json, bincode or msgpack
+    #[cfg(feature = "serde")]
+    #[arg(long, default_value = "json")]
+    report_format: ReportFormat,
+
+    /// How the progress is printed: human, or ndjson, a JSON object on stdout for every
+    /// reported generation and one for the result, the other lines going to stderr
+    #[cfg(feature = "serde")]
+    #[arg(long, default_value = "human")]
+    progress_format: ProgressFormat,
+
+    /// Write how many sampled individuals have each rotor and position in each slot, every
+    /// --heatmap-every generations and for the last one, as a file per generation into this
+    /// directory
+    #[arg(long)]
+    heatmap: Option<PathBuf>,
+
+    /// Generations between two --heatmap files
+    #[arg(long, default_value_t = 10)]
+    heatmap_every: u64,
+
+    /// Format of --heatmap: csv or json
+    #[arg(long, default_value = "csv")]
+    heatmap_format: HeatmapFormat,
+
+    /// Print and pass on to the outputs (--report, --heatmap, --parquet, --db, --explain) every
+    /// n-th generation only, and the last one; 0 reports the last one alone
+    #[arg(long, default_value_t = 1)]
+    report_every: u64,
+
+    /// Print and pass on to the outputs only the generations that improved the best fitness,
+    /// and the last one
+    #[arg(long)]
+    report_improvements: bool,
+
+    /// Write the ancestry of the best --genealogy-top keys of the last generation to this file
+    /// as a Graphviz DOT graph, each key with its fitness and the operator that bred it; ga
+    /// engine without tempering only
+    #[arg(long)]
+    genealogy: Option<PathBuf>,
+
+    /// Keys of the last generation --genealogy traces back
+    #[arg(long, default_value_t = 5)]
+    genealogy_top: usize,
+
+    /// Number of individuals, evenly spaced by rank, sampled from every generation
+    /// for --parquet, --report and --heatmap
+    #[arg(long, default_value_t = 100)]
+    sample_size: usize,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Crack the --ciphertext or --morse intercept, the key is unknown: what runs without a
+    /// subcommand
+    Crack,
+
+    /// Encrypt a known message and crack it, to show the search converging: the search stops
+    /// when it scores as well as the message
+    Demo,
+
+    /// Write ciphertexts with hidden keys, for exercises or benchmarking the solver
+    Challenge {
+        /// easy (600 letters, no plugs), medium (400, 2 plugs, B or C reflector),
+        /// hard (250, 5 plugs) or expert (150, 10 plugs, rewired UKW-D)
+        #[arg(long, default_value = "medium")]
+        difficulty: Difficulty,
+
+        /// Number of ciphertexts
+        #[arg(long, default_value_t = 10)]
+        count: usize,
+
+        /// Directory the ciphertexts are written to, one file each
+        #[arg(long, default_value = "challenges")]
+        out: PathBuf,
+
+        /// File the keys and plaintexts are written to, keep it away from the students
+        #[arg(long, default_value = "answers.tsv")]
+        answers: PathBuf,
+
+        /// Seed, the same seed generates the same challenges
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Write synthetic message traffic with its keys, labelled data for evaluating attacks:
+    /// German messages under the daily keys of a random key sheet, with indicators
+    Traffic {
+        /// Number of messages
+        #[arg(long, default_value_t = 100)]
+        count: usize,
 
+        /// Messages sent under a daily key
+        #[arg(long, default_value_t = 10)]
+        per_day: usize,
+
+        /// doubled (the message key typed twice at the ground setting of the day, until May
+        /// 1940) or single (typed once at a ground setting sent in the clear)
+        #[arg(long, default_value = "doubled")]
+        procedure: Procedure,
+
+        /// Fraction of the letters received as another letter
+        #[arg(long, default_value_t = 0.0)]
+        garble: f64,
+
+        /// Fraction of the message keys the operators picked without thinking
+        #[arg(long, default_value_t = 0.0)]
+        cillies: f64,
+
+        /// File the messages are written to, tab separated with their keys and plaintexts
+        #[arg(long, default_value = "traffic.tsv")]
+        out: PathBuf,
+
+        /// Seed, the same seed generates the same traffic
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+
+    /// Build an n-gram table from a corpus of the expected plaintext, for --metric ngrams:<out>
+    TrainNgrams {
+        /// Text file, or directory whose files are all read
+        #[arg(long)]
+        corpus: PathBuf,
+
+        /// Letters per n-gram, 4 for quadgrams
+        #[arg(long, default_value_t = 4)]
+        order: usize,
+
+        /// File the table is written to
+        #[arg(long)]
+        out: PathBuf,
+    },
+
+    /// Recover the rotor starting positions of a ciphertext whose rotors, ring settings,
+    /// reflector and plugboard are known, by a deterministic beam search
+    Positions {
+        /// File with the ciphertext
+        #[arg(long)]
+        ciphertext: PathBuf,
+
+        /// The known key, e.g. "II V III / 08 05 20 / A A A / AQ BT", its positions are ignored
+        #[arg(long)]
+        key: enigma::Settings,
+
+        /// Hypotheses kept after every --stride letters
+        #[arg(long, default_value_t = 1000)]
+        width: usize,
+
+        /// Letters decrypted between two prunings of the beam
+        #[arg(long, default_value_t = beam::DEFAULT_STRIDE)]
+        stride: usize,
+
+        /// Plaintext language the decryptions are scored in
+        #[arg(long, default_value = "english")]
+        language: Language,
+    },
+
+    /// Rank the message settings of a ciphertext sent under a known daily key (rotor order,
+    /// ring settings and plugboard) by trying all 26^3 of them
+    MessageKey {
+        /// File with the ciphertext
+        #[arg(long)]
+        ciphertext: PathBuf,
+
+        /// The daily key, e.g. "II V III / 08 05 20 / A A A / AQ BT", its positions are ignored
+        /// unless there is an --indicator
+        #[arg(long)]
+        key: enigma::Settings,
+
+        /// The doubled message key sent ahead of messages before May 1940, six letters typed at
+        /// the ground setting, which are the positions of --key; only the message settings it
+        /// allows are ranked
+        #[arg(long)]
+        indicator: Option<String>,
+
+        /// Metric the decryptions are ranked by
+        #[arg(long, default_value = "bigram")]
+        metric: Metric,
+
+        /// Number of best message settings printed
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+
+    /// Slide a crib along a ciphertext and rank the placements that don't crash by the loops
+    /// of their menus
+    Crib {
+        /// File with the ciphertext
+        #[arg(long)]
+        ciphertext: PathBuf,
+
+        /// Plaintext expected somewhere in the message, e.g. WETTERVORHERSAGE
+        #[arg(long)]
+        crib: String,
+
+        /// Number of best placements printed
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+
+        /// Print the bombe menu of every placement printed
+        #[arg(long)]
+        menu: bool,
+
+        /// Write the bombe menus of the placements printed to this file as JSON
+        #[cfg(feature = "serde")]
+        #[arg(long)]
+        menu_json: Option<PathBuf>,
+    },
+
+    /// Run the bombe on the menu of a crib and turn its stops into keys with the checking
+    /// machine
+    Bombe {
+        /// File with the ciphertext
+        #[arg(long)]
+        ciphertext: PathBuf,
+
+        /// Plaintext expected at --offset, e.g. WETTERVORHERSAGE
+        #[arg(long)]
+        crib: String,
+
+        /// Letters of the ciphertext before the crib, the placement with the best menu by
+        /// default
+        #[arg(long)]
+        offset: Option<usize>,
+
+        /// Metric the keys of the stops are ranked by after the letters of the crib
+        #[arg(long, default_value = "bigram")]
+        metric: Metric,
+
+        /// Number of best keys printed
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+
+    /// List past runs recorded with --db, or show one of them
+    #[cfg(feature = "history")]
+    History {
+        /// Show the configuration, result and generations of this run
+        #[arg(long)]
+        run: Option<i64>,
+
+        /// Number of most recent runs to list
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+
+    /// Open a window to paste a ciphertext, configure and watch the search
+    #[cfg(feature = "gui")]
+    Gui,
+}
+
+impl Args {
+    // individuals are only sampled for the outputs that take them
+    fn sample_size(&self) -> usize {
+        let sampled = self.heatmap.is_some();
+        #[cfg(feature = "parquet")]
+        let sampled = sampled || self.parquet.is_some();
+        #[cfg(feature = "serde")]
+        let sampled = sampled || self.report.is_some();
+
+        if sampled {
+            self.sample_size
+        } else {
+            0
+        }
+    }
+
+    // the worker threads of the subcommands that run no search
+    fn pool_options(&self) -> gen::EngineOptions {
+        gen::EngineOptions {
+            threads: self.threads,
+            pin_cores: self.pin_cores.clone(),
+            ..Default::default()
+        }
+    }
+
+    // the outputs given on the command line that are fed the generations of a search
+    fn search_outputs(&self) -> Vec<&'static str> {
+        let mut outputs = Vec::new();
+        #[cfg(feature = "history")]
+        if self.db.is_some() {
+            outputs.push("--db");
+        }
+        #[cfg(feature = "parquet")]
+        if self.parquet.is_some() {
+            outputs.push("--parquet");
+        }
+        #[cfg(feature = "serde")]
+        if self.report.is_some() {
+            outputs.push("--report");
+        }
+        for (given, flag) in [
+            (self.heatmap.is_some(), "--heatmap"),
+            (self.genealogy.is_some(), "--genealogy"),
+            (self.explain, "--explain"),
+            (self.diff, "--diff"),
+        ] {
+            if given {
+                outputs.push(flag);
+            }
+        }
+        outputs
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    // notes go to stderr from the start, the ciphertext read below may already print some
+    #[cfg(feature = "serde")]
+    if args.progress_format == ProgressFormat::Ndjson {
+        NOTES_TO_STDERR.store(true, Ordering::Relaxed);
+    }
+    // checked before the history database or any output is opened
+    ensure!(
+        !args.diff || matches!(args.command, Some(Command::Demo)),
+        "--diff needs the known plaintext of the demo"
+    );
+    let cracks = matches!(args.command, None | Some(Command::Crack | Command::Demo));
+    let outputs = args.search_outputs();
+    ensure!(
+        !cracks || args.strategy == Strategy::Search || outputs.is_empty(),
+        "the gillogly strategy has no generations for {}",
+        outputs.join(", ")
+    );
+    ensure!(
+        !cracks || args.pareto.is_empty() || outputs.is_empty(),
+        "--pareto reports a front of keys, with no generations for {}",
+        outputs.join(", ")
+    );
+    // flushed when main returns
+    #[cfg(feature = "otel")]
+    let _telemetry = match args.otlp {
+        true => Some(enigmagen_rs::telemetry::init()?),
+        false => None,
+    };
+
+    #[cfg(feature = "history")]
+    if let Some(Command::History { run, limit }) = args.command {
+        let db = args.db.as_deref();
+        let db = db.ok_or_else(|| anyhow!("the history subcommand needs --db"))?;
+        return print_history(&History::open(db)?, run, limit);
+    }
+    if let Some(Command::Challenge {
+        difficulty,
+        count,
+        out,
+        answers,
+        seed,
+    }) = &args.command
+    {
+        return write_challenges(*difficulty, *count, out, answers, *seed);
+    }
+    if let Some(Command::Traffic {
+        count,
+        per_day,
+        procedure,
+        garble,
+        cillies,
+        out,
+        seed,
+    }) = &args.command
+    {
+        let opts = traffic::Options {
+            messages_per_day: *per_day,
+            procedure: *procedure,
+            garble_rate: *garble,
+            cilly_rate: *cillies,
+            ..Default::default()
+        };
+        return write_traffic(opts, *count, out, *seed);
+    }
+    if let Some(Command::Positions {
+        ciphertext,
+        key,
+        width,
+        stride,
+        language,
+    }) = &args.command
+    {
+        return print_positions(ciphertext, key, *width, *stride, *language, args.key_format);
+    }
+    if let Some(Command::MessageKey {
+        ciphertext,
+        key,
+        indicator,
+        metric,
+        top,
+    }) = &args.command
+    {
+        let ciphertext = fs::read_to_string(ciphertext)?;
+        let (started, engine) = (Instant::now(), args.pool_options());
+        let ranked = match indicator {
+            Some(indicator) => {
+                let indicator = Indicator::analyze(key, indicator)?;
+                print_indicator(&indicator, key.rotor_positions);
+                let positions = indicator.candidates;
+                message_key::rank(&ciphertext, key, metric, positions, *top, &engine)?
+            }
+            None => message_key::recover(&ciphertext, key, metric, *top, &engine)?,
+        };
+        println!("Message settings by {} in {:?}", metric, started.elapsed());
+        return print_ranked(&ciphertext, &ranked, args.key_format);
+    }
+    if let Some(Command::Bombe {
+        ciphertext,
+        crib,
+        offset,
+        metric,
+        top,
+    }) = &args.command
+    {
+        let ciphertext = fs::read_to_string(ciphertext)?;
+        let offset = match offset {
+            Some(offset) => *offset,
+            None => {
+                crib::drag(&ciphertext, crib)?
+                    .first()
+                    .ok_or_else(|| anyhow!("the crib crashes at every offset"))?
+                    .offset
+            }
+        };
+        let menu = Menu::new(&ciphertext, crib, offset)?;
+        print!("{}", menu.diagram());
+
+        let started = Instant::now();
+        let engine = args.pool_options();
+        let candidates = Bombe::default().attack(&ciphertext, crib, offset, metric, &engine)?;
+        println!(
+            "{} stops survive the checking machine in {:?}",
+            candidates.len(),
+            started.elapsed()
+        );
+        return print_candidates(
+            &candidates[..candidates.len().min(*top)],
+            &menu,
+            args.key_format,
+        );
+    }
+    if let Some(Command::Crib {
+        ciphertext,
+        crib,
+        top,
+        menu,
+        ..
+    }) = &args.command
+    {
+        let ciphertext = fs::read_to_string(ciphertext)?;
+        let menus = print_placements(&ciphertext, crib, *top, *menu)?;
+        #[cfg(feature = "serde")]
+        if let Some(Command::Crib {
+            menu_json: Some(path),
+            ..
+        }) = &args.command
+        {
+            serde_json::to_writer_pretty(std::io::BufWriter::new(fs::File::create(path)?), &menus)?;
+            println!("\nMenus written to {}", path.display());
+        }
+        #[cfg(not(feature = "serde"))]
+        let _ = menus;
+        return Ok(());
+    }
+    if let Some(Command::TrainNgrams { corpus, order, out }) = &args.command {
+        let table = NgramTable::train_path(corpus, *order)?;
+        table.save(out)?;
+        println!(
+            "{}-gram table of {} written to {}",
+            order,
+            corpus.display(),
+            out.display()
+        );
+        return Ok(());
+    }
+    #[cfg(feature = "gui")]
+    if let Some(Command::Gui) = args.command {
+        return enigmagen_rs::gui::run();
+    }
+    #[cfg(feature = "rpc")]
+    if args.rpc {
+        return enigmagen_rs::rpc::serve(std::io::stdin().lock(), std::io::stdout());
+    }
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = args.serve {
+        println!("Serving gRPC on {}", addr);
+        return tokio::runtime::Runtime::new()?.block_on(grpc::serve(addr));
+    }
+
+    // the demo encrypts a message of its own, the crack path only has the ciphertext
+    let demo = matches!(args.command, Some(Command::Demo));
+    let (mut ciphertext, demo_key) = match demo {
+        true => {
+            ensure!(
+                args.ciphertext.is_none() && args.morse.is_none(),
+                "the demo encrypts its own message, it takes no --ciphertext or --morse"
+            );
+            let (key, ciphertext) = demo_message(&args.reflector)?;
+            (ciphertext, Some(key))
+        }
+        false => (read_ciphertext(&args)?, None),
+    };
+    let known = demo_key.as_ref().map(|_| DEMO_PLAINTEXT);
+    // the search runs until its limits unless the plaintext is known
+    let target_fitness = known.map(|plaintext| Fitness(args.metric.score(plaintext)));
+
+    let mut sim_opts = gen::Options {
+        engine: gen::EngineOptions {
+            max_plugs: args.max_plugs,
+            encoding: args.encoding,
+            reflector: args.reflector.clone(),
+            algorithm: args.engine,
+            seed: Some(args.seed.unwrap_or_else(rand::random)),
+            rng: args.rng,
+            mutation_schedule: args.mutation_schedule,
+            elitism: args.elitism,
+            selection_adaptation: args.adapt_selection,
+            selection: args.selection,
+            mutation_weights: args.mutation_weights.unwrap_or_default(),
+            crossover: args.crossover,
+            rotor_crossover: args.rotor_crossover,
+            plugboard_crossover: args.plugboard_crossover,
+            population_schedule: args.population_schedule.clone(),
+            tempering: args.tempering,
+            threads: args.threads,
+            pin_cores: args.pin_cores.clone(),
+            herivel_spread: args.herivel,
+            fixed_rings: args.fix_rings,
+            top_size: args
+                .rescore
+                .as_ref()
+                .map_or(0, |_| args.rescore_top)
+                .max(if args.explain { explain::TOP } else { 0 }),
+            sample_size: args.sample_size(),
+            genealogy: args.genealogy.as_ref().map_or(0, |_| args.genealogy_top),
+            explored_memory: args.explored_memory.map_or(0, |size| size.0),
+            ..Default::default()
+        },
+        fitness: gen::FitnessOptions {
+            metric: Some(args.metric.clone()),
+            scrambler_cache_size: args.scrambler_cache,
+            cache_backend: args.cache_backend,
+            coverage: args.coverage,
+            ..Default::default()
+        },
+        termination: gen::TerminationOptions {
+            target_fitness,
+            dictionary: args
+                .dictionary
+                .as_deref()
+                .map(Dictionary::load)
+                .transpose()?
+                .unwrap_or_default(),
+            dictionary_hits: args.dictionary_hits,
+            pattern: args.pattern.clone(),
+            ..Default::default()
+        },
+    };
+    if !args.stop.is_empty() {
+        let termination = &mut sim_opts.termination;
+        let criterion = args
+            .stop
+            .iter()
+            .cloned()
+            .fold(termination.limits(), |stop, trigger| {
+                stop.or(termination::trigger(trigger))
+            });
+        termination.criterion = Some(criterion);
+    }
+
+    if let Some(budget) = args.max_memory {
+        if sim_opts.fit_memory(budget)? {
+            note!(
+                "Memory budget {}: population {}, cache {} entries",
+                budget,
+                sim_opts.engine.population_size,
+                sim_opts.fitness.cache_size
+            );
+        }
+    }
+
+    if args.dry_run {
+        return print_plan(&ciphertext, &sim_opts);
+    }
+
+    if let Some(plaintext) = known {
+        note!("Plaintext: {}", plaintext);
+    }
+    if sim_opts.engine.algorithm == Algorithm::Ga && args.strategy == Strategy::Search {
+        note!("Seed: {}", sim_opts.engine.seed.unwrap_or_default());
+    }
+    note!("Ciphertext: {}", ciphertext);
+
+    if !args.pareto.is_empty() {
+        let mut opts = sim_opts;
+        opts.engine.population_size = args.pareto_population;
+        return print_pareto_front(&ciphertext, args.pareto.clone(), &opts, args.key_format);
+    }
+
+    // every step is passed on to the outputs enabled on the command line
+    let mut recorders: Vec<Recorder> = Vec::new();
+    // opened only now, a dry run or a Pareto front leaves the database as it is
+    #[cfg(feature = "history")]
+    if let Some(db) = &args.db {
+        let history = History::open(db)?;
+        let run = history.start_run(&ciphertext, &sim_opts)?;
+        recorders.push(Box::new(move |step| history.record(run, step)));
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(dir) = &args.parquet {
+        let mut export = ParquetExport::create(dir)?;
+        recorders.push(Box::new(move |step| export.record(step)));
+    }
+
+    #[cfg(feature = "serde")]
+    if let Some(path) = args.report.clone() {
+        let (ciphertext, seed, format) =
+            (ciphertext.clone(), sim_opts.engine.seed, args.report_format);
+        recorders.push(Box::new(move |step| match step {
+            sim::StepResult::Final { generation, reason } => {
+                let report = RunReport::new(&ciphertext, seed, generation, reason)?;
+                report.write(std::io::BufWriter::new(fs::File::create(&path)?), format)
+            }
+            sim::StepResult::Intermediate(_) => Ok(()),
+        }));
+    }
+
+    if let Some(dir) = args.heatmap.clone() {
+        let mut export = HeatmapExport::create(dir, args.heatmap_every, args.heatmap_format)?;
+        recorders.push(Box::new(move |step| export.record(step)));
+    }
+
+    if let Some(path) = args.genealogy.clone() {
+        recorders.push(Box::new(move |step| match step {
+            sim::StepResult::Final { generation, .. } => {
+                Ok(fs::write(&path, genealogy::dot(&generation.ancestry))?)
+            }
+            sim::StepResult::Intermediate(_) => Ok(()),
+        }));
+    }
+
+    if args.explain {
+        let mut narrator = Narrator::new(&sim_opts.engine);
+        recorders.push(Box::new(move |step| {
+            for line in narrator.step(step) {
+                note!("explain: {}", line);
+            }
+            Ok(())
+        }));
+    }
+
+    if let (true, Some(plaintext)) = (args.diff, known) {
+        let ciphertext = ciphertext.clone();
+        // escapes only when the notes go to a terminal, and never with NO_COLOR set
+        let terminal = match NOTES_TO_STDERR.load(Ordering::Relaxed) {
+            true => io::stderr().is_terminal(),
+            false => io::stdout().is_terminal(),
+        };
+        let color = terminal && env::var_os("NO_COLOR").is_none();
+        recorders.push(Box::new(move |step| {
+            let settings = &step.generation().best_settings;
+            let decrypted = enigma::Machine::new(settings)?.decrypt(&ciphertext);
+            let (line, right, letters) = compare::highlight(&decrypted, plaintext, color);
+            note!("diff: {}/{} {}", right, letters, line);
+            Ok(())
+        }));
+    }
+
+    // the search is held before its next generation until the next SIGUSR1
+    #[cfg(unix)]
+    {
+        let pause = sim_opts.engine.pause.clone();
+        // toggling is a single atomic operation, safe in a signal handler
+        unsafe {
+            signal_hook::low_level::register(signal_hook::consts::SIGUSR1, move || {
+                pause.toggle();
+            })
+        }?;
+        note!("Pause or resume: kill -USR1 {}", std::process::id());
+    }
+
+    let seed = sim_opts.engine.seed.unwrap_or_default();
+    // the last generation of the search and why it ended, for the final NDJSON event
+    #[cfg_attr(not(feature = "serde"), allow(unused_variables))]
+    let (found_settings, search) = match args.strategy {
+        Strategy::Search => {
+            let intercept = args.morse.as_deref().filter(|_| args.follow).map(|path| {
+                let tolerance = args.morse_tolerance;
+                move || -> anyhow::Result<String> {
+                    let decoded = morse::decode(&fs::read_to_string(path)?, tolerance);
+                    Ok(enigma::normalize(&decoded.text))
+                }
+            });
+            let reporting = Reporting {
+                every: args.report_every,
+                improvements: args.report_improvements,
+                #[cfg(feature = "serde")]
+                format: args.progress_format,
+            };
+            let (generation, reason) =
+                run_simulation(&mut ciphertext, sim_opts, intercept, reporting, |step| {
+                    recorders.iter_mut().try_for_each(|record| record(step))
+                })?;
+            if let Some(coverage) = &generation.coverage {
+                note!("Coverage: {}", coverage);
+            }
+            if let Some((count, first)) = &generation.invalid_genomes {
+                note!("Invalid genomes: {} scored 0, the first {}", count, first);
+            }
+            let settings = match &args.rescore {
+                Some(rescorer) => rescore(&ciphertext, rescorer, args.rescore_top, &generation)?,
+                None => generation.best_settings.clone(),
+            };
+            (settings, Some((generation, reason)))
+        }
+        Strategy::Gillogly => {
+            let gillogly = Gillogly {
+                climb_metric: args
+                    .climb
+                    .clone()
+                    .unwrap_or(Gillogly::default().climb_metric),
+                climb_search: args.climb_search,
+                ..Default::default()
+            };
+            let attack = gillogly.run(&ciphertext, &sim_opts)?;
+            for (stage, duration) in &attack.stages {
+                note!("Gillogly: {} in {:?}", stage, duration);
+            }
+            note!("Score: {:.5}", attack.score);
+            (attack.settings, None)
+        }
+    };
+    let found_settings = match &args.climb {
+        Some(metric) if args.strategy == Strategy::Search => {
+            if args.explain {
+                note!("explain: {}", explain::climb(metric));
+            }
+            let mut climb = PlugboardClimb::new(&ciphertext, &found_settings, metric)?;
+            let score = climb.refine(args.max_plugs, args.climb_search);
+            note!(
+                "Plugboard climb: {} {:.5} after {} candidates",
+                metric,
+                score,
+                climb.evaluations()
+            );
+            enigma::Settings {
+                plugboard: climb.plugboard(),
+                ..found_settings
+            }
+        }
+        _ => found_settings,
+    };
+    let found_machine = enigma::Machine::new(&found_settings)?;
+    let found_plaintext = found_machine.decrypt(&ciphertext);
+
+    note!("Decrypted plaintext: {}", found_plaintext);
+    if args.confidence_samples > 0 {
+        let metric = args.climb.as_ref().unwrap_or(&args.metric);
+        let baseline = Baseline::sample(
+            &ciphertext,
+            metric,
+            &args.reflector,
+            args.max_plugs,
+            args.confidence_samples,
+            &mut ChaCha8Rng::seed_from_u64(seed),
+        )?;
+        let confidence = baseline.confidence(metric.score(&found_plaintext));
+        note!(
+            "Confidence: {:.1} standard deviations above {} random keys by {}, chance reaches {:.1}: {}",
+            confidence.sigmas, baseline.samples, metric, confidence.chance, confidence.level
+        );
+    }
+    match language::detect(&found_plaintext) {
+        Some(detection) => {
+            note!(
+                "Language: {} (confidence {:.3})",
+                detection.language,
+                detection.confidence
+            );
+            note!(
+                "Wrong letters: {:.1}% estimated",
+                100.0 * detection.language.corruption_rate(&found_plaintext)
+            );
+        }
+        None => note!("Language: unknown, the decryption doesn't read as any"),
+    }
+    // the ring setting of the left rotor is the search's guess, any goes with its position
+    let key = interchange::export(&found_settings.canonicalize(), args.key_format)?;
+    note!("Key:\n{}", key.trim_end());
+    if let Some(demo_key) = demo_key {
+        match found_settings.is_equivalent_to(&demo_key, enigma::keystrokes(&ciphertext)) {
+            true => note!("Demo key recovered, or one that encrypts the message alike"),
+            false => note!("Demo key not recovered, it is {}", demo_key),
+        }
+    }
+    #[cfg(feature = "serde")]
+    if let (ProgressFormat::Ndjson, Some((generation, reason))) = (args.progress_format, &search) {
+        let fitness = Fitness(args.metric.score(&found_plaintext));
+        let event = ProgressEvent::finished(
+            generation,
+            reason,
+            &found_settings.canonicalize(),
+            found_plaintext,
+            fitness,
+        );
+        println!("{}", event.to_line()?);
+    }
+    Ok(())
+}
+
+// the message the demo encrypts and cracks, with the key below
+const DEMO_PLAINTEXT: &str = "TO BE OR NOT TO BE THAT IS THE QUESTION WHETHER TIS NOBLER IN THE MIND TO SUFFER THE SLINGS AND ARROWS OF OUTRAGEOUS FORTUNE OR TO TAKE ARMS AGAINST A SEA OF TROUBLES AND BY OPPOSING END THEM TO DIE TO SLEEP NO MORE AND BY A SLEEP TO SAY WE END THE HEARTACHE AND THE THOUSAND NATURAL SHOCKS THAT FLESH IS HEIR TO TIS A CONSUMMATION DEVOUTLY TO BE WISHD TO DIE TO SLEEP TO SLEEP PERCHANCE TO DREAM AY THERES THE RUB FOR IN THAT SLEEP OF DEATH WHAT DREAMS MAY COME WHEN WE HAVE SHUFFLED OFF THIS MORTAL COIL MUST GIVE US PAUSE THERES THE RESPECT THAT MAKES CALAMITY OF SO LONG LIFE";
+
+// the demo key and the message encrypted with it, under a reflector the search can find
+fn demo_message(reflector: &gen::ReflectorSearch) -> anyhow::Result<(enigma::Settings, String)> {
     let settings = enigma::Settings {
         rotors: (2, 5, 3),
         ring_settings: (8, 5, 20),
         rotor_positions: (13, 3, 21),
+        plugboard: vec![],
+        // B is also one of the UKW-D wirings, so every search mode can find it
+        reflector: match reflector {
+            gen::ReflectorSearch::Known(reflector) => reflector.clone(),
+            _ => enigma::Reflector::B,
+        },
     };
+    let ciphertext = enigma::Machine::new(&settings)?.encrypt(DEMO_PLAINTEXT);
+    Ok((settings, ciphertext))
+}
 
-    let sim_opts = gen::Options {
-        fitness_scale: 1_000_000,
-        population_size: 1_500_000,
-        generation_limit: 300,
-        time_limit: Duration::minutes(15),
-        selection_ratio: 0.5,
-        mutation_rate: 0.05,
-        reinsertion_ratio: 0.7,
-        cache_size: 3_000_000,
-    };
+// the ciphertext to crack: a file, - for stdin, or a Morse intercept
+fn read_ciphertext(args: &Args) -> anyhow::Result<String> {
+    match (&args.morse, &args.ciphertext) {
+        (Some(path), _) => {
+            let decoded = morse::decode(&fs::read_to_string(path)?, args.morse_tolerance);
+            note!(
+                "Morse: {} garbled codes corrected, {} unreadable dropped",
+                decoded.corrected,
+                decoded.unreadable
+            );
+            Ok(enigma::normalize(&decoded.text))
+        }
+        (None, Some(path)) => {
+            let text = match path.as_os_str() == "-" {
+                true => std::io::read_to_string(std::io::stdin())?,
+                false => fs::read_to_string(path)?,
+            };
+            Ok(enigma::normalize(text.trim()))
+        }
+        (None, None) => bail!(
+            "no ciphertext to crack, give --ciphertext or --morse, or try the demo subcommand"
+        ),
+    }
+}
 
-    let target_fitness = Some(index_of_coincidence_norm(
-        &plaintext,
-        sim_opts.fitness_scale,
-    ));
+fn print_pareto_front(
+    ciphertext: &str,
+    metrics: Vec<Metric>,
+    opts: &gen::Options,
+    key_format: KeyFormat,
+) -> anyhow::Result<()> {
+    let names = metrics.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+    let (front, reason, duration) = pareto::search(ciphertext, metrics, opts)?;
+    note!(
+        "Pareto front of {} keys ({}), {} after {:?}",
+        front.len(),
+        names.join(", "),
+        reason,
+        duration
+    );
 
-    let machine = enigma::Machine::new(&settings).unwrap();
-    let ciphertext = machine.encrypt(plaintext);
+    for point in front {
+        let scores = point
+            .objectives
+            .iter()
+            .map(|score| format!("{:.5}", score))
+            .collect::<Vec<_>>();
+        let plaintext = enigma::Machine::new(&point.genome)?.decrypt(ciphertext);
+        let key = interchange::export(&point.genome, key_format)?;
+        note!("\n[{}] {}", scores.join(", "), plaintext);
+        note!("{}", key.trim_end());
+    }
+    Ok(())
+}
 
-    println!("Plaintext: {}", plaintext);
-    println!("Ciphertext: {}", ciphertext);
+fn print_positions(
+    ciphertext: &Path,
+    key: &enigma::Settings,
+    width: usize,
+    stride: usize,
+    language: Language,
+    key_format: KeyFormat,
+) -> anyhow::Result<()> {
+    const SHOWN: usize = 5;
 
-    let found_settings = run_simulation(&ciphertext, sim_opts, target_fitness).unwrap();
-    let found_machine = enigma::Machine::new(&found_settings).unwrap();
-    let found_plaintext = found_machine.decrypt(&ciphertext);
+    let ciphertext = fs::read_to_string(ciphertext)?;
+    let started = Instant::now();
+    let beam = beam::search(&ciphertext, key, language, width, stride)?;
+    println!(
+        "Beam of {} over the rotor positions in {:?}",
+        width,
+        started.elapsed()
+    );
+    print_ranked(&ciphertext, &beam[..beam.len().min(SHOWN)], key_format)
+}
+
+// the menus of the placements printed
+fn print_placements(
+    ciphertext: &str,
+    crib: &str,
+    top: usize,
+    print_menus: bool,
+) -> anyhow::Result<Vec<Menu>> {
+    let placements = crib::drag(ciphertext, crib)?;
+    let letters = enigma::normalize(ciphertext).replace(' ', "");
+    let crib = enigma::normalize(crib).replace(' ', "");
+    println!(
+        "{} of {} placements don't crash",
+        placements.len(),
+        letters.len() - crib.len() + 1
+    );
+
+    let mut menus = Vec::new();
+    for placement in placements.iter().take(top) {
+        let cipher = &letters[placement.offset..placement.offset + crib.len()];
+        println!(
+            "\noffset {}: {} loops, {} links of {} letters connected",
+            placement.offset, placement.loops, placement.links, placement.letters
+        );
+        println!("{}\n{}", crib, cipher);
 
-    println!("Decrypted plaintext: {}", found_plaintext);
+        let menu = Menu::new(ciphertext, &crib, placement.offset)?;
+        if print_menus {
+            print!("{}", menu.diagram());
+        }
+        menus.push(menu);
+    }
+    Ok(menus)
 }
 
-fn run_simulation(
+fn print_candidates(
+    candidates: &[Candidate],
+    menu: &Menu,
+    key_format: KeyFormat,
+) -> anyhow::Result<()> {
+    let letter = |x: u8| (b'A' + x) as char;
+    for (i, candidate) in candidates.iter().enumerate() {
+        let stop = &candidate.stop;
+        println!(
+            "\n#{} stop at {}, {} plugged to {}, {} crib letters [{:.5}] {}",
+            i + 1,
+            message_key::letters(stop.positions),
+            letter(menu.central),
+            letter(stop.partner),
+            candidate.crib_matches,
+            candidate.score,
+            candidate.plaintext
+        );
+        let key = interchange::export(&candidate.settings, key_format)?;
+        println!("{}", key.trim_end());
+    }
+    let plaintexts = candidates.iter().map(|c| c.plaintext.clone());
+    print_comparison(&plaintexts.collect::<Vec<_>>());
+    Ok(())
+}
+
+fn print_indicator(indicator: &Indicator, ground: (u8, u8, u8)) {
+    println!(
+        "Indicator decrypts to {}, {} of 3 letters doubled",
+        indicator.decrypted, indicator.agreeing
+    );
+    if indicator.agreeing == 0 {
+        println!("No letter doubled, the daily key or the ground setting is likely wrong");
+    }
+    for &key in &indicator.candidates {
+        let letters = message_key::letters(key);
+        match message_key::cilly(key, ground) {
+            Some(cilly) => println!("Message key {} (cilly: {})", letters, cilly),
+            None => println!("Message key {}", letters),
+        }
+    }
+}
+
+// keys best first with their score, message setting and decryption
+fn print_ranked(
     ciphertext: &str,
-    opts: gen::Options,
-    target_fitness: Option<usize>,
-) -> anyhow::Result<enigma::Settings> {
+    ranked: &[(enigma::Settings, f64)],
+    key_format: KeyFormat,
+) -> anyhow::Result<()> {
+    let mut plaintexts = Vec::new();
+    for (i, (settings, score)) in ranked.iter().enumerate() {
+        let plaintext = enigma::Machine::new(settings)?.decrypt(ciphertext);
+        let key = interchange::export(settings, key_format)?;
+        println!(
+            "\n#{} {} [{:.5}] {}",
+            i + 1,
+            message_key::letters(settings.rotor_positions),
+            score,
+            plaintext
+        );
+        println!("{}", key.trim_end());
+        plaintexts.push(plaintext);
+    }
+    print_comparison(&plaintexts);
+    Ok(())
+}
+
+// the decryptions of keys close in score aligned, with the letters where they part from the best
+fn print_comparison(plaintexts: &[String]) {
+    const WIDTH: usize = 60;
+
+    if plaintexts.len() < 2 {
+        return;
+    }
+    let differences = compare::differences(plaintexts)
+        .iter()
+        .skip(1)
+        .map(|n| n.to_string())
+        .collect::<Vec<_>>();
+    println!(
+        "\nDecryptions side by side, #2 on differ from #1 in {} characters:\n",
+        differences.join(", ")
+    );
+    print!("{}", compare::side_by_side(plaintexts, WIDTH));
+}
+
+fn write_challenges(
+    difficulty: Difficulty,
+    count: usize,
+    out: &Path,
+    answers: &Path,
+    seed: Option<u64>,
+) -> anyhow::Result<()> {
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let level = difficulty.level();
+    let challenges = (0..count)
+        .map(|_| Challenge::generate(&level, &mut rng))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    challenge::write(out, answers, &challenges)?;
+
+    println!(
+        "{} {} challenges ({} letters, {} plugs, reflector {}) written to {}, answers to {}, seed {}",
+        count,
+        difficulty,
+        level.message_length,
+        level.plugs,
+        level.reflector,
+        out.display(),
+        answers.display(),
+        seed
+    );
+    Ok(())
+}
+
+fn write_traffic(
+    opts: traffic::Options,
+    count: usize,
+    out: &Path,
+    seed: Option<u64>,
+) -> anyhow::Result<()> {
+    let seed = seed.unwrap_or_else(rand::random);
+    let traffic = Traffic::new(opts.clone(), ChaCha8Rng::seed_from_u64(seed))?;
+    let messages = traffic.take(count).collect::<Vec<_>>();
+    traffic::write(out, &messages)?;
+
+    println!(
+        "{} messages of {} days ({} indicators, {} garbled letters) written to {}, seed {}",
+        count,
+        messages.last().map_or(0, |message| message.day),
+        opts.procedure,
+        messages
+            .iter()
+            .map(|message| message.garbled)
+            .sum::<usize>(),
+        out.display(),
+        seed
+    );
+    Ok(())
+}
+
+fn print_plan(ciphertext: &str, opts: &gen::Options) -> anyhow::Result<()> {
+    const SAMPLE_SIZE: u32 = 1_000;
+    const MIB: usize = 1024 * 1024;
+
+    opts.validate()?;
+
     let fitness_calc = gen::FitnessCalc {
-        ciphertext: Arc::new(ciphertext.to_string()),
-        max_value: opts.fitness_scale,
-        cache: Cache::new(opts.cache_size as u64),
+        ciphertext: gen::Ciphertext::new(ciphertext.to_string()),
+        cache: FitnessCache::new(0),
+        scramblers: ScramblerCache::new(0),
+        coverage: Coverage::default(),
+        invalid: Default::default(),
+        metric: opts.fitness.metric.clone(),
     };
 
-    let selector = MaximizeSelector::new(opts.selection_ratio, 2);
+    let mut rng = rand::thread_rng();
+    let started = Instant::now();
+    for i in 0..SAMPLE_SIZE {
+        let settings = gen::SettingsBuilder {
+            max_plugs: opts.engine.max_plugs,
+            reflector: opts.engine.reflector.clone(),
+            herivel_spread: opts.engine.herivel_spread,
+            fixed_rings: opts.engine.fixed_rings,
+        }
+        .build_genome(i as usize, &mut rng);
+        fitness_calc.fitness_of(&settings);
+    }
+    let per_evaluation = started.elapsed() / SAMPLE_SIZE;
 
-    let mutator = gen::SettingsMutator {
-        mutation_rate: opts.mutation_rate,
+    // upper bound: every individual is evaluated without cache hits, spread over all cores
+    let threads = opts.engine.worker_threads();
+    // in nanoseconds, any generation limit up to u64::MAX fits; past the time limit it's unbounded
+    let per_generation =
+        per_evaluation.as_nanos() * opts.engine.population_size as u128 / threads as u128;
+    let termination = &opts.termination;
+    let total = per_generation
+        .saturating_mul(termination.generation_limit as u128)
+        .min(termination.time_limit.to_std()?.as_nanos());
+    let shown = |nanos: u128| match u64::try_from(nanos) {
+        Ok(nanos) => format!("{:?}", std::time::Duration::from_nanos(nanos)),
+        Err(_) => "unbounded".to_string(),
     };
 
-    let reinserter = ElitistReinserter::new(fitness_calc.clone(), true, opts.reinsertion_ratio);
+    println!("Dry run, the search is not started");
+    println!("Enigma model: {}", enigma::MODEL_NAME);
+    println!("Reflector: {}", opts.engine.reflector);
+    println!(
+        "Keyspace size: {} (up to {} plugboard pairs)",
+        enigma::keyspace_size(opts.engine.max_plugs) * opts.engine.reflector.choices(),
+        opts.engine.max_plugs
+    );
+    if opts.engine.fixed_rings {
+        println!(
+            "Searched keyspace: {} with the left and right rings fixed",
+            enigma::fixed_rings_keyspace_size(opts.engine.max_plugs)
+                * opts.engine.reflector.choices()
+        );
+    }
+    println!(
+        "Fitness metric: {}",
+        opts.fitness
+            .metric
+            .as_ref()
+            .unwrap_or(&Metric::IndexOfCoincidence)
+    );
+    println!("Ciphertext length: {}", ciphertext.len());
+    println!(
+        "Memory estimate: population {} MiB, cache {} MiB",
+        opts.estimated_population_bytes() / MIB,
+        opts.estimated_cache_bytes() / MIB,
+    );
+    println!(
+        "Runtime estimate: {:?} per evaluation, {} per generation on {} threads, \
+         up to {} for {} generations (time limit {:?})",
+        per_evaluation,
+        shown(per_generation),
+        threads,
+        shown(total),
+        termination.generation_limit,
+        termination.time_limit.to_std()?,
+    );
 
-    let initial_population = build_population()
-        .with_genome_builder(gen::SettingsBuilder)
-        .of_size(opts.population_size)
-        .uniform_at_random();
+    // the generations the time limit leaves, if it cuts the search short
+    let generations = match per_generation {
+        0 => termination.generation_limit,
+        nanos => u64::try_from(total / nanos).map_or(termination.generation_limit, |generations| {
+            generations.min(termination.generation_limit)
+        }),
+    };
+    let estimate = estimate::estimate(ciphertext, opts, generations, &mut rng)?;
+    println!(
+        "Difficulty: {}, the right rotors without the plugs score {:.1} standard deviations above \
+         random keys of {} letters where chance reaches {:.1}; {:.0} generations expected, \
+         {:.1}% chance within {} generations",
+        estimate.outlook,
+        estimate.signal,
+        estimate.letters,
+        estimate.chance,
+        estimate.expected_generations,
+        estimate.probability * 100.0,
+        generations,
+    );
 
-    let termination = or(
-        or(
-            GenerationLimit::new(opts.generation_limit),
-            TimeLimit::new(opts.time_limit),
-        ),
-        FitnessLimit::new(target_fitness.unwrap_or(opts.fitness_scale)),
-    );
-
-    let mut sim = simulate(
-        genetic_algorithm()
-            .with_evaluation(fitness_calc)
-            .with_selection(selector)
-            .with_crossover(gen::SettingsCrossover)
-            .with_mutation(mutator)
-            .with_reinsertion(reinserter)
-            .with_initial_population(initial_population)
-            .build(),
-    )
-    .until(termination)
-    .build();
-
-    loop {
-        match sim.step() {
-            Ok(SimResult::Intermediate(step)) => {
-                let evaluated_population = step.result.evaluated_population;
-                let best_solution = step.result.best_solution;
-                println!(
-                    "step: generation: {}, average_fitness: {}, \
-                     best fitness: {}, duration: {}, processing_time: {}",
-                    step.iteration,
-                    evaluated_population.average_fitness(),
-                    best_solution.solution.fitness,
-                    step.duration.fmt(),
-                    step.processing_time.fmt(),
-                );
-                let settings = best_solution.solution.genome;
-                println!("settings: {:?}", settings);
+    Ok(())
+}
+
+// the generations printed and passed on to the outputs, the last one always is
+#[derive(Debug, Clone, Copy)]
+struct Reporting {
+    every: u64,
+    // only the generations that improved the best fitness
+    improvements: bool,
+    #[cfg(feature = "serde")]
+    format: ProgressFormat,
+}
+
+impl Reporting {
+    fn includes(&self, generation: &sim::Generation) -> bool {
+        generation.iteration.is_multiple_of(self.every)
+            && (!self.improvements || generation.best_found_in == generation.iteration)
+    }
+}
+
+// with follow the ciphertext is read again between generations, and left as the search ended
+// with
+fn run_simulation(
+    ciphertext: &mut String,
+    opts: gen::Options,
+    mut follow: Option<impl FnMut() -> anyhow::Result<String>>,
+    reporting: Reporting,
+    mut on_step: impl FnMut(&sim::StepResult) -> anyhow::Result<()>,
+) -> anyhow::Result<(sim::Generation, String)> {
+    let mut sim = sim::Simulation::new(ciphertext, opts)?;
+
+    while let Some(step) = sim.next() {
+        let step = step?;
+        let reported = match &step {
+            sim::StepResult::Intermediate(generation) => reporting.includes(generation),
+            sim::StepResult::Final { .. } => true,
+        };
+        if reported {
+            on_step(&step)?;
+            print_step(ciphertext, &step, &reporting)?;
+        }
+        match step {
+            sim::StepResult::Intermediate(_) => {
+                if let Some(read) = follow.as_mut() {
+                    let grown = read()?;
+                    if grown != *ciphertext {
+                        note!("Ciphertext: {} letters now, {}", grown.len(), grown);
+                        sim.replace_ciphertext(&grown);
+                        *ciphertext = grown;
+                    }
+                }
             }
-            Ok(SimResult::Final(step, processing_time, duration, reason)) => {
-                let best_solution = step.result.best_solution;
-                println!(
-                    "Final result after {}: generation: {}, \
-                     best solution with fitness {} found in generation {}, processing_time: {}, reason: {}",
-                    duration.fmt(),
-                    step.iteration,
-                    best_solution.solution.fitness,
-                    best_solution.generation,
-                    processing_time.fmt(),
+            sim::StepResult::Final { generation, reason } => return Ok((generation, reason)),
+        }
+    }
+
+    Err(anyhow!("simulation finished without a final result"))
+}
+
+#[cfg_attr(not(feature = "serde"), allow(unused_variables))]
+fn print_step(
+    ciphertext: &str,
+    step: &sim::StepResult,
+    reporting: &Reporting,
+) -> anyhow::Result<()> {
+    #[cfg(feature = "serde")]
+    if reporting.format == ProgressFormat::Ndjson {
+        // the final event is printed by main, with the key it reports
+        if let sim::StepResult::Intermediate(_) = step {
+            println!("{}", ProgressEvent::new(ciphertext, step)?.to_line()?);
+        }
+        return Ok(());
+    }
+    match step {
+        sim::StepResult::Intermediate(generation) => {
+            println!(
+                "step: generation: {}, average_fitness: {:.6}, median_fitness: {:.6}, \
+                 fitness_std_dev: {:.6}, best fitness: {:.6}, duration: {:?}, processing_time: {:?}",
+                generation.iteration,
+                generation.average_fitness,
+                generation.median_fitness,
+                generation.fitness_std_dev,
+                generation.best_fitness,
+                generation.duration,
+                generation.processing_time,
+            );
+            println!("settings: {:?}", generation.best_settings);
+        }
+        sim::StepResult::Final { generation, reason } => {
+            println!(
+                "Final result after {:?}: generation: {}, \
+                 best solution with fitness {:.6} found in generation {}, processing_time: {:?}, reason: {}",
+                generation.duration,
+                generation.iteration,
+                generation.best_fitness,
+                generation.best_found_in,
+                generation.processing_time,
+                reason,
+            );
+            println!("settings: {:?}", generation.best_settings);
+        }
+    }
+
+    Ok(())
+}
+
+// the best key of the run and the best of the last generation ranked again by the rescorer,
+// one of the keys that decrypt the ciphertext alike each
+fn rescore(
+    ciphertext: &str,
+    rescorer: &Rescorer,
+    top: usize,
+    generation: &sim::Generation,
+) -> anyhow::Result<enigma::Settings> {
+    let len = enigma::keystrokes(ciphertext);
+    let mut keys = vec![generation.best_settings.canonicalize()];
+    for individual in generation.top.iter().take(top) {
+        let key = individual.genome.canonicalize();
+        if !keys.iter().any(|other| other.is_equivalent_to(&key, len)) {
+            keys.push(key);
+        }
+    }
+
+    let started = Instant::now();
+    let ranked = rescorer.rank(ciphertext, keys.clone())?;
+    let (settings, score) = ranked.into_iter().next().expect("the best key is ranked");
+    note!(
+        "Rescored {} keys by {} in {:?}: the search's #{} is best with {:.5}",
+        keys.len(),
+        rescorer,
+        started.elapsed(),
+        keys.iter()
+            .position(|key| *key == settings)
+            .unwrap_or_default()
+            + 1,
+        score
+    );
+    Ok(settings)
+}
+
+#[cfg(feature = "history")]
+fn print_history(history: &History, run: Option<i64>, limit: usize) -> anyhow::Result<()> {
+    let Some(id) = run else {
+        for run in history.runs(limit)? {
+            let result = match (run.best_fitness, &run.best_key, &run.reason) {
+                (Some(fitness), Some(key), Some(reason)) => format!(
+                    "{} generations, best fitness {:.6}, {}, key {}",
+                    run.generations.unwrap_or_default(),
+                    fitness,
                     reason,
-                );
-                let settings = best_solution.solution.genome;
-                println!("settings: {:?}", settings);
-                return Ok(settings);
-            }
-            Err(err) => {
-                return Err(anyhow!(err));
-            }
+                    key
+                ),
+                _ => "unfinished".to_string(),
+            };
+            let seed = run.seed.map_or("-".to_string(), |seed| seed.to_string());
+            println!("{} {} seed {}: {}", run.id, run.started_at, seed, result);
         }
+        return Ok(());
+    };
+
+    let run = history.run(id)?;
+    println!("Run {} started at {}", run.id, run.started_at);
+    println!("Configuration: {}", run.config);
+    println!("Ciphertext: {}", run.ciphertext);
+    match (&run.best_key, &run.reason) {
+        (Some(key), Some(reason)) => {
+            let machine = enigma::Machine::new(&key.parse()?)?;
+            println!(
+                "Finished at {}: {}",
+                run.finished_at.unwrap_or_default(),
+                reason
+            );
+            println!("Key: {}", key);
+            println!("Decrypted plaintext: {}", machine.decrypt(&run.ciphertext));
+        }
+        _ => println!("Unfinished"),
     }
+    for generation in history.generations(id)? {
+        println!(
+            "generation: {}, average_fitness: {:.6}, median_fitness: {:.6}, \
+             fitness_std_dev: {:.6}, best fitness: {:.6}, duration: {}ms, settings: {}",
+            generation.iteration,
+            generation.average_fitness,
+            generation.median_fitness,
+            generation.fitness_std_dev,
+            generation.best_fitness,
+            generation.duration_ms,
+            generation.best_key,
+        );
+    }
+
+    Ok(())
 }
@@ -0,0 +1,266 @@
+// message settings under a broken daily key: the operator picked the starting positions of the
+// rotors for every message, the rotor order, ring settings and plugboard were the day's
+use std::fmt;
+
+use anyhow::ensure;
+use rayon::prelude::*;
+
+use crate::{
+    engine,
+    enigma::{self, Machine, Settings, LETTERS_NUM},
+    gen,
+    metrics::Metric,
+};
+
+// keys decrypted in lockstep by a worker at a time
+const BATCH: usize = 1024;
+
+// rows of the Enigma keyboard, QWERTZ with P on the bottom row
+const KEYBOARD: [&str; 3] = ["QWERTZUIO", "ASDFGHJK", "PYXCVBNML"];
+
+/// Every one of the 26^3 message settings under the daily key, ranked by the metric of its
+/// decryption, the best `top` first; equal scores keep the alphabetical order of the
+/// settings. The rotor positions of the daily key are ignored; the threads are the ones of the
+/// engine options.
+pub fn recover(
+    ciphertext: &str,
+    daily_key: &Settings,
+    metric: &Metric,
+    top: usize,
+    engine: &gen::EngineOptions,
+) -> anyhow::Result<Vec<(Settings, f64)>> {
+    let l = LETTERS_NUM;
+    let positions = (1..=l)
+        .flat_map(|left| {
+            (1..=l).flat_map(move |middle| (1..=l).map(move |right| (left, middle, right)))
+        })
+        .collect();
+    rank(ciphertext, daily_key, metric, positions, top, engine)
+}
+
+/// The message settings among `positions` under the daily key, ranked as by `recover`.
+pub fn rank(
+    ciphertext: &str,
+    daily_key: &Settings,
+    metric: &Metric,
+    positions: Vec<(u8, u8, u8)>,
+    top: usize,
+    engine: &gen::EngineOptions,
+) -> anyhow::Result<Vec<(Settings, f64)>> {
+    daily_key.validate()?;
+    let keys = positions
+        .into_iter()
+        .map(|rotor_positions| Settings {
+            rotor_positions,
+            ..daily_key.clone()
+        })
+        .collect::<Vec<_>>();
+
+    let pool = engine::thread_pool(engine)?;
+    let scores = pool
+        .install(|| {
+            keys.par_chunks(BATCH)
+                .map(|batch| {
+                    let plaintexts = enigma::decrypt_batch(batch, ciphertext)?;
+                    Ok(plaintexts
+                        .iter()
+                        .map(|p| metric.score(p))
+                        .collect::<Vec<_>>())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?
+        .concat();
+
+    let mut ranked = keys.into_iter().zip(scores).collect::<Vec<_>>();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked.truncate(top);
+    Ok(ranked)
+}
+
+// rotor positions as the operator wrote them, e.g. QEW
+pub fn letters((left, middle, right): (u8, u8, u8)) -> String {
+    [left, middle, right]
+        .iter()
+        .map(|&p| (b'A' + p - 1) as char)
+        .collect()
+}
+
+/// A message key an operator picked without thinking: the same letter three times, keys next
+/// to each other on the keyboard, letters in alphabetical order or the ground setting itself.
+/// Such "cillies" helped break the daily keys at Bletchley Park.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cilly {
+    Repeated,
+    Keyboard,
+    Alphabet,
+    Ground,
+}
+
+impl fmt::Display for Cilly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cilly::Repeated => write!(f, "repeated letter"),
+            Cilly::Keyboard => write!(f, "keyboard run"),
+            Cilly::Alphabet => write!(f, "alphabetical run"),
+            Cilly::Ground => write!(f, "ground setting"),
+        }
+    }
+}
+
+// the kind of cilly the message key is, if any; positions 1..=26
+pub fn cilly(key: (u8, u8, u8), ground: (u8, u8, u8)) -> Option<Cilly> {
+    let (a, b, c) = key;
+    let steps = (b as i8 - a as i8, c as i8 - b as i8);
+    let letter = |p: u8| (b'A' + p - 1) as char;
+    // in a row or a column of the keyboard, either way
+    let keyboard = |x: char, y: char| {
+        let place = |c: char| {
+            KEYBOARD
+                .iter()
+                .enumerate()
+                .find_map(|(row, keys)| keys.find(c).map(|column| (row as i8, column as i8)))
+        };
+        place(y).zip(place(x)).map(|(y, x)| (y.0 - x.0, y.1 - x.1))
+    };
+
+    if a == b && b == c {
+        Some(Cilly::Repeated)
+    } else if key == ground {
+        Some(Cilly::Ground)
+    } else if steps.0 == steps.1 && steps.0.abs() == 1 {
+        Some(Cilly::Alphabet)
+    } else {
+        let first = keyboard(letter(a), letter(b))?;
+        let second = keyboard(letter(b), letter(c))?;
+        let adjacent = matches!(first, (0, 1 | -1) | (1 | -1, 0));
+        (adjacent && first == second).then_some(Cilly::Keyboard)
+    }
+}
+
+/// What the doubled indicator of a message sent before May 1940 gives away: the operator
+/// typed the message key twice at the ground setting of the day, so under the right daily key
+/// the six letters decrypt to the same three twice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Indicator {
+    // the six letters decrypted at the ground setting
+    pub decrypted: String,
+    // letters whose two copies agree, 3 for a key sent intact
+    pub agreeing: usize,
+    // the message keys that fit: a letter whose copies disagree was garbled in one of them,
+    // a copy marked garbled leaves the other, both all 26 letters
+    pub candidates: Vec<(u8, u8, u8)>,
+}
+
+impl Indicator {
+    // the rotor positions of the daily key are the ground setting
+    pub fn analyze(daily_key: &Settings, indicator: &str) -> anyhow::Result<Self> {
+        let indicator = enigma::normalize(indicator).replace(' ', "");
+        ensure!(
+            indicator.len() == 6,
+            "a doubled indicator has 6 letters, got {:?}",
+            indicator
+        );
+        let decrypted = Machine::new(daily_key)?.decrypt(&indicator);
+        let d = decrypted
+            .bytes()
+            .map(|c| (c != enigma::GARBLE).then(|| c - b'A' + 1))
+            .collect::<Vec<_>>();
+
+        let choices = |i: usize| match (d[i], d[i + 3]) {
+            (Some(a), Some(b)) if a == b => vec![a],
+            (Some(a), Some(b)) => vec![a, b],
+            (Some(a), None) | (None, Some(a)) => vec![a],
+            (None, None) => (1..=LETTERS_NUM).collect(),
+        };
+        let candidates = choices(0)
+            .into_iter()
+            .flat_map(|a| choices(1).into_iter().map(move |b| (a, b)))
+            .flat_map(|(a, b)| choices(2).into_iter().map(move |c| (a, b, c)))
+            .collect();
+
+        Ok(Self {
+            agreeing: (0..3)
+                .filter(|&i| d[i].is_some() && d[i] == d[i + 3])
+                .count(),
+            decrypted,
+            candidates,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{language::Language, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_recover() {
+        let settings = "IV II V / 04 11 23 / Q E W / AZ BY CX DW".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..200]);
+        let daily_key = Settings {
+            rotor_positions: (1, 1, 1),
+            ..settings.clone()
+        };
+
+        let metric = Metric::Bigram(Language::English);
+        let engine = gen::EngineOptions::default();
+        let ranked = recover(&ciphertext, &daily_key, &metric, 10, &engine).unwrap();
+        assert_eq!(ranked.len(), 10);
+        assert_eq!(ranked[0].0, settings);
+        assert_eq!(letters(ranked[0].0.rotor_positions), "QEW");
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert_eq!(
+            recover(&ciphertext, &daily_key, &metric, usize::MAX, &engine)
+                .unwrap()
+                .len(),
+            26 * 26 * 26
+        );
+    }
+
+    #[test]
+    fn test_indicator() {
+        let daily_key = "IV II V / 04 11 23 / G K X / AZ BY CX DW".parse::<Settings>();
+        let daily_key = daily_key.unwrap();
+        let machine = Machine::new(&daily_key).unwrap();
+
+        let indicator = Indicator::analyze(&daily_key, &machine.encrypt("QEWQEW")).unwrap();
+        assert_eq!(indicator.decrypted, "QEWQEW");
+        assert_eq!(indicator.agreeing, 3);
+        assert_eq!(indicator.candidates, vec![(17, 5, 23)]);
+
+        // the second E was misheard, either copy may be the right one
+        let mut garbled = machine.encrypt("QEWQEW").into_bytes();
+        garbled[4] = if garbled[4] == b'A' { b'B' } else { b'A' };
+        let indicator = Indicator::analyze(&daily_key, &String::from_utf8(garbled).unwrap());
+        let indicator = indicator.unwrap();
+        assert_eq!(indicator.agreeing, 2);
+        assert_eq!(indicator.candidates.len(), 2);
+        assert!(indicator.candidates.contains(&(17, 5, 23)));
+        assert!(Indicator::analyze(&daily_key, "ABCDE").is_err());
+
+        // a letter marked unreadable leaves its other copy
+        let mut garbled = machine.encrypt("QEWQEW").into_bytes();
+        garbled[1] = b'?';
+        let indicator = Indicator::analyze(&daily_key, &String::from_utf8(garbled).unwrap());
+        let indicator = indicator.unwrap();
+        assert_eq!(indicator.agreeing, 2);
+        assert_eq!(indicator.candidates, vec![(17, 5, 23)]);
+
+        let ground = (7, 11, 24);
+        for (key, expected) in [
+            ((1, 1, 1), Some(Cilly::Repeated)),
+            ((17, 23, 5), Some(Cilly::Keyboard)),
+            ((5, 23, 17), Some(Cilly::Keyboard)),
+            ((17, 1, 16), Some(Cilly::Keyboard)),
+            ((1, 2, 3), Some(Cilly::Alphabet)),
+            ((26, 25, 24), Some(Cilly::Alphabet)),
+            (ground, Some(Cilly::Ground)),
+            ((17, 5, 23), None),
+            ((15, 16, 1), None),
+        ] {
+            assert_eq!(cilly(key, ground), expected, "{:?}", key);
+        }
+    }
+}
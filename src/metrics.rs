@@ -0,0 +1,414 @@
+// scores of candidate plaintexts, shared by the solvers
+use std::{fmt, path::PathBuf, str::FromStr, sync::Arc};
+
+use crate::{dictionary::WordTrie, enigma::GARBLE, language::Language, ngrams::NgramTable};
+
+// public domain English prose the n-gram statistics are built from
+pub(crate) const ENGLISH_SAMPLE: &str = include_str!("data/english.txt");
+
+// relative letter frequencies of English text, A..Z
+pub const ENGLISH_FREQUENCIES: [f64; 26] = [
+    0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015, 0.06094, 0.06966, 0.00153,
+    0.00772, 0.04025, 0.02406, 0.06749, 0.07507, 0.01929, 0.00095, 0.05987, 0.06327, 0.09056,
+    0.02758, 0.00978, 0.02360, 0.00150, 0.01974, 0.00074,
+];
+
+pub fn index_of_coincidence(text: &str) -> f64 {
+    debug_assert!(
+        text.chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_whitespace() || c == GARBLE as char),
+        "only A..Z, garbles and whitespace are supported"
+    );
+
+    let (hist, n) = letter_counts(text);
+    index_of_coincidence_of(&hist, n)
+}
+
+// index of coincidence of a letter histogram of n letters
+pub(crate) fn index_of_coincidence_of(hist: &[usize; 26], n: usize) -> f64 {
+    if n <= 1 {
+        return 0.0;
+    }
+
+    let numerator = hist
+        .iter()
+        .copied()
+        .filter(|&freq| freq > 0)
+        .map(|freq| freq * (freq - 1))
+        .sum::<usize>();
+
+    let denominator = n * (n - 1);
+
+    numerator as f64 / denominator as f64
+}
+
+// Shannon entropy of the letter distribution in bits, log2(26) for evenly spread letters
+pub fn entropy(text: &str) -> f64 {
+    let (hist, n) = letter_counts(text);
+    entropy_of(&hist, n)
+}
+
+pub(crate) fn entropy_of(hist: &[usize; 26], n: usize) -> f64 {
+    hist.iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / n as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// letter histogram and number of letters, anything but A..Z is skipped
+pub fn letter_counts(text: &str) -> ([usize; 26], usize) {
+    let mut hist = [0; 26];
+    let mut n = 0;
+
+    for c in text.bytes().filter(u8::is_ascii_uppercase) {
+        hist[(c - b'A') as usize] += 1;
+        n += 1;
+    }
+
+    (hist, n)
+}
+
+// chi-squared distance of the letter frequencies from English, 0 for a perfect match
+pub fn english_chi_squared(text: &str) -> f64 {
+    Language::English.chi_squared(text)
+}
+
+// english_chi_squared mapped to (0, 1], higher is closer to English
+pub fn english_similarity(text: &str) -> f64 {
+    Language::English.similarity(text)
+}
+
+// the letters either side of a garble aren't a pair, its letter is unknown
+pub(crate) fn for_each_bigram(text: &str, mut f: impl FnMut(usize, usize)) {
+    for readable in text.split(GARBLE as char) {
+        let letters = readable
+            .bytes()
+            .filter(u8::is_ascii_uppercase)
+            .map(|c| (c - b'A') as usize)
+            .collect::<Vec<_>>();
+
+        letters.windows(2).for_each(|w| f(w[0], w[1]));
+    }
+}
+
+// geometric mean of the English probabilities of the letter pairs, in (0, 1), higher is better;
+// unlike the index of coincidence it tells apart texts with the same letter frequencies
+pub fn english_bigram_score(text: &str) -> f64 {
+    Language::English.bigram_score(text)
+}
+
+/// Score of a decryption the search maximizes, higher is closer to the plaintext.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Metric {
+    IndexOfCoincidence,
+    Sinkov(Language),
+    // entropy mapped to [0, 1], 1 - H / log2(26): language has fewer, more frequent letters
+    Entropy,
+    Bigram(Language),
+    // bigram score of the pairs a share of wrong letters leaves trusted, in [0, 1)
+    Trimmed(Language, f64),
+    // n-gram table built in, named by its language, or trained with train-ngrams and
+    // mapped from the path
+    Ngrams(PathBuf, Arc<NgramTable>),
+    // share of the letters covered by the words of a word list, mapped from the path
+    Words(PathBuf, Arc<WordTrie>),
+    // weighted sum of the metrics, each normalized to 0 for random letters and 1 for English
+    // or the language of the metric, so that the weights compare
+    Composite(Vec<(f64, Metric)>),
+}
+
+impl Metric {
+    pub fn score(&self, text: &str) -> f64 {
+        match self {
+            Metric::IndexOfCoincidence => index_of_coincidence(text),
+            Metric::Sinkov(language) => language.sinkov(text),
+            Metric::Entropy => 1.0 - entropy(text) / 26f64.log2(),
+            Metric::Bigram(language) => language.bigram_score(text),
+            Metric::Trimmed(language, corrupted) => language.trimmed_bigram_score(text, *corrupted),
+            Metric::Ngrams(_, table) => table.score(text),
+            Metric::Words(_, trie) => trie.coverage(text),
+            Metric::Composite(terms) => terms
+                .iter()
+                .map(|(weight, metric)| weight * metric.normalized(text))
+                .sum(),
+        }
+    }
+
+    // the score of a text with this letter histogram, None for the metrics that look at the
+    // order of the letters
+    pub(crate) fn score_counts(&self, hist: &[usize; 26], n: usize) -> Option<f64> {
+        match self {
+            Metric::IndexOfCoincidence => Some(index_of_coincidence_of(hist, n)),
+            Metric::Sinkov(language) => Some(language.sinkov_of(hist, n)),
+            Metric::Entropy => Some(1.0 - entropy_of(hist, n) / 26f64.log2()),
+            Metric::Bigram(_)
+            | Metric::Trimmed(..)
+            | Metric::Ngrams(..)
+            | Metric::Words(..)
+            | Metric::Composite(_) => None,
+        }
+    }
+
+    // score scaled to 0 for random letters and 1 for plaintext
+    pub fn normalized(&self, text: &str) -> f64 {
+        let (random, plaintext) = self.range();
+        (self.score(text) - random) / (plaintext - random)
+    }
+
+    // expected scores of evenly spread letters and of plaintext
+    fn range(&self) -> (f64, f64) {
+        match self {
+            Metric::IndexOfCoincidence => {
+                (1.0 / 26.0, ENGLISH_FREQUENCIES.iter().map(|p| p * p).sum())
+            }
+            Metric::Sinkov(language) => language.sinkov_range(),
+            Metric::Entropy => {
+                let english = ENGLISH_FREQUENCIES
+                    .iter()
+                    .map(|p| -p * p.log2())
+                    .sum::<f64>();
+                (0.0, 1.0 - english / 26f64.log2())
+            }
+            Metric::Bigram(language) => language.bigram_range(),
+            Metric::Trimmed(language, corrupted) => language.trimmed_bigram_range(*corrupted),
+            Metric::Ngrams(_, table) => table.range(),
+            // random letters make few words of three letters or more, plaintext is all words
+            Metric::Words(..) | Metric::Composite(_) => (0.0, 1.0),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::IndexOfCoincidence => write!(f, "ioc"),
+            Metric::Sinkov(language) => write!(f, "sinkov:{}", language),
+            Metric::Entropy => write!(f, "entropy"),
+            Metric::Bigram(language) => write!(f, "bigram:{}", language),
+            Metric::Trimmed(language, corrupted) => {
+                write!(f, "trimmed:{}:{}", corrupted, language)
+            }
+            Metric::Ngrams(path, _) => write!(f, "ngrams:{}", path.display()),
+            Metric::Words(path, _) => write!(f, "words:{}", path.display()),
+            Metric::Composite(terms) => {
+                for (i, (weight, metric)) in terms.iter().enumerate() {
+                    let separator = if i > 0 { "+" } else { "" };
+                    write!(f, "{}{}*{}", separator, weight, metric)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// ioc, entropy, sinkov or bigram optionally followed by the language, e.g. sinkov:german,
+// trimmed:<share of wrong letters>[:language], ngrams:<english, german or a table file>,
+// words:<word list>, or a weighted sum of them, e.g. 0.5*ioc+0.5*bigram
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['+', '*']) {
+            let terms = s
+                .split('+')
+                .map(|term| {
+                    let (weight, metric) = match term.split_once('*') {
+                        Some((weight, metric)) => (
+                            weight
+                                .trim()
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|weight| weight.is_finite())
+                                .ok_or_else(|| format!("invalid weight {weight} in {s}"))?,
+                            metric,
+                        ),
+                        None => (1.0, term),
+                    };
+                    Ok((weight, metric.trim().parse::<Metric>()?))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            return Ok(Metric::Composite(terms));
+        }
+        if let Some(path) = s.strip_prefix("ngrams:") {
+            let table = match path.parse::<Language>() {
+                Ok(language) => NgramTable::embedded(language).ok_or_else(|| {
+                    format!("the {language} n-gram table needs the {language}-ngrams feature")
+                })?,
+                Err(_) => NgramTable::load(path.as_ref())
+                    .map_err(|e| format!("can't load n-gram table {path}: {e}"))?,
+            };
+            return Ok(Metric::Ngrams(path.into(), Arc::new(table)));
+        }
+        if let Some(path) = s.strip_prefix("words:") {
+            let trie = WordTrie::load(path.as_ref())
+                .map_err(|e| format!("can't load word list {path}: {e}"))?;
+            return Ok(Metric::Words(path.into(), Arc::new(trie)));
+        }
+        if let Some(trimmed) = s.strip_prefix("trimmed:") {
+            let (corrupted, language) = match trimmed.split_once(':') {
+                Some((corrupted, language)) => (corrupted, language.parse::<Language>()?),
+                None => (trimmed, Language::default()),
+            };
+            let corrupted = corrupted
+                .parse::<f64>()
+                .ok()
+                .filter(|corrupted| (0.0..1.0).contains(corrupted))
+                .ok_or_else(|| format!("the share of wrong letters must be in [0, 1), got {s}"))?;
+            return Ok(Metric::Trimmed(language, corrupted));
+        }
+
+        let (name, language) = match s.split_once(':') {
+            Some((name, language)) => (name, Some(language.parse::<Language>()?)),
+            None => (s, None),
+        };
+        match (name, language) {
+            ("ioc", None) => Ok(Metric::IndexOfCoincidence),
+            ("entropy", None) => Ok(Metric::Entropy),
+            ("sinkov", language) => Ok(Metric::Sinkov(language.unwrap_or_default())),
+            ("bigram", language) => Ok(Metric::Bigram(language.unwrap_or_default())),
+            _ => Err(format!(
+                "unknown metric {s}, expected ioc, entropy, sinkov[:language], bigram[:language], \
+                 trimmed:<share>[:language], ngrams:<language or table file>, words:<word list> \
+                 or a weighted sum such as 0.5*ioc+0.5*bigram"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::enigma::Machine;
+
+    use super::*;
+
+    pub(crate) const LONG_TEXT: &str = "TO BE OR NOT TO BE THAT IS THE QUESTION WHETHER TIS NOBLER IN THE MIND TO SUFFER THE SLINGS AND ARROWS OF OUTRAGEOUS FORTUNE OR TO TAKE ARMS AGAINST A SEA OF TROUBLES AND BY OPPOSING END THEMTO DIETO SLEEP NO MORE AND BY A SLEEP TO SAY WE END THE HEARTACHE AND THE THOUSAND NATURAL SHOCKS THAT FLESH IS HEIR TOTIS A CONSUMMATION DEVOUTLY TO BE WISHD TO DIETO SLEEP TO SLEEP PERCHANCE TO DREAMAY THERES THE RUB FOR IN THAT SLEEP OF DEATH WHAT DREAMS MAY COME WHEN WE HAVE SHUFFLED OFF THIS MORTAL COIL MUST GIVE US PAUSE THERES THE RESPECT THAT MAKES CALAMITY OF SO LONG LIFE";
+
+    #[test]
+    fn test_ioc() {
+        assert_relative_eq!(index_of_coincidence(""), 0.0);
+        assert_relative_eq!(index_of_coincidence("A"), 0.0);
+        assert_relative_eq!(index_of_coincidence("AB"), 0.0);
+        assert_relative_eq!(index_of_coincidence("ABAA"), 0.5);
+        assert_relative_eq!(index_of_coincidence(LONG_TEXT), 0.0700307611754696);
+    }
+
+    #[test]
+    fn test_english_similarity() {
+        assert_relative_eq!(ENGLISH_FREQUENCIES.iter().sum::<f64>(), 1.0, epsilon = 1e-3);
+        assert_eq!(english_chi_squared(""), f64::INFINITY);
+        assert_eq!(letter_counts("AB A?").0[..2], [2, 1]);
+
+        let shifted = LONG_TEXT
+            .bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' => (b'A' + (c - b'A' + 3) % 26) as char,
+                c => c as char,
+            })
+            .collect::<String>();
+        assert!(english_similarity(LONG_TEXT) > 0.5);
+        assert!(english_similarity(LONG_TEXT) > 2.0 * english_similarity(&shifted));
+    }
+
+    #[test]
+    fn test_english_bigram_score() {
+        assert_eq!(english_bigram_score("A"), 0.0);
+        assert_eq!(english_bigram_score("A?B"), 0.0);
+        assert_eq!(english_bigram_score("TH?E"), english_bigram_score("TH"));
+
+        // same letters, different order
+        let reversed = LONG_TEXT.chars().rev().collect::<String>();
+        assert!(english_bigram_score(LONG_TEXT) > 1.5 * english_bigram_score(&reversed));
+        assert!(english_bigram_score(LONG_TEXT) < 1.0);
+    }
+
+    #[test]
+    fn test_metric() {
+        for metric in [
+            "ioc",
+            "sinkov:english",
+            "sinkov:german",
+            "entropy",
+            "bigram:german",
+            "trimmed:0.1:german",
+            "0.5*ioc+0.25*bigram:english+1*entropy",
+        ] {
+            assert_eq!(metric.parse::<Metric>().unwrap().to_string(), metric);
+        }
+        assert_eq!(
+            "trimmed:0.2".parse(),
+            Ok(Metric::Trimmed(Language::English, 0.2))
+        );
+        assert!("trimmed:1".parse::<Metric>().is_err());
+        assert!("trimmed".parse::<Metric>().is_err());
+        assert_eq!("sinkov".parse(), Ok(Metric::Sinkov(Language::English)));
+        assert!("sinkov:french".parse::<Metric>().is_err());
+        assert!("shannon".parse::<Metric>().is_err());
+
+        let sinkov = Metric::Sinkov(Language::English);
+        assert_relative_eq!(
+            Metric::IndexOfCoincidence.score(LONG_TEXT),
+            index_of_coincidence(LONG_TEXT)
+        );
+        assert_eq!(sinkov.score(""), 0.0);
+        // an even spread of letters is as unlikely as a repetitive text to the IoC
+        let spread = (b'A'..=b'Z').map(char::from).collect::<String>().repeat(20);
+        assert!(sinkov.score(LONG_TEXT) > 1.2 * sinkov.score(&spread));
+        assert!(sinkov.score(LONG_TEXT) < 1.0);
+
+        assert_relative_eq!(entropy(""), 0.0);
+        assert_relative_eq!(entropy("AAAA"), 0.0);
+        assert_relative_eq!(entropy("ABAB"), 1.0);
+        assert_relative_eq!(entropy(&spread), 26f64.log2(), epsilon = 1e-12);
+        assert_relative_eq!(Metric::Entropy.score(&spread), 0.0, epsilon = 1e-12);
+        let shuffled = Machine::new(&"I II III / 01 01 01 / A A A".parse().unwrap())
+            .unwrap()
+            .encrypt(LONG_TEXT);
+        assert!(Metric::Entropy.score(LONG_TEXT) > 2.0 * Metric::Entropy.score(&shuffled));
+
+        let path = std::env::temp_dir().join(format!("enigmagen-words-{}", std::process::id()));
+        std::fs::write(&path, "to be or not that is the question\nsleep\ndream").unwrap();
+        let words = format!("words:{}", path.display())
+            .parse::<Metric>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(words.to_string(), format!("words:{}", path.display()));
+        assert!(words.score(LONG_TEXT) > 5.0 * words.score(&shuffled));
+        assert!("words:/no/such/list".parse::<Metric>().is_err());
+    }
+
+    #[test]
+    fn test_composite() {
+        let ciphertext = Machine::new(&"I II III / 01 01 01 / A A A".parse().unwrap())
+            .unwrap()
+            .encrypt(LONG_TEXT);
+        for metric in ["ioc", "sinkov", "entropy", "bigram", "trimmed:0.1"] {
+            let metric = metric.parse::<Metric>().unwrap();
+            let plaintext = metric.normalized(LONG_TEXT);
+            assert!((0.7..1.3).contains(&plaintext), "{} {}", metric, plaintext);
+            let random = metric.normalized(&ciphertext);
+            assert!((-0.3..0.3).contains(&random), "{} {}", metric, random);
+        }
+
+        let composite = "0.5*ioc + 0.5*bigram".parse::<Metric>().unwrap();
+        assert_eq!(
+            composite,
+            Metric::Composite(vec![
+                (0.5, Metric::IndexOfCoincidence),
+                (0.5, Metric::Bigram(Language::English))
+            ])
+        );
+        assert_relative_eq!(
+            composite.score(LONG_TEXT),
+            0.5 * Metric::IndexOfCoincidence.normalized(LONG_TEXT)
+                + 0.5 * Metric::Bigram(Language::English).normalized(LONG_TEXT)
+        );
+        assert!(composite.score(LONG_TEXT) > composite.score(&ciphertext) + 0.5);
+        assert!("x*ioc".parse::<Metric>().is_err());
+        assert!("0.5*ioc+0.5*trigram".parse::<Metric>().is_err());
+    }
+}
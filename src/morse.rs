@@ -0,0 +1,184 @@
+// International Morse code of the letters and digits
+const CODES: [(char, &str); 36] = [
+    ('A', ".-"),
+    ('B', "-..."),
+    ('C', "-.-."),
+    ('D', "-.."),
+    ('E', "."),
+    ('F', "..-."),
+    ('G', "--."),
+    ('H', "...."),
+    ('I', ".."),
+    ('J', ".---"),
+    ('K', "-.-"),
+    ('L', ".-.."),
+    ('M', "--"),
+    ('N', "-."),
+    ('O', "---"),
+    ('P', ".--."),
+    ('Q', "--.-"),
+    ('R', ".-."),
+    ('S', "..."),
+    ('T', "-"),
+    ('U', "..-"),
+    ('V', "...-"),
+    ('W', ".--"),
+    ('X', "-..-"),
+    ('Y', "-.--"),
+    ('Z', "--.."),
+    ('0', "-----"),
+    ('1', ".----"),
+    ('2', "..---"),
+    ('3', "...--"),
+    ('4', "....-"),
+    ('5', "....."),
+    ('6', "-...."),
+    ('7', "--..."),
+    ('8', "---.."),
+    ('9', "----."),
+];
+
+// a word break is a slash or a pause of this many spaces, a single space ends a letter
+const WORD_PAUSE: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Decoded {
+    pub text: String,
+    // garbled codes replaced with the nearest letter
+    pub corrected: usize,
+    // garbled codes too far from any letter, dropped
+    pub unreadable: usize,
+}
+
+// letters separated by a space, words by a slash or three spaces; other characters are dropped
+pub fn encode(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter_map(|c| code_of(c.to_ascii_uppercase()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+fn code_of(c: char) -> Option<&'static str> {
+    CODES.iter().find(|&&(l, _)| l == c).map(|&(_, code)| code)
+}
+
+// dots and dashes as transcribed, typographic variants included, anything else is a garbled element
+fn element(c: char) -> char {
+    match c {
+        '.' | '·' | '•' | '*' => '.',
+        '-' | '−' | '–' | '—' | '_' => '-',
+        _ => '?',
+    }
+}
+
+/// Decodes a transcribed intercept. A code that isn't Morse is replaced with the letter at the
+/// smallest edit distance, ties going to the earlier letter, if it's at most max_distance away.
+/// Dropped letters shift the rotor stepping of everything after them, so a wrong guess is better.
+pub fn decode(morse: &str, max_distance: usize) -> Decoded {
+    let mut decoded = Decoded::default();
+    let mut code = String::new();
+    let mut pause = 0;
+
+    let end_letter = |code: &mut String, decoded: &mut Decoded| {
+        if code.is_empty() {
+            return;
+        }
+        match nearest(code) {
+            Some((c, 0)) => decoded.text.push(c),
+            Some((c, distance)) if distance <= max_distance => {
+                decoded.text.push(c);
+                decoded.corrected += 1;
+            }
+            _ => decoded.unreadable += 1,
+        }
+        code.clear();
+    };
+
+    for c in morse.chars() {
+        match c {
+            '/' | '|' => {
+                end_letter(&mut code, &mut decoded);
+                pause = WORD_PAUSE;
+            }
+            c if c.is_whitespace() => {
+                end_letter(&mut code, &mut decoded);
+                pause += 1;
+            }
+            c => {
+                if pause >= WORD_PAUSE && !decoded.text.is_empty() {
+                    decoded.text.push(' ');
+                }
+                pause = 0;
+                code.push(element(c));
+            }
+        }
+    }
+    end_letter(&mut code, &mut decoded);
+
+    decoded
+}
+
+fn nearest(code: &str) -> Option<(char, usize)> {
+    CODES
+        .iter()
+        .map(|&(c, known)| (c, edit_distance(code, known)))
+        .min_by_key(|&(_, distance)| distance)
+}
+
+// Levenshtein distance, a garbled element never matches
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.bytes().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca == b'?' || ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{enigma::normalize, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        assert_eq!(encode("SOS, 42"), "... --- ... / ....- ..---");
+        assert_eq!(decode(&encode(LONG_TEXT), 0).text, LONG_TEXT);
+        assert_eq!(decode("...   ---\n... / ·− −···", 0).text, "S OS AB");
+        assert_eq!(
+            normalize(&decode(&encode("ATTACK 0600"), 0).text),
+            "ATTACK "
+        );
+    }
+
+    #[test]
+    fn test_garbles() {
+        // one element off J, K and L, an unreadable element off E and T, the earlier letter wins
+        let decoded = decode(".-. .-.- ?", 1);
+        assert_eq!(decoded.text, "RJE");
+        assert_eq!(decoded.corrected, 2);
+        assert_eq!(decoded.unreadable, 0);
+
+        let decoded = decode(".-.-.-.- .-", 1);
+        assert_eq!(decoded.text, "A");
+        assert_eq!(decoded.unreadable, 1);
+
+        assert_eq!(decode(".-.-.-.- .-", 3).text, "1A");
+        assert_eq!(decode("", 1), Decoded::default());
+    }
+}
@@ -0,0 +1,361 @@
+// n-gram log-probability tables trained on a corpus of the expected plaintext
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    ops::Deref,
+    path::Path,
+};
+
+use anyhow::{bail, ensure};
+
+use crate::{
+    enigma::{normalize, GARBLE},
+    language::Language,
+};
+
+const MAGIC: &[u8; 4] = b"ENGR";
+const VERSION: u8 = 2;
+// magic, version, order, then ln p of the lowest and the step between levels as LE f32
+const HEADER_LEN: usize = 14;
+// 26^5 entries are already 11.9 MB
+pub const MAX_ORDER: usize = 5;
+// pseudo count of every n-gram, n-grams the corpus lacks are unlikely but not impossible
+const SMOOTHING: f64 = 0.01;
+
+// trigrams of the built-in samples, a few KB of prose are too little for quadgrams
+#[cfg(feature = "english-ngrams")]
+static ENGLISH: &[u8] = include_bytes!("data/english.3grams");
+#[cfg(feature = "german-ngrams")]
+static GERMAN: &[u8] = include_bytes!("data/german.3grams");
+
+/// ln of the probability of every n-gram of letters, indexed by the letters as digits in
+/// base 26; the scores of a decryption tuned to the corpus, e.g. naval weather reports.
+/// Every log probability is quantized to one of 256 levels, one byte per n-gram keeps a
+/// quadgram table at 457 KB, in the cache of a core for the millions of lookups per
+/// generation. Tables are used in place: embedded in the binary or memory-mapped, so the
+/// pages of a file are only read as they're looked up.
+pub struct NgramTable {
+    order: usize,
+    // ln p of level 0 and the difference between consecutive levels
+    floor: f64,
+    step: f64,
+    bytes: Bytes,
+}
+
+enum Bytes {
+    Static(&'static [u8]),
+    Owned(Vec<u8>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped(memmap2::Mmap),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Static(bytes) => bytes,
+            Bytes::Owned(bytes) => bytes,
+            #[cfg(not(target_arch = "wasm32"))]
+            Bytes::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+impl NgramTable {
+    /// Counts the n-grams of the letters of the texts, across word breaks as decryptions
+    /// have none, add-k smoothed.
+    pub fn train<S: AsRef<str>>(
+        texts: impl IntoIterator<Item = S>,
+        order: usize,
+    ) -> anyhow::Result<Self> {
+        ensure!(
+            (1..=MAX_ORDER).contains(&order),
+            "order must be between 1 and {}",
+            MAX_ORDER
+        );
+
+        let mut counts = vec![0u64; 26usize.pow(order as u32)];
+        let mut total = 0;
+        for text in texts {
+            for_each_ngram(&normalize(text.as_ref()), order, |index| {
+                counts[index] += 1;
+                total += 1;
+            });
+        }
+        ensure!(total > 0, "the corpus has no {}-letter n-grams", order);
+
+        let total = total as f64 + SMOOTHING * counts.len() as f64;
+        let log_probs = counts
+            .iter()
+            .map(|&count| ((count as f64 + SMOOTHING) / total).ln())
+            .collect::<Vec<_>>();
+
+        let floor = log_probs.iter().copied().fold(f64::INFINITY, f64::min) as f32;
+        let top = log_probs.iter().copied().fold(f64::NEG_INFINITY, f64::max) as f32;
+        // a corpus of one repeated n-gram has a single level
+        let step = ((top - floor) / 255.0).max(f32::MIN_POSITIVE);
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + log_probs.len());
+        bytes.extend(MAGIC);
+        bytes.extend([VERSION, order as u8]);
+        bytes.extend(floor.to_le_bytes());
+        bytes.extend(step.to_le_bytes());
+        bytes.extend(
+            log_probs
+                .iter()
+                .map(|&ln_p| ((ln_p - floor as f64) / step as f64).round() as u8),
+        );
+
+        Self::parse(Bytes::Owned(bytes))
+    }
+
+    fn parse(bytes: Bytes) -> anyhow::Result<Self> {
+        ensure!(
+            bytes.len() >= HEADER_LEN && &bytes[..4] == MAGIC,
+            "not an n-gram table"
+        );
+        if bytes[4] != VERSION {
+            bail!("unsupported n-gram table version {}", bytes[4]);
+        }
+        let order = bytes[5] as usize;
+        ensure!(
+            (1..=MAX_ORDER).contains(&order),
+            "invalid n-gram order {}",
+            order
+        );
+        ensure!(
+            bytes.len() == HEADER_LEN + 26usize.pow(order as u32),
+            "truncated {}-gram table",
+            order
+        );
+
+        let f32_at =
+            |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let (floor, step) = (f32_at(6) as f64, f32_at(10) as f64);
+
+        Ok(Self {
+            order,
+            floor,
+            step,
+            bytes,
+        })
+    }
+
+    /// The table of a language built into the binary with the english-ngrams or
+    /// german-ngrams feature.
+    pub fn embedded(language: Language) -> Option<Self> {
+        let bytes: Option<&'static [u8]> = match language {
+            #[cfg(feature = "english-ngrams")]
+            Language::English => Some(ENGLISH),
+            #[cfg(feature = "german-ngrams")]
+            Language::German => Some(GERMAN),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        };
+        bytes.and_then(|bytes| Self::parse(Bytes::Static(bytes)).ok())
+    }
+
+    // every file of a directory and its subdirectories, or a single file
+    pub fn train_path(corpus: &Path, order: usize) -> anyhow::Result<Self> {
+        let mut texts = Vec::new();
+        read_texts(corpus, &mut texts)?;
+        Self::train(texts, order)
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    fn levels(&self) -> &[u8] {
+        &self.bytes[HEADER_LEN..]
+    }
+
+    fn log_prob(&self, level: u8) -> f64 {
+        self.floor + self.step * level as f64
+    }
+
+    // ln of the probability of the n-gram with the letters as digits in base 26, A = 0
+    pub(crate) fn log_prob_of(&self, index: usize) -> f64 {
+        self.log_prob(self.levels()[index])
+    }
+
+    // geometric mean of the probabilities of the n-grams, in (0, 1), higher is better
+    pub fn score(&self, text: &str) -> f64 {
+        let levels = self.levels();
+        // the levels are summed as integers and turned into ln p once
+        let (mut sum, mut n) = (0u64, 0);
+        for_each_ngram(text, self.order, |index| {
+            sum += levels[index] as u64;
+            n += 1;
+        });
+
+        match n {
+            0 => 0.0,
+            n => (self.floor + self.step * sum as f64 / n as f64).exp(),
+        }
+    }
+
+    // score of evenly spread letters and of text like the corpus
+    pub(crate) fn range(&self) -> (f64, f64) {
+        let levels = self.levels();
+        let sum = levels.iter().map(|&level| level as u64).sum::<u64>();
+        let random = self.floor + self.step * sum as f64 / levels.len() as f64;
+        let corpus = levels
+            .iter()
+            .map(|&level| self.log_prob(level))
+            .map(|ln_p| ln_p.exp() * ln_p)
+            .sum::<f64>();
+        (random.exp(), corpus.exp())
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(&self.bytes)?;
+        w.flush()
+    }
+
+    // written next to the path and renamed over it, a mapped table it replaces stays intact
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let part = path.with_extension("part");
+        self.write(io::BufWriter::new(fs::File::create(&part)?))?;
+        fs::rename(part, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let file = fs::File::open(path)?;
+            // SAFETY: the file must not be changed in place while mapped, save replaces it
+            let mmap = unsafe { memmap2::Mmap::map(&file)? };
+            Self::parse(Bytes::Mapped(mmap))
+        }
+        #[cfg(target_arch = "wasm32")]
+        Self::parse(Bytes::Owned(fs::read(path)?))
+    }
+}
+
+impl PartialEq for NgramTable {
+    fn eq(&self, other: &Self) -> bool {
+        *self.bytes == *other.bytes
+    }
+}
+
+// the levels would flood logged configurations
+impl fmt::Debug for NgramTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NgramTable(order {})", self.order)
+    }
+}
+
+// index of every n-gram of the letters, anything but A..Z is skipped and no n-gram spans a
+// garble
+fn for_each_ngram(text: &str, order: usize, mut f: impl FnMut(usize)) {
+    for readable in text.split(GARBLE as char) {
+        let letters = readable
+            .bytes()
+            .filter(u8::is_ascii_uppercase)
+            .map(|c| (c - b'A') as usize)
+            .collect::<Vec<_>>();
+
+        letters
+            .windows(order)
+            .for_each(|w| f(w.iter().fold(0, |index, &c| index * 26 + c)));
+    }
+}
+
+fn read_texts(path: &Path, texts: &mut Vec<String>) -> anyhow::Result<()> {
+    if path.is_dir() {
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+        for entry in entries {
+            read_texts(&entry, texts)?;
+        }
+    } else {
+        // corpora in legacy encodings still have their ASCII letters
+        texts.push(String::from_utf8_lossy(&fs::read(path)?).into_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::{tests::LONG_TEXT, Metric, ENGLISH_SAMPLE};
+
+    use super::*;
+
+    #[test]
+    fn test_train() {
+        let table = NgramTable::train([ENGLISH_SAMPLE], 4).unwrap();
+        assert_eq!(table.order(), 4);
+        assert_eq!(table.levels().len(), 26usize.pow(4));
+        // quantized to 1/255 of the range of ln p
+        let total = table
+            .levels()
+            .iter()
+            .map(|&level| table.log_prob(level).exp());
+        assert!((total.sum::<f64>() - 1.0).abs() < 0.05);
+
+        let garbage = "QXZJV WKQPF XJZQV BKXQJ ZVPWQ XJKZQ";
+        assert!(table.score(LONG_TEXT) > 4.0 * table.score(garbage));
+        let (random, corpus) = table.range();
+        assert!(random < table.score(LONG_TEXT) / 4.0 && corpus > table.score(LONG_TEXT));
+        assert_eq!(table.score("ABC"), 0.0);
+
+        assert!(NgramTable::train([ENGLISH_SAMPLE], 0).is_err());
+        assert!(NgramTable::train(["ABC"], 4).is_err());
+    }
+
+    #[test]
+    fn test_embedded() {
+        let garbage = "QXZJV WKQPF XJZQV BKXQJ ZVPWQ XJKZQ";
+        for language in Language::ALL {
+            let metric = format!("ngrams:{}", language).parse::<Metric>();
+            match NgramTable::embedded(language) {
+                Some(table) => {
+                    assert_eq!(table.order(), 3);
+                    assert_eq!(metric.unwrap().to_string(), format!("ngrams:{}", language));
+                    assert!(table.score(LONG_TEXT) > table.score(garbage));
+                }
+                None => assert!(metric.is_err()),
+            }
+        }
+        #[cfg(feature = "english-ngrams")]
+        assert_eq!(
+            NgramTable::embedded(Language::English).unwrap(),
+            NgramTable::train([ENGLISH_SAMPLE], 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_save() {
+        let dir = std::env::temp_dir().join(format!("enigmagen-ngrams-{}", std::process::id()));
+        fs::create_dir_all(dir.join("corpus/nested")).unwrap();
+        fs::write(
+            dir.join("corpus/a.txt"),
+            "Wetter heute: Regen, Wind aus Nordwest",
+        )
+        .unwrap();
+        fs::write(dir.join("corpus/nested/b.txt"), "Wetter morgen: Nebel").unwrap();
+
+        let table = NgramTable::train_path(&dir.join("corpus"), 3).unwrap();
+        let path = dir.join("trigrams.bin");
+        table.save(&path).unwrap();
+        assert_eq!(NgramTable::load(&path).unwrap(), table);
+        assert!(table.score("WETTERREGEN") > table.score("QUIZJOCKEYS"));
+
+        let metric = format!("ngrams:{}", path.display());
+        let parsed = metric.parse::<Metric>().unwrap();
+        assert_eq!(parsed.to_string(), metric);
+        assert_eq!(parsed.score("WETTERREGEN"), table.score("WETTERREGEN"));
+        assert!("ngrams:missing.bin".parse::<Metric>().is_err());
+
+        assert!(NgramTable::parse(Bytes::Static(b"NOPE\x02\x03")).is_err());
+        let mut truncated = table.bytes.to_vec();
+        truncated.pop();
+        assert!(NgramTable::parse(Bytes::Owned(truncated)).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
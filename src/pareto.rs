@@ -0,0 +1,465 @@
+use std::{cmp::Ordering, sync::Arc, time::Duration};
+
+use anyhow::ensure;
+use rand::Rng;
+use rayon::{prelude::*, ThreadPool};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use crate::{
+    engine::{self, CrossoverOp, GenomeBuilder, MutationOp},
+    enigma::{Machine, Settings},
+    gen,
+    metrics::Metric,
+    rng::SearchRng,
+};
+
+// non-dominated sorting is quadratic in the population
+pub const MAX_POPULATION_SIZE: usize = 20_000;
+
+/// Scores of a genome on several objectives, all maximized.
+pub trait ObjectivesFunction<G>: Sync {
+    fn objectives_of(&self, genome: &G) -> Vec<f64>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point<G> {
+    pub genome: G,
+    pub objectives: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct Ranked<G> {
+    point: Point<G>,
+    // 0 is the non-dominated front
+    front: usize,
+    crowding: f64,
+}
+
+/// NSGA-II: parents are picked by binary tournaments on front and crowding distance, and
+/// parents and offspring together are cut back to the population size front by front, the
+/// least crowded first within the last front that fits.
+pub struct Nsga2<G, F, C, M> {
+    objectives: F,
+    crossover: C,
+    mutator: M,
+    per_parents: usize,
+    termination: gen::TerminationOptions,
+    rng: SearchRng,
+    pool: ThreadPool,
+    population: Vec<Ranked<G>>,
+    generation: u64,
+    started_at: Instant,
+}
+
+impl<G, F, C, M> Nsga2<G, F, C, M>
+where
+    G: Clone + PartialEq + Send + Sync,
+    F: ObjectivesFunction<G>,
+    C: CrossoverOp<G>,
+    M: MutationOp<G>,
+{
+    pub fn new<B: GenomeBuilder<G>>(
+        objectives: F,
+        genome_builder: B,
+        crossover: C,
+        mutator: M,
+        opts: &gen::Options,
+    ) -> anyhow::Result<Self> {
+        opts.validate()?;
+        let size = opts.engine.population_size;
+        ensure!(
+            size <= MAX_POPULATION_SIZE,
+            "population_size must be at most {} for the multi-objective search",
+            MAX_POPULATION_SIZE
+        );
+
+        let seed = opts.engine.seed.unwrap_or_else(rand::random);
+        let mut rng = SearchRng::new(opts.engine.rng, seed);
+        let genomes = (0..size)
+            .map(|i| genome_builder.build_genome(i, &mut rng))
+            .collect();
+        let mut nsga = Self {
+            objectives,
+            crossover,
+            mutator,
+            per_parents: opts.engine.num_individuals_per_parents,
+            termination: opts.termination.clone(),
+            rng,
+            pool: engine::thread_pool(&opts.engine)?,
+            population: Vec::new(),
+            generation: 0,
+            started_at: Instant::now(),
+        };
+        let points = nsga.evaluate(genomes);
+        nsga.population = select(points, size);
+
+        Ok(nsga)
+    }
+
+    /// Breeds and selects one generation, returns why the search should stop if it should.
+    pub fn step(&mut self) -> Option<String> {
+        let size = self.population.len();
+        let mut offspring = Vec::with_capacity(size);
+        while offspring.len() < size {
+            let parents = (0..self.per_parents)
+                .map(|_| self.tournament().point.genome.clone())
+                .collect();
+            for child in self.crossover.crossover(parents, &mut self.rng) {
+                offspring.push(self.mutator.mutate(child, &mut self.rng));
+            }
+        }
+        offspring.truncate(size);
+
+        let mut points = self.evaluate(offspring);
+        points.extend(self.population.drain(..).map(|ranked| ranked.point));
+        self.population = select(points, size);
+        self.generation += 1;
+
+        self.stop_reason()
+    }
+
+    // steps until a termination condition is met and returns the non-dominated front
+    pub fn run(&mut self) -> Vec<Point<G>> {
+        while self.step().is_none() {}
+        self.front()
+    }
+
+    /// Distinct genomes of the non-dominated front, best on the first objective first.
+    pub fn front(&self) -> Vec<Point<G>> {
+        let mut front: Vec<Point<G>> = Vec::new();
+        for ranked in self.population.iter().filter(|ranked| ranked.front == 0) {
+            if !front.iter().any(|p| p.genome == ranked.point.genome) {
+                front.push(ranked.point.clone());
+            }
+        }
+        front.sort_by(|a, b| b.objectives[0].total_cmp(&a.objectives[0]));
+        front
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn tournament(&mut self) -> &Ranked<G> {
+        let a = &self.population[self.rng.gen_range(0..self.population.len())];
+        let b = &self.population[self.rng.gen_range(0..self.population.len())];
+        match crowded_cmp(a, b) {
+            Ordering::Greater => b,
+            _ => a,
+        }
+    }
+
+    fn evaluate(&self, genomes: Vec<G>) -> Vec<Point<G>> {
+        self.pool.install(|| {
+            genomes
+                .into_par_iter()
+                .map(|genome| Point {
+                    objectives: self.objectives.objectives_of(&genome),
+                    genome,
+                })
+                .collect()
+        })
+    }
+
+    fn stop_reason(&self) -> Option<String> {
+        let termination = &self.termination;
+
+        if self.generation >= termination.generation_limit {
+            return Some(format!(
+                "generation limit of {} reached",
+                termination.generation_limit
+            ));
+        }
+        let duration = self.started_at.elapsed();
+        if let Ok(limit) = termination.time_limit.to_std() {
+            if duration >= limit {
+                return Some(format!("time limit of {:?} reached", limit));
+            }
+        }
+
+        None
+    }
+}
+
+// lower front first, then the less crowded
+fn crowded_cmp<G>(a: &Ranked<G>, b: &Ranked<G>) -> Ordering {
+    a.front
+        .cmp(&b.front)
+        .then_with(|| b.crowding.total_cmp(&a.crowding))
+}
+
+// a is at least as good on every objective and better on one
+fn dominates(a: &[f64], b: &[f64]) -> bool {
+    a.iter().zip(b).all(|(a, b)| a >= b) && a.iter().zip(b).any(|(a, b)| a > b)
+}
+
+// indices of the points by front, the non-dominated first
+fn fronts<G>(points: &[Point<G>]) -> Vec<Vec<usize>> {
+    let n = points.len();
+    let mut dominated_by = vec![0; n];
+    let mut dominates_of = vec![Vec::new(); n];
+    for i in 0..n {
+        for j in i + 1..n {
+            if dominates(&points[i].objectives, &points[j].objectives) {
+                dominates_of[i].push(j);
+                dominated_by[j] += 1;
+            } else if dominates(&points[j].objectives, &points[i].objectives) {
+                dominates_of[j].push(i);
+                dominated_by[i] += 1;
+            }
+        }
+    }
+
+    let mut fronts = Vec::new();
+    let mut front = (0..n).filter(|&i| dominated_by[i] == 0).collect::<Vec<_>>();
+    while !front.is_empty() {
+        let mut next = Vec::new();
+        for &i in &front {
+            for &j in &dominates_of[i] {
+                dominated_by[j] -= 1;
+                if dominated_by[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+// sum over the objectives of the normalized distance between the neighbours of every point,
+// the extremes are always kept
+fn crowding<G>(points: &[Point<G>], front: &[usize]) -> Vec<f64> {
+    let mut distance = vec![0.0; front.len()];
+    let objectives = points[front[0]].objectives.len();
+
+    for m in 0..objectives {
+        let value = |k: usize| points[front[k]].objectives[m];
+        let mut order = (0..front.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| value(a).total_cmp(&value(b)));
+
+        let (first, last) = (order[0], order[order.len() - 1]);
+        distance[first] = f64::INFINITY;
+        distance[last] = f64::INFINITY;
+        let range = value(last) - value(first);
+        if range <= 0.0 {
+            continue;
+        }
+        for w in order.windows(3) {
+            distance[w[1]] += (value(w[2]) - value(w[0])) / range;
+        }
+    }
+    distance
+}
+
+fn select<G: Clone>(points: Vec<Point<G>>, size: usize) -> Vec<Ranked<G>> {
+    let mut selected = Vec::with_capacity(size);
+
+    for (rank, front) in fronts(&points).into_iter().enumerate() {
+        let mut ranked = crowding(&points, &front)
+            .into_iter()
+            .zip(&front)
+            .map(|(crowding, &i)| Ranked {
+                point: points[i].clone(),
+                front: rank,
+                crowding,
+            })
+            .collect::<Vec<_>>();
+
+        let left = size - selected.len();
+        if ranked.len() > left {
+            ranked.sort_by(crowded_cmp);
+            ranked.truncate(left);
+        }
+        selected.extend(ranked);
+        if selected.len() == size {
+            break;
+        }
+    }
+    selected
+}
+
+/// Enigma settings scored on every metric of the decryption.
+#[derive(Debug, Clone)]
+pub struct MetricObjectives {
+    pub ciphertext: Arc<String>,
+    pub metrics: Vec<Metric>,
+}
+
+impl ObjectivesFunction<Settings> for MetricObjectives {
+    fn objectives_of(&self, settings: &Settings) -> Vec<f64> {
+        match Machine::new(settings) {
+            Ok(machine) => {
+                let plaintext = machine.decrypt(&self.ciphertext);
+                self.metrics.iter().map(|m| m.score(&plaintext)).collect()
+            }
+            // an invalid genome never makes it to the front
+            Err(_) => vec![f64::NEG_INFINITY; self.metrics.len()],
+        }
+    }
+}
+
+/// Searches the Enigma settings that trade the metrics off against each other, every key
+/// of the returned front is better than the others on at least one metric.
+pub fn search(
+    ciphertext: &str,
+    metrics: Vec<Metric>,
+    opts: &gen::Options,
+) -> anyhow::Result<(Vec<Point<Settings>>, String, Duration)> {
+    ensure!(
+        metrics.len() >= 2,
+        "the multi-objective search needs at least two metrics"
+    );
+    ensure!(
+        !opts.engine.fixed_rings,
+        "the multi-objective search doesn't recover fixed rings"
+    );
+    let started = Instant::now();
+    let max_plugs = opts.engine.max_plugs;
+
+    let mut nsga = Nsga2::new(
+        MetricObjectives {
+            ciphertext: Arc::new(ciphertext.to_string()),
+            metrics,
+        },
+        gen::SettingsBuilder {
+            max_plugs,
+            reflector: opts.engine.reflector.clone(),
+            herivel_spread: opts.engine.herivel_spread,
+            fixed_rings: false,
+        },
+        gen::SettingsCrossover {
+            mode: opts.engine.crossover,
+            rotors: opts.engine.rotor_crossover,
+            plugboard: opts.engine.plugboard_crossover,
+        },
+        gen::SettingsMutator {
+            fixed_rings: false,
+            ..gen::SettingsMutator::new(&opts.engine)
+        },
+        opts,
+    )?;
+
+    let reason = loop {
+        if let Some(reason) = nsga.step() {
+            break reason;
+        }
+    };
+    Ok((nsga.front(), reason, started.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    // Schaffer's problem: maximize -x² and -(x - 2)², the front is x in [0, 2]
+    struct Schaffer;
+
+    impl ObjectivesFunction<f64> for Schaffer {
+        fn objectives_of(&self, x: &f64) -> Vec<f64> {
+            vec![-x * x, -(x - 2.0) * (x - 2.0)]
+        }
+    }
+
+    impl GenomeBuilder<f64> for Schaffer {
+        fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> f64 {
+            rng.gen_range(-10.0..10.0)
+        }
+    }
+
+    impl CrossoverOp<f64> for Schaffer {
+        fn crossover<R: Rng>(&self, parents: Vec<f64>, rng: &mut R) -> Vec<f64> {
+            let t = rng.gen::<f64>();
+            vec![t * parents[0] + (1.0 - t) * parents[1]]
+        }
+    }
+
+    impl MutationOp<f64> for Schaffer {
+        fn mutate<R: Rng>(&self, x: f64, rng: &mut R) -> f64 {
+            x + rng.gen_range(-0.1..0.1)
+        }
+    }
+
+    #[test]
+    fn test_nsga2() {
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 100;
+        opts.engine.seed = Some(1);
+        opts.termination.generation_limit = 50;
+
+        let mut nsga = Nsga2::new(Schaffer, Schaffer, Schaffer, Schaffer, &opts).unwrap();
+        let front = nsga.run();
+        assert_eq!(nsga.generation(), 50);
+
+        assert!(front.len() > 50);
+        assert!(front.iter().all(|p| (-0.05..=2.05).contains(&p.genome)));
+        // spread over the whole front by the crowding distance
+        assert!(front.iter().any(|p| p.genome < 0.2) && front.iter().any(|p| p.genome > 1.8));
+        for a in &front {
+            assert!(!front
+                .iter()
+                .any(|b| dominates(&b.objectives, &a.objectives)));
+        }
+        assert!(front
+            .windows(2)
+            .all(|w| w[0].objectives[0] >= w[1].objectives[0]));
+
+        opts.engine.population_size = MAX_POPULATION_SIZE + 1;
+        assert!(Nsga2::new(Schaffer, Schaffer, Schaffer, Schaffer, &opts).is_err());
+    }
+
+    #[test]
+    fn test_fronts() {
+        let points = [
+            [3.0, 1.0],
+            [1.0, 3.0],
+            [2.0, 2.0],
+            [1.0, 1.0],
+            [0.0, 0.0],
+            [2.0, 2.0],
+        ]
+        .map(|objectives| Point {
+            genome: (),
+            objectives: objectives.to_vec(),
+        });
+        assert_eq!(fronts(&points), [vec![0, 1, 2, 5], vec![3], vec![4]]);
+        assert_eq!(
+            crowding(&points, &[0, 1, 2]),
+            [f64::INFINITY, f64::INFINITY, 2.0]
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 300;
+        opts.engine.seed = Some(2);
+        opts.termination.generation_limit = 5;
+
+        let metrics = vec![
+            Metric::IndexOfCoincidence,
+            "bigram".parse::<Metric>().unwrap(),
+        ];
+        let (front, reason, _) = search(&ciphertext, metrics.clone(), &opts).unwrap();
+        assert!(reason.starts_with("generation limit"));
+        assert!(!front.is_empty());
+        for point in &front {
+            let plaintext = Machine::new(&point.genome).unwrap().decrypt(&ciphertext);
+            let scores = metrics
+                .iter()
+                .map(|m| m.score(&plaintext))
+                .collect::<Vec<_>>();
+            assert_eq!(point.objectives, scores);
+        }
+
+        assert!(search(&ciphertext, vec![Metric::IndexOfCoincidence], &opts).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+// preset attacks chaining the crate's stages, as an alternative to a search over whole keys
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use rayon::prelude::*;
+
+use crate::{
+    climb::{PlugboardClimb, PlugboardSearch},
+    engine,
+    enigma::{self, Settings, LETTERS_NUM, MAX_ROTOR_NUM},
+    gen::{self, ReflectorSearch},
+    keyspace::{Constraints, Keyspace, Plugboards},
+    language::Language,
+    metrics::{self, Metric},
+};
+
+/// How a ciphertext is attacked: a search over whole keys with the configured engine, or
+/// Gillogly's stages one after the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    #[default]
+    Search,
+    Gillogly,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Search => write!(f, "search"),
+            Strategy::Gillogly => write!(f, "gillogly"),
+        }
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "search" => Ok(Strategy::Search),
+            "gillogly" => Ok(Strategy::Gillogly),
+            _ => Err(format!("unknown strategy {s}, expected search or gillogly")),
+        }
+    }
+}
+
+/// Gillogly's attack in the published order: the index of coincidence of every rotor order
+/// and starting position with the rings at A, then the ring settings of the right and middle
+/// rotor turned together with their positions, so the rotors stay aligned at the start and
+/// only their turnovers move, then a plugboard climb with the climb metric. The index of
+/// coincidence hardly moves with a turnover a few letters off, so the ring settings are
+/// refined once more by the climb metric under the climbed plugboard and the plugs climbed
+/// again.
+#[derive(Debug, Clone)]
+pub struct Gillogly {
+    // all orders of distinct rotors by default
+    pub rotor_orders: Vec<(u8, u8, u8)>,
+    // keys carried from a stage to the next
+    pub candidates: usize,
+    pub climb_metric: Metric,
+    pub climb_search: PlugboardSearch,
+}
+
+impl Default for Gillogly {
+    fn default() -> Self {
+        Self {
+            rotor_orders: rotor_orders(),
+            candidates: 20,
+            climb_metric: Metric::Bigram(Language::English),
+            climb_search: PlugboardSearch::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Attack {
+    pub settings: Settings,
+    // score of the decryption by the climb metric
+    pub score: f64,
+    // name and duration of every stage
+    pub stages: Vec<(&'static str, Duration)>,
+}
+
+impl Gillogly {
+    // the reflector, plug limit and threads are the ones of the search options
+    pub fn run(&self, ciphertext: &str, opts: &gen::Options) -> anyhow::Result<Attack> {
+        if opts.engine.reflector == ReflectorSearch::Rewirable {
+            bail!("the gillogly strategy needs a B or C reflector");
+        }
+        let pool = engine::thread_pool(&opts.engine)?;
+        let mut stages = Vec::new();
+
+        let started = Instant::now();
+        let candidates = pool.install(|| self.rotor_scan(ciphertext, &opts.engine.reflector))?;
+        stages.push(("rotor orders and positions", started.elapsed()));
+
+        let started = Instant::now();
+        let mut candidates = pool.install(|| {
+            candidates
+                .into_par_iter()
+                .map(|settings| refine_rings(ciphertext, settings, &Metric::IndexOfCoincidence))
+                .collect::<anyhow::Result<Vec<_>>>()
+        })?;
+        sort_by_score(&mut candidates);
+        stages.push(("ring settings", started.elapsed()));
+
+        // a turnover a few letters off still leaves most of the message for the climb to find
+        // the plugs in, under them the letter pairs put the turnover right
+        let started = Instant::now();
+        let mut climbed = candidates
+            .into_iter()
+            .map(|(settings, _)| {
+                let (settings, _) = self.climb(ciphertext, &settings, opts.engine.max_plugs)?;
+                let (settings, _) = refine_rings(ciphertext, settings, &self.climb_metric)?;
+                self.climb(ciphertext, &settings, opts.engine.max_plugs)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        sort_by_score(&mut climbed);
+        stages.push(("plugboard", started.elapsed()));
+
+        let (settings, score) = climbed
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("no rotor orders to try"))?;
+        Ok(Attack {
+            settings,
+            score,
+            stages,
+        })
+    }
+
+    fn climb(
+        &self,
+        ciphertext: &str,
+        settings: &Settings,
+        max_plugs: usize,
+    ) -> anyhow::Result<(Settings, f64)> {
+        let mut climb = PlugboardClimb::new(ciphertext, settings, &self.climb_metric)?;
+        let score = climb.refine(max_plugs, self.climb_search);
+        let settings = Settings {
+            plugboard: climb.plugboard(),
+            ..settings.clone()
+        };
+        Ok((settings, score))
+    }
+
+    // the best candidates of all rotor orders and reflectors by the index of coincidence
+    fn rotor_scan(
+        &self,
+        ciphertext: &str,
+        reflector: &ReflectorSearch,
+    ) -> anyhow::Result<Vec<Settings>> {
+        let constraints = Constraints {
+            rotor_orders: self.rotor_orders.clone(),
+            ring_settings: vec![(1, 1, 1)],
+            plugboards: Plugboards::Fixed(vec![]),
+            ..Default::default()
+        };
+        let keyspace = Keyspace::new(reflector, constraints)?;
+
+        // a shard per rotor order and reflector, whose positions are numbered in a row
+        let cores = keyspace.len() / (LETTERS_NUM as u128).pow(3);
+        let mut candidates = (0..cores)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|core| {
+                let keys = keyspace.shard(core, cores).collect::<Vec<_>>();
+                let plaintexts = enigma::decrypt_batch(&keys, ciphertext)?;
+
+                let mut scored = keys
+                    .into_iter()
+                    .zip(plaintexts.iter().map(|p| metrics::index_of_coincidence(p)))
+                    .collect::<Vec<_>>();
+                sort_by_score(&mut scored);
+                scored.truncate(self.candidates);
+                Ok(scored)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .concat();
+
+        sort_by_score(&mut candidates);
+        candidates.truncate(self.candidates);
+        Ok(candidates
+            .into_iter()
+            .map(|(settings, _)| settings)
+            .collect())
+    }
+}
+
+// the ring settings of the right and middle rotor that score best by the metric together, each
+// rotor's position turned with its ring; the left rotor never carries, so its ring setting
+// doesn't change the decryption, but a middle rotor that no longer double steps at the first
+// letter leaves the left one a position off either way
+pub(crate) fn refine_rings(
+    ciphertext: &str,
+    settings: Settings,
+    metric: &Metric,
+) -> anyhow::Result<(Settings, f64)> {
+    let turn = |letter: u8, by: u8| (letter - 1 + by) % LETTERS_NUM + 1;
+    let keys = [0, 1, LETTERS_NUM - 1]
+        .into_iter()
+        .flat_map(|left| (0..LETTERS_NUM).map(move |middle| (left, middle)))
+        .flat_map(|(left, middle)| (0..LETTERS_NUM).map(move |right| (left, middle, right)))
+        .map(|(left, middle, right)| {
+            let (rings, positions) = (settings.ring_settings, settings.rotor_positions);
+            Settings {
+                ring_settings: (rings.0, turn(rings.1, middle), turn(rings.2, right)),
+                rotor_positions: (
+                    turn(positions.0, left),
+                    turn(positions.1, middle),
+                    turn(positions.2, right),
+                ),
+                ..settings.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    let plaintexts = enigma::decrypt_batch(&keys, ciphertext)?;
+
+    // the settings as they came first, so a tie keeps them
+    let mut scored = keys
+        .into_iter()
+        .zip(plaintexts.iter().map(|p| metric.score(p)))
+        .collect::<Vec<_>>();
+    sort_by_score(&mut scored);
+    Ok(scored.swap_remove(0))
+}
+
+// all orders of three distinct rotors
+pub(crate) fn rotor_orders() -> Vec<(u8, u8, u8)> {
+    let rotors = 1..=MAX_ROTOR_NUM;
+    rotors
+        .clone()
+        .flat_map(|r0| rotors.clone().map(move |r1| (r0, r1)))
+        .flat_map(|(r0, r1)| rotors.clone().map(move |r2| (r0, r1, r2)))
+        .filter(|&(r0, r1, r2)| r0 != r1 && r1 != r2 && r0 != r2)
+        .collect()
+}
+
+// best first, ties in the order they came
+fn sort_by_score(scored: &mut [(Settings, f64)]) {
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{enigma::Machine, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_gillogly() {
+        for s in ["search", "gillogly"] {
+            assert_eq!(s.parse::<Strategy>().unwrap().to_string(), s);
+        }
+        assert!("bombe".parse::<Strategy>().is_err());
+        assert_eq!(Gillogly::default().rotor_orders.len(), 120);
+
+        let settings = "II V III / 01 05 20 / M C U / AQ BT CZ EK".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let gillogly = Gillogly {
+            rotor_orders: vec![(5, 2, 3), (2, 5, 3)],
+            candidates: 5,
+            ..Default::default()
+        };
+        let mut opts = gen::Options::default();
+        opts.engine.max_plugs = 10;
+
+        let attack = gillogly.run(&ciphertext, &opts).unwrap();
+        let plaintext = Machine::new(&attack.settings).unwrap().decrypt(&ciphertext);
+        assert_eq!(plaintext, LONG_TEXT);
+        assert_eq!(attack.stages.len(), 3);
+
+        opts.engine.reflector = ReflectorSearch::Rewirable;
+        assert!(gillogly.run(&ciphertext, &opts).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use rand::{seq::SliceRandom, Rng};
+use rayon::ThreadPool;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use crate::{
+    engine::{self, Bookkeeping, FitnessFunction, GenomeBuilder, Progress},
+    enigma::{self, Reflector, Settings, LETTERS_NUM, MAX_ROTOR_NUM},
+    gen::{self, Fitness, ReflectorSearch},
+    rng::SearchRng,
+};
+
+// ring settings and rotor positions, each a point on a circle of 26 letters
+const DIM: usize = 6;
+const CIRCLE: f64 = LETTERS_NUM as f64;
+// constriction coefficients of Clerc and Kennedy
+const INERTIA: f64 = 0.729;
+const COGNITIVE: f64 = 1.49445;
+const SOCIAL: f64 = 1.49445;
+const MAX_VELOCITY: f64 = CIRCLE / 2.0;
+// the discrete recombination per step: a particle takes over the rotors and reflector of its
+// own best or the swarm's best, or rarely picks random ones
+const ADOPT_PERSONAL_ROTORS: f64 = 0.1;
+const ADOPT_GLOBAL_ROTORS: f64 = 0.1;
+const RANDOM_ROTORS: f64 = 0.02;
+// every plug of the two best keys is taken over, replacing the plugs it conflicts with, with
+// this probability; own plugs are dropped and random ones added with the others
+const ADOPT_PLUG: f64 = 0.2;
+const DROP_PLUG: f64 = 0.05;
+const NEW_PLUG: f64 = 0.05;
+
+/// Particle swarm over keys: the ring settings and rotor positions of a particle are a point
+/// on a circle of 26 per coordinate that moves with a velocity pulled towards the best point
+/// of the particle and of the swarm, the rotors, reflector and plugboard follow the two best
+/// keys by discrete recombination.
+pub struct ParticleSwarm<F> {
+    fitness_calc: F,
+    particles: Vec<Particle>,
+    // best key of the swarm, its point and fitness
+    global_best: Option<(Settings, [f64; DIM], Fitness)>,
+    max_plugs: usize,
+    reflector: ReflectorSearch,
+    rng: SearchRng,
+    pool: ThreadPool,
+    bookkeeping: Bookkeeping<Settings>,
+}
+
+#[derive(Debug, Clone)]
+struct Particle {
+    point: [f64; DIM],
+    velocity: [f64; DIM],
+    // the discrete part and the rounded point
+    key: Settings,
+    best: Option<(Settings, [f64; DIM], Fitness)>,
+}
+
+impl<F: FitnessFunction<Settings>> ParticleSwarm<F> {
+    // population_size is the number of particles
+    pub fn new(fitness_calc: F, opts: &gen::Options) -> anyhow::Result<Self> {
+        let seed = opts.engine.seed.unwrap_or_else(rand::random);
+        let mut rng = SearchRng::new(opts.engine.rng, seed);
+        let builder = gen::SettingsBuilder {
+            max_plugs: opts.engine.max_plugs,
+            reflector: opts.engine.reflector.clone(),
+            herivel_spread: opts.engine.herivel_spread,
+            fixed_rings: false,
+        };
+
+        let particles = (0..opts.engine.population_size)
+            .map(|i| {
+                let key = builder.build_genome(i, &mut rng);
+                let (r, p) = (key.ring_settings, key.rotor_positions);
+                let point = [r.0, r.1, r.2, p.0, p.1, p.2].map(|letter| (letter - 1) as f64);
+                let velocity = [(); DIM].map(|_| rng.gen_range(-1.0..1.0) * MAX_VELOCITY / 2.0);
+                Particle {
+                    point,
+                    velocity,
+                    key,
+                    best: None,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            fitness_calc,
+            particles,
+            global_best: None,
+            max_plugs: opts.engine.max_plugs,
+            reflector: opts.engine.reflector.clone(),
+            rng,
+            pool: engine::thread_pool(&opts.engine)?,
+            bookkeeping: Bookkeeping::new(opts),
+        })
+    }
+
+    /// Moves every particle, the first step evaluates the initial swarm.
+    pub fn step(&mut self) -> Progress<Settings> {
+        self.bookkeeping.wait_while_paused();
+        let started = Instant::now();
+        if let Some(global_best) = &self.global_best {
+            for particle in &mut self.particles {
+                particle.fly(global_best, &self.reflector, self.max_plugs, &mut self.rng);
+            }
+        }
+
+        let keys = self
+            .particles
+            .iter()
+            .map(|particle| particle.key.clone())
+            .collect::<Vec<_>>();
+        let fitness = self.pool.install(|| {
+            engine::fitness_of_all(&self.fitness_calc, &keys, self.bookkeeping.deadline())
+        });
+
+        for (particle, &fitness) in self.particles.iter_mut().zip(&fitness) {
+            if particle.best.as_ref().is_none_or(|best| fitness > best.2) {
+                particle.best = Some((particle.key.clone(), particle.point, fitness));
+            }
+            if self
+                .global_best
+                .as_ref()
+                .is_none_or(|best| fitness > best.2)
+            {
+                self.global_best = Some((particle.key.clone(), particle.point, fitness));
+            }
+        }
+
+        self.bookkeeping
+            .record(keys.into_iter().zip(fitness).collect(), started)
+    }
+}
+
+impl Particle {
+    fn fly<R: Rng>(
+        &mut self,
+        global_best: &(Settings, [f64; DIM], Fitness),
+        reflector: &ReflectorSearch,
+        max_plugs: usize,
+        rng: &mut R,
+    ) {
+        let personal_best = self.best.clone().expect("evaluated before it moves");
+
+        for i in 0..DIM {
+            let personal = circular_difference(personal_best.1[i], self.point[i]);
+            let global = circular_difference(global_best.1[i], self.point[i]);
+            let velocity = INERTIA * self.velocity[i]
+                + COGNITIVE * rng.gen::<f64>() * personal
+                + SOCIAL * rng.gen::<f64>() * global;
+            self.velocity[i] = velocity.clamp(-MAX_VELOCITY, MAX_VELOCITY);
+            self.point[i] = (self.point[i] + self.velocity[i]).rem_euclid(CIRCLE);
+        }
+
+        let key = &mut self.key;
+        let draw = rng.gen::<f64>();
+        if draw < ADOPT_PERSONAL_ROTORS {
+            key.rotors = personal_best.0.rotors;
+            key.reflector = personal_best.0.reflector.clone();
+        } else if draw < ADOPT_PERSONAL_ROTORS + ADOPT_GLOBAL_ROTORS {
+            key.rotors = global_best.0.rotors;
+            key.reflector = global_best.0.reflector.clone();
+        } else if draw < ADOPT_PERSONAL_ROTORS + ADOPT_GLOBAL_ROTORS + RANDOM_ROTORS {
+            // a slice sample is in random order, every rotor order is equally likely
+            let rotors = (1..=MAX_ROTOR_NUM).collect::<Vec<_>>();
+            let rotors = rotors.choose_multiple(rng, 3).collect::<Vec<_>>();
+            key.rotors = (*rotors[0], *rotors[1], *rotors[2]);
+            if *reflector == ReflectorSearch::Standard {
+                key.reflector = [Reflector::B, Reflector::C][rng.gen_range(0..2)].clone();
+            }
+        }
+
+        let letters = self.point.map(|x| (x.round().rem_euclid(CIRCLE) as u8) + 1);
+        key.ring_settings = (letters[0], letters[1], letters[2]);
+        key.rotor_positions = (letters[3], letters[4], letters[5]);
+        key.plugboard = recombine_plugs(
+            &key.plugboard,
+            [&personal_best.0.plugboard, &global_best.0.plugboard],
+            max_plugs,
+            rng,
+        );
+    }
+}
+
+// b - a the short way around the circle, in -13..13
+fn circular_difference(b: f64, a: f64) -> f64 {
+    (b - a + CIRCLE / 2.0).rem_euclid(CIRCLE) - CIRCLE / 2.0
+}
+
+fn recombine_plugs<R: Rng>(
+    own: &[(u8, u8)],
+    bests: [&[(u8, u8)]; 2],
+    max_plugs: usize,
+    rng: &mut R,
+) -> Vec<(u8, u8)> {
+    let mut plugboard = own
+        .iter()
+        .filter(|_| !rng.gen_bool(DROP_PLUG))
+        .copied()
+        .collect::<Vec<_>>();
+
+    for &plug in bests.into_iter().flatten() {
+        if !plugboard.contains(&plug) && rng.gen_bool(ADOPT_PLUG) {
+            plugboard.retain(|&own| !enigma::is_plug_conflicting(&[own], plug));
+            if plugboard.len() < max_plugs {
+                plugboard.push(plug);
+            }
+        }
+    }
+    if plugboard.len() < max_plugs && rng.gen_bool(NEW_PLUG) {
+        gen::add_plug(&mut plugboard, rng);
+    }
+    plugboard
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha12Rng;
+
+    use crate::{
+        engine::Algorithm,
+        enigma::Machine,
+        metrics::tests::LONG_TEXT,
+        sim::{Simulation, StepResult},
+    };
+
+    use super::*;
+
+    // share of the key that matches a target: rotors, reflector, each ring setting and
+    // position by its distance on the circle, and the plugs
+    struct Likeness(Settings);
+
+    impl FitnessFunction<Settings> for Likeness {
+        fn fitness_of(&self, key: &Settings) -> Fitness {
+            let letters = |s: &Settings| {
+                let (r, p) = (s.ring_settings, s.rotor_positions);
+                [r.0, r.1, r.2, p.0, p.1, p.2].map(|letter| letter as f64)
+            };
+            let target = &self.0;
+            let closeness = letters(key)
+                .iter()
+                .zip(letters(target))
+                .map(|(&a, b)| 1.0 - circular_difference(b, a).abs() / 13.0)
+                .sum::<f64>();
+            let plugs = key
+                .plugboard
+                .iter()
+                .filter(|plug| target.plugboard.contains(plug))
+                .count() as f64
+                - (key.plugboard.len() as f64 - target.plugboard.len() as f64).abs();
+            let rotors = (key.rotors == target.rotors) as u8 as f64;
+
+            Fitness((closeness + plugs + 6.0 * rotors) / (12.0 + target.plugboard.len() as f64))
+        }
+    }
+
+    #[test]
+    fn test_pso() {
+        assert_eq!(circular_difference(1.0, 25.0), 2.0);
+        assert_eq!(circular_difference(25.0, 1.0), -2.0);
+        let mut rng = ChaCha12Rng::seed_from_u64(1);
+        let plugs = recombine_plugs(&[(1, 2), (3, 4)], [&[(2, 5)], &[]], 2, &mut rng);
+        assert!(plugs.len() <= 2);
+        assert!(plugs
+            .iter()
+            .enumerate()
+            .all(|(i, &plug)| !enigma::is_plug_conflicting(&plugs[..i], plug)));
+
+        let target = "IV I V / 02 20 11 / Q A Z / AB CD"
+            .parse::<Settings>()
+            .unwrap();
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 300;
+        opts.engine.max_plugs = 2;
+        opts.engine.seed = Some(2);
+        opts.engine.sample_size = 3;
+        opts.termination.generation_limit = 300;
+
+        let mut pso = ParticleSwarm::new(Likeness(target.clone()), &opts).unwrap();
+        let progress = loop {
+            let progress = pso.step();
+            assert_eq!(progress.sample.len(), 3);
+            if progress.stop_reason.is_some() {
+                break progress;
+            }
+        };
+        assert!(progress.iteration < 300);
+        assert_eq!(progress.best_fitness, Fitness(1.0));
+        assert_eq!(progress.best_genome.rotors, target.rotors);
+    }
+
+    #[test]
+    fn test_pso_simulation() {
+        let settings = "II IV I / 03 17 09 / K E Y / AQ"
+            .parse::<Settings>()
+            .unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let mut opts = gen::Options::default();
+        opts.engine.algorithm = Algorithm::Pso;
+        opts.engine.population_size = 200;
+        opts.engine.max_plugs = 3;
+        opts.engine.reflector = ReflectorSearch::Standard;
+        opts.engine.seed = Some(1);
+        opts.termination.generation_limit = 4;
+
+        let last = Simulation::new(&ciphertext, opts.clone()).unwrap().last();
+        assert!(
+            matches!(last, Some(Ok(StepResult::Final { generation, .. }))
+            if generation.iteration == 4 && generation.best_settings.plugboard.len() <= 3)
+        );
+
+        opts.engine.reflector = ReflectorSearch::Rewirable;
+        assert!(Simulation::new(&ciphertext, opts).is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coverage::KeyCoverage,
+    engine::Individual,
+    enigma::{Machine, Settings},
+    gen::Fitness,
+    sim::{Generation, StepResult},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    // compact binary formats for reports carrying large populations
+    Bincode,
+    MessagePack,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ReportFormat::Json),
+            "bincode" => Ok(ReportFormat::Bincode),
+            "msgpack" => Ok(ReportFormat::MessagePack),
+            _ => Err(format!(
+                "unknown report format {s}, expected json, bincode or msgpack"
+            )),
+        }
+    }
+}
+
+// how the progress of a search is printed: lines for people or a JSON object per line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProgressFormat {
+    #[default]
+    Human,
+    Ndjson,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ProgressFormat::Human),
+            "ndjson" => Ok(ProgressFormat::Ndjson),
+            _ => Err(format!(
+                "unknown progress format {s}, expected human or ndjson"
+            )),
+        }
+    }
+}
+
+/// A line of the NDJSON progress stream, told apart by `event`: `generation` for every
+/// generation reported and `final` once at the end. The field names are kept stable for the
+/// wrappers reading them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    Generation {
+        generation: u64,
+        average_fitness: f64,
+        median_fitness: f64,
+        fitness_std_dev: f64,
+        best_fitness: f64,
+        best_found_in: u64,
+        best_key: String,
+        duration_ms: u64,
+        processing_time_ms: u64,
+    },
+    Final {
+        generation: u64,
+        best_fitness: f64,
+        best_found_in: u64,
+        best_key: String,
+        plaintext: String,
+        reason: String,
+        duration_ms: u64,
+        processing_time_ms: u64,
+    },
+}
+
+impl ProgressEvent {
+    pub fn new(ciphertext: &str, step: &StepResult) -> anyhow::Result<Self> {
+        Ok(match step {
+            StepResult::Intermediate(generation) => ProgressEvent::Generation {
+                generation: generation.iteration,
+                average_fitness: generation.average_fitness.0,
+                median_fitness: generation.median_fitness.0,
+                fitness_std_dev: generation.fitness_std_dev,
+                best_fitness: generation.best_fitness.0,
+                best_found_in: generation.best_found_in,
+                best_key: generation.best_settings.to_string(),
+                duration_ms: generation.duration.as_millis() as u64,
+                processing_time_ms: generation.processing_time.as_millis() as u64,
+            },
+            StepResult::Final { generation, reason } => {
+                let plaintext = Machine::new(&generation.best_settings)?.decrypt(ciphertext);
+                let (settings, fitness) = (&generation.best_settings, generation.best_fitness);
+                ProgressEvent::finished(generation, reason, settings, plaintext, fitness)
+            }
+        })
+    }
+
+    // the final event for the key reported in the end, which a plugboard climb or rescoring
+    // may have changed after the search
+    pub fn finished(
+        generation: &Generation,
+        reason: &str,
+        settings: &Settings,
+        plaintext: String,
+        fitness: Fitness,
+    ) -> Self {
+        ProgressEvent::Final {
+            generation: generation.iteration,
+            best_fitness: fitness.0,
+            best_found_in: generation.best_found_in,
+            best_key: settings.to_string(),
+            plaintext,
+            reason: reason.to_string(),
+            duration_ms: generation.duration.as_millis() as u64,
+            processing_time_ms: generation.processing_time.as_millis() as u64,
+        }
+    }
+
+    // a single line, without the newline
+    pub fn to_line(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Result of a run: the recovered key and plaintext, how it was found and the population
+/// sampled from the last generation (see `EngineOptions::sample_size`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunReport {
+    pub ciphertext: String,
+    pub plaintext: String,
+    pub seed: Option<u64>,
+    pub settings: Settings,
+    pub fitness: Fitness,
+    pub generations: u64,
+    pub best_found_in: u64,
+    pub reason: String,
+    pub duration_ms: u64,
+    pub population: Vec<Individual<Settings>>,
+    // see FitnessOptions::coverage
+    pub coverage: Option<KeyCoverage>,
+}
+
+impl RunReport {
+    pub fn new(
+        ciphertext: &str,
+        seed: Option<u64>,
+        generation: &Generation,
+        reason: &str,
+    ) -> anyhow::Result<Self> {
+        let machine = Machine::new(&generation.best_settings)?;
+
+        Ok(Self {
+            ciphertext: ciphertext.to_string(),
+            plaintext: machine.decrypt(ciphertext),
+            seed,
+            settings: generation.best_settings.clone(),
+            fitness: generation.best_fitness,
+            generations: generation.iteration,
+            best_found_in: generation.best_found_in,
+            reason: reason.to_string(),
+            duration_ms: generation.duration.as_millis() as u64,
+            population: generation.sample.clone(),
+            coverage: generation.coverage,
+        })
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W, format: ReportFormat) -> anyhow::Result<()> {
+        match format {
+            ReportFormat::Json => serde_json::to_writer_pretty(&mut writer, self)?,
+            ReportFormat::Bincode => bincode::serialize_into(&mut writer, self)?,
+            ReportFormat::MessagePack => rmp_serde::encode::write_named(&mut writer, self)?,
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: R, format: ReportFormat) -> anyhow::Result<Self> {
+        Ok(match format {
+            ReportFormat::Json => serde_json::from_reader(reader)?,
+            ReportFormat::Bincode => bincode::deserialize_from(reader)?,
+            ReportFormat::MessagePack => rmp_serde::from_read(reader)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::metrics::tests::LONG_TEXT;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let settings =
+            "D AB CD EF GH IJ KL MN OP QR ST UV WX YZ / II V III / 08 05 20 / M C U / AB CZ"
+                .parse::<Settings>()
+                .unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let generation = Generation {
+            iteration: 40,
+            average_fitness: Fitness(0.04),
+            median_fitness: Fitness(0.039),
+            fitness_std_dev: 0.002,
+            best_fitness: Fitness(0.066),
+            best_settings: settings.clone(),
+            best_found_in: 31,
+            duration: Duration::from_secs(90),
+            processing_time: Duration::from_secs(80),
+            sample: (0..500)
+                .map(|rank| Individual {
+                    rank,
+                    genome: settings.clone(),
+                    fitness: Fitness(0.066 - rank as f64 * 1e-5),
+                })
+                .collect(),
+            top: Vec::new(),
+            ancestry: Vec::new(),
+            coverage: Some(KeyCoverage {
+                keys: 1_234_567,
+                keyspace: crate::enigma::keyspace_size(10),
+            }),
+            invalid_genomes: None,
+        };
+        let report = RunReport::new(&ciphertext, Some(7), &generation, "target reached").unwrap();
+        assert_eq!(report.plaintext, LONG_TEXT);
+
+        let mut sizes = Vec::new();
+        for format in [
+            ReportFormat::Json,
+            ReportFormat::Bincode,
+            ReportFormat::MessagePack,
+        ] {
+            let mut bytes = Vec::new();
+            report.write(&mut bytes, format).unwrap();
+            assert_eq!(RunReport::read(&bytes[..], format).unwrap(), report);
+            sizes.push(bytes.len());
+        }
+        assert!(sizes[1] < sizes[0] / 2 && sizes[2] < sizes[0] / 2);
+
+        // a line per event, named by its field event
+        let step = StepResult::Intermediate(generation.clone());
+        let line = ProgressEvent::new(&ciphertext, &step)
+            .unwrap()
+            .to_line()
+            .unwrap();
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert!(!line.contains('\n'));
+        assert_eq!(event["event"], "generation");
+        assert_eq!(event["generation"], 40);
+        assert_eq!(event["best_found_in"], 31);
+        let step = StepResult::Final {
+            generation,
+            reason: "target reached".to_string(),
+        };
+        let line = ProgressEvent::new(&ciphertext, &step)
+            .unwrap()
+            .to_line()
+            .unwrap();
+        match serde_json::from_str(&line).unwrap() {
+            ProgressEvent::Final {
+                plaintext, reason, ..
+            } => assert_eq!(
+                (plaintext.as_str(), reason.as_str()),
+                (LONG_TEXT, "target reached")
+            ),
+            event => panic!("{:?}", event),
+        }
+        assert!("xml".parse::<ProgressFormat>().is_err());
+    }
+}
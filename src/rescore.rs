@@ -0,0 +1,166 @@
+// rescoring the best keys of a run with a stronger model of the plaintext than the search can
+// afford on millions of keys a generation: a metric with a higher order n-gram table, or a
+// program of the user's, e.g. running a character-level neural model
+use std::{
+    fmt,
+    io::{Read, Write},
+    process::{Command, Stdio},
+    str::FromStr,
+    thread,
+};
+
+use anyhow::{anyhow, ensure, Context};
+
+use crate::{
+    enigma::{self, Settings},
+    metrics::Metric,
+};
+
+#[derive(Debug, Clone)]
+pub enum Rescorer {
+    Metric(Metric),
+    // program and arguments, split at whitespace; it reads a decryption per line on stdin and
+    // writes a score per line, higher is better
+    Command(String),
+}
+
+impl Rescorer {
+    /// The keys ranked by the score of their decryptions, best first; equal scores keep the
+    /// order they came in, the search's.
+    pub fn rank(
+        &self,
+        ciphertext: &str,
+        keys: Vec<Settings>,
+    ) -> anyhow::Result<Vec<(Settings, f64)>> {
+        let plaintexts = enigma::decrypt_batch(&keys, ciphertext)?;
+        let scores = match self {
+            Rescorer::Metric(metric) => plaintexts.iter().map(|p| metric.score(p)).collect(),
+            Rescorer::Command(command) => run(command, &plaintexts)?,
+        };
+
+        let mut ranked = keys.into_iter().zip(scores).collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(ranked)
+    }
+}
+
+fn run(command: &str, plaintexts: &[String]) -> anyhow::Result<Vec<f64>> {
+    let mut args = command.split_whitespace();
+    let program = args
+        .next()
+        .ok_or_else(|| anyhow!("the rescoring command is empty"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting {}", program))?;
+
+    // written from a thread of its own, a model that answers line by line would otherwise
+    // fill its output pipe while the input still waits to be written
+    let mut stdin = child.stdin.take().expect("piped");
+    let mut stdout = child.stdout.take().expect("piped");
+    let output = thread::scope(|scope| {
+        let writer = scope.spawn(move || {
+            plaintexts
+                .iter()
+                .try_for_each(|plaintext| writeln!(stdin, "{}", plaintext))
+        });
+        let mut output = String::new();
+        stdout.read_to_string(&mut output)?;
+        writer.join().expect("the writer doesn't panic")?;
+        anyhow::Ok(output)
+    })?;
+    let status = child.wait()?;
+    ensure!(status.success(), "{} exited with {}", program, status);
+
+    let scores = output
+        .lines()
+        .map(|line| {
+            line.trim()
+                .parse::<f64>()
+                .with_context(|| format!("{} wrote {:?} instead of a score", program, line))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    ensure!(
+        scores.len() == plaintexts.len(),
+        "{} wrote {} scores for {} decryptions",
+        program,
+        scores.len(),
+        plaintexts.len()
+    );
+    Ok(scores)
+}
+
+impl fmt::Display for Rescorer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rescorer::Metric(metric) => write!(f, "{}", metric),
+            Rescorer::Command(command) => write!(f, "command:{}", command),
+        }
+    }
+}
+
+// command:<program and arguments>, or a metric
+impl FromStr for Rescorer {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix("command:") {
+            Some(command) if command.trim().is_empty() => {
+                Err("the rescoring command is empty".to_string())
+            }
+            Some(command) => Ok(Rescorer::Command(command.trim().to_string())),
+            None => s.parse().map(Rescorer::Metric),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{enigma::Machine, language::Language, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_rescore() {
+        let settings = "II V III / 08 05 20 / M C U / AQ BT".parse::<Settings>();
+        let settings = settings.unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..300]);
+        let keys = vec![
+            Settings {
+                plugboard: vec![],
+                ..settings.clone()
+            },
+            settings.clone(),
+            Settings {
+                rotor_positions: (1, 1, 1),
+                ..settings.clone()
+            },
+        ];
+
+        let bigram = "bigram".parse::<Rescorer>().unwrap();
+        assert!(matches!(
+            bigram,
+            Rescorer::Metric(Metric::Bigram(Language::English))
+        ));
+        let ranked = bigram.rank(&ciphertext, keys.clone()).unwrap();
+        assert_eq!(ranked[0].0, settings);
+        assert!(ranked.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        // the number of Es of every decryption
+        let command = "command: awk {print(gsub(/E/,\"\"))}".parse::<Rescorer>();
+        let command = command.unwrap();
+        assert_eq!(command.to_string(), "command:awk {print(gsub(/E/,\"\"))}");
+        let ranked = command.rank(&ciphertext, keys.clone()).unwrap();
+        assert_eq!(ranked[0].0, settings);
+        let es = LONG_TEXT[..300].bytes().filter(|&c| c == b'E').count();
+        assert_eq!(ranked[0].1, es as f64);
+
+        assert!("command:".parse::<Rescorer>().is_err());
+        let missing = Rescorer::Command("no-such-model-runner".to_string());
+        assert!(missing.rank(&ciphertext, keys.clone()).is_err());
+        let silent = Rescorer::Command("true".to_string());
+        assert!(silent.rank(&ciphertext, keys).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+// the random numbers of the search engines: ChaCha by default, whose streams rand_chacha keeps
+// the same across releases, or xoshiro256++, several times faster to draw from and
+// reproducible with the same seed and version
+use std::{fmt, str::FromStr};
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RngAlgorithm {
+    #[default]
+    ChaCha,
+    Xoshiro,
+}
+
+impl FromStr for RngAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chacha" => Ok(RngAlgorithm::ChaCha),
+            "xoshiro" => Ok(RngAlgorithm::Xoshiro),
+            _ => Err(format!("unknown rng {s}, expected chacha or xoshiro")),
+        }
+    }
+}
+
+impl fmt::Display for RngAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RngAlgorithm::ChaCha => write!(f, "chacha"),
+            RngAlgorithm::Xoshiro => write!(f, "xoshiro"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchRng {
+    // boxed, its block buffer makes it ten times the size of xoshiro
+    ChaCha(Box<ChaCha12Rng>),
+    Xoshiro(Xoshiro256PlusPlus),
+}
+
+impl SearchRng {
+    pub fn new(algorithm: RngAlgorithm, seed: u64) -> Self {
+        match algorithm {
+            RngAlgorithm::ChaCha => SearchRng::ChaCha(Box::new(ChaCha12Rng::seed_from_u64(seed))),
+            RngAlgorithm::Xoshiro => SearchRng::Xoshiro(Xoshiro256PlusPlus::seed_from_u64(seed)),
+        }
+    }
+
+    /// Chunk `chunk` of stream `stream` of a seed, independent of the order the chunks are
+    /// drawn in: ChaCha seeks to it, 2^32 words per chunk, xoshiro is seeded with a mix of
+    /// the three.
+    pub fn stream(algorithm: RngAlgorithm, seed: u64, stream: u64, chunk: usize) -> Self {
+        match algorithm {
+            RngAlgorithm::ChaCha => {
+                let mut rng = ChaCha12Rng::seed_from_u64(seed);
+                rng.set_stream(stream);
+                rng.set_word_pos((chunk as u128) << 32);
+                SearchRng::ChaCha(Box::new(rng))
+            }
+            RngAlgorithm::Xoshiro => {
+                let seed = mix(mix(mix(seed) ^ stream) ^ chunk as u64);
+                SearchRng::Xoshiro(Xoshiro256PlusPlus::seed_from_u64(seed))
+            }
+        }
+    }
+}
+
+impl RngCore for SearchRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            SearchRng::ChaCha(rng) => rng.next_u32(),
+            SearchRng::Xoshiro(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            SearchRng::ChaCha(rng) => rng.next_u64(),
+            SearchRng::Xoshiro(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            SearchRng::ChaCha(rng) => rng.fill_bytes(dest),
+            SearchRng::Xoshiro(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            SearchRng::ChaCha(rng) => rng.try_fill_bytes(dest),
+            SearchRng::Xoshiro(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+// splitmix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn test_search_rng() {
+        for algorithm in [RngAlgorithm::ChaCha, RngAlgorithm::Xoshiro] {
+            let draw = |stream, chunk| {
+                let mut rng = SearchRng::stream(algorithm, 7, stream, chunk);
+                (0..4).map(|_| rng.gen::<u64>()).collect::<Vec<_>>()
+            };
+            assert_eq!(draw(1, 3), draw(1, 3), "{algorithm}");
+            assert_ne!(draw(1, 3), draw(1, 4), "{algorithm}");
+            assert_ne!(draw(1, 3), draw(2, 3), "{algorithm}");
+
+            let mut rng = SearchRng::new(algorithm, 7);
+            let mean = (0..10_000).map(|_| rng.gen::<f64>()).sum::<f64>() / 10_000.0;
+            assert!((mean - 0.5).abs() < 0.02, "{algorithm} {mean}");
+        }
+        assert_eq!("xoshiro".parse(), Ok(RngAlgorithm::Xoshiro));
+        assert!("mt19937".parse::<RngAlgorithm>().is_err());
+    }
+}
@@ -0,0 +1,495 @@
+use std::{
+    io::{BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    engine::Pause,
+    enigma::Machine,
+    gen::{self, Fitness},
+    sim::{Generation, Simulation, StepResult},
+};
+
+// JSON-RPC 2.0 error codes, -32000 and below are ours
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const WRONG_STATE: i64 = -32000;
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    // notifications have no id and get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+// every option but the ciphertext takes the default of the command line when left out
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StartParams {
+    ciphertext: String,
+    seed: Option<u64>,
+    max_plugs: Option<usize>,
+    reflector: Option<String>,
+    population_size: Option<usize>,
+    generation_limit: Option<u64>,
+    time_limit_secs: Option<u64>,
+    target_fitness: Option<f64>,
+    threads: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    #[default]
+    Idle,
+    Running,
+    Paused,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Progress {
+    pub generation: u64,
+    pub average_fitness: f64,
+    pub median_fitness: f64,
+    pub fitness_std_dev: f64,
+    pub best_fitness: f64,
+    pub best_key: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outcome {
+    pub key: String,
+    pub plaintext: String,
+    pub fitness: f64,
+    pub generations: u64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Status {
+    pub state: State,
+    pub seed: Option<u64>,
+    pub progress: Option<Progress>,
+    pub result: Option<Outcome>,
+    pub error: Option<String>,
+}
+
+struct Search {
+    cancel: Arc<AtomicBool>,
+    pause: Pause,
+    worker: JoinHandle<()>,
+}
+
+/// Serves JSON-RPC 2.0 with one message per line: `start` a search, `pause` and `resume` it
+/// between generations, `cancel` it, query its `status` or `result`. Every generation is sent
+/// as a `progress` notification and the end of the search as a `finished` notification with
+/// the status. One search runs at a time; at the end of the input the running search is
+/// finished before returning.
+pub fn serve<R: BufRead, W: Write + Send + 'static>(input: R, output: W) -> anyhow::Result<()> {
+    let output = Arc::new(Mutex::new(output));
+    let status = Arc::new(Mutex::new(Status::default()));
+    let mut search: Option<Search> = None;
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match serde_json::from_str::<Value>(&line) {
+            Ok(value) => serde_json::from_value::<Request>(value),
+            Err(err) => {
+                send(&output, error(Value::Null, PARSE_ERROR, err))?;
+                continue;
+            }
+        };
+        let request = match request {
+            Ok(request) => request,
+            Err(err) => {
+                send(&output, error(Value::Null, INVALID_REQUEST, err))?;
+                continue;
+            }
+        };
+
+        let id = request.id.clone();
+        let response = match handle(request, &output, &status, &mut search) {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => error(id.clone().unwrap_or_default(), code, message),
+        };
+        if id.is_some() {
+            send(&output, response)?;
+        }
+    }
+
+    if let Some(search) = search {
+        let _ = search.worker.join();
+    }
+
+    Ok(())
+}
+
+fn handle<W: Write + Send + 'static>(
+    request: Request,
+    output: &Arc<Mutex<W>>,
+    status: &Arc<Mutex<Status>>,
+    search: &mut Option<Search>,
+) -> Result<Value, (i64, String)> {
+    let current = || status.lock().expect("not poisoned").clone();
+    let active = || matches!(current().state, State::Running | State::Paused);
+
+    match request.method.as_str() {
+        "start" => {
+            if active() {
+                return Err((WRONG_STATE, "a search is already running".to_string()));
+            }
+            let params = serde_json::from_value::<StartParams>(request.params)
+                .map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+            let opts = options(&params).map_err(|err| (INVALID_PARAMS, err.to_string()))?;
+            let seed = opts.engine.seed;
+
+            if let Some(previous) = search.take() {
+                let _ = previous.worker.join();
+            }
+            *status.lock().expect("not poisoned") = Status {
+                state: State::Running,
+                seed,
+                ..Default::default()
+            };
+            *search = Some(start(params.ciphertext, opts, output, status));
+
+            Ok(json!({ "seed": seed }))
+        }
+        "cancel" => match search {
+            Some(search) if active() => {
+                search.cancel.store(true, Ordering::Relaxed);
+                // a paused search only sees the cancel once it runs again
+                search.pause.set(false);
+                Ok(json!(true))
+            }
+            _ => Err((WRONG_STATE, "no search is running".to_string())),
+        },
+        "pause" | "resume" => {
+            let (from, to, wrong_state) = match request.method.as_str() {
+                "pause" => (State::Running, State::Paused, "no search is running"),
+                _ => (State::Paused, State::Running, "no search is paused"),
+            };
+            let mut status = status.lock().expect("not poisoned");
+            match search {
+                Some(search) if status.state == from => {
+                    search.pause.set(to == State::Paused);
+                    status.state = to;
+                    Ok(json!(true))
+                }
+                _ => Err((WRONG_STATE, wrong_state.to_string())),
+            }
+        }
+        "status" => Ok(json!(current())),
+        "result" => match current().result {
+            Some(outcome) => Ok(json!(outcome)),
+            None => Err((WRONG_STATE, "no search has finished".to_string())),
+        },
+        method => Err((METHOD_NOT_FOUND, format!("unknown method {}", method))),
+    }
+}
+
+fn options(params: &StartParams) -> anyhow::Result<gen::Options> {
+    let mut opts = gen::Options::default();
+    let engine = &mut opts.engine;
+    let termination = &mut opts.termination;
+
+    engine.seed = Some(params.seed.unwrap_or_else(rand::random));
+    engine.max_plugs = params.max_plugs.unwrap_or(engine.max_plugs);
+    engine.threads = params.threads.unwrap_or(engine.threads);
+    engine.population_size = params.population_size.unwrap_or(engine.population_size);
+    if let Some(reflector) = &params.reflector {
+        engine.reflector = reflector.parse()?;
+    }
+    termination.generation_limit = params
+        .generation_limit
+        .unwrap_or(termination.generation_limit);
+    if let Some(secs) = params.time_limit_secs {
+        termination.time_limit = chrono::Duration::seconds(secs as i64);
+    }
+    termination.target_fitness = params.target_fitness.map(Fitness);
+
+    opts.validate()?;
+    Ok(opts)
+}
+
+fn start<W: Write + Send + 'static>(
+    ciphertext: String,
+    mut opts: gen::Options,
+    output: &Arc<Mutex<W>>,
+    status: &Arc<Mutex<Status>>,
+) -> Search {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let pause = Pause::default();
+    opts.engine.pause = pause.clone();
+    let (output, status) = (output.clone(), status.clone());
+
+    let worker = thread::spawn({
+        let cancel = cancel.clone();
+        move || {
+            let end = |state: State, result: Option<Outcome>, error: Option<String>| {
+                let mut status = status.lock().expect("not poisoned");
+                status.state = state;
+                status.result = result;
+                status.error = error;
+                let _ = send(&output, notification("finished", json!(*status)));
+            };
+
+            let sim = match Simulation::new(&ciphertext, opts) {
+                Ok(sim) => sim,
+                Err(err) => return end(State::Failed, None, Some(err.to_string())),
+            };
+            for step in sim {
+                // checked between generations, the current one is finished first
+                if cancel.load(Ordering::Relaxed) {
+                    return end(State::Cancelled, None, None);
+                }
+                let step = match step {
+                    Ok(step) => step,
+                    Err(err) => return end(State::Failed, None, Some(err.to_string())),
+                };
+
+                let progress = progress(step.generation());
+                status.lock().expect("not poisoned").progress = Some(progress.clone());
+                let _ = send(&output, notification("progress", json!(progress)));
+
+                if let StepResult::Final { generation, reason } = step {
+                    return match outcome(&ciphertext, &generation, reason) {
+                        Ok(outcome) => end(State::Finished, Some(outcome), None),
+                        Err(err) => end(State::Failed, None, Some(err.to_string())),
+                    };
+                }
+            }
+        }
+    });
+
+    Search {
+        cancel,
+        pause,
+        worker,
+    }
+}
+
+fn progress(generation: &Generation) -> Progress {
+    Progress {
+        generation: generation.iteration,
+        average_fitness: generation.average_fitness.0,
+        median_fitness: generation.median_fitness.0,
+        fitness_std_dev: generation.fitness_std_dev,
+        best_fitness: generation.best_fitness.0,
+        best_key: generation.best_settings.to_string(),
+        duration_ms: generation.duration.as_millis() as u64,
+    }
+}
+
+fn outcome(ciphertext: &str, generation: &Generation, reason: String) -> anyhow::Result<Outcome> {
+    Ok(Outcome {
+        key: generation.best_settings.to_string(),
+        plaintext: Machine::new(&generation.best_settings)?.decrypt(ciphertext),
+        fitness: generation.best_fitness.0,
+        generations: generation.iteration,
+        reason,
+    })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn error(id: Value, code: i64, message: impl ToString) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.to_string() },
+    })
+}
+
+fn send<W: Write>(output: &Mutex<W>, message: Value) -> anyhow::Result<()> {
+    let mut output = output.lock().expect("not poisoned");
+    writeln!(output, "{}", message)?;
+    output.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, PipeWriter};
+
+    use crate::{enigma::Settings, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    struct Client {
+        requests: PipeWriter,
+        messages: io::Lines<BufReader<io::PipeReader>>,
+        server: JoinHandle<anyhow::Result<()>>,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let (input, requests) = io::pipe().unwrap();
+            let (messages, output) = io::pipe().unwrap();
+            let server = thread::spawn(move || serve(BufReader::new(input), output));
+
+            Self {
+                requests,
+                messages: BufReader::new(messages).lines(),
+                server,
+            }
+        }
+
+        fn send(&mut self, request: Value) {
+            writeln!(self.requests, "{}", request).unwrap();
+        }
+
+        fn next(&mut self) -> Value {
+            serde_json::from_str(&self.messages.next().unwrap().unwrap()).unwrap()
+        }
+
+        // skips notifications until the response with this id
+        fn response(&mut self, id: u64) -> Value {
+            loop {
+                let message = self.next();
+                if message["id"] == id {
+                    return message;
+                }
+            }
+        }
+    }
+
+    fn start_params(generation_limit: u64) -> Value {
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        json!({
+            "ciphertext": Machine::new(&settings).unwrap().encrypt(LONG_TEXT),
+            "seed": 5,
+            "population_size": 300,
+            "generation_limit": generation_limit,
+        })
+    }
+
+    #[test]
+    fn test_search() {
+        let mut client = Client::new();
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "result" }));
+        assert_eq!(client.response(1)["error"]["code"], WRONG_STATE);
+
+        client.send(
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "start", "params": start_params(3) }),
+        );
+        assert_eq!(client.response(2)["result"]["seed"], 5);
+
+        let mut generations = Vec::new();
+        let finished = loop {
+            let message = client.next();
+            match message["method"].as_str() {
+                Some("progress") => generations.push(message["params"]["generation"].clone()),
+                Some("finished") => break message["params"].clone(),
+                _ => {}
+            }
+        };
+        assert_eq!(generations, [1, 2, 3]);
+        assert_eq!(finished["state"], "finished");
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "result" }));
+        let result = client.response(3)["result"].clone();
+        assert_eq!(result, finished["result"]);
+        assert_eq!(result["generations"], 3);
+
+        drop(client.requests);
+        client.server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_cancel_and_errors() {
+        let mut client = Client::new();
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "start", "params": start_params(1_000_000) }));
+        client.response(1);
+        client.send(
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "start", "params": start_params(3) }),
+        );
+        assert_eq!(client.response(2)["error"]["code"], WRONG_STATE);
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "cancel" }));
+        assert_eq!(client.response(3)["result"], true);
+        let finished = loop {
+            let message = client.next();
+            if message["method"] == "finished" {
+                break message["params"].clone();
+            }
+        };
+        assert_eq!(finished["state"], "cancelled");
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 4, "method": "status" }));
+        assert_eq!(client.response(4)["result"]["state"], "cancelled");
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 5, "method": "pause" }));
+        assert_eq!(client.response(5)["error"]["code"], WRONG_STATE);
+        client.send(json!({ "jsonrpc": "2.0", "id": 5, "method": "start", "params": { "ciphertext": "A", "reflector": "E" } }));
+        assert_eq!(client.response(5)["error"]["code"], INVALID_PARAMS);
+        client.send(json!({ "jsonrpc": "2.0", "id": 6, "method": "crack" }));
+        assert_eq!(client.response(6)["error"]["code"], METHOD_NOT_FOUND);
+        writeln!(client.requests, "{{").unwrap();
+        assert_eq!(client.next()["error"]["code"], PARSE_ERROR);
+
+        drop(client.requests);
+        client.server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut client = Client::new();
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "start", "params": start_params(1_000_000) }));
+        client.response(1);
+        client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "resume" }));
+        assert_eq!(client.response(2)["error"]["code"], WRONG_STATE);
+        client.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "pause" }));
+        assert_eq!(client.response(3)["result"], true);
+        client.send(json!({ "jsonrpc": "2.0", "id": 4, "method": "status" }));
+        assert_eq!(client.response(4)["result"]["state"], "paused");
+        client.send(
+            json!({ "jsonrpc": "2.0", "id": 5, "method": "start", "params": start_params(3) }),
+        );
+        assert_eq!(client.response(5)["error"]["code"], WRONG_STATE);
+
+        client.send(json!({ "jsonrpc": "2.0", "id": 6, "method": "resume" }));
+        assert_eq!(client.response(6)["result"], true);
+        client.send(json!({ "jsonrpc": "2.0", "id": 7, "method": "pause" }));
+        assert_eq!(client.response(7)["result"], true);
+
+        // a paused search can be cancelled
+        client.send(json!({ "jsonrpc": "2.0", "id": 8, "method": "cancel" }));
+        assert_eq!(client.response(8)["result"], true);
+        let finished = loop {
+            let message = client.next();
+            if message["method"] == "finished" {
+                break message["params"].clone();
+            }
+        };
+        assert_eq!(finished["state"], "cancelled");
+
+        drop(client.requests);
+        client.server.join().unwrap().unwrap();
+    }
+}
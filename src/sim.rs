@@ -0,0 +1,654 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+#[cfg(feature = "serde")]
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+};
+
+#[cfg(feature = "serde")]
+use anyhow::{anyhow, ensure};
+
+use crate::{
+    cache::{FitnessCache, ScramblerCache},
+    coverage::{Coverage, KeyCoverage},
+    dna::{self, Encoding},
+    engine::{Algorithm, CrossoverOp, Engine, FitnessFunction, Individual, MutationOp, Progress},
+    enigma::{self, Machine, Settings},
+    es::EvolutionStrategy,
+    gen::{self, Fitness},
+    genealogy::Node,
+    metrics::Metric,
+    pipeline,
+    pso::ParticleSwarm,
+    tempering::ParallelTempering,
+};
+#[cfg(feature = "serde")]
+use crate::{engine::Instant, report::RunReport, termination::StopSignal};
+
+// share of the time to the deadline left for the generation the deadline cuts short to breed
+// and reinsert after its evaluation stops
+#[cfg(feature = "serde")]
+const DEADLINE_MARGIN: u32 = 20;
+
+#[derive(Debug, Clone)]
+pub struct Generation {
+    pub iteration: u64,
+    pub average_fitness: Fitness,
+    pub median_fitness: Fitness,
+    pub fitness_std_dev: f64,
+    pub best_fitness: Fitness,
+    pub best_settings: Settings,
+    pub best_found_in: u64,
+    pub duration: Duration,
+    pub processing_time: Duration,
+    // see EngineOptions::sample_size
+    pub sample: Vec<Individual<Settings>>,
+    // see EngineOptions::top_size
+    pub top: Vec<Individual<Settings>>,
+    // see EngineOptions::genealogy
+    pub ancestry: Vec<Node<Settings>>,
+    // keys evaluated so far, with FitnessOptions::coverage
+    pub coverage: Option<KeyCoverage>,
+    // genomes scored 0 so far as no key could be built of them, and the first with its error
+    pub invalid_genomes: Option<(u64, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum StepResult {
+    Intermediate(Generation),
+    Final {
+        generation: Generation,
+        reason: String,
+    },
+}
+
+impl StepResult {
+    pub fn generation(&self) -> &Generation {
+        match self {
+            StepResult::Intermediate(generation) | StepResult::Final { generation, .. } => {
+                generation
+            }
+        }
+    }
+}
+
+pub(crate) type Step = Box<dyn FnMut() -> anyhow::Result<StepResult>>;
+
+/// Ciphertext pushed to a running simulation chunk by chunk, e.g. by the thread receiving an
+/// intercept group by group. The chunks are appended before the next generation, so every
+/// generation scores its keys on one text.
+#[derive(Debug, Clone, Default)]
+pub struct CiphertextFeed(Arc<Mutex<String>>);
+
+impl CiphertextFeed {
+    pub fn push(&self, chunk: &str) {
+        let mut pending = self.0.lock().expect("not poisoned");
+        // the groups of an intercept don't break words
+        pending.push_str(&enigma::normalize(chunk).replace(' ', ""));
+    }
+
+    fn take(&self) -> String {
+        mem::take(&mut *self.0.lock().expect("not poisoned"))
+    }
+}
+
+/// Genetic search over Enigma settings that is advanced one generation per `step` call,
+/// so callers can run their own logic between generations.
+pub struct Simulation {
+    step: Step,
+    finished: bool,
+    ciphertext: String,
+    termination: gen::TerminationOptions,
+    // shares the ciphertext and the caches with the search
+    fitness_calc: gen::FitnessCalc,
+    // recovers the rings of the best key the search held fixed
+    fixed_rings: bool,
+    feed: CiphertextFeed,
+    // canonical keys the search chooses from, for the coverage
+    keyspace: u128,
+}
+
+impl Simulation {
+    pub fn new(ciphertext: &str, mut opts: gen::Options) -> anyhow::Result<Self> {
+        opts.validate()?;
+        // the engines run until the criterion is met, checked after each generation
+        if opts.termination.criterion.is_some() {
+            opts.termination.generation_limit = u64::MAX;
+            opts.termination.time_limit = chrono::Duration::MAX;
+            opts.termination.target_fitness = Some(Fitness(f64::INFINITY));
+        }
+        let max_plugs = opts.engine.max_plugs;
+
+        let fitness_calc = gen::FitnessCalc {
+            ciphertext: gen::Ciphertext::new(ciphertext.to_string()),
+            cache: FitnessCache::with_backend(opts.fitness.cache_backend, opts.fitness.cache_size),
+            scramblers: ScramblerCache::new(opts.fitness.scrambler_cache_size),
+            coverage: Coverage::new(opts.fitness.coverage),
+            invalid: Default::default(),
+            metric: opts.fitness.metric.clone(),
+        };
+
+        let shared = fitness_calc.clone();
+        let step: Step = match (opts.engine.algorithm, opts.engine.encoding) {
+            (Algorithm::Ga, Encoding::Settings) => ga_step(
+                |opts| {
+                    Engine::new(
+                        fitness_calc.clone(),
+                        gen::SettingsBuilder {
+                            max_plugs,
+                            reflector: opts.engine.reflector.clone(),
+                            herivel_spread: opts.engine.herivel_spread,
+                            fixed_rings: opts.engine.fixed_rings,
+                        },
+                        gen::SettingsCrossover {
+                            mode: opts.engine.crossover,
+                            rotors: opts.engine.rotor_crossover,
+                            plugboard: opts.engine.plugboard_crossover,
+                        },
+                        gen::SettingsMutator::new(&opts.engine),
+                        opts,
+                    )
+                },
+                &opts,
+                Settings::clone,
+            )?,
+            (Algorithm::Ga, Encoding::FlatDna) => ga_step(
+                |opts| {
+                    Engine::new(
+                        dna::DnaFitnessCalc {
+                            inner: fitness_calc.clone(),
+                            max_plugs,
+                        },
+                        dna::DnaBuilder { max_plugs },
+                        dna::DnaCrossover,
+                        dna::DnaMutator {
+                            mutation_rate: opts.engine.mutation_rate,
+                        },
+                        opts,
+                    )
+                },
+                &opts,
+                move |genome: &Vec<u8>| dna::decode(genome, max_plugs),
+            )?,
+            (Algorithm::Es, _) => {
+                let mut es = EvolutionStrategy::new(fitness_calc, &opts)?;
+                progress_step(move || es.step(), Settings::clone)
+            }
+            (Algorithm::Pso, _) => {
+                let mut swarm = ParticleSwarm::new(fitness_calc, &opts)?;
+                progress_step(move || swarm.step(), Settings::clone)
+            }
+            #[cfg(feature = "genevo")]
+            (Algorithm::Genevo, _) => crate::compat::build_step(fitness_calc, &opts),
+            #[cfg(not(feature = "genevo"))]
+            (Algorithm::Genevo, _) => unreachable!("rejected by Options::validate"),
+        };
+
+        Ok(Self {
+            step,
+            finished: false,
+            ciphertext: ciphertext.to_string(),
+            termination: opts.termination,
+            fitness_calc: shared,
+            fixed_rings: opts.engine.fixed_rings,
+            feed: CiphertextFeed::default(),
+            keyspace: canonical_keyspace_size(&opts.engine),
+        })
+    }
+
+    /// A handle to push more ciphertext with while the simulation runs, so the search can start
+    /// on the first groups of an intercept.
+    pub fn feed(&self) -> CiphertextFeed {
+        self.feed.clone()
+    }
+
+    /// The ciphertext with what the feed brought in by the last generation.
+    pub fn ciphertext(&self) -> &str {
+        &self.ciphertext
+    }
+
+    /// Scores the following generations on another ciphertext, e.g. an intercept that grew
+    /// while the search ran; a longer text tells the right key apart more clearly. The genetic
+    /// engines score the population they carry over again.
+    pub fn replace_ciphertext(&mut self, ciphertext: &str) {
+        if ciphertext != self.ciphertext {
+            self.fitness_calc.replace_ciphertext(ciphertext.to_string());
+            self.ciphertext = ciphertext.to_string();
+        }
+    }
+
+    pub fn step(&mut self) -> anyhow::Result<StepResult> {
+        anyhow::ensure!(!self.finished, "simulation has already finished");
+        let arrived = self.feed.take();
+        if !arrived.is_empty() {
+            let ciphertext = self.ciphertext.clone() + &arrived;
+            self.replace_ciphertext(&ciphertext);
+        }
+
+        let result = match (self.step)().map(|step| self.with_counts(step)) {
+            Ok(StepResult::Intermediate(generation)) => {
+                let criterion = &self.termination.criterion;
+                let reason = match criterion.as_ref().and_then(|c| c.reason(&generation)) {
+                    Some(reason) => Some(reason),
+                    None => self.plaintext_stop_reason(&generation)?,
+                };
+                match reason {
+                    Some(reason) => Ok(StepResult::Final { generation, reason }),
+                    None => Ok(StepResult::Intermediate(generation)),
+                }
+            }
+            Ok(StepResult::Final { generation, reason }) => Ok(StepResult::Final {
+                generation: self.recover_rings(generation)?,
+                reason,
+            }),
+            result => result,
+        };
+        self.finished = !matches!(result, Ok(StepResult::Intermediate(_)));
+        result
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn with_counts(&self, mut step: StepResult) -> StepResult {
+        let (StepResult::Intermediate(generation) | StepResult::Final { generation, .. }) =
+            &mut step;
+        let coverage = &self.fitness_calc.coverage;
+        if coverage.is_enabled() {
+            generation.coverage = Some(KeyCoverage {
+                keys: coverage.keys(),
+                keyspace: self.keyspace,
+            });
+        }
+        generation.invalid_genomes = self.fitness_calc.invalid.get();
+        step
+    }
+
+    // the right ring, turned together with the right position, only moves where the middle
+    // rotor steps, so the sweep for the middle ring finds it with the search over
+    fn recover_rings(&self, mut generation: Generation) -> anyhow::Result<Generation> {
+        if !self.fixed_rings {
+            return Ok(generation);
+        }
+        let metric = self
+            .fitness_calc
+            .metric
+            .clone()
+            .unwrap_or(Metric::IndexOfCoincidence);
+        let (settings, _) =
+            pipeline::refine_rings(&self.ciphertext, generation.best_settings, &metric)?;
+        generation.best_fitness = self.fitness_calc.fitness_of(&settings);
+        generation.best_settings = settings;
+        Ok(generation)
+    }
+
+    // CTF mode, the engine only knows fitness, the decryption is checked here
+    fn plaintext_stop_reason(&self, generation: &Generation) -> anyhow::Result<Option<String>> {
+        let termination = &self.termination;
+        if termination.dictionary.is_empty() && termination.pattern.is_none() {
+            return Ok(None);
+        }
+        let plaintext = Machine::new(&generation.best_settings)?.decrypt(&self.ciphertext);
+
+        if let Some(found) = termination
+            .pattern
+            .as_ref()
+            .and_then(|pattern| pattern.find(&plaintext))
+        {
+            return Ok(Some(format!("pattern matched {}", found.as_str())));
+        }
+        let hits = termination.dictionary.hits(&plaintext);
+        if !termination.dictionary.is_empty() && hits >= termination.dictionary_hits {
+            return Ok(Some(format!("{} dictionary words found", hits)));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Cracks the ciphertext and returns by the deadline with the report of the best key found,
+/// for callers with a latency bound. The search runs on a thread of its own and is told to stop
+/// short of the deadline, part way through a generation if need be; should the generation it
+/// stops in still run late, the one before it is reported and the search cancelled after it. A
+/// search that ends earlier is reported as it ends, and the seed in the report repeats it.
+#[cfg(feature = "serde")]
+pub fn crack_within(
+    ciphertext: &str,
+    mut opts: gen::Options,
+    deadline: Instant,
+) -> anyhow::Result<RunReport> {
+    let budget = deadline.saturating_duration_since(Instant::now());
+    ensure!(!budget.is_zero(), "the deadline has passed");
+    let stop = deadline - budget / DEADLINE_MARGIN;
+    let termination = &mut opts.termination;
+    termination.deadline = Some(termination.deadline.map_or(stop, |d| d.min(stop)));
+    let seed = *opts.engine.seed.get_or_insert_with(rand::random);
+
+    let (sender, receiver) = mpsc::channel();
+    let cancel = StopSignal::default();
+    let (search, cancelled) = (ciphertext.to_string(), cancel.clone());
+    thread::spawn(move || {
+        let simulation = match Simulation::new(&search, opts) {
+            Ok(simulation) => simulation,
+            Err(err) => {
+                let _ = sender.send(Err(err));
+                return;
+            }
+        };
+        for step in simulation {
+            // the caller has gone at the deadline
+            if cancelled.is_stopped() || sender.send(step).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut last = None;
+    let found = loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(StepResult::Final { generation, reason })) => break Ok((generation, reason)),
+            Ok(Ok(StepResult::Intermediate(generation))) => last = Some(generation),
+            Ok(Err(err)) => break Err(err),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                break match last {
+                    Some(generation) => Ok((generation, "deadline reached".to_string())),
+                    None => Err(anyhow!("no generation finished by the deadline")),
+                }
+            }
+        }
+    };
+    cancel.stop();
+    let (generation, reason) = found?;
+    RunReport::new(ciphertext, Some(seed), &generation, &reason)
+}
+
+impl Iterator for Simulation {
+    type Item = anyhow::Result<StepResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            None
+        } else {
+            Some(self.step())
+        }
+    }
+}
+
+// a single engine, or islands of engines when tempering is configured
+fn ga_step<G, F, C, M>(
+    engine: impl Fn(&gen::Options) -> anyhow::Result<Engine<G, F, C, M>>,
+    opts: &gen::Options,
+    decode: impl Fn(&G) -> Settings + 'static,
+) -> anyhow::Result<Step>
+where
+    G: Clone + PartialEq + Send + Sync + 'static,
+    F: FitnessFunction<G> + Send + 'static,
+    C: CrossoverOp<G> + Send + 'static,
+    M: MutationOp<G> + Send + 'static,
+{
+    Ok(match &opts.engine.tempering {
+        Some(tempering) => {
+            let mut islands = ParallelTempering::new(tempering, opts, engine)?;
+            progress_step(move || islands.step(), decode)
+        }
+        None => {
+            let mut engine = engine(opts)?;
+            progress_step(move || engine.step(), decode)
+        }
+    })
+}
+
+fn progress_step<G: 'static>(
+    mut step: impl FnMut() -> Progress<G> + 'static,
+    decode: impl Fn(&G) -> Settings + 'static,
+) -> Step {
+    Box::new(move || {
+        let progress = step();
+        let decode_all = |individuals: Vec<Individual<G>>| {
+            individuals
+                .into_iter()
+                .map(|individual| Individual {
+                    rank: individual.rank,
+                    genome: decode(&individual.genome),
+                    fitness: individual.fitness,
+                })
+                .collect()
+        };
+        let generation = Generation {
+            iteration: progress.iteration,
+            average_fitness: progress.average_fitness,
+            median_fitness: progress.median_fitness,
+            fitness_std_dev: progress.fitness_std_dev,
+            best_fitness: progress.best_fitness,
+            best_settings: decode(&progress.best_genome),
+            best_found_in: progress.best_found_in,
+            duration: progress.duration,
+            processing_time: progress.processing_time,
+            sample: decode_all(progress.sample),
+            top: decode_all(progress.top),
+            coverage: None,
+            invalid_genomes: None,
+            ancestry: progress
+                .ancestry
+                .into_iter()
+                .map(|node| Node {
+                    genome: decode(&node.genome),
+                    id: node.id,
+                    generation: node.generation,
+                    parents: node.parents,
+                    operator: node.operator,
+                    fitness: node.fitness,
+                })
+                .collect(),
+        };
+
+        Ok(match progress.stop_reason {
+            Some(reason) => StepResult::Final { generation, reason },
+            None => StepResult::Intermediate(generation),
+        })
+    })
+}
+
+// keys the search chooses from, counted as canonical keys like the coverage: the left ring is
+// taken along by the left position
+fn canonical_keyspace_size(opts: &gen::EngineOptions) -> u128 {
+    let keys = if opts.fixed_rings {
+        enigma::fixed_rings_keyspace_size(opts.max_plugs)
+    } else {
+        enigma::keyspace_size(opts.max_plugs) / enigma::MAX_RING_SETTINGS_NUM as u128
+    };
+    keys * opts.reflector.choices()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{dictionary::Dictionary, language::Language, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_crack_within() {
+        let settings = "II V III / 08 05 20 / M C U".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 2_000;
+        opts.termination.generation_limit = u64::MAX;
+
+        // a search with no other limit runs until the deadline
+        let started = Instant::now();
+        let deadline = started + Duration::from_secs(1);
+        let cracked = crack_within(&ciphertext, opts.clone(), deadline).unwrap();
+        assert_eq!(cracked.reason, "deadline reached");
+        assert!(cracked.generations >= 1);
+        assert_eq!(
+            cracked.plaintext,
+            Machine::new(&cracked.settings)
+                .unwrap()
+                .decrypt(&ciphertext)
+        );
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // a search that ends earlier is returned as it ends
+        opts.engine.population_size = 200;
+        opts.engine.seed = Some(7);
+        opts.termination.generation_limit = 2;
+        let cracked = crack_within(&ciphertext, opts, started + Duration::from_secs(60));
+        let cracked = cracked.unwrap();
+        assert_eq!((cracked.generations, cracked.seed), (2, Some(7)));
+        assert!(cracked.reason.starts_with("generation limit"));
+        assert!(crack_within(&ciphertext, gen::Options::default(), started).is_err());
+    }
+
+    #[test]
+    fn test_plaintext_termination() {
+        let settings = "I II III / 01 01 01 / A A A".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(LONG_TEXT);
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 200;
+        opts.engine.seed = Some(1);
+        opts.termination.generation_limit = 3;
+
+        // a dictionary of words the demo text doesn't have
+        opts.termination.dictionary = Dictionary::new(["ZYXWV", "QQQQQ"]).unwrap();
+        let sim = Simulation::new(&ciphertext, opts.clone()).unwrap();
+        let last = sim.last().unwrap().unwrap();
+        assert!(matches!(&last, StepResult::Final { generation, reason }
+            if generation.iteration == 3 && reason.starts_with("generation limit")));
+
+        opts.termination.pattern = Some(regex::Regex::new("[A-Z]{3}").unwrap());
+        let mut sim = Simulation::new(&ciphertext, opts).unwrap();
+        match sim.step().unwrap() {
+            StepResult::Final { generation, reason } => {
+                assert_eq!(generation.iteration, 1);
+                assert!(reason.starts_with("pattern matched "));
+            }
+            StepResult::Intermediate(_) => panic!("the pattern should have stopped the search"),
+        }
+        assert!(sim.is_finished());
+    }
+
+    #[test]
+    fn test_recover_rings() {
+        let settings = "II V III / 01 05 20 / M C U".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..300]);
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 20;
+        opts.engine.seed = Some(1);
+        opts.engine.fixed_rings = true;
+        opts.fitness.metric = Some(Metric::Bigram(Language::English));
+        let mut sim = Simulation::new(&ciphertext, opts).unwrap();
+        let generation = sim.step().unwrap().generation().clone();
+        assert_eq!(generation.best_settings.ring_settings.0, 1);
+        assert_eq!(generation.best_settings.ring_settings.2, 1);
+
+        // the right ring at A with the right position turned along, as the search finds it
+        let found = Settings {
+            ring_settings: (1, 5, 1),
+            rotor_positions: (13, 3, 2),
+            ..settings.clone()
+        };
+        let recovered = sim.recover_rings(Generation {
+            best_settings: found,
+            ..generation
+        });
+        let recovered = recovered.unwrap();
+        let plaintext = Machine::new(&recovered.best_settings)
+            .unwrap()
+            .decrypt(&ciphertext);
+        assert_eq!(plaintext, LONG_TEXT[..300]);
+        assert_eq!(
+            recovered.best_fitness,
+            Fitness(Metric::Bigram(Language::English).score(&plaintext))
+        );
+    }
+
+    #[test]
+    fn test_explored_memory() {
+        let settings = "II V III / 08 05 20 / M C U".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..100]);
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 200;
+        opts.engine.seed = Some(3);
+        opts.termination.generation_limit = 30;
+        opts.fitness.coverage = true;
+        let coverage = |opts: &gen::Options| {
+            let sim = Simulation::new(&ciphertext, opts.clone()).unwrap();
+            let last = sim.last().unwrap().unwrap();
+            last.generation().coverage.unwrap()
+        };
+
+        // as many evaluations, fewer of them on keys seen before
+        let without = coverage(&opts);
+        opts.engine.explored_memory = 1 << 16;
+        let with = coverage(&opts);
+        assert!(with.keys > without.keys, "{with} against {without}");
+        assert_eq!(with.keyspace, enigma::keyspace_size(0) / 26);
+    }
+
+    #[test]
+    fn test_replace_ciphertext() {
+        let settings = "II V III / 08 05 20 / M C U / AQ BT"
+            .parse::<Settings>()
+            .unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..300]);
+        let metric = Metric::Bigram(Language::English);
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 50;
+        opts.engine.seed = Some(1);
+        opts.fitness.metric = Some(metric.clone());
+        opts.fitness.cache_size = 100;
+        let mut sim = Simulation::new(&ciphertext[..40], opts).unwrap();
+        sim.step().unwrap();
+
+        // the best so far and the population are scored on the whole intercept from now on
+        sim.replace_ciphertext(&ciphertext);
+        for _ in 0..2 {
+            let generation = sim.step().unwrap().generation().clone();
+            let machine = Machine::new(&generation.best_settings).unwrap();
+            let plaintext = machine.decrypt(&ciphertext);
+            assert_eq!(generation.best_fitness, Fitness(metric.score(&plaintext)));
+        }
+    }
+
+    #[test]
+    fn test_feed() {
+        let settings = "II V III / 08 05 20 / M C U".parse::<Settings>().unwrap();
+        let ciphertext = Machine::new(&settings).unwrap().encrypt(&LONG_TEXT[..200]);
+        let ciphertext = ciphertext.replace(' ', "");
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 50;
+        opts.engine.seed = Some(1);
+        let mut sim = Simulation::new(&ciphertext[..25], opts).unwrap();
+        sim.step().unwrap();
+
+        // the rest arrives in groups of five from another thread
+        let feed = sim.feed();
+        let rest = ciphertext[25..].to_string();
+        std::thread::spawn(move || {
+            for chunk in rest.as_bytes().chunks(50) {
+                let groups = chunk
+                    .chunks(5)
+                    .map(|group| std::str::from_utf8(group).unwrap());
+                feed.push(&groups.collect::<Vec<_>>().join(" "));
+            }
+        })
+        .join()
+        .unwrap();
+        assert_eq!(sim.ciphertext(), &ciphertext[..25]);
+        let generation = sim.step().unwrap().generation().clone();
+        assert_eq!(sim.ciphertext(), ciphertext);
+        let machine = Machine::new(&generation.best_settings).unwrap();
+        let plaintext = machine.decrypt(&ciphertext);
+        assert_eq!(
+            generation.best_fitness,
+            Fitness(gen::index_of_coincidence(&plaintext))
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, ensure};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    cache::{FitnessCache, ScramblerCache},
+    cipher::Cipher,
+    coverage::Coverage,
+    engine::{CrossoverOp, Engine, GenomeBuilder, MutationOp},
+    enigma::{normalize, LETTERS_NUM},
+    gen::{self, CipherFitness},
+    metrics::english_bigram_score,
+};
+
+const ALPHABET_LEN: usize = LETTERS_NUM as usize;
+
+// cipher alphabet: plaintext letter i is enciphered as letter key[i], A = 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubstitutionKey(pub [u8; ALPHABET_LEN]);
+
+impl SubstitutionKey {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut seen = [false; ALPHABET_LEN];
+        for &c in &self.0 {
+            ensure!(
+                c < LETTERS_NUM && !seen[c as usize],
+                "key must be a permutation of the alphabet, got {}",
+                self
+            );
+            seen[c as usize] = true;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SubstitutionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|&c| match c {
+            c if c < LETTERS_NUM => write!(f, "{}", (b'A' + c) as char),
+            _ => write!(f, "?"),
+        })
+    }
+}
+
+impl FromStr for SubstitutionKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let letters = s
+            .bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' | b'a'..=b'z' => Ok(c.to_ascii_uppercase() - b'A'),
+                _ => Err(anyhow!("key must only contain letters, got {:?}", s)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let key = SubstitutionKey(
+            letters
+                .try_into()
+                .map_err(|_| anyhow!("key must have {} letters, got {:?}", ALPHABET_LEN, s))?,
+        );
+        key.validate()?;
+
+        Ok(key)
+    }
+}
+
+pub struct Substitution {
+    encryption: [u8; ALPHABET_LEN],
+    decryption: [u8; ALPHABET_LEN],
+}
+
+impl Substitution {
+    fn apply(table: &[u8; ALPHABET_LEN], text: &str) -> String {
+        normalize(text)
+            .bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' => (b'A' + table[(c - b'A') as usize]) as char,
+                c => c as char,
+            })
+            .collect()
+    }
+}
+
+impl Cipher for Substitution {
+    type Key = SubstitutionKey;
+
+    const MODEL_NAME: &'static str = "Monoalphabetic substitution";
+
+    fn new(key: &SubstitutionKey) -> anyhow::Result<Self> {
+        key.validate()?;
+
+        let mut decryption = [0; ALPHABET_LEN];
+        for (p, &c) in key.0.iter().enumerate() {
+            decryption[c as usize] = p as u8;
+        }
+
+        Ok(Self {
+            encryption: key.0,
+            decryption,
+        })
+    }
+
+    fn encrypt(&self, text: &str) -> String {
+        Self::apply(&self.encryption, text)
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        Self::apply(&self.decryption, text)
+    }
+
+    // 26!
+    fn keyspace_size() -> u128 {
+        (1..=ALPHABET_LEN as u128).product()
+    }
+
+    // letter frequencies survive a substitution, so they can't score it, letter pairs can
+    fn score(plaintext: &str) -> f64 {
+        english_bigram_score(plaintext)
+    }
+}
+
+pub struct KeyBuilder;
+
+impl GenomeBuilder<SubstitutionKey> for KeyBuilder {
+    fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> SubstitutionKey {
+        let mut key = [0; ALPHABET_LEN];
+        key.iter_mut().enumerate().for_each(|(i, c)| *c = i as u8);
+        key.shuffle(rng);
+
+        SubstitutionKey(key)
+    }
+}
+
+// every letter takes its image from a parent chosen uniformly at random,
+// the letter holding that image is swapped so the child stays a permutation
+#[derive(Debug, Clone, Copy)]
+pub struct KeyCrossover;
+
+impl CrossoverOp<SubstitutionKey> for KeyCrossover {
+    fn crossover<R: Rng>(
+        &self,
+        parents: Vec<SubstitutionKey>,
+        rng: &mut R,
+    ) -> Vec<SubstitutionKey> {
+        let mut child = parents[0].0;
+
+        for i in 0..ALPHABET_LEN {
+            let image = parents.choose(rng).unwrap().0[i];
+            let j = child.iter().position(|&c| c == image).unwrap();
+            child.swap(i, j);
+        }
+
+        vec![SubstitutionKey(child)]
+    }
+}
+
+// swaps the images of random letter pairs, the number of swaps grows with mutation_rate
+#[derive(Debug, Clone, Copy)]
+pub struct KeyMutator {
+    pub mutation_rate: f64,
+}
+
+impl MutationOp<SubstitutionKey> for KeyMutator {
+    fn mutate<R: Rng>(&self, mut key: SubstitutionKey, rng: &mut R) -> SubstitutionKey {
+        let num_swaps = ((9_f64 * self.mutation_rate) + rng.gen::<f64>()).floor() as usize;
+
+        for _ in 0..num_swaps {
+            let (a, b) = (
+                rng.gen_range(0..ALPHABET_LEN),
+                rng.gen_range(0..ALPHABET_LEN),
+            );
+            key.0.swap(a, b);
+        }
+
+        key
+    }
+}
+
+// GA over cipher alphabets scored by English letter pairs
+pub fn solve(ciphertext: &str, opts: &gen::Options) -> anyhow::Result<SubstitutionKey> {
+    opts.validate()?;
+
+    let mut engine = Engine::new(
+        CipherFitness::<Substitution> {
+            ciphertext: gen::Ciphertext::new(normalize(ciphertext)),
+            cache: FitnessCache::with_backend(opts.fitness.cache_backend, opts.fitness.cache_size),
+            scramblers: ScramblerCache::new(0),
+            coverage: Coverage::default(),
+            invalid: Default::default(),
+            metric: opts.fitness.metric.clone(),
+        },
+        KeyBuilder,
+        KeyCrossover,
+        KeyMutator {
+            mutation_rate: opts.engine.mutation_rate,
+        },
+        opts,
+    )?;
+
+    Ok(engine.run().best_genome)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{engine::FitnessFunction, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    const KEY: &str = "QWERTYUIOPASDFGHJKLZXCVBNM";
+
+    #[test]
+    fn test_cipher() {
+        let key = KEY.parse::<SubstitutionKey>().unwrap();
+        assert_eq!(key.to_string(), KEY);
+        assert!("QWERTY".parse::<SubstitutionKey>().is_err());
+        assert!("QQERTYUIOPASDFGHJKLZXCVBNM"
+            .parse::<SubstitutionKey>()
+            .is_err());
+
+        let cipher = Substitution::new(&key).unwrap();
+        assert_eq!(cipher.encrypt("Hello world"), "ITSSG VGKSR");
+        assert_eq!(cipher.decrypt(&cipher.encrypt(LONG_TEXT)), LONG_TEXT);
+        assert_eq!(
+            Substitution::keyspace_size(),
+            403_291_461_126_605_635_584_000_000
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        let mut rng = rand::thread_rng();
+        let m = KeyMutator { mutation_rate: 0.5 };
+
+        for _ in 0..1000 {
+            let parents = (0..3)
+                .map(|_| KeyBuilder.build_genome(0, &mut rng))
+                .collect::<Vec<_>>();
+
+            for child in KeyCrossover.crossover(parents, &mut rng) {
+                assert!(child.validate().is_ok());
+                assert!(m.mutate(child, &mut rng).validate().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve() {
+        let key = KEY.parse::<SubstitutionKey>().unwrap();
+        let ciphertext = Substitution::new(&key).unwrap().encrypt(LONG_TEXT);
+
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 1000;
+        opts.engine.seed = Some(7);
+        opts.fitness.cache_size = 100_000;
+
+        let found = solve(&ciphertext, &opts).unwrap();
+        let calc = CipherFitness::<Substitution> {
+            ciphertext: gen::Ciphertext::new(ciphertext.clone()),
+            cache: FitnessCache::new(0),
+            scramblers: ScramblerCache::new(0),
+            coverage: Coverage::default(),
+            invalid: Default::default(),
+            metric: None,
+        };
+        assert!(calc.fitness_of(&found) >= calc.fitness_of(&key));
+
+        // letters missing from the text may be mapped anywhere
+        let plaintext = Substitution::new(&found).unwrap().decrypt(&ciphertext);
+        assert_eq!(plaintext, LONG_TEXT);
+    }
+}
@@ -0,0 +1,240 @@
+// OpenTelemetry instrumentation of the search, it goes to the global providers installed by
+// `init`; without the otel feature, or before `init`, everything here is a no-op
+use std::time::Duration;
+
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    global,
+    trace::{TraceContextExt, Tracer},
+    Context, ContextGuard, KeyValue,
+};
+
+#[cfg(feature = "otel")]
+const SCOPE: &str = "enigmagen-rs";
+
+/// A span that is the parent of the spans started while it's alive, ended when dropped.
+/// Must be dropped on the thread it was started on.
+pub(crate) struct Span {
+    #[cfg(feature = "otel")]
+    cx: Context,
+    #[cfg(feature = "otel")]
+    _guard: ContextGuard,
+}
+
+impl Span {
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    pub(crate) fn start(name: &'static str) -> Self {
+        #[cfg(feature = "otel")]
+        {
+            let span = global::tracer(SCOPE).start(name);
+            let cx = Context::current_with_span(span);
+            Span {
+                _guard: cx.clone().attach(),
+                cx,
+            }
+        }
+        #[cfg(not(feature = "otel"))]
+        Span {}
+    }
+
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    pub(crate) fn set_u64(&self, key: &'static str, value: u64) {
+        #[cfg(feature = "otel")]
+        self.cx
+            .span()
+            .set_attribute(KeyValue::new(key, value as i64));
+    }
+
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+    pub(crate) fn set_f64(&self, key: &'static str, value: f64) {
+        #[cfg(feature = "otel")]
+        self.cx.span().set_attribute(KeyValue::new(key, value));
+    }
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.cx.span().end();
+    }
+}
+
+// metrics of a finished generation
+#[cfg_attr(not(feature = "otel"), allow(unused_variables))]
+pub(crate) fn record_generation(
+    evaluations: u64,
+    duration: Duration,
+    best_fitness: f64,
+    average_fitness: f64,
+) {
+    #[cfg(feature = "otel")]
+    {
+        use std::sync::OnceLock;
+
+        use opentelemetry::metrics::{Counter, Gauge, Histogram};
+
+        struct Instruments {
+            generations: Counter<u64>,
+            evaluations: Counter<u64>,
+            duration: Histogram<f64>,
+            best_fitness: Gauge<f64>,
+            average_fitness: Gauge<f64>,
+        }
+        static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+
+        // instruments bind to the provider when they're created, so only after `init`
+        let instruments = INSTRUMENTS.get_or_init(|| {
+            let meter = global::meter(SCOPE);
+            Instruments {
+                generations: meter.u64_counter("enigmagen.generations").build(),
+                evaluations: meter
+                    .u64_counter("enigmagen.evaluations")
+                    .with_description("genomes evaluated, cache hits included")
+                    .build(),
+                duration: meter
+                    .f64_histogram("enigmagen.generation.duration")
+                    .with_unit("s")
+                    .build(),
+                best_fitness: meter.f64_gauge("enigmagen.fitness.best").build(),
+                average_fitness: meter.f64_gauge("enigmagen.fitness.average").build(),
+            }
+        });
+
+        instruments.generations.add(1, &[]);
+        instruments.evaluations.add(evaluations, &[]);
+        instruments.duration.record(duration.as_secs_f64(), &[]);
+        instruments.best_fitness.record(best_fitness, &[]);
+        instruments.average_fitness.record(average_fitness, &[]);
+    }
+}
+
+/// Installs global providers exporting spans and metrics over OTLP/HTTP. The collector is
+/// configured with the standard variables, OTEL_EXPORTER_OTLP_ENDPOINT (http://localhost:4318
+/// by default), OTEL_EXPORTER_OTLP_HEADERS and so on. Dropping the guard flushes and shuts
+/// the exporters down.
+#[cfg(feature = "otel")]
+pub fn init() -> anyhow::Result<TelemetryGuard> {
+    use opentelemetry_otlp::{MetricExporter, SpanExporter};
+    use opentelemetry_sdk::{metrics::SdkMeterProvider, trace::SdkTracerProvider, Resource};
+
+    let resource = Resource::builder().with_service_name(SCOPE).build();
+    let tracer_provider = SdkTracerProvider::builder()
+        .with_batch_exporter(SpanExporter::builder().with_http().build()?)
+        .with_resource(resource.clone())
+        .build();
+    let meter_provider = SdkMeterProvider::builder()
+        .with_periodic_exporter(MetricExporter::builder().with_http().build()?)
+        .with_resource(resource)
+        .build();
+
+    global::set_tracer_provider(tracer_provider.clone());
+    global::set_meter_provider(meter_provider.clone());
+
+    Ok(TelemetryGuard {
+        tracer_provider,
+        meter_provider,
+    })
+}
+
+#[cfg(feature = "otel")]
+pub struct TelemetryGuard {
+    tracer_provider: opentelemetry_sdk::trace::SdkTracerProvider,
+    meter_provider: opentelemetry_sdk::metrics::SdkMeterProvider,
+}
+
+#[cfg(feature = "otel")]
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.tracer_provider.shutdown() {
+            eprintln!("exporting spans failed: {}", err);
+        }
+        if let Err(err) = self.meter_provider.shutdown() {
+            eprintln!("exporting metrics failed: {}", err);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use opentelemetry_sdk::{
+        error::OTelSdkResult,
+        trace::{SdkTracerProvider, SpanData, SpanExporter},
+    };
+
+    use crate::{engine::Engine, gen, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[derive(Debug, Clone, Default)]
+    struct Collector(Arc<Mutex<Vec<SpanData>>>);
+
+    impl SpanExporter for Collector {
+        async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+            self.0.lock().unwrap().extend(batch);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let collector = Collector::default();
+        global::set_tracer_provider(
+            SdkTracerProvider::builder()
+                .with_simple_exporter(collector.clone())
+                .build(),
+        );
+
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 100;
+        opts.engine.seed = Some(1);
+        let mut engine = Engine::new(
+            gen::FitnessCalc {
+                ciphertext: gen::Ciphertext::new(LONG_TEXT.to_string()),
+                cache: crate::cache::FitnessCache::new(0),
+                scramblers: crate::cache::ScramblerCache::new(0),
+                coverage: crate::coverage::Coverage::default(),
+                invalid: Default::default(),
+                metric: None,
+            },
+            gen::SettingsBuilder {
+                max_plugs: 0,
+                reflector: Default::default(),
+                herivel_spread: None,
+                fixed_rings: false,
+            },
+            gen::SettingsCrossover::default(),
+            gen::SettingsMutator {
+                mutation_rate: 0.05,
+                max_plugs: 0,
+                search_reflector: false,
+                fixed_rings: false,
+                weights: Default::default(),
+            },
+            &opts,
+        )
+        .unwrap();
+        engine.step();
+        engine.step();
+
+        // other tests may run engines at the same time, this one is told apart by its population
+        let spans = collector.0.lock().unwrap().clone();
+        let generations = spans
+            .iter()
+            .filter(|s| s.name == "generation")
+            .filter(|s| {
+                s.attributes
+                    .iter()
+                    .any(|kv| kv.key.as_str() == "population_size" && kv.value.as_str() == "100")
+            })
+            .collect::<Vec<_>>();
+        assert!(generations.len() >= 2);
+        for name in ["evaluate", "breed"] {
+            assert!(spans.iter().any(|s| s.name == name
+                && generations
+                    .iter()
+                    .any(|g| g.span_context.span_id() == s.parent_span_id)));
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::ensure;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use crate::{
+    engine::{CrossoverOp, Engine, FitnessFunction, MutationOp, Progress},
+    gen,
+};
+
+/// Islands at a ladder of temperatures: the coldest runs the configured mutation rate and
+/// selection, hotter ones mutate more and select less, up to max_temperature times as much.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempering {
+    pub islands: usize,
+    pub max_temperature: f64,
+    // generations between swap attempts of adjacent islands
+    pub swap_interval: u64,
+}
+
+impl Tempering {
+    pub fn validate(&self, opts: &gen::Options) -> anyhow::Result<()> {
+        ensure!(self.islands >= 2, "tempering needs at least 2 islands");
+        ensure!(
+            self.max_temperature >= 1.0,
+            "max_temperature must be at least 1"
+        );
+        ensure!(self.swap_interval > 0, "swap_interval must be positive");
+        ensure!(
+            opts.engine.population_size / self.islands
+                >= opts.engine.num_individuals_per_parents.max(2),
+            "population_size is too small for {} islands",
+            self.islands
+        );
+        Ok(())
+    }
+
+    // geometric from 1 for the coldest island to max_temperature for the hottest
+    pub fn temperatures(&self) -> Vec<f64> {
+        let steps = (self.islands - 1).max(1) as f64;
+        (0..self.islands)
+            .map(|i| self.max_temperature.powf(i as f64 / steps))
+            .collect()
+    }
+
+    // the options of an island: its share of the population, its own seed and the mutation
+    // rate and selection ratio scaled by its temperature
+    pub fn island_options(&self, opts: &gen::Options, island: usize) -> gen::Options {
+        let temperature = self.temperatures()[island];
+        let mut opts = opts.clone();
+        let engine = &mut opts.engine;
+        engine.population_size /= self.islands;
+        for size in engine.population_schedule.sizes_mut() {
+            *size = (*size / self.islands).max(2);
+        }
+        engine.mutation_rate = (engine.mutation_rate * temperature).min(1.0);
+        engine.selection_ratio = (engine.selection_ratio * temperature).min(1.0);
+        engine.seed = engine.seed.map(|seed| seed.wrapping_add(island as u64));
+        opts
+    }
+}
+
+impl Default for Tempering {
+    fn default() -> Self {
+        Self {
+            islands: 4,
+            max_temperature: 4.0,
+            swap_interval: 5,
+        }
+    }
+}
+
+impl fmt::Display for Tempering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} islands up to temperature {}, swaps every {} generations",
+            self.islands, self.max_temperature, self.swap_interval
+        )
+    }
+}
+
+// <islands>:<max temperature>:<swap interval>, or just the islands with the other defaults
+impl FromStr for Tempering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid tempering {s}, expected e.g. 4 or 4:4.0:5");
+        let mut parts = s.split(':');
+        let mut tempering = Tempering {
+            islands: parts
+                .next()
+                .unwrap_or_default()
+                .parse()
+                .map_err(|_| invalid())?,
+            ..Default::default()
+        };
+        if let Some(max_temperature) = parts.next() {
+            tempering.max_temperature = max_temperature.parse().map_err(|_| invalid())?;
+        }
+        if let Some(swap_interval) = parts.next() {
+            tempering.swap_interval = swap_interval.parse().map_err(|_| invalid())?;
+        }
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(tempering)
+    }
+}
+
+/// Parallel tempering over GA islands: every island steps a generation, and every
+/// swap_interval generations adjacent islands exchange their populations by the Metropolis
+/// criterion, so a good population found by a hot island moves down to be refined by the
+/// colder ones while the hot ones keep exploring.
+pub struct ParallelTempering<G, F, C, M> {
+    // coldest first
+    islands: Vec<Engine<G, F, C, M>>,
+    temperatures: Vec<f64>,
+    swap_interval: u64,
+    rng: ChaCha12Rng,
+    generation: u64,
+    // accepted and attempted swaps of island i and i + 1
+    swaps: Vec<(u64, u64)>,
+}
+
+impl<G, F, C, M> ParallelTempering<G, F, C, M>
+where
+    G: Clone + PartialEq + Send + Sync,
+    F: FitnessFunction<G> + Send,
+    C: CrossoverOp<G> + Send,
+    M: MutationOp<G> + Send,
+{
+    // island builds the engine of an island from its options, see Tempering::island_options
+    pub fn new(
+        tempering: &Tempering,
+        opts: &gen::Options,
+        island: impl Fn(&gen::Options) -> anyhow::Result<Engine<G, F, C, M>>,
+    ) -> anyhow::Result<Self> {
+        tempering.validate(opts)?;
+        let islands = (0..tempering.islands)
+            .map(|i| island(&tempering.island_options(opts, i)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let seed = opts.engine.seed.unwrap_or_else(rand::random);
+
+        Ok(Self {
+            islands,
+            temperatures: tempering.temperatures(),
+            swap_interval: tempering.swap_interval,
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            generation: 0,
+            swaps: vec![(0, 0); tempering.islands - 1],
+        })
+    }
+
+    /// Steps every island a generation, the progress is the one of the island with the best
+    /// solution found so far and stops as soon as an island does.
+    pub fn step(&mut self) -> Progress<G> {
+        let mut progress = self
+            .islands
+            .iter_mut()
+            .map(|island| island.step())
+            .collect::<Vec<_>>();
+        self.generation += 1;
+
+        if self.generation.is_multiple_of(self.swap_interval) {
+            self.swap(&progress);
+        }
+
+        let stop_reason = progress.iter().find_map(|p| p.stop_reason.clone());
+        let best = (0..progress.len())
+            .max_by(|&a, &b| progress[a].best_fitness.cmp(&progress[b].best_fitness))
+            .unwrap_or_default();
+        let mut best = progress.swap_remove(best);
+        best.stop_reason = stop_reason;
+        best
+    }
+
+    // steps until a termination condition is met
+    pub fn run(&mut self) -> Progress<G> {
+        loop {
+            let progress = self.step();
+            if progress.stop_reason.is_some() {
+                return progress;
+            }
+        }
+    }
+
+    pub fn temperatures(&self) -> &[f64] {
+        &self.temperatures
+    }
+
+    // fraction of the attempted swaps of island i and i + 1 that were accepted
+    pub fn swap_rates(&self) -> Vec<f64> {
+        self.swaps
+            .iter()
+            .map(|&(accepted, attempted)| accepted as f64 / attempted.max(1) as f64)
+            .collect()
+    }
+
+    // even pairs on one attempt and odd pairs on the next so that no island swaps twice;
+    // fitness differences are measured in the spread of the two populations
+    fn swap(&mut self, progress: &[Progress<G>]) {
+        let first = (self.generation / self.swap_interval % 2) as usize;
+        for cold in (first..self.islands.len() - 1).step_by(2) {
+            let hot = cold + 1;
+            let spread = progress[cold].fitness_std_dev + progress[hot].fitness_std_dev;
+            let gain = self.islands[hot].current_best().0 - self.islands[cold].current_best().0;
+            let beta = 1.0 / self.temperatures[cold] - 1.0 / self.temperatures[hot];
+            let log_acceptance = gain / spread.max(f64::EPSILON) * beta;
+
+            self.swaps[cold].1 += 1;
+            if log_acceptance >= 0.0 || self.rng.gen::<f64>() < log_acceptance.exp() {
+                self.swaps[cold].0 += 1;
+                let (colder, hotter) = self.islands.split_at_mut(hot);
+                colder[cold].swap_population(&mut hotter[0]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use crate::{engine::tests::OneMax, gen::Fitness};
+
+    use super::*;
+
+    fn one_max(
+        tempering: &Tempering,
+        opts: &gen::Options,
+    ) -> ParallelTempering<u32, OneMax, OneMax, OneMax> {
+        ParallelTempering::new(tempering, opts, |opts| {
+            Engine::new(OneMax, OneMax, OneMax, OneMax, opts)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tempering() {
+        let tempering = "4:8:2".parse::<Tempering>().unwrap();
+        assert_eq!(
+            tempering,
+            Tempering {
+                islands: 4,
+                max_temperature: 8.0,
+                swap_interval: 2
+            }
+        );
+        assert_eq!("3".parse::<Tempering>().unwrap().islands, 3);
+        assert!("4:8:2:1".parse::<Tempering>().is_err());
+
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 400;
+        opts.engine.seed = Some(3);
+        opts.termination.generation_limit = 100;
+        for (temperature, expected) in tempering.temperatures().iter().zip([1.0, 2.0, 4.0, 8.0]) {
+            assert_relative_eq!(*temperature, expected, epsilon = 1e-9);
+        }
+        let hottest = tempering.island_options(&opts, 3);
+        assert_eq!(hottest.engine.population_size, 100);
+        assert_relative_eq!(hottest.engine.mutation_rate, 0.4);
+        assert_relative_eq!(hottest.engine.selection_ratio, 1.0);
+
+        let run = || {
+            let mut pt = one_max(&tempering, &opts);
+            (pt.run(), pt.swap_rates())
+        };
+        let (progress, swap_rates) = run();
+        assert_eq!(progress.best_fitness, Fitness(1.0));
+        assert_eq!(progress.best_genome, u32::MAX);
+        assert_eq!(swap_rates.len(), 3);
+        assert!(swap_rates.iter().all(|rate| (0.0..=1.0).contains(rate)));
+        assert_eq!(run().1, swap_rates);
+
+        let too_many = Tempering {
+            islands: 300,
+            ..tempering
+        };
+        assert!(too_many.validate(&opts).is_err());
+    }
+}
@@ -0,0 +1,432 @@
+// stop conditions of a simulation composed by the caller: limits, stagnation, convergence of the
+// population, a stop from outside or a closure, joined by AND and OR, e.g.
+// stagnation(50).and(generation_limit(200)).or(fitness_limit(Fitness(0.07)))
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail};
+
+use crate::{engine::Individual, enigma::Settings, gen::Fitness, sim::Generation};
+
+/// Ends a simulation from outside, e.g. from a signal handler or another thread, after the
+/// generation running. Clones stop the same simulation.
+#[derive(Debug, Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+// an endpoint is asked this often, on a thread of its own
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Ends a run from another process or machine without signals: when a sentinel file appears,
+/// or when a plain HTTP endpoint answers `stop` to a GET. The endpoint is asked on a thread of
+/// its own from the first check on, so a slow one doesn't hold the search up; one that can't
+/// be reached doesn't stop the run.
+#[derive(Debug, Clone)]
+pub enum StopTrigger {
+    File(PathBuf),
+    Http {
+        // host:port
+        address: String,
+        path: String,
+        poll: Arc<HttpPoll>,
+    },
+}
+
+// what the endpoint last answered; the thread asking it ends with the last clone of the trigger
+#[derive(Debug, Default)]
+pub struct HttpPoll {
+    started: AtomicBool,
+    stop: AtomicBool,
+}
+
+impl StopTrigger {
+    pub fn is_triggered(&self) -> bool {
+        match self {
+            StopTrigger::File(path) => path.exists(),
+            StopTrigger::Http {
+                address,
+                path,
+                poll,
+            } => {
+                if !poll.started.swap(true, Ordering::Relaxed) {
+                    let (address, path) = (address.clone(), path.clone());
+                    let poll = Arc::downgrade(poll);
+                    thread::spawn(move || {
+                        while let Some(poll) = poll.upgrade() {
+                            let stop = http_get(&address, &path)
+                                .is_ok_and(|body| body.trim().eq_ignore_ascii_case("stop"));
+                            poll.stop.store(stop, Ordering::Relaxed);
+                            drop(poll);
+                            thread::sleep(POLL_INTERVAL);
+                        }
+                    });
+                }
+                poll.stop.load(Ordering::Relaxed)
+            }
+        }
+    }
+}
+
+// a path, or an http:// URL
+impl FromStr for StopTrigger {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("https://") {
+            return Err(format!("{s}: only plain http endpoints are supported"));
+        }
+        let Some(url) = s.strip_prefix("http://") else {
+            return Ok(StopTrigger::File(PathBuf::from(s)));
+        };
+        let (host, path) = url.split_at(url.find('/').unwrap_or(url.len()));
+        if host.is_empty() {
+            return Err(format!("{s}: no host"));
+        }
+        let address = match host.contains(':') {
+            true => host.to_string(),
+            false => format!("{host}:80"),
+        };
+        Ok(StopTrigger::Http {
+            address,
+            path: if path.is_empty() { "/" } else { path }.to_string(),
+            poll: Arc::default(),
+        })
+    }
+}
+
+impl fmt::Display for StopTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopTrigger::File(path) => write!(f, "stop file {}", path.display()),
+            StopTrigger::Http { address, path, .. } => write!(f, "http://{address}{path}"),
+        }
+    }
+}
+
+// the body of a 2xx answer
+fn http_get(address: &str, path: &str) -> anyhow::Result<String> {
+    let socket = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("{address} doesn't resolve"))?;
+    let mut stream = TcpStream::connect_timeout(&socket, HTTP_TIMEOUT)?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT))?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT))?;
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    let request = format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .ok_or_else(|| anyhow!("malformed response from {address}"))?;
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        bail!("{address}{path} answered {status}");
+    }
+    Ok(body.to_string())
+}
+
+#[derive(Clone)]
+pub enum Criterion {
+    GenerationLimit(u64),
+    TimeLimit(Duration),
+    FitnessLimit(Fitness),
+    // generations without a better key
+    Stagnation(u64),
+    // the sampled keys (or the best ones without a sample) down to this many bits of entropy,
+    // the population converged
+    Entropy(f64),
+    Signal(StopSignal),
+    Trigger(StopTrigger),
+    Custom(String, Arc<dyn Fn(&Generation) -> bool + Send + Sync>),
+    All(Vec<Criterion>),
+    Any(Vec<Criterion>),
+}
+
+pub fn generation_limit(generations: u64) -> Criterion {
+    Criterion::GenerationLimit(generations)
+}
+
+pub fn time_limit(limit: Duration) -> Criterion {
+    Criterion::TimeLimit(limit)
+}
+
+pub fn fitness_limit(fitness: Fitness) -> Criterion {
+    Criterion::FitnessLimit(fitness)
+}
+
+pub fn stagnation(generations: u64) -> Criterion {
+    Criterion::Stagnation(generations)
+}
+
+pub fn entropy(bits: f64) -> Criterion {
+    Criterion::Entropy(bits)
+}
+
+pub fn signal(signal: &StopSignal) -> Criterion {
+    Criterion::Signal(signal.clone())
+}
+
+pub fn trigger(trigger: StopTrigger) -> Criterion {
+    Criterion::Trigger(trigger)
+}
+
+// met when the closure says so, the name is the reason told
+pub fn custom(name: &str, met: impl Fn(&Generation) -> bool + Send + Sync + 'static) -> Criterion {
+    Criterion::Custom(name.to_string(), Arc::new(met))
+}
+
+impl Criterion {
+    // met when both are
+    pub fn and(self, other: Criterion) -> Criterion {
+        match self {
+            Criterion::All(mut all) => {
+                all.push(other);
+                Criterion::All(all)
+            }
+            criterion => Criterion::All(vec![criterion, other]),
+        }
+    }
+
+    // met when either is
+    pub fn or(self, other: Criterion) -> Criterion {
+        match self {
+            Criterion::Any(mut any) => {
+                any.push(other);
+                Criterion::Any(any)
+            }
+            criterion => Criterion::Any(vec![criterion, other]),
+        }
+    }
+
+    /// Why the simulation stops after the generation, None if it goes on.
+    pub fn reason(&self, generation: &Generation) -> Option<String> {
+        match self {
+            Criterion::GenerationLimit(limit) => (generation.iteration >= *limit)
+                .then(|| format!("generation limit of {} reached", limit)),
+            Criterion::TimeLimit(limit) => (generation.duration >= *limit)
+                .then(|| format!("time limit of {:?} reached", limit)),
+            Criterion::FitnessLimit(limit) => (generation.best_fitness >= *limit)
+                .then(|| format!("fitness limit of {} reached", limit)),
+            Criterion::Stagnation(generations) => {
+                let since = generation
+                    .iteration
+                    .saturating_sub(generation.best_found_in);
+                (since >= *generations).then(|| format!("no better key for {} generations", since))
+            }
+            Criterion::Entropy(bits) => {
+                let individuals = match generation.sample.is_empty() {
+                    true => &generation.top,
+                    false => &generation.sample,
+                };
+                let entropy = key_entropy(individuals)?;
+                (entropy <= *bits).then(|| format!("population converged to {:.2} bits", entropy))
+            }
+            Criterion::Signal(signal) => signal.is_stopped().then(|| "stopped".to_string()),
+            Criterion::Trigger(trigger) => trigger
+                .is_triggered()
+                .then(|| format!("stop requested by {trigger}")),
+            Criterion::Custom(name, met) => met(generation).then(|| name.clone()),
+            Criterion::All(all) => all
+                .iter()
+                .map(|criterion| criterion.reason(generation))
+                .collect::<Option<Vec<_>>>()
+                .filter(|reasons| !reasons.is_empty())
+                .map(|reasons| reasons.join(" and ")),
+            Criterion::Any(any) => any
+                .iter()
+                .find_map(|criterion| criterion.reason(generation)),
+        }
+    }
+}
+
+impl fmt::Debug for Criterion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Criterion::GenerationLimit(limit) => write!(f, "GenerationLimit({limit})"),
+            Criterion::TimeLimit(limit) => write!(f, "TimeLimit({limit:?})"),
+            Criterion::FitnessLimit(limit) => write!(f, "FitnessLimit({limit})"),
+            Criterion::Stagnation(generations) => write!(f, "Stagnation({generations})"),
+            Criterion::Entropy(bits) => write!(f, "Entropy({bits})"),
+            Criterion::Signal(signal) => write!(f, "Signal({})", signal.is_stopped()),
+            Criterion::Trigger(trigger) => write!(f, "Trigger({trigger})"),
+            Criterion::Custom(name, _) => write!(f, "Custom({name:?})"),
+            Criterion::All(all) => f.debug_tuple("All").field(all).finish(),
+            Criterion::Any(any) => f.debug_tuple("Any").field(any).finish(),
+        }
+    }
+}
+
+// Shannon entropy in bits of the keys that encrypt alike, None without keys
+fn key_entropy(individuals: &[Individual<Settings>]) -> Option<f64> {
+    if individuals.is_empty() {
+        return None;
+    }
+    let mut counts = HashMap::new();
+    for individual in individuals {
+        *counts.entry(individual.genome.canonicalize()).or_insert(0) += 1;
+    }
+    let n = individuals.len() as f64;
+    Some(
+        counts
+            .values()
+            .map(|&count| {
+                let p = count as f64 / n;
+                p * (1.0 / p).log2()
+            })
+            .sum(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_criterion() {
+        let key = |key: &str| key.parse::<Settings>().unwrap();
+        let individuals = |keys: &[&str]| {
+            keys.iter()
+                .enumerate()
+                .map(|(rank, k)| Individual {
+                    rank,
+                    genome: key(k),
+                    fitness: Fitness(0.05),
+                })
+                .collect::<Vec<_>>()
+        };
+        let generation = Generation {
+            iteration: 60,
+            average_fitness: Fitness(0.04),
+            median_fitness: Fitness(0.04),
+            fitness_std_dev: 0.01,
+            best_fitness: Fitness(0.05),
+            best_settings: key("II V III / 01 01 01 / A B C"),
+            best_found_in: 20,
+            duration: Duration::from_secs(30),
+            processing_time: Duration::from_secs(30),
+            sample: individuals(&["I II III / 01 01 01 / A B C"; 4]),
+            top: vec![],
+            ancestry: vec![],
+            coverage: None,
+            invalid_genomes: None,
+        };
+
+        let stop = stagnation(50).and(generation_limit(100));
+        assert_eq!(stop.reason(&generation), None);
+        let stop = stop.or(fitness_limit(Fitness(0.05)));
+        assert_eq!(
+            stop.reason(&generation).unwrap(),
+            "fitness limit of 0.05 reached"
+        );
+        let stop = stagnation(40).and(time_limit(Duration::from_secs(20)));
+        assert_eq!(
+            stop.reason(&generation).unwrap(),
+            "no better key for 40 generations and time limit of 20s reached"
+        );
+
+        // four copies of a key have no entropy, four different keys two bits
+        assert_eq!(
+            entropy(0.0).reason(&generation).unwrap(),
+            "population converged to 0.00 bits"
+        );
+        let spread = Generation {
+            sample: individuals(&[
+                "I II III / 01 01 01 / A B C",
+                "I II III / 01 01 01 / A B D",
+                "I II III / 01 01 01 / A B E",
+                "I II III / 01 01 01 / A B F",
+            ]),
+            ..generation.clone()
+        };
+        assert_eq!(key_entropy(&spread.sample), Some(2.0));
+        assert_eq!(entropy(1.0).reason(&spread), None);
+
+        let signal_handle = StopSignal::default();
+        let stop = signal(&signal_handle).or(custom("found a crib", |g| g.iteration > 100));
+        assert_eq!(stop.reason(&generation), None);
+        signal_handle.stop();
+        assert_eq!(stop.reason(&generation).unwrap(), "stopped");
+        assert_eq!(
+            custom("found a crib", |g| g.iteration == 60)
+                .reason(&generation)
+                .unwrap(),
+            "found a crib"
+        );
+        assert_eq!(Criterion::All(vec![]).reason(&generation), None);
+    }
+
+    #[test]
+    fn test_stop_trigger() {
+        use std::{fs, net::TcpListener};
+
+        use crate::engine::Instant;
+
+        let path = std::env::temp_dir().join(format!("stop-{}", std::process::id()));
+        let file = path.to_str().unwrap().parse::<StopTrigger>().unwrap();
+        assert!(!file.is_triggered());
+        fs::write(&path, "").unwrap();
+        assert!(file.is_triggered());
+        fs::remove_file(&path).unwrap();
+
+        // answers "go on" to the first request and "stop" to the next
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            for body in ["go on", "stop\n"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend(&buf[..n]);
+                }
+                assert!(request.starts_with(b"GET /control HTTP/1.0\r\n"));
+                write!(stream, "HTTP/1.0 200 OK\r\n\r\n{body}").unwrap();
+            }
+        });
+        let url = format!("http://{address}/control")
+            .parse::<StopTrigger>()
+            .unwrap();
+        // asked in the background, the check doesn't wait for the answer
+        let started = Instant::now();
+        assert!(!url.is_triggered());
+        assert!(started.elapsed() < HTTP_TIMEOUT);
+        while !url.is_triggered() {
+            assert!(started.elapsed() < 10 * POLL_INTERVAL);
+            thread::sleep(Duration::from_millis(10));
+        }
+        server.join().unwrap();
+        assert_eq!(url.to_string(), format!("http://{address}/control"));
+
+        assert!("https://example.com/stop".parse::<StopTrigger>().is_err());
+        let StopTrigger::Http { address, path, .. } = "http://example.com".parse().unwrap() else {
+            unreachable!()
+        };
+        assert_eq!((address.as_str(), path.as_str()), ("example.com:80", "/"));
+    }
+}
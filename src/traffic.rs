@@ -0,0 +1,396 @@
+// synthetic radio traffic with known keys, as labelled data for evaluating attacks: German
+// messages from templates, encrypted under the daily keys of a key sheet with the indicator
+// procedure of the period, sent in groups of five and garbled in reception
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use anyhow::ensure;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    challenge,
+    engine::GenomeBuilder,
+    enigma::{normalize, Machine, Settings, LETTERS_NUM},
+    gen::{self, ReflectorSearch, SettingsBuilder},
+    language::GERMAN_SAMPLE,
+    message_key,
+};
+
+const UNITS: [&str; 8] = [
+    "OKH",
+    "HEERESGRUPPE NORD",
+    "PANZERGRUPPE ZWO",
+    "ARMEEKORPS DREI",
+    "INFANTERIEDIVISION SIEBEN",
+    "FLIEGERKORPS EINS",
+    "MARINEGRUPPE WEST",
+    "FLAKREGIMENT VIER",
+];
+const SUBJECTS: [&str; 6] = [
+    "WETTERVORHERSAGE",
+    "LAGEMELDUNG",
+    "TAGESMELDUNG",
+    "VERSORGUNGSLAGE",
+    "FEINDLAGE",
+    "BEFEHL",
+];
+const SIGNATURES: [&str; 5] = [
+    "KOMMANDEUR",
+    "IA",
+    "CHEF DES STABES",
+    "OBERST",
+    "FUNKSTELLE",
+];
+// digits were spelled out, ZWO rather than ZWEI so it isn't heard as DREI
+const DIGITS: [&str; 10] = [
+    "NULL", "EINS", "ZWO", "DREI", "VIER", "FUENF", "SECHS", "SIEBEN", "ACHT", "NEUN",
+];
+
+/// How the operator told the receiver the message key, the starting positions of the rotors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Procedure {
+    // until May 1940: the key typed twice at the ground setting of the key sheet
+    #[default]
+    Doubled,
+    // from May 1940: a ground setting of the operator's own, sent in the clear, and the key
+    // typed once at it
+    Single,
+}
+
+impl fmt::Display for Procedure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Procedure::Doubled => write!(f, "doubled"),
+            Procedure::Single => write!(f, "single"),
+        }
+    }
+}
+
+impl FromStr for Procedure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubled" => Ok(Procedure::Doubled),
+            "single" => Ok(Procedure::Single),
+            _ => Err(format!(
+                "unknown indicator procedure {s}, expected doubled or single"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub messages_per_day: usize,
+    // letters of the plaintext, both inclusive
+    pub min_length: usize,
+    pub max_length: usize,
+    pub plugs: usize,
+    pub procedure: Procedure,
+    // letters received as another letter
+    pub garble_rate: f64,
+    // message keys the operator picked without thinking, see message_key::Cilly
+    pub cilly_rate: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            messages_per_day: 10,
+            min_length: 60,
+            max_length: 250,
+            plugs: 10,
+            procedure: Procedure::default(),
+            garble_rate: 0.0,
+            cilly_rate: 0.0,
+        }
+    }
+}
+
+/// A message as intercepted, with the key it was sent with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    // from 1, a new daily key every day
+    pub day: usize,
+    // the key of the day, its positions are the ground setting of the key sheet
+    pub daily_key: Settings,
+    // as sent ahead of the message: the six letters of the doubled key, or the ground setting
+    // in the clear and the three letters of the key
+    pub indicator: String,
+    // the daily key at the message key
+    pub settings: Settings,
+    // letters only
+    pub plaintext: String,
+    // in groups of five letters, as received
+    pub ciphertext: String,
+    pub garbled: usize,
+}
+
+/// An endless stream of messages, `messages_per_day` a day under the keys of a random key
+/// sheet.
+pub struct Traffic<R: Rng> {
+    opts: Options,
+    rng: R,
+    daily_key: Option<Settings>,
+    day: usize,
+    sent_today: usize,
+}
+
+impl<R: Rng> Traffic<R> {
+    pub fn new(opts: Options, rng: R) -> anyhow::Result<Self> {
+        ensure!(opts.messages_per_day > 0, "no messages are sent a day");
+        ensure!(
+            0 < opts.min_length && opts.min_length <= opts.max_length,
+            "the message length must be a range of at least one letter"
+        );
+        ensure!(opts.plugs <= 13, "a plugboard has at most 13 plugs");
+        ensure!(
+            (0.0..=1.0).contains(&opts.garble_rate) && (0.0..=1.0).contains(&opts.cilly_rate),
+            "the garble and cilly rates must be between 0 and 1"
+        );
+        Ok(Self {
+            opts,
+            rng,
+            daily_key: None,
+            day: 0,
+            sent_today: 0,
+        })
+    }
+
+    fn daily_key(&mut self) -> Settings {
+        if self.daily_key.is_none() || self.sent_today == self.opts.messages_per_day {
+            let mut settings = SettingsBuilder {
+                max_plugs: 0,
+                reflector: ReflectorSearch::default(),
+                herivel_spread: None,
+                fixed_rings: false,
+            }
+            .build_genome(0, &mut self.rng);
+            for _ in 0..self.opts.plugs {
+                gen::add_plug(&mut settings.plugboard, &mut self.rng);
+            }
+            self.daily_key = Some(settings);
+            self.day += 1;
+            self.sent_today = 0;
+        }
+        self.sent_today += 1;
+        self.daily_key.clone().expect("set above")
+    }
+
+    fn positions(&mut self) -> (u8, u8, u8) {
+        let mut position = || self.rng.gen_range(1..=LETTERS_NUM);
+        (position(), position(), position())
+    }
+
+    // the same letter three times, three letters in a row of the alphabet or the ground
+    // setting itself; runs along the keyboard aside
+    fn message_key(&mut self, ground: (u8, u8, u8)) -> (u8, u8, u8) {
+        if !self.rng.gen_bool(self.opts.cilly_rate) {
+            return self.positions();
+        }
+        let first = self.rng.gen_range(0..LETTERS_NUM);
+        let letter = |offset: u8| (first + offset) % LETTERS_NUM + 1;
+        match self.rng.gen_range(0..3) {
+            0 => (letter(0), letter(0), letter(0)),
+            1 => (letter(0), letter(1), letter(2)),
+            _ => ground,
+        }
+    }
+
+    fn plaintext(&mut self, length: usize) -> String {
+        let rng = &mut self.rng;
+        let mut pick = |words: &[&'static str]| *words.choose(rng).expect("not empty");
+        let (to, from, subject) = (pick(&UNITS), pick(&UNITS), pick(&SUBJECTS));
+        let hour = self.rng.gen_range(0..24);
+        let header = format!(
+            "AN {to} X VON {from} X {subject} {} {} UHR X",
+            DIGITS[hour / 10],
+            DIGITS[hour % 10]
+        );
+        let closing = format!("X {}", SIGNATURES.choose(&mut self.rng).expect("not empty"));
+        let letters = |text: &str| normalize(text).replace(' ', "");
+        let (header, closing) = (letters(&header), letters(&closing));
+
+        // the body is prose, its letters are as frequent as in real traffic
+        let sample = normalize(GERMAN_SAMPLE);
+        let words = sample.split_whitespace().collect::<Vec<_>>();
+        let start = self.rng.gen_range(0..words.len());
+        let body_length = length.saturating_sub(header.len() + closing.len());
+        let body = words
+            .iter()
+            .cycle()
+            .skip(start)
+            .flat_map(|word| word.chars())
+            .take(body_length)
+            .collect::<String>();
+
+        let mut plaintext = header + &body + &closing;
+        plaintext.truncate(length);
+        plaintext
+    }
+
+    fn garble(&mut self, ciphertext: &str) -> (String, usize) {
+        let mut garbled = 0;
+        let received = ciphertext
+            .chars()
+            .map(|c| {
+                if !c.is_ascii_uppercase() || !self.rng.gen_bool(self.opts.garble_rate) {
+                    return c;
+                }
+                garbled += 1;
+                let shift = self.rng.gen_range(1..LETTERS_NUM);
+                ((c as u8 - b'A' + shift) % LETTERS_NUM + b'A') as char
+            })
+            .collect();
+        (received, garbled)
+    }
+}
+
+impl<R: Rng> Iterator for Traffic<R> {
+    type Item = Message;
+
+    fn next(&mut self) -> Option<Message> {
+        let daily_key = self.daily_key();
+        let at = |rotor_positions| {
+            let settings = Settings {
+                rotor_positions,
+                ..daily_key.clone()
+            };
+            Machine::new(&settings).expect("key sheets hold valid keys")
+        };
+
+        let (key, indicator) = match self.opts.procedure {
+            Procedure::Doubled => {
+                let ground = daily_key.rotor_positions;
+                let key = self.message_key(ground);
+                let doubled = message_key::letters(key).repeat(2);
+                (key, at(ground).encrypt(&doubled))
+            }
+            Procedure::Single => {
+                let ground = self.positions();
+                let key = self.message_key(ground);
+                let sent = at(ground).encrypt(&message_key::letters(key));
+                (key, format!("{} {}", message_key::letters(ground), sent))
+            }
+        };
+
+        let length = self
+            .rng
+            .gen_range(self.opts.min_length..=self.opts.max_length);
+        let plaintext = self.plaintext(length);
+        let (ciphertext, garbled) = self.garble(&at(key).encrypt(&plaintext));
+
+        Some(Message {
+            day: self.day,
+            settings: Settings {
+                rotor_positions: key,
+                ..daily_key.clone()
+            },
+            daily_key,
+            indicator,
+            plaintext,
+            ciphertext: challenge::groups(&ciphertext),
+            garbled,
+        })
+    }
+}
+
+/// Writes the messages tab separated: day, daily key in key sheet notation, indicator,
+/// ciphertext, message settings, plaintext and garbled letters.
+pub fn write(path: &Path, messages: &[Message]) -> anyhow::Result<()> {
+    let mut lines =
+        String::from("day\tdaily key\tindicator\tciphertext\tkey\tplaintext\tgarbled\n");
+    for message in messages {
+        lines += &format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            message.day,
+            message.daily_key,
+            message.indicator,
+            message.ciphertext,
+            message.settings,
+            message.plaintext,
+            message.garbled
+        );
+    }
+    fs::write(path, lines)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::message_key::Indicator;
+
+    use super::*;
+
+    #[test]
+    fn test_traffic() {
+        let opts = Options {
+            messages_per_day: 3,
+            ..Default::default()
+        };
+        let traffic = Traffic::new(opts.clone(), ChaCha8Rng::seed_from_u64(2)).unwrap();
+        let messages = traffic.take(7).collect::<Vec<_>>();
+        let days = messages.iter().map(|m| m.day).collect::<Vec<_>>();
+        assert_eq!(days, [1, 1, 1, 2, 2, 2, 3]);
+        assert_eq!(messages[0].daily_key, messages[2].daily_key);
+        assert_ne!(messages[2].daily_key, messages[3].daily_key);
+
+        for message in &messages {
+            let length = message.plaintext.len();
+            assert!((opts.min_length..=opts.max_length).contains(&length));
+            assert!(message.plaintext.starts_with("AN"));
+            assert_eq!(message.settings.plugboard.len(), 10);
+            assert_eq!(message.garbled, 0);
+            let machine = Machine::new(&message.settings).unwrap();
+            let ciphertext = message.ciphertext.replace(' ', "");
+            assert_eq!(machine.decrypt(&ciphertext), message.plaintext);
+
+            let indicator = Indicator::analyze(&message.daily_key, &message.indicator).unwrap();
+            assert_eq!(indicator.candidates, [message.settings.rotor_positions]);
+        }
+
+        let opts = Options {
+            procedure: Procedure::Single,
+            garble_rate: 0.1,
+            cilly_rate: 1.0,
+            ..Default::default()
+        };
+        let mut traffic = Traffic::new(opts, ChaCha8Rng::seed_from_u64(2)).unwrap();
+        let message = traffic.next().unwrap();
+        let key = message.settings.rotor_positions;
+
+        // the key once at the ground setting sent in the clear, which the operator's lazy key
+        // may be
+        let (clear, sent) = message.indicator.split_once(' ').unwrap();
+        let ground = clear.bytes().map(|b| b - b'A' + 1).collect::<Vec<_>>();
+        let ground = (ground[0], ground[1], ground[2]);
+        let machine = Machine::new(&Settings {
+            rotor_positions: ground,
+            ..message.daily_key.clone()
+        });
+        assert_eq!(machine.unwrap().decrypt(sent), message_key::letters(key));
+        assert!(message_key::cilly(key, ground).is_some());
+
+        // a garbled letter decrypts to a wrong one and leaves the others be
+        assert!(message.garbled > 0);
+        let machine = Machine::new(&message.settings).unwrap();
+        let decrypted = machine.decrypt(&message.ciphertext.replace(' ', ""));
+        let wrong = decrypted
+            .chars()
+            .zip(message.plaintext.chars())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert_eq!(wrong, message.garbled);
+
+        assert_eq!("single".parse(), Ok(Procedure::Single));
+        assert!("tripled".parse::<Procedure>().is_err());
+        let none = Options {
+            messages_per_day: 0,
+            ..Default::default()
+        };
+        assert!(Traffic::new(none, ChaCha8Rng::seed_from_u64(2)).is_err());
+    }
+}
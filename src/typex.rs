@@ -0,0 +1,376 @@
+use std::fmt;
+
+use anyhow::ensure;
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    Rng,
+};
+
+use crate::{
+    cache::{FitnessCache, ScramblerCache},
+    cipher::Cipher,
+    coverage::Coverage,
+    engine::{CrossoverOp, Engine, GenomeBuilder, MutationOp},
+    enigma::{normalize, GARBLE, LETTERS_NUM},
+    gen::{self, CipherFitness},
+};
+
+pub const ROTORS_IN_MACHINE: usize = 5;
+pub const MAX_ROTOR_NUM: u8 = 8;
+pub const MODEL_NAME: &str = "Typex (5 rotors, 2 stators)";
+
+// the service wirings were never published, this is a fixed representative set:
+// wiring and notch letters of every rotor
+const ROTORS: [(&str, &str); MAX_ROTOR_NUM as usize] = [
+    ("FKTYWCQXGZAOIVJHPLBDMRNUES", "CLOUY"),
+    ("XSGFZYWIUVQKBPTDLNOHAJCREM", "AIKMO"),
+    ("GJZTSIFDEWRBYLUMAXVPKHOQNC", "DINRX"),
+    ("ISBGFQJATNVMCEODYXLKUZHRWP", "BEJOY"),
+    ("NVCQMEBAGOIZLFWXRPHUDYSTJK", "GIKNS"),
+    ("JOMKEPQIFADGCZBRVSLYTXNUWH", "FJRYZ"),
+    ("BYQOSDJNLWPFAGIKEHTURMVXCZ", "DLOUY"),
+    ("SPQYDFKJEOXWZRBUMVNCGHTIAL", "BCFMS"),
+];
+const REFLECTOR: &str = "QS NC KX JM OW ZU DP BT EV HL IA RF YG";
+
+// rotors from left (next to the reflector) to right (next to the keyboard), the two left
+// ones are stators that never move; all values are 1-based
+#[derive(Hash, PartialEq, Eq, Debug, Clone)]
+pub struct TypexSettings {
+    pub rotors: [u8; ROTORS_IN_MACHINE],
+    pub rotor_positions: [u8; ROTORS_IN_MACHINE],
+}
+
+impl TypexSettings {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.rotors.iter().all(|r| (1..=MAX_ROTOR_NUM).contains(r)),
+            "rotors must be in 1..={}, got {:?}",
+            MAX_ROTOR_NUM,
+            self.rotors
+        );
+        ensure!(
+            (1..ROTORS_IN_MACHINE).all(|i| !self.rotors[..i].contains(&self.rotors[i])),
+            "rotors must be unique, got {:?}",
+            self.rotors
+        );
+        ensure!(
+            self.rotor_positions
+                .iter()
+                .all(|p| (1..=LETTERS_NUM).contains(p)),
+            "rotor positions must be in 1..={}, got {:?}",
+            LETTERS_NUM,
+            self.rotor_positions
+        );
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypexSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rotors = self.rotors.map(|r| r.to_string()).join(" ");
+        let positions = self
+            .rotor_positions
+            .map(|p| ((b'A' + p.wrapping_sub(1) % LETTERS_NUM) as char).to_string())
+            .join(" ");
+        write!(f, "{} / {}", rotors, positions)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rotor {
+    forward: [u8; 26],
+    backward: [u8; 26],
+    notches: Vec<u8>,
+}
+
+impl Rotor {
+    fn new(index: u8) -> Self {
+        let (wiring, notches) = ROTORS[index as usize - 1];
+        let mut forward = [0; 26];
+        let mut backward = [0; 26];
+        for (i, c) in wiring.bytes().enumerate() {
+            forward[i] = c - b'A';
+            backward[(c - b'A') as usize] = i as u8;
+        }
+
+        Self {
+            forward,
+            backward,
+            notches: notches.bytes().map(|c| c - b'A').collect(),
+        }
+    }
+}
+
+pub struct TypexMachine {
+    rotors: Vec<Rotor>,
+    positions: [u8; ROTORS_IN_MACHINE],
+    reflector: [u8; 26],
+}
+
+impl TypexMachine {
+    pub fn new(s: &TypexSettings) -> anyhow::Result<Self> {
+        s.validate()?;
+
+        let mut reflector = [0; 26];
+        for pair in REFLECTOR.split_whitespace().map(str::as_bytes) {
+            let (a, b) = (pair[0] - b'A', pair[1] - b'A');
+            reflector[a as usize] = b;
+            reflector[b as usize] = a;
+        }
+
+        Ok(Self {
+            rotors: s.rotors.iter().map(|&r| Rotor::new(r)).collect(),
+            positions: s.rotor_positions.map(|p| p - 1),
+            reflector,
+        })
+    }
+
+    // reciprocal like Enigma, so encryption and decryption are the same operation
+    fn process(&self, text: &str) -> String {
+        let mut positions = self.positions;
+
+        normalize(text)
+            .bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' => {
+                    self.step(&mut positions);
+                    (b'A' + self.encipher(c - b'A', &positions)) as char
+                }
+                GARBLE => {
+                    self.step(&mut positions);
+                    GARBLE as char
+                }
+                c => c as char,
+            })
+            .collect()
+    }
+
+    // the fast rotor steps on every key press, a rotor at one of its notches also steps
+    // its left neighbour and itself (double stepping), stators never move
+    fn step(&self, positions: &mut [u8; ROTORS_IN_MACHINE]) {
+        let (slow, middle, fast) = (2, 3, 4);
+        let at_notch = |i: usize| self.rotors[i].notches.contains(&positions[i]);
+
+        if at_notch(middle) {
+            positions[slow] = (positions[slow] + 1) % LETTERS_NUM;
+            positions[middle] = (positions[middle] + 1) % LETTERS_NUM;
+        } else if at_notch(fast) {
+            positions[middle] = (positions[middle] + 1) % LETTERS_NUM;
+        }
+        positions[fast] = (positions[fast] + 1) % LETTERS_NUM;
+    }
+
+    fn encipher(&self, c: u8, positions: &[u8; ROTORS_IN_MACHINE]) -> u8 {
+        let shift = |c: u8, by: u8| (c + by) % LETTERS_NUM;
+        let unshift = |c: u8, by: u8| (c + LETTERS_NUM - by) % LETTERS_NUM;
+
+        let mut c = c;
+        for (rotor, &pos) in self.rotors.iter().zip(positions).rev() {
+            c = unshift(rotor.forward[shift(c, pos) as usize], pos);
+        }
+        c = self.reflector[c as usize];
+        for (rotor, &pos) in self.rotors.iter().zip(positions) {
+            c = unshift(rotor.backward[shift(c, pos) as usize], pos);
+        }
+        c
+    }
+}
+
+impl Cipher for TypexMachine {
+    type Key = TypexSettings;
+
+    const MODEL_NAME: &'static str = MODEL_NAME;
+
+    fn new(key: &TypexSettings) -> anyhow::Result<Self> {
+        TypexMachine::new(key)
+    }
+
+    fn encrypt(&self, text: &str) -> String {
+        self.process(text)
+    }
+
+    fn decrypt(&self, text: &str) -> String {
+        self.process(text)
+    }
+
+    fn keyspace_size() -> u128 {
+        let orders = (0..ROTORS_IN_MACHINE as u128)
+            .map(|i| MAX_ROTOR_NUM as u128 - i)
+            .product::<u128>();
+        orders * (LETTERS_NUM as u128).pow(ROTORS_IN_MACHINE as u32)
+    }
+}
+
+pub struct TypexBuilder;
+
+impl GenomeBuilder<TypexSettings> for TypexBuilder {
+    fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> TypexSettings {
+        let mut rotors = [0; ROTORS_IN_MACHINE];
+        let chosen = (1..=MAX_ROTOR_NUM).choose_multiple(rng, ROTORS_IN_MACHINE);
+        rotors.copy_from_slice(&chosen);
+        rotors.shuffle(rng);
+
+        TypexSettings {
+            rotors,
+            rotor_positions: [(); ROTORS_IN_MACHINE].map(|_| rng.gen_range(1..=LETTERS_NUM)),
+        }
+    }
+}
+
+// every gene is inherited from a parent chosen uniformly at random,
+// a rotor already in the machine is replaced by an unused one
+#[derive(Debug, Clone, Copy)]
+pub struct TypexCrossover;
+
+impl CrossoverOp<TypexSettings> for TypexCrossover {
+    fn crossover<R: Rng>(&self, parents: Vec<TypexSettings>, rng: &mut R) -> Vec<TypexSettings> {
+        let mut child = parents[0].clone();
+
+        for i in 0..ROTORS_IN_MACHINE {
+            child.rotors[i] = parents.choose(rng).unwrap().rotors[i];
+            child.rotor_positions[i] = parents.choose(rng).unwrap().rotor_positions[i];
+        }
+        repair_rotors(&mut child.rotors, rng);
+
+        vec![child]
+    }
+}
+
+fn repair_rotors<R: Rng>(rotors: &mut [u8; ROTORS_IN_MACHINE], rng: &mut R) {
+    for i in 1..ROTORS_IN_MACHINE {
+        if rotors[..i].contains(&rotors[i]) {
+            let used = *rotors;
+            rotors[i] = (1..=MAX_ROTOR_NUM)
+                .filter(|r| !used.contains(r))
+                .choose(rng)
+                .unwrap();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TypexMutator {
+    pub mutation_rate: f64,
+}
+
+impl MutationOp<TypexSettings> for TypexMutator {
+    fn mutate<R: Rng>(&self, mut sett: TypexSettings, rng: &mut R) -> TypexSettings {
+        let num_mutations = ((9_f64 * self.mutation_rate) + rng.gen::<f64>()).floor() as usize;
+
+        for _ in 0..num_mutations {
+            let i = rng.gen_range(0..ROTORS_IN_MACHINE);
+            if rng.gen_bool(0.5) {
+                sett.rotor_positions[i] = rng.gen_range(1..=LETTERS_NUM);
+            } else {
+                // swaps with another slot or takes a rotor out of the box
+                let r = (1..=MAX_ROTOR_NUM)
+                    .filter(|&r| r != sett.rotors[i])
+                    .choose(rng)
+                    .unwrap();
+                match sett.rotors.iter().position(|&x| x == r) {
+                    Some(j) => sett.rotors.swap(i, j),
+                    None => sett.rotors[i] = r,
+                }
+            }
+        }
+
+        sett
+    }
+}
+
+pub fn solve(ciphertext: &str, opts: &gen::Options) -> anyhow::Result<TypexSettings> {
+    opts.validate()?;
+
+    let mut engine = Engine::new(
+        CipherFitness::<TypexMachine> {
+            ciphertext: gen::Ciphertext::new(normalize(ciphertext)),
+            cache: FitnessCache::with_backend(opts.fitness.cache_backend, opts.fitness.cache_size),
+            scramblers: ScramblerCache::new(0),
+            coverage: Coverage::default(),
+            invalid: Default::default(),
+            metric: opts.fitness.metric.clone(),
+        },
+        TypexBuilder,
+        TypexCrossover,
+        TypexMutator {
+            mutation_rate: opts.engine.mutation_rate,
+        },
+        opts,
+    )?;
+
+    Ok(engine.run().best_genome)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{engine::FitnessFunction, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    fn settings() -> TypexSettings {
+        TypexSettings {
+            rotors: [3, 7, 1, 8, 5],
+            rotor_positions: [1, 2, 3, 25, 26],
+        }
+    }
+
+    #[test]
+    fn test_machine() {
+        let machine = TypexMachine::new(&settings()).unwrap();
+        let ciphertext = machine.encrypt(LONG_TEXT);
+
+        assert_eq!(machine.decrypt(&ciphertext), LONG_TEXT);
+        assert!(LONG_TEXT
+            .bytes()
+            .zip(ciphertext.bytes())
+            .all(|(p, c)| p == b' ' && c == b' ' || p != c));
+
+        let mut positions = machine.positions;
+        for _ in 0..1000 {
+            machine.step(&mut positions);
+            assert_eq!(positions[..2], machine.positions[..2]);
+        }
+
+        let invalid = TypexSettings {
+            rotors: [1, 1, 2, 3, 4],
+            ..settings()
+        };
+        assert!(TypexMachine::new(&invalid).is_err());
+        assert_eq!(TypexMachine::keyspace_size(), 6720 * 26_u128.pow(5));
+    }
+
+    #[test]
+    fn test_fitness() {
+        let ciphertext = TypexMachine::new(&settings()).unwrap().encrypt(LONG_TEXT);
+        let calc = CipherFitness::<TypexMachine> {
+            ciphertext: gen::Ciphertext::new(ciphertext),
+            cache: FitnessCache::new(10),
+            scramblers: ScramblerCache::new(0),
+            coverage: Coverage::default(),
+            invalid: Default::default(),
+            metric: None,
+        };
+
+        let mut wrong = settings();
+        wrong.rotor_positions[4] = 1;
+        assert!(calc.fitness_of(&settings()) > calc.fitness_of(&wrong));
+    }
+
+    #[test]
+    fn test_operators() {
+        let mut rng = rand::thread_rng();
+        let m = TypexMutator { mutation_rate: 0.5 };
+
+        for _ in 0..10000 {
+            let parents = (0..2)
+                .map(|_| TypexBuilder.build_genome(0, &mut rng))
+                .collect::<Vec<_>>();
+
+            for child in TypexCrossover.crossover(parents, &mut rng) {
+                assert!(child.validate().is_ok());
+                assert!(m.mutate(child, &mut rng).validate().is_ok());
+            }
+        }
+    }
+}
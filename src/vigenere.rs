@@ -0,0 +1,341 @@
+use std::{fmt, str::FromStr, sync::Arc};
+
+use anyhow::{anyhow, ensure};
+use rand::Rng;
+
+use crate::{
+    dna::DnaCrossover,
+    engine::{Engine, FitnessFunction, GenomeBuilder, MutationOp},
+    enigma::{normalize, LETTERS_NUM},
+    gen::{self, Fitness},
+    language::{Language, LanguageMode},
+    metrics::index_of_coincidence,
+};
+
+// columns of a ciphertext enciphered with the right key length look like English,
+// the shortest length within this ratio of the best one wins over its multiples
+const KEY_LENGTH_IOC_RATIO: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    // c = p + k
+    #[default]
+    Vigenere,
+    // c = k - p, reciprocal like Enigma
+    Beaufort,
+}
+
+impl Variant {
+    fn encrypt_letter(self, p: u8, k: u8) -> u8 {
+        match self {
+            Variant::Vigenere => (p + k) % LETTERS_NUM,
+            Variant::Beaufort => (LETTERS_NUM + k - p) % LETTERS_NUM,
+        }
+    }
+
+    fn decrypt_letter(self, c: u8, k: u8) -> u8 {
+        match self {
+            Variant::Vigenere => (LETTERS_NUM + c - k) % LETTERS_NUM,
+            Variant::Beaufort => (LETTERS_NUM + k - c) % LETTERS_NUM,
+        }
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vigenere" => Ok(Variant::Vigenere),
+            "beaufort" => Ok(Variant::Beaufort),
+            _ => Err(format!(
+                "unknown variant {s}, expected vigenere or beaufort"
+            )),
+        }
+    }
+}
+
+// shifts, A = 0 .. Z = 25
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key(pub Vec<u8>);
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0
+            .iter()
+            .try_for_each(|&k| write!(f, "{}", (b'A' + k % LETTERS_NUM) as char))
+    }
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        ensure!(!s.is_empty(), "key must not be empty");
+
+        s.bytes()
+            .map(|c| match c {
+                b'A'..=b'Z' | b'a'..=b'z' => Ok(c.to_ascii_uppercase() - b'A'),
+                _ => Err(anyhow!("key must only contain letters, got {:?}", s)),
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Key)
+    }
+}
+
+pub fn encrypt(text: &str, key: &Key, variant: Variant) -> String {
+    apply(text, key, |c, k| variant.encrypt_letter(c, k))
+}
+
+pub fn decrypt(text: &str, key: &Key, variant: Variant) -> String {
+    apply(text, key, |c, k| variant.decrypt_letter(c, k))
+}
+
+// only letters advance the key, whitespace is passed through
+fn apply(text: &str, key: &Key, f: impl Fn(u8, u8) -> u8) -> String {
+    if key.0.is_empty() {
+        return normalize(text);
+    }
+    let mut key = key.0.iter().cycle();
+
+    normalize(text)
+        .bytes()
+        .map(|c| match c {
+            b'A'..=b'Z' => (b'A' + f(c - b'A', *key.next().unwrap())) as char,
+            c => c as char,
+        })
+        .collect()
+}
+
+fn letters(text: &str) -> Vec<u8> {
+    normalize(text)
+        .bytes()
+        .filter(u8::is_ascii_uppercase)
+        .collect()
+}
+
+fn column(letters: &[u8], start: usize, step: usize) -> String {
+    letters
+        .iter()
+        .skip(start)
+        .step_by(step)
+        .map(|&c| c as char)
+        .collect()
+}
+
+// Friedman test: average index of coincidence of the columns for every candidate length
+pub fn estimate_key_length(ciphertext: &str, max_len: usize) -> usize {
+    let letters = letters(ciphertext);
+    let max_len = max_len.clamp(1, letters.len().max(1));
+
+    let column_iocs = (1..=max_len)
+        .map(|len| {
+            (0..len)
+                .map(|i| index_of_coincidence(&column(&letters, i, len)))
+                .sum::<f64>()
+                / len as f64
+        })
+        .collect::<Vec<_>>();
+
+    let best = column_iocs.iter().copied().fold(0.0, f64::max);
+    column_iocs
+        .iter()
+        .position(|&ioc| ioc >= KEY_LENGTH_IOC_RATIO * best)
+        .unwrap_or(0)
+        + 1
+}
+
+// frequency attack: every column is a Caesar cipher, its shift is the one closest to the language
+pub fn solve(
+    ciphertext: &str,
+    variant: Variant,
+    max_key_len: usize,
+    language: LanguageMode,
+) -> Key {
+    let letters = letters(ciphertext);
+    let len = estimate_key_length(ciphertext, max_key_len);
+
+    let candidates = language.languages().into_iter().map(|language| {
+        let key = (0..len)
+            .map(|i| {
+                let column = column(&letters, i, len);
+                let score = |k| language.chi_squared(&decrypt(&column, &Key(vec![k]), variant));
+
+                (0..LETTERS_NUM)
+                    .min_by(|&a, &b| score(a).total_cmp(&score(b)))
+                    .unwrap()
+            })
+            .collect();
+        (language, Key(key))
+    });
+
+    best_candidate(&normalize(ciphertext), variant, candidates)
+}
+
+// GA over keys of the given length, scored by how much the decryption looks like the language
+pub fn solve_ga(
+    ciphertext: &str,
+    variant: Variant,
+    key_len: usize,
+    language: LanguageMode,
+    opts: &gen::Options,
+) -> anyhow::Result<Key> {
+    opts.validate()?;
+    ensure!(key_len > 0, "key length must be positive");
+    let ciphertext = Arc::new(normalize(ciphertext));
+
+    let candidates = language
+        .languages()
+        .into_iter()
+        .map(|language| {
+            let mut engine = Engine::new(
+                KeyFitness {
+                    ciphertext: ciphertext.clone(),
+                    variant,
+                    language,
+                },
+                KeyBuilder { key_len },
+                DnaCrossover,
+                KeyMutator {
+                    mutation_rate: opts.engine.mutation_rate,
+                },
+                opts,
+            )?;
+            Ok((language, Key(engine.run().best_genome)))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(best_candidate(&ciphertext, variant, candidates))
+}
+
+// the key whose decryption reads best in the language it was found with; letter pairs
+// compare across languages where the frequencies the columns were scored by can't
+fn best_candidate(
+    ciphertext: &str,
+    variant: Variant,
+    candidates: impl IntoIterator<Item = (Language, Key)>,
+) -> Key {
+    candidates
+        .into_iter()
+        .map(|(language, key)| {
+            let score = language.bigram_score(&decrypt(ciphertext, &key, variant));
+            (score, key)
+        })
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, key)| key)
+        .expect("at least one language is tried")
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyFitness {
+    pub ciphertext: Arc<String>,
+    pub variant: Variant,
+    pub language: Language,
+}
+
+impl FitnessFunction<Vec<u8>> for KeyFitness {
+    fn fitness_of(&self, key: &Vec<u8>) -> Fitness {
+        let plaintext = decrypt(&self.ciphertext, &Key(key.clone()), self.variant);
+        Fitness(self.language.similarity(&plaintext))
+    }
+}
+
+pub struct KeyBuilder {
+    pub key_len: usize,
+}
+
+impl GenomeBuilder<Vec<u8>> for KeyBuilder {
+    fn build_genome<R: Rng>(&self, _: usize, rng: &mut R) -> Vec<u8> {
+        (0..self.key_len)
+            .map(|_| rng.gen_range(0..LETTERS_NUM))
+            .collect()
+    }
+}
+
+// every shift is replaced by a random one with probability mutation_rate
+#[derive(Debug, Clone, Copy)]
+pub struct KeyMutator {
+    pub mutation_rate: f64,
+}
+
+impl MutationOp<Vec<u8>> for KeyMutator {
+    fn mutate<R: Rng>(&self, mut key: Vec<u8>, rng: &mut R) -> Vec<u8> {
+        for k in key.iter_mut() {
+            if rng.gen_bool(self.mutation_rate) {
+                *k = rng.gen_range(0..LETTERS_NUM);
+            }
+        }
+
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{language::GERMAN_SAMPLE, metrics::tests::LONG_TEXT};
+
+    use super::*;
+
+    #[test]
+    fn test_cipher() {
+        let key = "LEMON".parse::<Key>().unwrap();
+        assert_eq!(key.to_string(), "LEMON");
+        assert!("".parse::<Key>().is_err());
+        assert!("LEM0N".parse::<Key>().is_err());
+
+        assert_eq!(
+            encrypt("attack at dawn", &key, Variant::Vigenere),
+            "LXFOPV EF RNHR"
+        );
+        for variant in [Variant::Vigenere, Variant::Beaufort] {
+            let ciphertext = encrypt(LONG_TEXT, &key, variant);
+            assert_eq!(decrypt(&ciphertext, &key, variant), LONG_TEXT);
+        }
+    }
+
+    #[test]
+    fn test_solve() {
+        let key = "LEMON".parse::<Key>().unwrap();
+
+        for variant in [Variant::Vigenere, Variant::Beaufort] {
+            let ciphertext = encrypt(LONG_TEXT, &key, variant);
+
+            assert_eq!(estimate_key_length(&ciphertext, 20), 5);
+            assert_eq!(
+                solve(&ciphertext, variant, 20, LanguageMode::default()),
+                key
+            );
+            assert_eq!(solve(&ciphertext, variant, 20, LanguageMode::Auto), key);
+        }
+
+        let german = encrypt(&normalize(&GERMAN_SAMPLE[..1000]), &key, Variant::Vigenere);
+        assert_eq!(
+            solve(&german, Variant::Vigenere, 20, LanguageMode::Auto),
+            key
+        );
+    }
+
+    #[test]
+    fn test_solve_ga() {
+        let key = "LEMON".parse::<Key>().unwrap();
+        let ciphertext = encrypt(LONG_TEXT, &key, Variant::Beaufort);
+
+        let mut opts = gen::Options::default();
+        opts.engine.population_size = 2000;
+        opts.engine.mutation_rate = 0.2;
+        opts.engine.seed = Some(1);
+        opts.termination.generation_limit = 50;
+
+        assert_eq!(
+            solve_ga(
+                &ciphertext,
+                Variant::Beaufort,
+                5,
+                LanguageMode::default(),
+                &opts
+            )
+            .unwrap(),
+            key
+        );
+    }
+}